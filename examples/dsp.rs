@@ -0,0 +1,230 @@
+//! Block-based audio processing with feedback.
+//!
+//! Oscillators, a filter, an echo and a gain stage are signals producing one
+//! block of samples per step. State that must carry across blocks (the clock,
+//! the filter's last output sample, the echo's feedback) flows through delay
+//! signals driven by a [`Simulation`].
+
+use std::{f32::consts::TAU, sync::Arc};
+
+use matrix::{
+  CustomPlanner, EvalContext, Signal, SignalDef, SignalDefMap, SignalMatrix,
+  Simulation,
+};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const BLOCK: usize = 256;
+
+#[derive(Debug, Clone)]
+enum Value {
+  /// The index of the first sample in the current block.
+  Time(u64),
+  Block(Arc<[f32]>),
+}
+
+impl Value {
+  fn time(&self) -> u64 {
+    match self {
+      Value::Time(t) => *t,
+      Value::Block(_) => panic!("expected a time value"),
+    }
+  }
+
+  fn block(&self) -> &[f32] {
+    match self {
+      Value::Block(b) => b,
+      Value::Time(_) => panic!("expected a block value"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Waveform {
+  Sine,
+  Saw,
+}
+
+#[derive(Debug)]
+enum Node {
+  /// Fed by the simulation with the previous block's value of its source.
+  Delay,
+  /// The previous block's time, advanced by one block.
+  Clock {
+    previous: Signal,
+  },
+  Oscillator {
+    clock:     Signal,
+    waveform:  Waveform,
+    frequency: f32,
+  },
+  Mix(Vec<(Signal, f32)>),
+  /// A one-pole low-pass filter, continuing from the last output sample.
+  LowPass {
+    input:    Signal,
+    previous: Signal,
+    cutoff:   f32,
+  },
+  /// Adds the previous output block back in, scaled by `feedback`.
+  Echo {
+    input:    Signal,
+    previous: Signal,
+    feedback: f32,
+  },
+}
+
+impl SignalDef for Node {
+  type Value = Value;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self {
+      Node::Delay => {}
+      Node::Clock { previous } => out.push(*previous),
+      Node::Oscillator { clock, .. } => out.push(*clock),
+      Node::Mix(inputs) => out.extend(inputs.iter().map(|(s, _)| *s)),
+      Node::LowPass {
+        input, previous, ..
+      }
+      | Node::Echo {
+        input, previous, ..
+      } => out.extend([*input, *previous]),
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    let get = |s: &Signal| ctx.get(*s).unwrap();
+    let block = |samples: Vec<f32>| Value::Block(samples.into());
+    match self {
+      Node::Delay => unreachable!("delays are fed by the simulation"),
+      Node::Clock { previous } => {
+        Value::Time(get(previous).time() + BLOCK as u64)
+      }
+      Node::Oscillator {
+        clock,
+        waveform,
+        frequency,
+      } => {
+        let start = get(clock).time();
+        block(
+          (0..BLOCK as u64)
+            .map(|i| {
+              let t = (start + i) as f32 / SAMPLE_RATE;
+              let phase = (t * frequency).fract();
+              match waveform {
+                Waveform::Sine => (phase * TAU).sin(),
+                Waveform::Saw => phase * 2.0 - 1.0,
+              }
+            })
+            .collect(),
+        )
+      }
+      Node::Mix(inputs) => {
+        let mut out = vec![0.0; BLOCK];
+        for (input, gain) in inputs {
+          for (o, x) in out.iter_mut().zip(get(input).block()) {
+            *o += x * gain;
+          }
+        }
+        block(out)
+      }
+      Node::LowPass {
+        input,
+        previous,
+        cutoff,
+      } => {
+        let alpha = 1.0 - (-TAU * cutoff / SAMPLE_RATE).exp();
+        let mut y = *get(previous).block().last().unwrap();
+        block(
+          get(input)
+            .block()
+            .iter()
+            .map(|x| {
+              y += alpha * (x - y);
+              y
+            })
+            .collect(),
+        )
+      }
+      Node::Echo {
+        input,
+        previous,
+        feedback,
+      } => block(
+        get(input)
+          .block()
+          .iter()
+          .zip(get(previous).block())
+          .map(|(x, p)| x + feedback * p)
+          .collect(),
+      ),
+    }
+  }
+}
+
+fn main() {
+  let mut defset = SignalDefMap::new();
+
+  let last_clock = defset.insert(Node::Delay);
+  let clock = defset.insert(Node::Clock {
+    previous: last_clock,
+  });
+  let sine = defset.insert(Node::Oscillator {
+    clock,
+    waveform: Waveform::Sine,
+    frequency: 220.0,
+  });
+  let saw = defset.insert(Node::Oscillator {
+    clock,
+    waveform: Waveform::Saw,
+    frequency: 330.0,
+  });
+  let mix = defset.insert(Node::Mix(vec![(sine, 0.6), (saw, 0.3)]));
+
+  let last_filtered = defset.insert(Node::Delay);
+  let filtered = defset.insert(Node::LowPass {
+    input:    mix,
+    previous: last_filtered,
+    cutoff:   800.0,
+  });
+
+  let last_output = defset.insert(Node::Delay);
+  let echo = defset.insert(Node::Echo {
+    input:    filtered,
+    previous: last_output,
+    feedback: 0.4,
+  });
+  let output = defset.insert(Node::Mix(vec![(echo, 0.8)]));
+
+  let matrix = SignalMatrix::new(defset);
+  let silence = || Value::Block(vec![0.0; BLOCK].into());
+  let mut sim = Simulation::new(&matrix, &CustomPlanner::new(), [output], [
+    (last_clock, clock, Value::Time(0)),
+    (last_filtered, filtered, silence()),
+    (last_output, output, silence()),
+  ]);
+  println!(
+    "{} passes per block of {BLOCK} samples",
+    sim.plan().passes().len()
+  );
+
+  let blocks = (SAMPLE_RATE as usize / BLOCK) / 2;
+  let now = std::time::Instant::now();
+  let mut peak = 0.0f32;
+  let mut sum_squares = 0.0f64;
+  for step in 0..blocks {
+    let values = sim.step();
+    let samples = values.get(output).unwrap().block();
+    peak = samples.iter().fold(peak, |p, x| p.max(x.abs()));
+    sum_squares += samples.iter().map(|x| (*x as f64).powi(2)).sum::<f64>();
+
+    if (step + 1) % 20 == 0 {
+      let rms = (sum_squares / (20 * BLOCK) as f64).sqrt();
+      println!("blocks {:>3}..{:>3}: rms {rms:.4}", step - 19, step);
+      sum_squares = 0.0;
+    }
+  }
+  println!(
+    "rendered {:.2}s of audio in {:?}, peak {peak:.3}",
+    (blocks * BLOCK) as f32 / SAMPLE_RATE,
+    now.elapsed()
+  );
+}
@@ -0,0 +1,374 @@
+//! A minimal spreadsheet on top of the signal matrix.
+//!
+//! Every cell is a signal labeled with its name (`A1`, `B2`, ...). Formulas
+//! reference other cells, and editing a cell invalidates only the cells that
+//! transitively depend on it, so recalculation is incremental.
+
+use matrix::{
+  CustomPlanner, EvalContext, EvaluationValueMap, Signal, SignalDef,
+  SignalDefMap, SignalMatrix,
+};
+
+/// The value of a cell.
+#[derive(Debug, Clone, PartialEq)]
+enum CellValue {
+  Number(f64),
+  Text(String),
+  Error(String),
+}
+
+impl std::fmt::Display for CellValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CellValue::Number(n) => write!(f, "{n}"),
+      CellValue::Text(t) => write!(f, "{t}"),
+      CellValue::Error(e) => write!(f, "#{e}"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Concat,
+}
+
+/// A parsed formula. Cell references are already resolved to signals.
+#[derive(Debug)]
+enum Expr {
+  Literal(CellValue),
+  Ref(Signal),
+  Sum(Vec<Signal>),
+  Neg(Box<Expr>),
+  Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+/// The signal definition of a cell.
+#[derive(Debug)]
+enum Cell {
+  Constant(CellValue),
+  Formula(Expr),
+}
+
+impl Expr {
+  fn refs(&self, out: &mut Vec<Signal>) {
+    match self {
+      Expr::Literal(_) => {}
+      Expr::Ref(s) => out.push(*s),
+      Expr::Sum(signals) => out.extend(signals),
+      Expr::Neg(e) => e.refs(out),
+      Expr::Binary(_, a, b) => {
+        a.refs(out);
+        b.refs(out);
+      }
+    }
+  }
+
+  fn eval(&self, ctx: &EvalContext<Cell>) -> CellValue {
+    let number = |value: CellValue| match value {
+      CellValue::Number(n) => Ok(n),
+      CellValue::Text(t) if t.is_empty() => Ok(0.0),
+      CellValue::Text(_) => Err(CellValue::Error("VALUE".into())),
+      e @ CellValue::Error(_) => Err(e),
+    };
+    let result = match self {
+      Expr::Literal(v) => Ok(v.clone()),
+      Expr::Ref(s) => Ok(ctx.get(*s).unwrap().clone()),
+      Expr::Sum(signals) => signals
+        .iter()
+        .map(|s| number(ctx.get(*s).unwrap().clone()))
+        .sum::<Result<f64, _>>()
+        .map(CellValue::Number),
+      Expr::Neg(e) => number(e.eval(ctx)).map(|n| CellValue::Number(-n)),
+      Expr::Binary(Op::Concat, a, b) => match (a.eval(ctx), b.eval(ctx)) {
+        (e @ CellValue::Error(_), _) | (_, e @ CellValue::Error(_)) => Err(e),
+        (a, b) => Ok(CellValue::Text(format!("{a}{b}"))),
+      },
+      Expr::Binary(op, a, b) => number(a.eval(ctx))
+        .and_then(|a| Ok((a, number(b.eval(ctx))?)))
+        .and_then(|(a, b)| match op {
+          Op::Add => Ok(a + b),
+          Op::Sub => Ok(a - b),
+          Op::Mul => Ok(a * b),
+          Op::Div if b == 0.0 => Err(CellValue::Error("DIV/0".into())),
+          Op::Div => Ok(a / b),
+          Op::Concat => unreachable!(),
+        })
+        .map(CellValue::Number),
+    };
+    result.unwrap_or_else(|e| e)
+  }
+}
+
+impl SignalDef for Cell {
+  type Value = CellValue;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    if let Cell::Formula(expr) = self {
+      expr.refs(out);
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    match self {
+      Cell::Constant(value) => value.clone(),
+      Cell::Formula(expr) => expr.eval(ctx),
+    }
+  }
+}
+
+/// A recursive-descent formula parser. `resolve` maps cell names to signals.
+struct Parser<'a, F> {
+  chars:   std::iter::Peekable<std::str::Chars<'a>>,
+  resolve: F,
+}
+
+impl<F: FnMut(&str) -> Signal> Parser<'_, F> {
+  fn skip_whitespace(&mut self) {
+    while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+  }
+
+  fn eat(&mut self, expected: char) -> bool {
+    self.skip_whitespace();
+    self.chars.next_if_eq(&expected).is_some()
+  }
+
+  fn expr(&mut self) -> Result<Expr, String> {
+    let mut lhs = self.term()?;
+    loop {
+      let op = if self.eat('+') {
+        Op::Add
+      } else if self.eat('-') {
+        Op::Sub
+      } else if self.eat('&') {
+        Op::Concat
+      } else {
+        return Ok(lhs);
+      };
+      lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+    }
+  }
+
+  fn term(&mut self) -> Result<Expr, String> {
+    let mut lhs = self.factor()?;
+    loop {
+      let op = if self.eat('*') {
+        Op::Mul
+      } else if self.eat('/') {
+        Op::Div
+      } else {
+        return Ok(lhs);
+      };
+      lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.factor()?));
+    }
+  }
+
+  fn factor(&mut self) -> Result<Expr, String> {
+    if self.eat('-') {
+      return Ok(Expr::Neg(Box::new(self.factor()?)));
+    }
+    if self.eat('(') {
+      let inner = self.expr()?;
+      return match self.eat(')') {
+        true => Ok(inner),
+        false => Err("expected `)`".into()),
+      };
+    }
+    if self.eat('"') {
+      let text: String =
+        std::iter::from_fn(|| self.chars.next_if(|c| *c != '"')).collect();
+      self.chars.next();
+      return Ok(Expr::Literal(CellValue::Text(text)));
+    }
+
+    self.skip_whitespace();
+    let word: String = std::iter::from_fn(|| {
+      self
+        .chars
+        .next_if(|c| c.is_ascii_alphanumeric() || *c == '.')
+    })
+    .collect();
+    if let Ok(n) = word.parse() {
+      return Ok(Expr::Literal(CellValue::Number(n)));
+    }
+    if word.eq_ignore_ascii_case("SUM") {
+      if !self.eat('(') {
+        return Err("expected `(` after SUM".into());
+      }
+      let from = self.cell_name()?;
+      if !self.eat(':') {
+        return Err("expected a range like A1:A3".into());
+      }
+      let to = self.cell_name()?;
+      if !self.eat(')') {
+        return Err("expected `)`".into());
+      }
+      let cells = expand_range(from, to)
+        .iter()
+        .map(|name| (self.resolve)(name))
+        .collect();
+      return Ok(Expr::Sum(cells));
+    }
+    parse_cell_name(&word)
+      .map(|_| Expr::Ref((self.resolve)(&word.to_ascii_uppercase())))
+      .ok_or_else(|| format!("unexpected `{word}`"))
+  }
+
+  fn cell_name(&mut self) -> Result<(char, u32), String> {
+    self.skip_whitespace();
+    let word: String =
+      std::iter::from_fn(|| self.chars.next_if(char::is_ascii_alphanumeric))
+        .collect();
+    parse_cell_name(&word).ok_or_else(|| format!("bad cell name `{word}`"))
+  }
+}
+
+/// Split a cell name like `B12` into its column and row.
+fn parse_cell_name(name: &str) -> Option<(char, u32)> {
+  let mut chars = name.chars();
+  let column = chars.next()?.to_ascii_uppercase();
+  let row = chars.as_str().parse().ok()?;
+  column.is_ascii_alphabetic().then_some((column, row))
+}
+
+fn expand_range(from: (char, u32), to: (char, u32)) -> Vec<String> {
+  let mut names = Vec::new();
+  for column in from.0.min(to.0)..=from.0.max(to.0) {
+    for row in from.1.min(to.1)..=from.1.max(to.1) {
+      names.push(format!("{column}{row}"));
+    }
+  }
+  names
+}
+
+struct Sheet {
+  matrix: SignalMatrix<Cell>,
+  values: EvaluationValueMap<Cell>,
+}
+
+impl Sheet {
+  fn new() -> Self {
+    Sheet {
+      matrix: SignalMatrix::new(SignalDefMap::new()),
+      values: EvaluationValueMap::new_empty(Default::default()),
+    }
+  }
+
+  /// Get the signal for a cell, creating an empty cell if needed.
+  fn cell(&mut self, name: &str) -> Signal {
+    match self.matrix.defset().lookup(name) {
+      Some(signal) => signal,
+      None => self
+        .matrix
+        .defset_mut()
+        .insert_labeled(name, Cell::Constant(CellValue::Text(String::new()))),
+    }
+  }
+
+  /// Set a cell from user input: `=formula`, a number, or text.
+  fn set(&mut self, name: &str, input: &str) -> Result<(), String> {
+    let cell = match input.strip_prefix('=') {
+      Some(formula) => {
+        let mut parser = Parser {
+          chars:   formula.chars().peekable(),
+          resolve: |name: &str| self.cell(name),
+        };
+        let expr = parser.expr()?;
+        if parser.chars.peek().is_some() {
+          return Err(format!("trailing input in `{formula}`"));
+        }
+        Cell::Formula(expr)
+      }
+      None => match input.parse() {
+        Ok(n) => Cell::Constant(CellValue::Number(n)),
+        Err(_) => Cell::Constant(CellValue::Text(input.to_string())),
+      },
+    };
+
+    let signal = self.cell(name);
+    let mut refs = Vec::new();
+    cell.dependencies_into(&mut refs);
+    let closure = self.matrix.dependencies_closure(refs.iter().copied());
+    if refs.contains(&signal) || closure.contains(&signal) {
+      return Err(format!("{name} would depend on itself"));
+    }
+
+    self.matrix.update(signal, cell);
+    let mut stale = self.matrix.dependents_closure([signal]);
+    stale.insert(signal);
+    self.values.invalidate(stale);
+    self.recalculate();
+    Ok(())
+  }
+
+  /// Evaluate every cell whose value is missing or stale.
+  fn recalculate(&mut self) {
+    let cells: Vec<_> = self
+      .matrix
+      .defset()
+      .iter()
+      .map(|(signal, _)| signal)
+      .collect();
+    let plan = self
+      .matrix
+      .plan_evaluation(&CustomPlanner::new(), cells)
+      .skip_evaluated(&self.values);
+    println!("  recalculated {} cell(s)", plan.all_queued_targets().len());
+
+    let values = std::mem::replace(
+      &mut self.values,
+      EvaluationValueMap::new_empty(Default::default()),
+    );
+    self.values = plan.run(values);
+  }
+
+  fn print(&self) {
+    print!("{:>4}", "");
+    for column in 'A'..='C' {
+      print!("{column:>16}");
+    }
+    println!();
+    for row in 1..=4 {
+      print!("{row:>4}");
+      for column in 'A'..='C' {
+        let value = self
+          .matrix
+          .defset()
+          .lookup(&format!("{column}{row}"))
+          .and_then(|signal| self.values.get(signal))
+          .map(ToString::to_string)
+          .unwrap_or_default();
+        print!("{value:>16}");
+      }
+      println!();
+    }
+  }
+}
+
+fn main() {
+  let mut sheet = Sheet::new();
+
+  let edits = [
+    ("A1", "10"),
+    ("A2", "20"),
+    ("A3", "=A1 + A2"),
+    ("A4", "=SUM(A1:A3)"),
+    ("B1", "Total"),
+    ("B4", "=B1 & \": \" & A4"),
+    ("C1", "=A4 / (A2 - 20)"),
+    ("A1", "15"),
+    ("A2", "=A4"),
+  ];
+  for (name, input) in edits {
+    println!("{name} <- {input}");
+    if let Err(e) = sheet.set(name, input) {
+      println!("  rejected: {e}");
+    }
+  }
+
+  println!();
+  sheet.print();
+}
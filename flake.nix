@@ -15,6 +15,7 @@
 
         toolchain = pkgs.rust-bin.selectLatestNightlyWith (toolchain: toolchain.default.override {
           extensions = [ "rust-src" "rust-analyzer" ];
+          targets = [ "wasm32-unknown-unknown" ];
         });
       in {
         devShell = pkgs.devshell.mkShell {
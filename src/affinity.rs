@@ -0,0 +1,110 @@
+use thread_priority::{ThreadPriority, ThreadPriorityValue};
+
+/// Where and at what priority the evaluation threads of a run execute, set
+/// with [`RunOptions::with_thread_placement`](crate::RunOptions::with_thread_placement).
+///
+/// A run with a placement evaluates its passes on a dedicated thread pool
+/// instead of the global one, and each worker applies the placement when it
+/// starts. The pool belongs to the [`RunOptions`](crate::RunOptions), so
+/// later runs with the same options reuse it instead of pinning new threads.
+/// Sequential passes still run on the calling thread, which is left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadPlacement {
+  cores:    Vec<usize>,
+  priority: Option<u8>,
+}
+
+impl ThreadPlacement {
+  /// Create a placement that leaves threads where the OS puts them.
+  pub fn new() -> Self { Self::default() }
+
+  /// Pin worker `i` to the `i`-th of the given core IDs, wrapping around if
+  /// there are more workers. The pool gets one worker per core unless a
+  /// pass's parallelism limits it.
+  pub fn with_cores(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+    self.cores = cores.into_iter().collect();
+    self
+  }
+
+  /// Set the priority of the workers, from 0 (lowest) to 99 (highest). How
+  /// this maps onto OS priorities is platform-specific, and raising it may
+  /// need elevated permissions.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the priority is above 99.
+  pub fn with_priority(mut self, priority: u8) -> Self {
+    assert!(priority <= 99, "thread priority must be at most 99");
+    self.priority = Some(priority);
+    self
+  }
+
+  /// Get the number of workers a full-width pool should have, if the
+  /// placement decides it.
+  pub(crate) fn threads(&self) -> Option<usize> {
+    (!self.cores.is_empty()).then_some(self.cores.len())
+  }
+
+  /// Apply the placement to the current thread, which is worker `worker` of
+  /// its pool. Failures are logged rather than aborting the run.
+  pub(crate) fn apply(&self, worker: usize) {
+    if !self.cores.is_empty() {
+      let id = self.cores[worker % self.cores.len()];
+      if !core_affinity::set_for_current(core_affinity::CoreId { id }) {
+        tracing::warn!(worker, core = id, "failed to pin thread to core");
+      }
+    }
+    if let Some(priority) = self.priority {
+      let value = ThreadPriorityValue::try_from(priority).unwrap();
+      let result = thread_priority::set_current_thread_priority(
+        ThreadPriority::Crossplatform(value),
+      );
+      if let Err(error) = result {
+        tracing::warn!(worker, priority, ?error, "failed to set priority");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_pinned_run() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 64,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let cores = core_affinity::get_core_ids().unwrap_or_default();
+    let first = cores.first().map_or(0, |core| core.id);
+    let placement = ThreadPlacement::new().with_cores([first, first]);
+    assert_eq!(placement.threads(), Some(2));
+
+    let options = RunOptions::new().with_thread_placement(placement);
+    for _ in 0..2 {
+      let values = plan.run_with(
+        EvaluationValueMap::new_empty(plan.all_queued_targets()),
+        &options,
+      );
+      for root in roots.iter() {
+        assert_eq!(values.get(*root), expected.get(*root));
+      }
+    }
+    // the full-width pinned pool was built once, and kept by the options
+    options
+      .thread_pools()
+      .get_or_build(0, || panic!("pinned pool was not kept"));
+  }
+}
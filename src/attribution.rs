@@ -0,0 +1,134 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{PlannedEvaluation, ProfileReport, Signal, SignalDef};
+
+/// The measured cost of one root target, from
+/// [`PlannedEvaluation::attribute_costs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootCost {
+  pub root:      Signal,
+  /// Time spent on signals only this root needs, including itself.
+  pub exclusive: Duration,
+  /// This root's share of the time spent on signals other roots need too.
+  pub shared:    Duration,
+}
+
+impl RootCost {
+  /// Get the total time attributed to this root.
+  pub fn total(&self) -> Duration { self.exclusive + self.shared }
+}
+
+/// The evaluation time of a run attributed to its root targets, answering
+/// which output makes a multi-root evaluation slow.
+#[derive(Debug, Clone, Default)]
+pub struct CostAttribution {
+  costs: Vec<RootCost>,
+}
+
+impl CostAttribution {
+  /// Get the cost attributed to a root target, if it's one.
+  pub fn get(&self, root: Signal) -> Option<&RootCost> {
+    self.costs.iter().find(|cost| cost.root == root)
+  }
+
+  /// Get the cost of every root target, most expensive first.
+  pub fn ranked(&self) -> &[RootCost] { &self.costs }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Attribute the signal timings of a profiled run of this plan to its root
+  /// targets. A signal's time goes to every root that transitively depends
+  /// on it, split evenly between them; a root needed by another root counts
+  /// as shared between the two.
+  pub fn attribute_costs(&self, report: &ProfileReport) -> CostAttribution {
+    let mut roots: Vec<_> = self.root_targets().iter().copied().collect();
+    roots.sort_by_key(|s| s.0);
+
+    let mut consumers: HashMap<Signal, Vec<usize>> = HashMap::new();
+    for (i, root) in roots.iter().enumerate() {
+      let mut closure = self.matrix().dependencies_closure([*root]);
+      closure.insert(*root);
+      for signal in closure {
+        consumers.entry(signal).or_default().push(i);
+      }
+    }
+
+    let mut costs: Vec<_> = roots
+      .iter()
+      .map(|root| RootCost {
+        root:      *root,
+        exclusive: Duration::ZERO,
+        shared:    Duration::ZERO,
+      })
+      .collect();
+    for profile in report.signals() {
+      let Some(consumers) = consumers.get(&profile.signal) else {
+        continue;
+      };
+      let share = profile.duration / consumers.len() as u32;
+      for i in consumers.iter() {
+        match consumers.len() {
+          1 => costs[*i].exclusive += share,
+          _ => costs[*i].shared += share,
+        }
+      }
+    }
+    costs
+      .sort_by(|a, b| b.total().cmp(&a.total()).then(a.root.0.cmp(&b.root.0)));
+    CostAttribution { costs }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    profile::{PassProfile, SignalProfile},
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap,
+    SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_attribute_costs() {
+    let mut defset = SignalDefMap::new();
+    let shared = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let own = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let cheap = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(shared)));
+    let slow = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(shared, own)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [cheap, slow]);
+
+    let millis = Duration::from_millis;
+    let timed = |signal, duration| SignalProfile {
+      signal,
+      label: String::new(),
+      variant: String::new(),
+      start: Duration::ZERO,
+      duration,
+      thread: 0,
+    };
+    let report = ProfileReport {
+      passes: vec![PassProfile {
+        index:    0,
+        start:    Duration::ZERO,
+        duration: millis(20),
+        signals:  vec![
+          timed(shared, millis(10)),
+          timed(own, millis(4)),
+          timed(cheap, millis(1)),
+          timed(slow, millis(2)),
+        ],
+      }],
+    };
+
+    let attribution = plan.attribute_costs(&report);
+    assert_eq!(attribution.ranked()[0], RootCost {
+      root:      slow,
+      exclusive: millis(6),
+      shared:    millis(5),
+    });
+    assert_eq!(attribution.get(cheap).unwrap().total(), millis(6));
+    assert_eq!(attribution.get(own), None);
+  }
+}
@@ -0,0 +1,265 @@
+use std::{
+  fmt,
+  hash::Hasher,
+  io::{self, Write},
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+  distributed::WireValue, EvaluationValueMap, PlannedEvaluation, RunOptions,
+  Signal, SignalDef, SignalHasher,
+};
+
+/// Who or what triggers an audited evaluation, and of which graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditContext {
+  actor:   String,
+  graph:   Option<String>,
+  version: Option<u64>,
+}
+
+impl AuditContext {
+  /// Create a context for evaluations triggered by the given actor, e.g. a
+  /// user name or a job ID.
+  pub fn new(actor: impl Into<String>) -> Self {
+    AuditContext {
+      actor: actor.into(),
+      ..Default::default()
+    }
+  }
+
+  /// Record the name and version of the evaluated graph.
+  pub fn with_graph(mut self, name: impl Into<String>, version: u64) -> Self {
+    self.graph = Some(name.into());
+    self.version = Some(version);
+    self
+  }
+}
+
+/// One audited evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+  /// Who or what triggered the evaluation.
+  pub actor:      String,
+  /// The name of the evaluated graph, if known.
+  pub graph:      Option<String>,
+  /// The version of the evaluated graph, if known.
+  pub version:    Option<u64>,
+  /// When the evaluation finished.
+  pub finished:   SystemTime,
+  /// The signals given values before the run, sorted by ID.
+  pub overridden: Vec<Signal>,
+  /// The root targets, sorted by ID.
+  pub roots:      Vec<Signal>,
+  /// A digest of the roots' encoded values. It identifies results; it isn't
+  /// a cryptographic hash.
+  pub digest:     u64,
+}
+
+/// Writes the record as one line, e.g. `1700000000.250 actor="ci"
+/// graph=loan@3 overridden=[#1] roots=[#4] digest=00ff00ff00ff00ff`.
+impl fmt::Display for AuditRecord {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let finished = self.finished.duration_since(UNIX_EPOCH).unwrap_or_default();
+    write!(
+      f,
+      "{}.{:03} actor={:?}",
+      finished.as_secs(),
+      finished.subsec_millis(),
+      self.actor
+    )?;
+    if let Some(graph) = &self.graph {
+      write!(f, " graph={graph}")?;
+    }
+    if let Some(version) = self.version {
+      write!(f, "@{version}")?;
+    }
+    let list = |signals: &[Signal]| {
+      signals
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+    };
+    write!(
+      f,
+      " overridden=[{}] roots=[{}] digest={:016x}",
+      list(&self.overridden),
+      list(&self.roots),
+      self.digest
+    )
+  }
+}
+
+/// A destination for [`AuditRecord`]s, e.g. a file, a database table or a
+/// log shipper.
+pub trait AuditSink: fmt::Debug + Send + Sync {
+  /// Persist a record.
+  fn record(&self, record: &AuditRecord) -> io::Result<()>;
+}
+
+/// An [`AuditSink`] keeping records in memory, for tests.
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+  records: Mutex<Vec<AuditRecord>>,
+}
+
+impl MemoryAuditSink {
+  /// Create an empty sink.
+  pub fn new() -> Self { Self::default() }
+
+  /// Get the records, oldest first.
+  pub fn records(&self) -> Vec<AuditRecord> {
+    self.records.lock().unwrap().clone()
+  }
+}
+
+impl AuditSink for MemoryAuditSink {
+  fn record(&self, record: &AuditRecord) -> io::Result<()> {
+    self.records.lock().unwrap().push(record.clone());
+    Ok(())
+  }
+}
+
+/// An [`AuditSink`] appending each record to a writer as its displayed
+/// line, flushing after each one.
+#[derive(Debug)]
+pub struct WriterAuditSink<W> {
+  writer: Mutex<W>,
+}
+
+impl<W: Write + fmt::Debug + Send> WriterAuditSink<W> {
+  /// Create a sink appending to the given writer.
+  pub fn new(writer: W) -> Self {
+    WriterAuditSink {
+      writer: Mutex::new(writer),
+    }
+  }
+
+  /// Take the writer back.
+  pub fn into_inner(self) -> W { self.writer.into_inner().unwrap() }
+}
+
+impl<W: Write + fmt::Debug + Send> AuditSink for WriterAuditSink<W> {
+  fn record(&self, record: &AuditRecord) -> io::Result<()> {
+    let mut writer = self.writer.lock().unwrap();
+    writeln!(writer, "{record}")?;
+    writer.flush()
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T>
+where
+  T::Value: WireValue,
+{
+  /// Run the planned evaluation like [`run_with`](Self::run_with), then
+  /// record who triggered it, which signals were given values, and a digest
+  /// of the root values to the sink.
+  ///
+  /// Fails if the record can't be persisted, so no audited run goes
+  /// unrecorded.
+  pub fn run_audited(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    context: &AuditContext,
+    sink: &dyn AuditSink,
+  ) -> io::Result<EvaluationValueMap<T>> {
+    let mut overridden: Vec<_> = values
+      .values
+      .iter()
+      .filter(|(_, v)| v.is_some())
+      .map(|(s, _)| s)
+      .collect();
+    overridden.sort_by_key(|s| s.0);
+    let values = self.run_with(values, options);
+
+    let mut roots: Vec<_> = self.root_targets().iter().copied().collect();
+    roots.sort_by_key(|s| s.0);
+    let mut hasher = SignalHasher::default();
+    let mut bytes = Vec::new();
+    for root in roots.iter() {
+      bytes.clear();
+      if let Some(value) = values.get(*root) {
+        value.encode(&mut bytes);
+      }
+      hasher.write_u64(root.0);
+      hasher.write_usize(bytes.len());
+      hasher.write(&bytes);
+    }
+
+    sink.record(&AuditRecord {
+      actor: context.actor.clone(),
+      graph: context.graph.clone(),
+      version: context.version,
+      finished: SystemTime::now(),
+      overridden,
+      roots,
+      digest: hasher.finish(),
+    })?;
+    Ok(values)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_audited_runs() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [sum])
+      .skip_targets([a]);
+
+    let sink = MemoryAuditSink::new();
+    let context = AuditContext::new("alice").with_graph("sums", 2);
+    for input in [1.0, 1.0, 5.0] {
+      let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+      values.insert(a, input);
+      plan
+        .run_audited(values, &RunOptions::new(), &context, &sink)
+        .unwrap();
+    }
+    let records = sink.records();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].actor, "alice");
+    assert_eq!(records[0].overridden, [a]);
+    assert_eq!(records[0].roots, [sum]);
+    // the digest follows the results
+    assert_eq!(records[0].digest, records[1].digest);
+    assert_ne!(records[1].digest, records[2].digest);
+
+    let line = records[0].to_string();
+    let fields: Vec<_> = line.split(' ').skip(1).collect();
+    assert_eq!(fields[..4], [
+      "actor=\"alice\"",
+      "graph=sums@2",
+      "overridden=[#0]",
+      "roots=[#2]"
+    ]);
+
+    let sink = WriterAuditSink::new(Vec::new());
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(a, 0.0);
+    let values = plan.run_audited(
+      values,
+      &RunOptions::new(),
+      &AuditContext::new("cron"),
+      &sink,
+    );
+    assert_eq!(values.unwrap().get(sum), Some(&2.0));
+    let log = String::from_utf8(sink.into_inner()).unwrap();
+    assert!(log.contains(" actor=\"cron\" overridden=[#0] roots=[#2] "));
+    assert!(log.ends_with('\n'));
+  }
+}
@@ -0,0 +1,244 @@
+use std::{
+  collections::BTreeMap,
+  time::{Duration, Instant},
+};
+
+use num_traits::Float;
+
+use crate::{
+  eval::RunHooks, par::current_thread_index, EvalContext, EvaluationValueMap,
+  FloatBinaryOp, FloatMapSignalDef, PlannedEvaluation, RunOptions, Signal,
+  SignalDef, SignalSet, UnaryOp,
+};
+
+/// A signal definition whose same-kind targets in a pass can be evaluated
+/// together in one call, e.g. one vectorized or BLAS call for thousands of
+/// additions, instead of one dispatch per target. Used by
+/// [`PlannedEvaluation::run_batched`].
+pub trait BatchSignalDef: SignalDef {
+  /// Get the batch this definition joins, or `None` to evaluate it on its own
+  /// like any other signal. Every target in a pass with the same key is
+  /// handed to one [`evaluate_batch`](Self::evaluate_batch) call.
+  fn batch_key(&self) -> Option<&'static str>;
+
+  /// Evaluate a batch of definitions sharing a key, each with its context,
+  /// returning their values in the same order.
+  fn evaluate_batch(
+    defs: &[&Self],
+    contexts: &[EvalContext<'_, Self>],
+  ) -> Vec<Self::Value>;
+}
+
+type EvaluateBatch<T> =
+  for<'c> fn(&[&T], &[EvalContext<'c, T>]) -> Vec<<T as SignalDef>::Value>;
+
+/// The batching functions of a [`BatchSignalDef`], so the executor can batch
+/// without requiring the trait everywhere.
+pub(crate) struct Batching<T: SignalDef> {
+  key:      fn(&T) -> Option<&'static str>,
+  evaluate: EvaluateBatch<T>,
+}
+
+impl<T: SignalDef> Clone for Batching<T> {
+  fn clone(&self) -> Self { *self }
+}
+
+impl<T: SignalDef> Copy for Batching<T> {}
+
+impl<T: BatchSignalDef> Batching<T> {
+  fn of() -> Self {
+    Batching {
+      key:      T::batch_key,
+      evaluate: T::evaluate_batch,
+    }
+  }
+}
+
+/// A target's value alongside its start time and duration, if timed, and the
+/// thread it was evaluated on.
+pub(crate) type Evaluation<V> =
+  (Signal, V, Option<(Duration, Duration)>, usize);
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Evaluate the batchable targets of a pass, one call per batch key,
+  /// returning the targets that aren't batched and the evaluations. Each
+  /// batched target is timed as an equal share of its batch.
+  pub(crate) fn evaluate_batches(
+    &self,
+    pass_index: usize,
+    batching: Batching<T>,
+    targets: &SignalSet,
+    values: &EvaluationValueMap<T>,
+    started: Option<Instant>,
+    options: &RunOptions,
+  ) -> (SignalSet, Vec<Evaluation<T::Value>>) {
+    let mut batches: BTreeMap<&str, Vec<Signal>> = BTreeMap::new();
+    let mut rest = SignalSet::default();
+    for target in targets.iter() {
+      let def = self.matrix().defset().get(*target).unwrap();
+      match (batching.key)(def) {
+        Some(key) => batches.entry(key).or_default().push(*target),
+        None => {
+          rest.insert(*target);
+        }
+      }
+    }
+
+    let mut evaluations = Vec::with_capacity(targets.len() - rest.len());
+    for (key, mut batch) in batches {
+      let _span = tracing::debug_span!("batch", key, size = batch.len());
+      let _enter = _span.enter();
+      batch.sort_by_key(|s| s.0);
+      let defs: Vec<_> = batch
+        .iter()
+        .map(|s| self.matrix().defset().get(*s).unwrap())
+        .collect();
+      let contexts: Vec<_> = batch
+        .iter()
+        .zip(defs.iter())
+        .map(|(s, def)| {
+          let mut context = self.gather_context(pass_index, *s, def, values);
+          context.summation = options.summation();
+          context
+        })
+        .collect();
+
+      let start = started.map(|started| started.elapsed());
+      let batch_values = (batching.evaluate)(&defs, &contexts);
+      assert_eq!(
+        batch_values.len(),
+        batch.len(),
+        "batch {key:?} returned the wrong number of values"
+      );
+      let timing = start.zip(started).map(|(start, started)| {
+        (start, (started.elapsed() - start) / batch.len() as u32)
+      });
+      let thread = current_thread_index();
+      evaluations.extend(
+        batch
+          .into_iter()
+          .zip(batch_values)
+          .map(|(s, value)| (s, value, timing, thread)),
+      );
+    }
+    (rest, evaluations)
+  }
+}
+
+impl<T: BatchSignalDef> PlannedEvaluation<'_, T> {
+  /// Run the planned evaluation like [`run_with`](Self::run_with), handing
+  /// every group of same-key targets in a pass to one
+  /// [`BatchSignalDef::evaluate_batch`] call. Batches run one after another
+  /// on the calling thread; the remaining targets run as usual.
+  pub fn run_batched(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> EvaluationValueMap<T> {
+    self.execute(values, options, RunHooks {
+      batching: Some(Batching::of()),
+      ..Default::default()
+    })
+  }
+}
+
+/// Batches arithmetic by operation, evaluating each batch as one loop over
+/// gathered operands. Constants aren't batched.
+impl<F: Float + std::fmt::Debug + Send + Sync> BatchSignalDef
+  for FloatMapSignalDef<F>
+{
+  fn batch_key(&self) -> Option<&'static str> {
+    match self {
+      FloatMapSignalDef::Constant(_) => None,
+      FloatMapSignalDef::UnaryOp(UnaryOp::Neg(_)) => Some("neg"),
+      FloatMapSignalDef::BinaryOp(op) => Some(match op {
+        FloatBinaryOp::Add(..) => "add",
+        FloatBinaryOp::Sub(..) => "sub",
+        FloatBinaryOp::Mul(..) => "mul",
+        FloatBinaryOp::Div(..) => "div",
+        FloatBinaryOp::Pow(..) => "pow",
+      }),
+    }
+  }
+
+  fn evaluate_batch(
+    defs: &[&Self],
+    contexts: &[EvalContext<'_, Self>],
+  ) -> Vec<F> {
+    let operands = defs.iter().zip(contexts).map(|(def, ctx)| {
+      let value = |s: &Signal| *ctx.values[s];
+      match def {
+        FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => (value(a), F::zero()),
+        FloatMapSignalDef::BinaryOp(
+          FloatBinaryOp::Add(a, b)
+          | FloatBinaryOp::Sub(a, b)
+          | FloatBinaryOp::Mul(a, b)
+          | FloatBinaryOp::Div(a, b)
+          | FloatBinaryOp::Pow(a, b),
+        ) => (value(a), value(b)),
+        FloatMapSignalDef::Constant(_) => {
+          unreachable!("constants aren't batched")
+        }
+      }
+    });
+    let (a, b): (Vec<F>, Vec<F>) = operands.unzip();
+
+    let apply =
+      |op: fn(F, F) -> F| a.iter().zip(&b).map(move |(a, b)| op(*a, *b));
+    match defs[0].batch_key().unwrap() {
+      "neg" => a.iter().map(|a| -*a).collect(),
+      "add" => apply(|a, b| a + b).collect(),
+      "sub" => apply(|a, b| a - b).collect(),
+      "mul" => apply(|a, b| a * b).collect(),
+      "div" => apply(|a, b| a / b).collect(),
+      _ => apply(F::powf).collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, ProfileReport, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_batched_run() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     5,
+      width:      30,
+      min_fan_in: 1,
+      max_fan_in: 3,
+      reach:      2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let batched = plan.run_batched(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    for root in roots.iter() {
+      assert_eq!(batched.get(*root), expected.get(*root));
+    }
+
+    // batched targets are still profiled
+    let mut report = ProfileReport::default();
+    let _ = plan.execute(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+      RunHooks {
+        profile: Some(&mut report),
+        batching: Some(Batching::of()),
+        ..Default::default()
+      },
+    );
+    assert_eq!(report.signals().count(), plan.all_queued_targets().len());
+  }
+}
@@ -0,0 +1,217 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+  eval::RunHooks, EvaluationPassDescriptor, EvaluationValueMap,
+  PlannedEvaluation, RunOptions, Signal, SignalDef, SignalSet,
+};
+
+/// Limits on a run, for
+/// [`PlannedEvaluation::run_budgeted`](crate::PlannedEvaluation::run_budgeted).
+///
+/// Budgets are checked at pass boundaries: a pass is only started if the
+/// wall time isn't used up yet and evaluating all of its targets keeps the
+/// signal count and estimated cost within their limits.
+#[derive(Default)]
+pub struct Budget {
+  max_signals:   Option<usize>,
+  max_wall_time: Option<Duration>,
+  max_cost:      Option<(f64, CostEstimate)>,
+}
+
+type CostEstimate = Box<dyn Fn(Signal) -> f64 + Sync>;
+
+/// The limit of a [`Budget`] that stopped a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLimit {
+  Signals,
+  WallTime,
+  Cost,
+}
+
+impl Budget {
+  /// Create a budget with no limits.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the number of signals evaluated.
+  pub fn with_max_signals(mut self, max: usize) -> Self {
+    self.max_signals = Some(max);
+    self
+  }
+
+  /// Limit the wall time of the run. Passes already started finish, so a
+  /// run can overshoot by up to one pass.
+  pub fn with_max_wall_time(mut self, max: Duration) -> Self {
+    self.max_wall_time = Some(max);
+    self
+  }
+
+  /// Limit the total estimated cost of the signals evaluated, with the given
+  /// estimate of each signal's cost.
+  pub fn with_max_cost(
+    mut self,
+    max: f64,
+    cost: impl Fn(Signal) -> f64 + Sync + 'static,
+  ) -> Self {
+    self.max_cost = Some((max, Box::new(cost)));
+    self
+  }
+
+  /// Decide whether the given pass may start, updating the report's spend if
+  /// it may.
+  pub(crate) fn admit(
+    &self,
+    index: usize,
+    pass: &EvaluationPassDescriptor,
+    elapsed: Duration,
+    report: &mut BudgetReport,
+  ) -> bool {
+    let cost = self.max_cost.as_ref().map_or(0.0, |(_, cost)| {
+      pass.targets().iter().map(|s| cost(*s)).sum()
+    });
+    let exhausted = if self.max_wall_time.is_some_and(|max| elapsed >= max) {
+      Some(BudgetLimit::WallTime)
+    } else if self
+      .max_signals
+      .is_some_and(|max| report.signals + pass.targets().len() > max)
+    {
+      Some(BudgetLimit::Signals)
+    } else if self
+      .max_cost
+      .as_ref()
+      .is_some_and(|(max, _)| report.cost + cost > *max)
+    {
+      Some(BudgetLimit::Cost)
+    } else {
+      None
+    };
+
+    match exhausted {
+      Some(limit) => {
+        tracing::info!(pass = index, ?limit, "evaluation budget exhausted");
+        report.exhausted = Some((limit, index));
+        false
+      }
+      None => {
+        report.signals += pass.targets().len();
+        report.cost += cost;
+        true
+      }
+    }
+  }
+}
+
+impl fmt::Debug for Budget {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Budget")
+      .field("max_signals", &self.max_signals)
+      .field("max_wall_time", &self.max_wall_time)
+      .field("max_cost", &self.max_cost.as_ref().map(|(max, _)| max))
+      .finish()
+  }
+}
+
+/// What a budgeted run spent, and what it left unevaluated if it stopped
+/// early.
+#[derive(Debug, Clone, Default)]
+pub struct BudgetReport {
+  pub(crate) signals:     usize,
+  pub(crate) cost:        f64,
+  pub(crate) exhausted:   Option<(BudgetLimit, usize)>,
+  pub(crate) unevaluated: SignalSet,
+}
+
+impl BudgetReport {
+  /// Check whether the run evaluated every pass.
+  pub fn is_complete(&self) -> bool { self.exhausted.is_none() }
+
+  /// Get the limit that stopped the run and the index of the first pass it
+  /// didn't start, if it stopped early.
+  pub fn exhausted(&self) -> Option<(BudgetLimit, usize)> { self.exhausted }
+
+  /// Get the number of signals evaluated.
+  pub fn signals_evaluated(&self) -> usize { self.signals }
+
+  /// Get the total estimated cost of the signals evaluated.
+  pub fn cost(&self) -> f64 { self.cost }
+
+  /// Get the targets of the passes that didn't run.
+  pub fn unevaluated(&self) -> &SignalSet { &self.unevaluated }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Run the planned evaluation like
+  /// [`run_with`](PlannedEvaluation::run_with) until the budget runs out,
+  /// returning the partial results and a report of what was left
+  /// unevaluated.
+  pub fn run_budgeted(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    budget: &Budget,
+  ) -> (EvaluationValueMap<T>, BudgetReport) {
+    let mut report = BudgetReport::default();
+    let values = self.execute(values, options, RunHooks {
+      budget: Some((budget, &mut report)),
+      ..Default::default()
+    });
+    if let Some((_, stopped)) = report.exhausted {
+      for pass in self.passes()[stopped..].iter() {
+        report.unevaluated.extend(pass.targets().iter().copied());
+      }
+    }
+    (values, report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_budget_stops_at_pass_boundary() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 16,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let widths: Vec<_> =
+      plan.passes().iter().map(|p| p.targets().len()).collect();
+
+    // enough for the first pass and part of the second
+    let budget = Budget::new().with_max_signals(widths[0] + 1);
+    let (values, report) = plan.run_budgeted(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+      &budget,
+    );
+    assert_eq!(report.exhausted(), Some((BudgetLimit::Signals, 1)));
+    assert_eq!(report.signals_evaluated(), widths[0]);
+    assert_eq!(
+      report.unevaluated().len(),
+      widths[1..].iter().sum::<usize>()
+    );
+    for root in roots.iter() {
+      assert!(report.unevaluated().contains(root));
+      assert_eq!(values.get(*root), None);
+    }
+    for signal in plan.passes()[0].targets() {
+      assert!(values.get(*signal).is_some());
+    }
+
+    let budget = Budget::new().with_max_cost(1e9, |_| 1.0);
+    let (_, report) = plan.run_budgeted(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+      &budget,
+    );
+    assert!(report.is_complete());
+    assert_eq!(report.cost(), widths.iter().sum::<usize>() as f64);
+  }
+}
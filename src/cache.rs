@@ -0,0 +1,319 @@
+use std::{
+  collections::HashMap,
+  fs,
+  hash::Hasher,
+  io::{self, Read, Write},
+  path::Path,
+};
+
+use num_traits::Float;
+
+use crate::{
+  distributed::{read_bytes, read_u64, WireValue},
+  siphash::SipHasher128,
+  EvaluationPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+  Signal, SignalDef, SignalDefMap, SignalMap, SignalMatrix, UnaryOp,
+};
+
+/// Content hashing for signal definitions and values, used to key a
+/// [`ResultCache`].
+///
+/// A definition hashes only its own operation and parameters, not the IDs of
+/// its dependencies: the cache combines it with the hashes of its
+/// dependencies, in [`SignalDef::dependencies_into`] order. Hashes must be
+/// stable across processes, so don't hash pointers or randomly seeded state.
+pub trait ContentHash {
+  /// Feed this definition's or value's content into the hasher.
+  fn content_hash(&self, state: &mut impl Hasher);
+}
+
+impl ContentHash for f64 {
+  fn content_hash(&self, state: &mut impl Hasher) {
+    state.write_u64(self.to_bits());
+  }
+}
+
+impl ContentHash for f32 {
+  fn content_hash(&self, state: &mut impl Hasher) {
+    state.write_u32(self.to_bits());
+  }
+}
+
+impl<F: Float> ContentHash for FloatMapSignalDef<F> {
+  fn content_hash(&self, state: &mut impl Hasher) {
+    let tag = match self {
+      FloatMapSignalDef::Constant(value) => {
+        let (mantissa, exponent, sign) = value.integer_decode();
+        state.write_u64(mantissa);
+        state.write_i16(exponent);
+        state.write_i8(sign);
+        0
+      }
+      FloatMapSignalDef::UnaryOp(UnaryOp::Neg(_)) => 1,
+      FloatMapSignalDef::BinaryOp(op) => match op {
+        FloatBinaryOp::Add(..) => 2,
+        FloatBinaryOp::Sub(..) => 3,
+        FloatBinaryOp::Mul(..) => 4,
+        FloatBinaryOp::Div(..) => 5,
+        FloatBinaryOp::Pow(..) => 6,
+      },
+    };
+    state.write_u8(tag);
+  }
+}
+
+impl<T: SignalDef + ContentHash> SignalMatrix<T>
+where
+  T::Value: ContentHash,
+{
+  /// Get the content key of each of the given signals: a 128-bit SipHash of
+  /// the signal's definition and, recursively, its dependencies. Signals with
+  /// a value in `inputs` hash that value instead of their definition, so the
+  /// key changes whenever an input does.
+  ///
+  /// Signals with equal keys compute the same value, across graphs and
+  /// processes, as long as their definitions are deterministic. Keys are wide
+  /// enough that distinct subgraphs don't collide by chance, but aren't a
+  /// defense against deliberately crafted collisions.
+  pub fn content_keys(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+    inputs: &EvaluationValueMap<T>,
+  ) -> SignalMap<u128> {
+    self.defset().content_keys(signals, inputs)
+  }
+}
+
+impl<T: SignalDef + ContentHash> SignalDefMap<T>
+where
+  T::Value: ContentHash,
+{
+  /// Get the content key of each of the given signals, like
+  /// [`SignalMatrix::content_keys`].
+  pub(crate) fn content_keys(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+    inputs: &EvaluationValueMap<T>,
+  ) -> SignalMap<u128> {
+    let mut keys = SignalMap::default();
+    let mut deps = Vec::new();
+    // post-order walk, so dependencies are keyed before their dependents
+    let mut stack: Vec<_> = signals.into_iter().map(|s| (s, false)).collect();
+    while let Some((signal, expanded)) = stack.pop() {
+      if keys.contains_key(&signal) {
+        continue;
+      }
+      let mut hasher = SipHasher128::default();
+      if let Some(value) = inputs.get(signal) {
+        hasher.write_u8(0);
+        value.content_hash(&mut hasher);
+        keys.insert(signal, hasher.finish128());
+        continue;
+      }
+      let Some(def) = self.get(signal) else {
+        continue;
+      };
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      if !expanded {
+        stack.push((signal, true));
+        stack.extend(deps.iter().map(|dep| (*dep, false)));
+        continue;
+      }
+      hasher.write_u8(1);
+      def.content_hash(&mut hasher);
+      for dep in deps.iter() {
+        hasher.write_u128(keys.get(dep).copied().unwrap_or_default());
+      }
+      keys.insert(signal, hasher.finish128());
+    }
+    keys
+  }
+}
+
+/// A cache of root results keyed by [content key](SignalMatrix::content_keys),
+/// so a root whose subgraph and inputs were evaluated before, in any graph,
+/// is answered without being scheduled.
+///
+/// The cache lives in memory and can be saved to and loaded from a file to
+/// share it across processes.
+#[derive(Debug, Clone)]
+pub struct ResultCache<V> {
+  entries: HashMap<u128, V>,
+  hits:    usize,
+  misses:  usize,
+}
+
+impl<V> Default for ResultCache<V> {
+  fn default() -> Self {
+    ResultCache {
+      entries: HashMap::new(),
+      hits:    0,
+      misses:  0,
+    }
+  }
+}
+
+impl<V: Clone> ResultCache<V> {
+  /// Create an empty cache.
+  pub fn new() -> Self { Self::default() }
+
+  /// Get the number of cached results.
+  pub fn len(&self) -> usize { self.entries.len() }
+
+  /// Check whether the cache is empty.
+  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+  /// Get the number of roots answered from the cache so far.
+  pub fn hits(&self) -> usize { self.hits }
+
+  /// Get the number of roots that had to be evaluated so far.
+  pub fn misses(&self) -> usize { self.misses }
+
+  /// Evaluate the given root targets, answering the ones with a cached
+  /// result from the cache and caching the results of the rest. Values
+  /// already in the map are treated as inputs.
+  pub fn evaluate<T, P>(
+    &mut self,
+    matrix: &SignalMatrix<T>,
+    planner: &P,
+    root_targets: impl IntoIterator<Item = Signal>,
+    mut values: EvaluationValueMap<T>,
+  ) -> EvaluationValueMap<T>
+  where
+    T: SignalDef<Value = V> + ContentHash,
+    V: ContentHash,
+    P: EvaluationPlanner<T> + ?Sized,
+  {
+    let roots: Vec<_> = root_targets.into_iter().collect();
+    let keys = matrix.content_keys(roots.iter().copied(), &values);
+
+    let (mut hits, mut misses) = (0, Vec::new());
+    for root in roots {
+      if values.get(root).is_some() {
+        continue;
+      }
+      match keys.get(&root).and_then(|key| self.entries.get(key)) {
+        Some(value) => {
+          values.insert(root, value.clone());
+          hits += 1;
+        }
+        None => misses.push(root),
+      }
+    }
+    tracing::info!(hits, misses = misses.len(), "checked result cache");
+    self.hits += hits;
+    self.misses += misses.len();
+
+    let plan = matrix
+      .plan_evaluation(planner, misses.iter().copied())
+      .skip_evaluated(&values);
+    let values = plan.run(values);
+    for root in misses {
+      if let (Some(key), Some(value)) = (keys.get(&root), values.get(root)) {
+        self.entries.insert(*key, value.clone());
+      }
+    }
+    values
+  }
+}
+
+impl<V: WireValue> ResultCache<V> {
+  /// Load a cache saved with [`save`](Self::save).
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+    let mut entries = HashMap::new();
+    let mut encoded = Vec::new();
+    for _ in 0..read_u64(&mut file)? {
+      let mut key = [0; 16];
+      file.read_exact(&mut key)?;
+      let key = u128::from_le_bytes(key);
+      read_bytes(&mut file, &mut encoded)?;
+      entries.insert(key, V::decode(&encoded)?);
+    }
+    Ok(ResultCache {
+      entries,
+      hits: 0,
+      misses: 0,
+    })
+  }
+
+  /// Save the cache's results to a file.
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    out.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+    let mut encoded = Vec::new();
+    for (key, value) in self.entries.iter() {
+      encoded.clear();
+      value.encode(&mut encoded);
+      out.write_all(&key.to_le_bytes())?;
+      out.write_all(&(encoded.len() as u64).to_le_bytes())?;
+      out.write_all(&encoded)?;
+    }
+    out.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, SignalDefMap};
+
+  fn build(rate: f64) -> (SignalMatrix<FloatMapSignalDef>, Signal, Signal) {
+    let mut defset = SignalDefMap::new();
+    // an unrelated signal first, so IDs differ from the other graph's
+    defset.insert(FloatMapSignalDef::Constant(rate));
+    let principal = defset.insert(FloatMapSignalDef::Constant(100.0));
+    let rate = defset.insert(FloatMapSignalDef::Constant(rate));
+    let interest = defset.insert(FloatMapSignalDef::BinaryOp(
+      FloatBinaryOp::Mul(principal, rate),
+    ));
+    let total = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(
+      interest, principal,
+    )));
+    (SignalMatrix::new(defset), rate, total)
+  }
+
+  #[test]
+  fn test_result_cache() {
+    let empty = || EvaluationValueMap::new_empty(Default::default());
+    let mut cache = ResultCache::new();
+
+    let (matrix, _, total) = build(0.5);
+    let values =
+      cache.evaluate(&matrix, &CustomPlanner::new(), [total], empty());
+    assert_eq!(values.get(total), Some(&-50.0));
+    assert_eq!((cache.hits(), cache.misses()), (0, 1));
+
+    // an identical graph built from scratch hits the cache
+    let (matrix, rate, total) = build(0.5);
+    let values =
+      cache.evaluate(&matrix, &CustomPlanner::new(), [total], empty());
+    assert_eq!(values.get(total), Some(&-50.0));
+    assert_eq!(values.get(rate), None);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+    // a different input value misses
+    let mut inputs = empty();
+    inputs.insert(rate, 2.0);
+    let values =
+      cache.evaluate(&matrix, &CustomPlanner::new(), [total], inputs);
+    assert_eq!(values.get(total), Some(&100.0));
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+    // and the cache carries over to another process through a file
+    let path = std::env::temp_dir()
+      .join(format!("matrix-result-cache-{}", std::process::id()));
+    cache.save(&path).unwrap();
+    let mut loaded = ResultCache::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), 2);
+    let (matrix, rate, total) = build(0.5);
+    let mut inputs = empty();
+    inputs.insert(rate, 2.0);
+    let values =
+      loaded.evaluate(&matrix, &CustomPlanner::new(), [total], inputs);
+    assert_eq!(values.get(total), Some(&100.0));
+    assert_eq!(loaded.hits(), 1);
+  }
+}
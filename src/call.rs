@@ -0,0 +1,199 @@
+use std::{fmt, sync::Arc};
+
+use crate::{EvalContext, Signal, SignalDef, SignalDefMap, SignalMap};
+
+/// A reusable sub-definition, called from [`CallSignalDef::Apply`] signals
+/// with different arguments at each call site.
+///
+/// The body is a small definition map of its own. Parameters are signals in
+/// the body whose values are bound to the call's arguments, so their own
+/// definitions are never evaluated.
+pub struct Function<T: SignalDef + Send> {
+  body:   SignalDefMap<CallSignalDef<T>>,
+  params: Vec<Signal>,
+  output: Signal,
+  /// Body signals to evaluate for the output, dependencies first.
+  order:  Vec<Signal>,
+}
+
+impl<T: SignalDef + Send> Function<T> {
+  /// Create a function computing `output` in `body` from the given parameter
+  /// signals.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the output is a parameter, or if the body is missing a
+  /// definition or has a cycle between the parameters and the output.
+  pub fn new(
+    body: SignalDefMap<CallSignalDef<T>>,
+    params: Vec<Signal>,
+    output: Signal,
+  ) -> Self {
+    assert!(!params.contains(&output), "function output is a parameter");
+
+    // depth-first post-order from the output, stopping at parameters
+    let mut order = Vec::new();
+    let mut done = SignalMap::default();
+    let mut stack = vec![(output, false)];
+    let mut deps = Vec::new();
+    while let Some((signal, expanded)) = stack.pop() {
+      if params.contains(&signal) || done.get(&signal) == Some(&true) {
+        continue;
+      }
+      if expanded {
+        done.insert(signal, true);
+        order.push(signal);
+        continue;
+      }
+      assert!(
+        done.insert(signal, false).is_none(),
+        "function body has a cycle through signal #{}",
+        signal.0
+      );
+      let def = body
+        .get(signal)
+        .unwrap_or_else(|| panic!("function body is missing #{}", signal.0));
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      stack.push((signal, true));
+      stack.extend(deps.iter().map(|dep| (*dep, false)));
+    }
+
+    Function {
+      body,
+      params,
+      output,
+      order,
+    }
+  }
+
+  /// Get the number of arguments the function takes.
+  pub fn arity(&self) -> usize { self.params.len() }
+
+  /// Evaluate the function with the given argument values.
+  pub fn call(&self, args: &[&T::Value]) -> T::Value {
+    assert_eq!(args.len(), self.arity(), "wrong number of arguments");
+    let mut locals = SignalMap::<T::Value>::default();
+    let mut deps = Vec::new();
+    for signal in self.order.iter() {
+      let def = self.body.get(*signal).unwrap();
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      let values = deps.iter().map(|dep| {
+        let value = match self.params.iter().position(|p| p == dep) {
+          Some(i) => args[i],
+          None => &locals[dep],
+        };
+        (*dep, value)
+      });
+      let value = def.evaluate(&EvalContext::for_def(*signal, def, values));
+      locals.insert(*signal, value);
+    }
+    locals.remove(&self.output).unwrap()
+  }
+}
+
+impl<T: SignalDef + Send> fmt::Debug for Function<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Function")
+      .field("params", &self.params)
+      .field("output", &self.output)
+      .field("body_len", &self.body.len())
+      .finish()
+  }
+}
+
+/// A signal definition that is either a plain definition or a call of a
+/// shared [`Function`], so a repeated graph pattern is defined once instead
+/// of being duplicated at every site that uses it.
+#[derive(Debug)]
+pub enum CallSignalDef<T: SignalDef + Send> {
+  Def(T),
+  /// Call the function with these signals as its arguments.
+  Apply(Arc<Function<T>>, Vec<Signal>),
+}
+
+impl<T: SignalDef + Send> CallSignalDef<T> {
+  /// Create a call of the given function.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the number of arguments doesn't match the function's arity.
+  pub fn apply(function: Arc<Function<T>>, args: Vec<Signal>) -> Self {
+    assert_eq!(args.len(), function.arity(), "wrong number of arguments");
+    CallSignalDef::Apply(function, args)
+  }
+}
+
+impl<T: SignalDef + Send> From<T> for CallSignalDef<T> {
+  fn from(def: T) -> Self { CallSignalDef::Def(def) }
+}
+
+impl<T: SignalDef + Send> SignalDef for CallSignalDef<T> {
+  type Value = T::Value;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self {
+      CallSignalDef::Def(def) => def.dependencies_into(out),
+      CallSignalDef::Apply(_, args) => out.extend_from_slice(args),
+    }
+  }
+
+  fn dependency_roles_into(&self, out: &mut Vec<(Signal, &'static str)>) {
+    if let CallSignalDef::Def(def) = self {
+      def.dependency_roles_into(out);
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    match self {
+      CallSignalDef::Def(def) => def.evaluate(&EvalContext {
+        signal:    ctx.signal,
+        values:    ctx.values.clone(),
+        roles:     ctx.roles.clone(),
+        summation: ctx.summation,
+      }),
+      CallSignalDef::Apply(function, args) => {
+        let args: Vec<_> = args.iter().map(|arg| ctx.values[arg]).collect();
+        function.call(&args)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalMatrix,
+  };
+
+  #[test]
+  fn test_apply() {
+    type Def = CallSignalDef<FloatMapSignalDef>;
+    let op = |op| Def::Def(FloatMapSignalDef::BinaryOp(op));
+
+    // ema(prev, x) = prev + 0.5 * (x - prev)
+    let mut body = SignalDefMap::<Def>::new();
+    let prev = body.insert(FloatMapSignalDef::Constant(0.0).into());
+    let x = body.insert(FloatMapSignalDef::Constant(0.0).into());
+    let alpha = body.insert(FloatMapSignalDef::Constant(0.5).into());
+    let delta = body.insert(op(FloatBinaryOp::Sub(x, prev)));
+    let step = body.insert(op(FloatBinaryOp::Mul(alpha, delta)));
+    let next = body.insert(op(FloatBinaryOp::Add(prev, step)));
+    let ema = Arc::new(Function::new(body, vec![prev, x], next));
+
+    let mut defset = SignalDefMap::<Def>::new();
+    let mut average = defset.insert(FloatMapSignalDef::Constant(0.0).into());
+    for sample in [4.0, 8.0, 8.0] {
+      let sample = defset.insert(FloatMapSignalDef::Constant(sample).into());
+      average = defset.insert(Def::apply(ema.clone(), vec![average, sample]));
+    }
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [average]);
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(average), Some(&6.5));
+  }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+
+use arrow::{
+  array::{Array, ArrayRef, AsArray, Datum, RecordBatch, Scalar},
+  compute::{
+    filter, filter_record_batch,
+    kernels::{cmp, numeric},
+  },
+  datatypes::DataType,
+  error::ArrowError,
+};
+
+use crate::{EvalContext, Signal, SignalDef};
+
+/// The value of an [`ArrowSignalDef`]: an Arrow array, scalar or record batch.
+///
+/// All of them are reference counted, so passing a value to a dependent signal,
+/// or pulling a column out of a batch, shares the underlying buffers instead of
+/// copying them.
+#[derive(Debug, Clone)]
+pub enum ArrowValue {
+  Array(ArrayRef),
+  /// A single value, broadcast against arrays by elementwise operations.
+  Scalar(Scalar<ArrayRef>),
+  Batch(RecordBatch),
+}
+
+impl ArrowValue {
+  /// Get the array, if this value is one.
+  pub fn as_array(&self) -> Option<&ArrayRef> {
+    match self {
+      ArrowValue::Array(array) => Some(array),
+      ArrowValue::Scalar(_) | ArrowValue::Batch(_) => None,
+    }
+  }
+
+  /// Get the record batch, if this value is one.
+  pub fn as_batch(&self) -> Option<&RecordBatch> {
+    match self {
+      ArrowValue::Batch(batch) => Some(batch),
+      ArrowValue::Array(_) | ArrowValue::Scalar(_) => None,
+    }
+  }
+}
+
+/// A signal definition for a columnar operation over [`ArrowValue`]s, so the
+/// engine can schedule column-oriented analytics pipelines.
+///
+/// Evaluates to a `Result`: a signal whose Arrow kernel fails holds an
+/// [`ArrowSignalError`] naming it, and every signal depending on it evaluates
+/// to that same error.
+#[derive(Debug)]
+pub enum ArrowSignalDef {
+  Constant(ArrowValue),
+  /// Assemble named arrays into a record batch.
+  Batch(Vec<(String, Signal)>),
+  /// Get the named column of a record batch.
+  Column(Signal, String),
+  /// Elementwise arithmetic on arrays and scalars.
+  Add(Signal, Signal),
+  Sub(Signal, Signal),
+  Mul(Signal, Signal),
+  Div(Signal, Signal),
+  /// Elementwise comparisons of arrays and scalars, producing boolean arrays.
+  Eq(Signal, Signal),
+  Lt(Signal, Signal),
+  Gt(Signal, Signal),
+  /// Keep the rows of an array or record batch where a boolean array is true.
+  Filter(Signal, Signal),
+}
+
+/// An error evaluating an [`ArrowSignalDef`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrowSignalError {
+  signal:  Signal,
+  message: String,
+}
+
+impl ArrowSignalError {
+  /// Get the signal where the error originated.
+  pub fn signal(&self) -> Signal { self.signal }
+
+  /// Get a description of the error.
+  pub fn message(&self) -> &str { &self.message }
+}
+
+impl fmt::Display for ArrowSignalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "signal #{}: {}", self.signal.0, self.message)
+  }
+}
+
+impl std::error::Error for ArrowSignalError {}
+
+impl SignalDef for ArrowSignalDef {
+  type Value = Result<ArrowValue, ArrowSignalError>;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self {
+      ArrowSignalDef::Constant(_) => {}
+      ArrowSignalDef::Batch(columns) => {
+        out.extend(columns.iter().map(|(_, s)| *s))
+      }
+      ArrowSignalDef::Column(s, _) => out.push(*s),
+      ArrowSignalDef::Add(a, b)
+      | ArrowSignalDef::Sub(a, b)
+      | ArrowSignalDef::Mul(a, b)
+      | ArrowSignalDef::Div(a, b)
+      | ArrowSignalDef::Eq(a, b)
+      | ArrowSignalDef::Lt(a, b)
+      | ArrowSignalDef::Gt(a, b)
+      | ArrowSignalDef::Filter(a, b) => out.extend([*a, *b]),
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    let error = |message: String| ArrowSignalError {
+      signal: ctx.signal(),
+      message,
+    };
+    let value = |s: &Signal| ctx.values[s].as_ref().map_err(Clone::clone);
+    let array = |s: &Signal| {
+      value(s)?
+        .as_array()
+        .ok_or_else(|| error(format!("signal #{} is not an array", s.0)))
+    };
+    let datum = |s: &Signal| match value(s)? {
+      ArrowValue::Array(array) => Ok(array as &dyn Datum),
+      ArrowValue::Scalar(scalar) => Ok(scalar as &dyn Datum),
+      ArrowValue::Batch(_) => {
+        Err(error(format!("signal #{} is a batch, not a column", s.0)))
+      }
+    };
+    let arrow = |e: ArrowError| error(e.to_string());
+    type Kernel = fn(&dyn Datum, &dyn Datum) -> Result<ArrayRef, ArrowError>;
+    let binary = |a, b, kernel: Kernel| {
+      kernel(datum(a)?, datum(b)?)
+        .map(ArrowValue::Array)
+        .map_err(arrow)
+    };
+
+    match self {
+      ArrowSignalDef::Constant(value) => Ok(value.clone()),
+      ArrowSignalDef::Batch(columns) => {
+        let columns = columns
+          .iter()
+          .map(|(name, s)| Ok((name.as_str(), array(s)?.clone())))
+          .collect::<Result<Vec<_>, _>>()?;
+        RecordBatch::try_from_iter(columns)
+          .map(ArrowValue::Batch)
+          .map_err(arrow)
+      }
+      ArrowSignalDef::Column(s, name) => {
+        let batch = value(s)?
+          .as_batch()
+          .ok_or_else(|| error(format!("signal #{} is not a batch", s.0)))?;
+        batch
+          .column_by_name(name)
+          .map(|column| ArrowValue::Array(column.clone()))
+          .ok_or_else(|| error(format!("no column named `{name}`")))
+      }
+      ArrowSignalDef::Add(a, b) => binary(a, b, numeric::add),
+      ArrowSignalDef::Sub(a, b) => binary(a, b, numeric::sub),
+      ArrowSignalDef::Mul(a, b) => binary(a, b, numeric::mul),
+      ArrowSignalDef::Div(a, b) => binary(a, b, numeric::div),
+      ArrowSignalDef::Eq(a, b) => {
+        binary(a, b, |a, b| Ok(std::sync::Arc::new(cmp::eq(a, b)?)))
+      }
+      ArrowSignalDef::Lt(a, b) => {
+        binary(a, b, |a, b| Ok(std::sync::Arc::new(cmp::lt(a, b)?)))
+      }
+      ArrowSignalDef::Gt(a, b) => {
+        binary(a, b, |a, b| Ok(std::sync::Arc::new(cmp::gt(a, b)?)))
+      }
+      ArrowSignalDef::Filter(s, mask) => {
+        let mask = array(mask)?;
+        if mask.data_type() != &DataType::Boolean {
+          return Err(error(format!(
+            "filter mask has type {}, not Boolean",
+            mask.data_type()
+          )));
+        }
+        let mask = mask.as_boolean();
+        match value(s)? {
+          ArrowValue::Array(array) => {
+            filter(array, mask).map(ArrowValue::Array).map_err(arrow)
+          }
+          ArrowValue::Batch(batch) => filter_record_batch(batch, mask)
+            .map(ArrowValue::Batch)
+            .map_err(arrow),
+          ArrowValue::Scalar(_) => {
+            Err(error(format!("signal #{} is a scalar", s.0)))
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use arrow::array::{Float64Array, Int64Array};
+
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_arrow_pipeline() {
+    let mut defset = SignalDefMap::new();
+
+    let prices: ArrayRef = Arc::new(Float64Array::from(vec![10.0, 25.0, 40.0]));
+    let prices_buffer = prices.to_data().buffers()[0].as_ptr();
+    let qty: ArrayRef = Arc::new(Float64Array::from(vec![3.0, 1.0, 2.0]));
+    let orders =
+      RecordBatch::try_from_iter([("price", prices), ("qty", qty)]).unwrap();
+
+    let orders =
+      defset.insert(ArrowSignalDef::Constant(ArrowValue::Batch(orders)));
+    let min = defset.insert(ArrowSignalDef::Constant(ArrowValue::Scalar(
+      Scalar::new(Arc::new(Float64Array::from(vec![26.0]))),
+    )));
+    let price = defset.insert(ArrowSignalDef::Column(orders, "price".into()));
+    let qty = defset.insert(ArrowSignalDef::Column(orders, "qty".into()));
+    let revenue = defset.insert(ArrowSignalDef::Mul(price, qty));
+    let big = defset.insert(ArrowSignalDef::Gt(revenue, min));
+    let with_revenue = defset.insert(ArrowSignalDef::Batch(vec![
+      ("price".into(), price),
+      ("revenue".into(), revenue),
+    ]));
+    let big_orders = defset.insert(ArrowSignalDef::Filter(with_revenue, big));
+    let wrong = defset.insert(ArrowSignalDef::Add(orders, price));
+    let ints = defset.insert(ArrowSignalDef::Constant(ArrowValue::Array(
+      Arc::new(Int64Array::from(vec![1, 2, 3])),
+    )));
+    let mixed = defset.insert(ArrowSignalDef::Add(price, ints));
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval =
+      matrix.plan_evaluation(&CustomPlanner::new(), [big_orders, wrong, mixed]);
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+
+    let batch = values.get(big_orders).unwrap().as_ref().unwrap();
+    let batch = batch.as_batch().unwrap();
+    assert_eq!(batch.num_rows(), 2);
+    let revenue = batch.column_by_name("revenue").unwrap();
+    assert_eq!(
+      revenue
+        .as_primitive::<arrow::datatypes::Float64Type>()
+        .values(),
+      &[30.0, 80.0]
+    );
+
+    // the column shares the batch's buffer rather than copying it
+    let column = values.get(price).unwrap().as_ref().unwrap();
+    let column = column.as_array().unwrap().to_data();
+    assert_eq!(column.buffers()[0].as_ptr(), prices_buffer);
+
+    assert!(values.get(wrong).unwrap().is_err());
+    assert_eq!(
+      values.get(mixed).unwrap().as_ref().unwrap_err().signal(),
+      mixed
+    );
+  }
+}
@@ -0,0 +1,216 @@
+use crate::{Signal, SignalDef, SignalMap, SignalMatrix, SignalSet};
+
+/// A group of root targets whose dependency closures overlap, from
+/// [`SignalMatrix::components`].
+#[derive(Debug, Clone)]
+pub struct Component {
+  roots:   Vec<Signal>,
+  signals: SignalSet,
+  shared:  SignalSet,
+}
+
+impl Component {
+  /// Get the roots in the component, by ID.
+  pub fn roots(&self) -> &[Signal] { &self.roots }
+
+  /// Get every signal the component's roots need, including the roots.
+  pub fn signals(&self) -> &SignalSet { &self.signals }
+
+  /// Get the signals more than one of the component's roots need. Empty for
+  /// a component with a single root.
+  pub fn shared(&self) -> &SignalSet { &self.shared }
+}
+
+/// The root targets of a graph grouped by whether they share any
+/// dependencies, from [`SignalMatrix::components`]. Roots in different
+/// components can be evaluated separately, e.g. in other processes, without
+/// evaluating anything twice.
+#[derive(Debug, Clone, Default)]
+pub struct Components {
+  components: Vec<Component>,
+  assignment: SignalMap<usize>,
+}
+
+impl Components {
+  /// Get the components, ordered by their lowest root ID.
+  pub fn components(&self) -> &[Component] { &self.components }
+
+  /// Get the number of components.
+  pub fn len(&self) -> usize { self.components.len() }
+
+  /// Whether there are no components, because there were no roots.
+  pub fn is_empty(&self) -> bool { self.components.is_empty() }
+
+  /// Get the index of the component holding the given root.
+  pub fn component_of(&self, root: Signal) -> Option<usize> {
+    self.assignment.get(&root).copied()
+  }
+
+  /// Check whether two roots share no dependencies, directly or through
+  /// other roots. Unknown roots are never independent.
+  pub fn are_independent(&self, a: Signal, b: Signal) -> bool {
+    match (self.component_of(a), self.component_of(b)) {
+      (Some(a), Some(b)) => a != b,
+      _ => false,
+    }
+  }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+  while parent[i] != i {
+    parent[i] = parent[parent[i]];
+    i = parent[i];
+  }
+  i
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Group the given roots into components whose dependency closures
+  /// overlap. Two roots land in one component if they need a common signal,
+  /// or if each shares one with a third root.
+  pub fn components(
+    &self,
+    roots: impl IntoIterator<Item = Signal>,
+  ) -> Components {
+    let mut roots: Vec<_> = roots.into_iter().collect();
+    roots.sort_by_key(|s| s.0);
+    roots.dedup();
+    let span = tracing::info_span!("components", roots = roots.len());
+    let _enter = span.enter();
+
+    // one walk from each root over union-find of root indices, stopping at
+    // signals an earlier root's walk reached: the two roots join there, and
+    // share that signal and everything below it
+    let space = roots
+      .iter()
+      .map(|root| root.index() + 1)
+      .fold(self.dependency_graph().len(), usize::max);
+    let mut parent: Vec<usize> = (0..roots.len()).collect();
+    let mut owner = vec![None; space];
+    let mut reached = Vec::new();
+    let mut meets = Vec::new();
+    let mut stack = Vec::new();
+    for (i, root) in roots.iter().enumerate() {
+      stack.push(*root);
+      while let Some(signal) = stack.pop() {
+        match owner[signal.index()] {
+          None => {
+            owner[signal.index()] = Some(i);
+            reached.push(signal);
+            stack.extend_from_slice(self.direct_dependencies(signal));
+          }
+          Some(j) if j != i => {
+            let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+            parent[a.max(b)] = a.min(b);
+            meets.push(signal);
+          }
+          Some(_) => {}
+        }
+      }
+    }
+    let mut shared = self.dependencies_closure(meets.iter().copied());
+    shared.extend(meets);
+
+    // components are ordered by their lowest root, as roots are sorted
+    let mut components = Components::default();
+    let mut index_of = vec![None; roots.len()];
+    for (i, root) in roots.iter().enumerate() {
+      let leader = find(&mut parent, i);
+      let index = *index_of[leader].get_or_insert_with(|| {
+        components.components.push(Component {
+          roots:   Vec::new(),
+          signals: SignalSet::default(),
+          shared:  SignalSet::default(),
+        });
+        components.components.len() - 1
+      });
+      components.components[index].roots.push(*root);
+      components.assignment.insert(*root, index);
+    }
+    for signal in reached {
+      let root = owner[signal.index()].unwrap();
+      let index = index_of[find(&mut parent, root)].unwrap();
+      let component = &mut components.components[index];
+      if shared.contains(&signal) {
+        component.shared.insert(signal);
+      }
+      component.signals.insert(signal);
+    }
+    components
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    FloatBinaryOp, FloatMapSignalDef, SignalDefMap, SignalMatrix, SignalSet,
+  };
+
+  #[test]
+  fn test_components() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let add = |x, y| FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(x, y));
+    let ab = defset.insert(add(a, b));
+    let bc = defset.insert(add(b, c));
+    let lone = defset.insert(add(c, c));
+    let other = defset.insert(FloatMapSignalDef::Constant(4.0));
+    let matrix = SignalMatrix::new(defset);
+
+    // `ab` and `bc` share `b`; `lone` joins them through `c`
+    let components = matrix.components([other, bc, ab, lone]);
+    assert_eq!(components.len(), 2);
+    let joined = &components.components()[0];
+    assert_eq!(joined.roots(), [ab, bc, lone]);
+    assert_eq!(joined.signals().len(), 6);
+    assert_eq!(joined.shared().len(), 2);
+    assert!(joined.shared().contains(&b) && joined.shared().contains(&c));
+    assert_eq!(components.components()[1].roots(), [other]);
+    assert!(components.components()[1].shared().is_empty());
+    assert!(components.are_independent(ab, other));
+    assert!(!components.are_independent(ab, lone));
+    assert_eq!(components.component_of(a), None);
+  }
+
+  #[test]
+  fn test_components_match_closures() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     5,
+      width:      12,
+      min_fan_in: 1,
+      max_fan_in: 2,
+      reach:      1,
+    })
+    .with_seed(3)
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+
+    // a signal is shared if more than one root's closure holds it
+    let closures: Vec<SignalSet> = roots
+      .iter()
+      .map(|root| {
+        let mut closure = matrix.dependencies_closure([*root]);
+        closure.insert(*root);
+        closure
+      })
+      .collect();
+    let components = matrix.components(roots.iter().copied());
+    for component in components.components() {
+      for signal in component.signals() {
+        let needing = closures.iter().filter(|c| c.contains(signal)).count();
+        assert_eq!(component.shared().contains(signal), needing > 1);
+      }
+    }
+    for (i, a) in roots.iter().enumerate() {
+      for (j, b) in roots.iter().enumerate() {
+        if !closures[i].is_disjoint(&closures[j]) {
+          assert!(!components.are_independent(*a, *b));
+        }
+      }
+    }
+  }
+}
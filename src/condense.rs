@@ -0,0 +1,258 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt::{self, Write},
+};
+
+use crate::{Signal, SignalDef, SignalMatrix, NAMESPACE_SEPARATOR};
+
+/// How [`SignalMatrix::condense`] groups signals into super-nodes.
+pub enum GroupBy<'a, T> {
+  /// Group by the first `depth` segments of the label's namespace, so
+  /// `portfolio.us.equities.total` is in `portfolio.us` at depth 2. Labels
+  /// with fewer segments are their own group; unlabeled signals are left out.
+  Namespace(usize),
+  /// Group by a signal's first tag, in tag order. Untagged signals are left
+  /// out.
+  Tag,
+  /// Group by the definition's variant, as the leading name of its `Debug`
+  /// output, e.g. `BinaryOp` for `BinaryOp(Add(#0, #1))`.
+  Variant,
+  /// Group by a function of each signal, leaving out those it maps to
+  /// `None`.
+  Custom(&'a dyn Fn(Signal, &T) -> Option<String>),
+}
+
+impl<T> fmt::Debug for GroupBy<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      GroupBy::Namespace(depth) => {
+        f.debug_tuple("Namespace").field(depth).finish()
+      }
+      GroupBy::Tag => f.write_str("Tag"),
+      GroupBy::Variant => f.write_str("Variant"),
+      GroupBy::Custom(_) => f.write_str("Custom"),
+    }
+  }
+}
+
+/// A super-node of a [`CondensedGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalGroup {
+  /// The name of the group.
+  pub name:           String,
+  /// The number of signals in the group.
+  pub signals:        usize,
+  /// The number of dependency edges between signals of the group.
+  pub internal_edges: usize,
+}
+
+/// The dependency edges from signals of one group to signals of another, as
+/// indices into [`CondensedGraph::groups`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupEdge {
+  /// The group depended on.
+  pub dependency: usize,
+  /// The group depending on it.
+  pub dependent:  usize,
+  /// The number of signal-level edges the edge stands for.
+  pub count:      usize,
+}
+
+/// A summary of a graph too large to draw node by node, from
+/// [`SignalMatrix::condense`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CondensedGraph {
+  groups:    Vec<SignalGroup>,
+  edges:     Vec<GroupEdge>,
+  ungrouped: usize,
+}
+
+impl CondensedGraph {
+  /// Get the groups, sorted by name.
+  pub fn groups(&self) -> &[SignalGroup] { &self.groups }
+
+  /// Get the edges between groups, sorted by dependency and then dependent.
+  pub fn edges(&self) -> &[GroupEdge] { &self.edges }
+
+  /// Find a group by name.
+  pub fn group(&self, name: &str) -> Option<&SignalGroup> {
+    self.groups.iter().find(|g| g.name == name)
+  }
+
+  /// Get the edge count from the `dependency` group to the `dependent` group,
+  /// if they are connected.
+  pub fn edge_count(&self, dependency: &str, dependent: &str) -> Option<usize> {
+    let index = |name| self.groups.iter().position(|g| g.name == name);
+    let (dependency, dependent) = (index(dependency)?, index(dependent)?);
+    self
+      .edges
+      .iter()
+      .find(|e| e.dependency == dependency && e.dependent == dependent)
+      .map(|e| e.count)
+  }
+
+  /// Get the number of signals that weren't put in any group.
+  pub fn ungrouped(&self) -> usize { self.ungrouped }
+
+  /// Render the summary in Graphviz DOT format, with each group labeled by
+  /// its name and size and each edge by its count.
+  pub fn to_dot(&self) -> String {
+    let mut out = String::from("digraph groups {\n");
+    for (i, group) in self.groups.iter().enumerate() {
+      let name = group.name.replace('\\', "\\\\").replace('"', "\\\"");
+      writeln!(out, "  g{i} [label=\"{name} ({})\"];", group.signals).unwrap();
+    }
+    for edge in self.edges.iter() {
+      writeln!(
+        out,
+        "  g{} -> g{} [label=\"{}\"];",
+        edge.dependency, edge.dependent, edge.count
+      )
+      .unwrap();
+    }
+    out.push_str("}\n");
+    out
+  }
+}
+
+/// Get the leading name of a definition's `Debug` output, which names its
+/// variant for derived impls.
+pub(crate) fn variant_name(def: &impl fmt::Debug) -> String {
+  let mut debug = format!("{def:?}");
+  let end = debug
+    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+    .unwrap_or(debug.len());
+  debug.truncate(end);
+  debug
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Collapse groups of signals into super-nodes, counting the dependency
+  /// edges within and between groups. Edges touching an ungrouped signal are
+  /// dropped.
+  pub fn condense(&self, group_by: GroupBy<'_, T>) -> CondensedGraph {
+    let group_of = |signal: Signal, def: &T| -> Option<String> {
+      match &group_by {
+        GroupBy::Namespace(depth) => {
+          let label = self.defset.label(signal)?;
+          let end = label
+            .match_indices(NAMESPACE_SEPARATOR)
+            .nth(depth.saturating_sub(1))
+            .map_or(label.len(), |(i, _)| i);
+          Some(label[..end].to_string())
+        }
+        GroupBy::Tag => self.defset.tags_of(signal).next().map(str::to_string),
+        GroupBy::Variant => Some(variant_name(def)),
+        GroupBy::Custom(f) => f(signal, def),
+      }
+    };
+
+    let mut names = BTreeMap::new();
+    let mut membership = HashMap::new();
+    let mut ungrouped = 0;
+    for (signal, def) in self.defset.iter() {
+      match group_of(signal, def) {
+        Some(name) => {
+          *names.entry(name.clone()).or_insert(0) += 1;
+          membership.insert(signal, name);
+        }
+        None => ungrouped += 1,
+      }
+    }
+    let index: HashMap<&str, usize> = names
+      .keys()
+      .enumerate()
+      .map(|(i, n)| (n.as_str(), i))
+      .collect();
+    let mut groups: Vec<_> = names
+      .iter()
+      .map(|(name, signals)| SignalGroup {
+        name:           name.clone(),
+        signals:        *signals,
+        internal_edges: 0,
+      })
+      .collect();
+
+    let graph = self.dependency_graph();
+    let mut counts = BTreeMap::new();
+    for (signal, group) in membership.iter() {
+      let dependent = index[group.as_str()];
+      for dep in graph.dependencies(*signal) {
+        let Some(dependency) = membership.get(dep) else {
+          continue;
+        };
+        let dependency = index[dependency.as_str()];
+        match dependency == dependent {
+          true => groups[dependent].internal_edges += 1,
+          false => *counts.entry((dependency, dependent)).or_insert(0) += 1,
+        }
+      }
+    }
+    let edges = counts
+      .into_iter()
+      .map(|((dependency, dependent), count)| GroupEdge {
+        dependency,
+        dependent,
+        count,
+      })
+      .collect();
+
+    CondensedGraph {
+      groups,
+      edges,
+      ungrouped,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap, UnaryOp};
+
+  #[test]
+  fn test_condense() {
+    let mut defset = SignalDefMap::new();
+    let mut us = defset.namespace("book.us");
+    let a = us.insert("a", FloatMapSignalDef::Constant(1.0));
+    let b = us.insert("b", FloatMapSignalDef::Constant(2.0));
+    let us_total = us.insert(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)),
+    );
+    let mut eu = defset.namespace("book.eu");
+    let c = eu.insert("c", FloatMapSignalDef::Constant(3.0));
+    let eu_total = eu.insert(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(c, us_total)),
+    );
+    defset.insert_tagged(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(eu_total)), [
+      "report",
+    ]);
+    let matrix = SignalMatrix::new(defset);
+
+    let summary = matrix.condense(GroupBy::Namespace(2));
+    assert_eq!(summary.ungrouped(), 1);
+    assert_eq!(summary.groups().len(), 2);
+    assert_eq!(summary.group("book.us").unwrap().signals, 3);
+    assert_eq!(summary.group("book.us").unwrap().internal_edges, 2);
+    assert_eq!(summary.edge_count("book.us", "book.eu"), Some(1));
+    assert_eq!(summary.edge_count("book.eu", "book.us"), None);
+    assert_eq!(
+      summary.to_dot(),
+      "digraph groups {\n  g0 [label=\"book.eu (2)\"];\n  g1 [label=\"book.us \
+       (3)\"];\n  g1 -> g0 [label=\"1\"];\n}\n"
+    );
+
+    let summary = matrix.condense(GroupBy::Variant);
+    assert_eq!(summary.group("Constant").unwrap().signals, 3);
+    assert_eq!(summary.edge_count("Constant", "BinaryOp"), Some(3));
+    assert_eq!(summary.edge_count("BinaryOp", "UnaryOp"), Some(1));
+
+    let summary = matrix.condense(GroupBy::Tag);
+    assert_eq!(summary.ungrouped(), 5);
+    let summary =
+      matrix.condense(GroupBy::Custom(&|s, _| Some((s.0 % 2).to_string())));
+    assert_eq!(summary.groups().len(), 2);
+  }
+}
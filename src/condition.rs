@@ -0,0 +1,181 @@
+use num_traits::Float;
+
+use crate::{
+  eval::RunHooks, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+  PlannedEvaluation, RunOptions, Signal, SignalMap, UnaryOp,
+};
+
+/// The local condition numbers of one operation: for each input, how many
+/// times the relative change of the input is magnified in the output,
+/// `|x · ∂f/∂x / f|`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditioning<F> {
+  inputs: Vec<(Signal, F)>,
+}
+
+impl<F: Float> Conditioning<F> {
+  /// Get the condition number with respect to each input, in operand order.
+  pub fn inputs(&self) -> &[(Signal, F)] { &self.inputs }
+
+  /// Get the largest condition number of any input, or one for constants.
+  pub fn worst(&self) -> F {
+    self
+      .inputs
+      .iter()
+      .fold(F::one(), |worst, (_, c)| worst.max(*c))
+  }
+}
+
+/// The conditioning of every operation in a run, from
+/// [`PlannedEvaluation::run_conditioned`].
+#[derive(Debug, Clone, Default)]
+pub struct ConditionReport<F> {
+  signals: SignalMap<Conditioning<F>>,
+}
+
+impl<F: Float> ConditionReport<F> {
+  /// Get the conditioning of a signal, if it was evaluated.
+  pub fn get(&self, signal: Signal) -> Option<&Conditioning<F>> {
+    self.signals.get(&signal)
+  }
+
+  /// Get the signals whose [worst](Conditioning::worst) condition number is
+  /// at least the threshold, worst first. These are the formulas where tiny
+  /// input changes produce huge output changes, e.g. a subtraction of nearly
+  /// equal values, and are worth restructuring.
+  pub fn ill_conditioned(&self, threshold: F) -> Vec<(Signal, F)> {
+    let mut signals: Vec<_> = self
+      .signals
+      .iter()
+      .map(|(s, c)| (*s, c.worst()))
+      .filter(|(_, worst)| worst.is_nan() || *worst >= threshold)
+      .collect();
+    signals.sort_by(|(a, x), (b, y)| {
+      y.partial_cmp(x)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then(a.0.cmp(&b.0))
+    });
+    signals
+  }
+}
+
+/// `|x / f|`, which is infinite if `f` is zero and `x` isn't, and zero if
+/// both are.
+fn ratio<F: Float>(x: F, f: F) -> F {
+  match x == F::zero() {
+    true => F::zero(),
+    false => (x / f).abs(),
+  }
+}
+
+impl<F: Float + std::fmt::Debug + Send + Sync>
+  PlannedEvaluation<'_, FloatMapSignalDef<F>>
+{
+  /// Run the planned evaluation like [`run_with`](Self::run_with), also
+  /// computing the local condition number of every evaluated operation with
+  /// respect to each of its inputs, from the values it saw.
+  ///
+  /// Condition numbers are analytic: one for negation, multiplication and
+  /// division, `|a / (a ± b)|` and `|b / (a ± b)|` for addition and
+  /// subtraction, and `|b|` and `|b · ln a|` for `a` to the power `b`.
+  ///
+  /// Intermediates the plan frees are kept until the run ends, so they can be
+  /// read.
+  pub fn run_conditioned(
+    &self,
+    values: EvaluationValueMap<FloatMapSignalDef<F>>,
+    options: &RunOptions,
+  ) -> (EvaluationValueMap<FloatMapSignalDef<F>>, ConditionReport<F>) {
+    let given: Vec<_> = values
+      .values
+      .iter()
+      .filter(|(_, v)| v.is_some())
+      .map(|(s, _)| s)
+      .collect();
+    let mut values = self.execute(values, options, RunHooks {
+      keep_intermediates: true,
+      ..Default::default()
+    });
+
+    let defset = self.matrix().defset();
+    let mut report = ConditionReport {
+      signals: SignalMap::default(),
+    };
+    for signal in self.all_queued_targets().iter() {
+      if given.contains(signal) {
+        continue;
+      }
+      let Some(f) = values.get(*signal).copied() else {
+        continue;
+      };
+      let value = |s: &Signal| *values.get(*s).unwrap();
+      let inputs = match defset.get(*signal).unwrap() {
+        FloatMapSignalDef::Constant(_) => Vec::new(),
+        FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => vec![(*a, F::one())],
+        FloatMapSignalDef::BinaryOp(op) => match op {
+          FloatBinaryOp::Add(a, b) | FloatBinaryOp::Sub(a, b) => {
+            vec![(*a, ratio(value(a), f)), (*b, ratio(value(b), f))]
+          }
+          FloatBinaryOp::Mul(a, b) | FloatBinaryOp::Div(a, b) => {
+            vec![(*a, F::one()), (*b, F::one())]
+          }
+          FloatBinaryOp::Pow(a, b) => {
+            let (x, y) = (value(a), value(b));
+            vec![(*a, y.abs()), (*b, (y * x.ln()).abs())]
+          }
+        },
+      };
+      report.signals.insert(*signal, Conditioning { inputs });
+    }
+
+    if self.free_intermediates() {
+      for signal in self.all_queued_targets().iter() {
+        if !self.root_targets().contains(signal) {
+          values.values.remove(signal);
+        }
+      }
+    }
+    (values, report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_condition_numbers() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1000.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(999.0));
+    let two = defset.insert(FloatMapSignalDef::Constant(2.0));
+    // 1000 - 999 magnifies relative changes in either input a thousandfold
+    let diff =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(a, b)));
+    let product =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(diff, two)));
+    let squared = defset.insert(FloatMapSignalDef::BinaryOp(
+      FloatBinaryOp::Pow(product, two),
+    ));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [squared])
+      .with_free_intermediates(true);
+
+    let (values, report) = plan.run_conditioned(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    assert_eq!(values.get(squared), Some(&4.0));
+    assert_eq!(values.get(diff), None);
+    assert_eq!(report.get(diff).unwrap().inputs(), [
+      (a, 1000.0),
+      (b, 999.0)
+    ]);
+    assert_eq!(report.get(product).unwrap().worst(), 1.0);
+    assert_eq!(report.get(squared).unwrap().inputs()[0], (product, 2.0));
+    assert_eq!(report.get(a).unwrap().worst(), 1.0);
+    assert_eq!(report.ill_conditioned(10.0), [(diff, 1000.0)]);
+  }
+}
@@ -0,0 +1,411 @@
+//! Loading [`FloatMapSignalDef`] graphs from configuration files, so graphs can
+//! be edited without recompiling.
+//!
+//! A graph is a table of named nodes. Each node is either a constant or an
+//! operation over other nodes, referenced by name in any order:
+//!
+//! ```toml
+//! roots = ["total"]
+//!
+//! [nodes]
+//! principal = 1000.0
+//! rate = 0.05
+//! years = 10.0
+//! one = 1.0
+//! growth = { op = "add", args = ["one", "rate"] }
+//! factor = { op = "pow", args = ["growth", "years"] }
+//! total = { op = "mul", args = ["principal", "factor"] }
+//! ```
+//!
+//! Nodes are public unless listed with another [`Visibility`]:
+//!
+//! ```toml
+//! [visibility]
+//! rate = "restricted"
+//! growth = "internal"
+//! ```
+//!
+//! Nodes can carry [`ExternalId`]s that other systems refer to them by:
+//!
+//! ```toml
+//! [ids]
+//! total = "6f1c29ab-0000-4e7f-8a1d-0123456789ab"
+//! ```
+//!
+//! Every node is labeled with its name in the resulting [`SignalDefMap`].
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  ExternalId, FloatBinaryOp, FloatMapSignalDef, Signal, SignalDefMap, UnaryOp,
+  Visibility,
+};
+
+/// A graph configuration: named nodes and the names of the root targets.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphConfig {
+  /// The nodes of the graph, by name.
+  pub nodes:      BTreeMap<String, NodeConfig>,
+  /// The names of the nodes to use as root targets. If empty, every node
+  /// nothing else depends on is a root.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub roots:      Vec<String>,
+  /// The visibility of nodes that aren't public, by name.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub visibility: BTreeMap<String, Visibility>,
+  /// The external IDs of nodes, by name.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub ids:        BTreeMap<String, ExternalId>,
+}
+
+/// A single node in a [`GraphConfig`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NodeConfig {
+  /// A constant value.
+  Constant(f64),
+  /// An operation over other nodes, referenced by name.
+  Op { op: OpConfig, args: Vec<String> },
+}
+
+/// The operations available to a [`NodeConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpConfig {
+  Neg,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Pow,
+}
+
+impl OpConfig {
+  fn arity(self) -> usize {
+    match self {
+      OpConfig::Neg => 1,
+      _ => 2,
+    }
+  }
+}
+
+/// An error loading a graph configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+  /// The configuration file couldn't be parsed.
+  Parse(String),
+  /// A node references a node that doesn't exist.
+  UnknownNode {
+    node:      String,
+    reference: String,
+  },
+  /// An operation has the wrong number of arguments.
+  Arity {
+    node:     String,
+    op:       OpConfig,
+    expected: usize,
+    found:    usize,
+  },
+  /// The nodes reference each other in a cycle, which passes through the named
+  /// node.
+  Cycle(String),
+  /// The external ID is given to more than one node.
+  DuplicateId(String),
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConfigError::Parse(e) => write!(f, "failed to parse graph config: {e}"),
+      ConfigError::UnknownNode { node, reference } => {
+        write!(f, "node `{node}` references unknown node `{reference}`")
+      }
+      ConfigError::Arity {
+        node,
+        op,
+        expected,
+        found,
+      } => write!(
+        f,
+        "node `{node}`: {op:?} takes {expected} argument(s), found {found}"
+      ),
+      ConfigError::Cycle(node) => {
+        write!(f, "node `{node}` is part of a dependency cycle")
+      }
+      ConfigError::DuplicateId(id) => {
+        write!(f, "external ID `{id}` is given to more than one node")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A graph built from a [`GraphConfig`].
+#[derive(Debug)]
+pub struct LoadedGraph {
+  /// The signal definitions, each labeled with its node name.
+  pub defset: SignalDefMap<FloatMapSignalDef>,
+  /// The root targets.
+  pub roots:  Vec<Signal>,
+}
+
+impl GraphConfig {
+  /// Parse a graph configuration from TOML.
+  pub fn from_toml(source: &str) -> Result<Self, ConfigError> {
+    toml::from_str(source).map_err(|e| ConfigError::Parse(e.to_string()))
+  }
+
+  /// Build the configured graph.
+  pub fn build(&self) -> Result<LoadedGraph, ConfigError> {
+    let mut defset = SignalDefMap::new();
+    for name in self.nodes.keys() {
+      self.insert(name, &mut defset)?;
+    }
+
+    let roots = match self.roots.is_empty() {
+      false => self
+        .roots
+        .iter()
+        .map(|root| {
+          defset.lookup(root).ok_or_else(|| ConfigError::UnknownNode {
+            node:      "roots".to_string(),
+            reference: root.clone(),
+          })
+        })
+        .collect::<Result<_, _>>()?,
+      true => {
+        let referenced: BTreeSet<&String> = self
+          .nodes
+          .values()
+          .flat_map(|node| match node {
+            NodeConfig::Constant(_) => &[][..],
+            NodeConfig::Op { args, .. } => args.as_slice(),
+          })
+          .collect();
+        self
+          .nodes
+          .keys()
+          .filter(|name| !referenced.contains(name))
+          .map(|name| defset.lookup(name).unwrap())
+          .collect()
+      }
+    };
+
+    for (name, visibility) in self.visibility.iter() {
+      let signal =
+        defset
+          .lookup(name)
+          .ok_or_else(|| ConfigError::UnknownNode {
+            node:      "visibility".to_string(),
+            reference: name.clone(),
+          })?;
+      defset.set_visibility(signal, *visibility);
+    }
+
+    for (name, id) in self.ids.iter() {
+      let signal =
+        defset
+          .lookup(name)
+          .ok_or_else(|| ConfigError::UnknownNode {
+            node:      "ids".to_string(),
+            reference: name.clone(),
+          })?;
+      defset
+        .set_external_id(signal, id.clone())
+        .map_err(|e| ConfigError::DuplicateId(e.id.to_string()))?;
+    }
+
+    Ok(LoadedGraph { defset, roots })
+  }
+
+  /// Insert the named node after its arguments, returning its signal. Walks
+  /// the arguments with an explicit stack, so deep chains don't overflow it.
+  fn insert(
+    &self,
+    name: &str,
+    defset: &mut SignalDefMap<FloatMapSignalDef>,
+  ) -> Result<Signal, ConfigError> {
+    if let Some(signal) = defset.lookup(name) {
+      return Ok(signal);
+    }
+
+    let mut in_progress = BTreeSet::from([name]);
+    let mut stack = vec![(name, 0)];
+    while let Some((node, next)) = stack.last_mut() {
+      let node = *node;
+      let args = match &self.nodes[node] {
+        NodeConfig::Constant(_) => &[][..],
+        NodeConfig::Op { op, args } => {
+          if args.len() != op.arity() {
+            return Err(ConfigError::Arity {
+              node:     node.to_string(),
+              op:       *op,
+              expected: op.arity(),
+              found:    args.len(),
+            });
+          }
+          args.as_slice()
+        }
+      };
+
+      if let Some(arg) = args.get(*next) {
+        *next += 1;
+        if !self.nodes.contains_key(arg) {
+          return Err(ConfigError::UnknownNode {
+            node:      node.to_string(),
+            reference: arg.clone(),
+          });
+        }
+        if defset.lookup(arg).is_some() {
+          continue;
+        }
+        if !in_progress.insert(arg) {
+          return Err(ConfigError::Cycle(arg.clone()));
+        }
+        stack.push((arg, 0));
+        continue;
+      }
+
+      stack.pop();
+      in_progress.remove(node);
+      let def = self.def(node, defset);
+      defset.insert_labeled(node, def);
+    }
+    Ok(defset.lookup(name).unwrap())
+  }
+
+  /// Build the definition of a node whose arguments are all inserted.
+  fn def(
+    &self,
+    name: &str,
+    defset: &SignalDefMap<FloatMapSignalDef>,
+  ) -> FloatMapSignalDef {
+    let (op, args) = match &self.nodes[name] {
+      NodeConfig::Constant(value) => {
+        return FloatMapSignalDef::Constant(*value)
+      }
+      NodeConfig::Op { op, args } => (op, args),
+    };
+    let signals: Vec<_> =
+      args.iter().map(|arg| defset.lookup(arg).unwrap()).collect();
+    match (op, signals.as_slice()) {
+      (OpConfig::Neg, [a]) => FloatMapSignalDef::UnaryOp(UnaryOp::Neg(*a)),
+      (OpConfig::Add, [a, b]) => {
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(*a, *b))
+      }
+      (OpConfig::Sub, [a, b]) => {
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(*a, *b))
+      }
+      (OpConfig::Mul, [a, b]) => {
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(*a, *b))
+      }
+      (OpConfig::Div, [a, b]) => {
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(*a, *b))
+      }
+      (OpConfig::Pow, [a, b]) => {
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(*a, *b))
+      }
+      _ => unreachable!("arity was checked when inserting"),
+    }
+  }
+}
+
+/// Parse and build a graph from TOML.
+pub fn load_toml(source: &str) -> Result<LoadedGraph, ConfigError> {
+  GraphConfig::from_toml(source)?.build()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalMatrix};
+
+  #[test]
+  fn test_load_toml() {
+    let graph = load_toml(
+      r#"
+      roots = ["total"]
+
+      [nodes]
+      total = { op = "mul", args = ["principal", "factor"] }
+      factor = { op = "pow", args = ["growth", "years"] }
+      growth = { op = "add", args = ["one", "rate"] }
+      principal = 1000.0
+      rate = 0.5
+      years = 2.0
+      one = 1.0
+
+      [visibility]
+      rate = "restricted"
+
+      [ids]
+      total = "total-v1"
+      "#,
+    )
+    .unwrap();
+
+    let total = graph.defset.lookup("total").unwrap();
+    assert_eq!(graph.roots, vec![total]);
+    let rate = graph.defset.lookup("rate").unwrap();
+    assert_eq!(graph.defset.visibility(rate), Visibility::Restricted);
+    assert_eq!(graph.defset.lookup_external_id("total-v1"), Some(total));
+
+    let matrix = SignalMatrix::new(graph.defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), graph.roots);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(total).unwrap(), &2250.0);
+  }
+
+  #[test]
+  fn test_config_errors() {
+    let err = |source| load_toml(source).unwrap_err();
+
+    assert!(matches!(
+      err("[nodes]\na = { op = \"neg\", args = [\"b\"] }"),
+      ConfigError::UnknownNode { .. }
+    ));
+    assert!(matches!(
+      err("[nodes]\na = { op = \"add\", args = [\"a\"] }"),
+      ConfigError::Arity { .. }
+    ));
+    assert!(matches!(
+      err(
+        "[nodes]\na = { op = \"neg\", args = [\"b\"] }\nb = { op = \"neg\", \
+         args = [\"a\"] }"
+      ),
+      ConfigError::Cycle(_)
+    ));
+    assert!(matches!(err("[nodes]\na = \"x\""), ConfigError::Parse(_)));
+  }
+
+  #[test]
+  fn test_deep_chain_config() {
+    // the root sorts first, so building walks the whole chain from it
+    let depth = 100_000;
+    let name = |i: usize| format!("n{:06}", depth - i);
+    let mut config = GraphConfig::default();
+    config.nodes.insert(name(0), NodeConfig::Constant(1.0));
+    for i in 1..=depth {
+      config.nodes.insert(name(i), NodeConfig::Op {
+        op:   OpConfig::Neg,
+        args: vec![name(i - 1)],
+      });
+    }
+    let graph = config.build().unwrap();
+    assert_eq!(graph.defset.len(), depth + 1);
+    assert_eq!(graph.roots, [graph.defset.lookup(&name(depth)).unwrap()]);
+
+    config.nodes.insert(name(0), NodeConfig::Op {
+      op:   OpConfig::Neg,
+      args: vec![name(depth)],
+    });
+    assert!(matches!(config.build(), Err(ConfigError::Cycle(_))));
+  }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use crate::{
+  EvaluationValueMap, PlanError, PlannedEvaluation, Signal, SignalDef,
+};
+
+/// An expectation of a [`PlanContract`] that a plan or value map broke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractViolation {
+  /// The plan has more passes than allowed.
+  TooManyPasses { passes: usize, max: usize },
+  /// A pass has more targets than allowed.
+  PassTooWide {
+    pass:  usize,
+    width: usize,
+    max:   usize,
+  },
+  /// More values would be alive at once than allowed.
+  TooManyLiveValues { peak: usize, max: usize },
+  /// A required signal isn't evaluated by the plan, or has no value in the
+  /// value map.
+  MissingSignal(Signal),
+  /// The plan doesn't pass [`PlannedEvaluation::verify`].
+  InvalidPlan(PlanError),
+}
+
+impl fmt::Display for ContractViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ContractViolation::TooManyPasses { passes, max } => {
+        write!(f, "plan has {passes} passes, more than the {max} allowed")
+      }
+      ContractViolation::PassTooWide { pass, width, max } => write!(
+        f,
+        "pass {pass} has {width} targets, more than the {max} allowed"
+      ),
+      ContractViolation::TooManyLiveValues { peak, max } => write!(
+        f,
+        "plan keeps up to {peak} values alive, more than the {max} allowed"
+      ),
+      ContractViolation::MissingSignal(signal) => {
+        write!(f, "required signal {signal} is missing")
+      }
+      ContractViolation::InvalidPlan(error) => {
+        write!(f, "plan is invalid: {error}")
+      }
+    }
+  }
+}
+
+/// Expectations about the size and shape of a plan and the values it
+/// produces, for gating deployments on graph or plan regressions.
+///
+/// Checks collect every broken expectation instead of stopping at the first
+/// one.
+#[derive(Debug, Clone, Default)]
+pub struct PlanContract {
+  max_passes:      Option<usize>,
+  max_pass_width:  Option<usize>,
+  max_live_values: Option<usize>,
+  required:        Vec<Signal>,
+  verified:        bool,
+}
+
+impl PlanContract {
+  /// Create a contract with no expectations.
+  pub fn new() -> Self { Self::default() }
+
+  /// Allow at most this many passes.
+  pub fn with_max_passes(mut self, max_passes: usize) -> Self {
+    self.max_passes = Some(max_passes);
+    self
+  }
+
+  /// Allow at most this many targets evaluated at once in any pass, as
+  /// measured by [`width`](crate::EvaluationPassDescriptor::width).
+  pub fn with_max_pass_width(mut self, max_pass_width: usize) -> Self {
+    self.max_pass_width = Some(max_pass_width);
+    self
+  }
+
+  /// Allow at most this many values alive at once, as counted by
+  /// [`PlannedEvaluation::peak_live_values`].
+  pub fn with_max_live_values(mut self, max_live_values: usize) -> Self {
+    self.max_live_values = Some(max_live_values);
+    self
+  }
+
+  /// Require the given signals to be evaluated by the plan, and to have
+  /// values in checked value maps.
+  pub fn with_required_signals(
+    mut self,
+    signals: impl IntoIterator<Item = Signal>,
+  ) -> Self {
+    self.required.extend(signals);
+    self
+  }
+
+  /// Also require the plan to pass [`PlannedEvaluation::verify`].
+  pub fn with_verified_plan(mut self) -> Self {
+    self.verified = true;
+    self
+  }
+
+  /// Check a plan against the contract, returning every violation.
+  pub fn check<T: SignalDef>(
+    &self,
+    plan: &PlannedEvaluation<'_, T>,
+  ) -> Vec<ContractViolation> {
+    let mut violations = Vec::new();
+    let passes = plan.passes();
+    if let Some(max) = self.max_passes.filter(|max| passes.len() > *max) {
+      violations.push(ContractViolation::TooManyPasses {
+        passes: passes.len(),
+        max,
+      });
+    }
+    if let Some(max) = self.max_pass_width {
+      for (pass, descriptor) in passes.iter().enumerate() {
+        let width = descriptor.width();
+        if width > max {
+          violations.push(ContractViolation::PassTooWide { pass, width, max });
+        }
+      }
+    }
+    if let Some(max) = self.max_live_values {
+      let peak = plan.peak_live_values();
+      if peak > max {
+        violations.push(ContractViolation::TooManyLiveValues { peak, max });
+      }
+    }
+    let queued = plan.all_queued_targets();
+    for signal in self.required.iter().filter(|s| !queued.contains(s)) {
+      violations.push(ContractViolation::MissingSignal(*signal));
+    }
+    if self.verified {
+      if let Err(error) = plan.verify() {
+        violations.push(ContractViolation::InvalidPlan(error));
+      }
+    }
+    violations
+  }
+
+  /// Check that every required signal has a value in a value map, returning
+  /// a violation for each one that doesn't.
+  pub fn check_values<T: SignalDef>(
+    &self,
+    values: &EvaluationValueMap<T>,
+  ) -> Vec<ContractViolation> {
+    self
+      .required
+      .iter()
+      .filter(|s| values.get(**s).is_none())
+      .map(|s| ContractViolation::MissingSignal(*s))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, FloatMapSignalDef, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_contract() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 8,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let stray = defset.insert(FloatMapSignalDef::Constant(0.0));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+
+    let lenient = PlanContract::new()
+      .with_max_passes(4)
+      .with_max_pass_width(8)
+      .with_required_signals(roots.clone())
+      .with_verified_plan();
+    assert_eq!(lenient.check(&plan), []);
+
+    let strict = PlanContract::new()
+      .with_max_passes(3)
+      .with_max_pass_width(4)
+      .with_max_live_values(2)
+      .with_required_signals([stray]);
+    let violations = strict.check(&plan);
+    assert_eq!(violations[..3], [
+      ContractViolation::TooManyPasses {
+        passes: 4,
+        max:    3,
+      },
+      ContractViolation::PassTooWide {
+        pass:  0,
+        width: 8,
+        max:   4,
+      },
+      ContractViolation::TooManyLiveValues { peak: 12, max: 2 },
+    ]);
+    assert_eq!(violations[3], ContractViolation::MissingSignal(stray));
+    assert_eq!(
+      violations[0].to_string(),
+      "plan has 4 passes, more than the 3 allowed"
+    );
+
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(lenient.check_values(&values), []);
+    assert_eq!(strict.check_values(&values), [
+      ContractViolation::MissingSignal(stray)
+    ]);
+  }
+
+  #[test]
+  fn test_contract_sequential_width() {
+    let mut defset = SignalDefMap::new();
+    let chain = GraphGenerator::new(GraphShape::Chain { length: 16 })
+      .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), chain)
+      .collapse_chains();
+    assert_eq!(plan.passes()[0].targets().len(), 16);
+
+    // a segment evaluates one target at a time
+    let contract = PlanContract::new().with_max_pass_width(1);
+    assert_eq!(contract.check(&plan), []);
+  }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::{
+  EvaluationPlanner, EvaluationValueMap, PlannedEvaluation, Signal, SignalDef,
+  SignalMatrix, SignalSet,
+};
+
+/// A problem with a [`PlannedEvaluation`], found by
+/// [`PlannedEvaluation::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanError {
+  /// A root target isn't queued in any pass.
+  MissingRoot(Signal),
+  /// A target has no definition in the matrix.
+  Undefined(Signal),
+  /// A target is queued in more than one pass.
+  Duplicate(Signal),
+  /// A target's dependency isn't evaluated in an earlier pass.
+  Unsatisfied {
+    signal:     Signal,
+    dependency: Signal,
+    pass:       usize,
+  },
+}
+
+impl fmt::Display for PlanError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PlanError::MissingRoot(signal) => {
+        write!(f, "root {signal} is not queued")
+      }
+      PlanError::Undefined(signal) => write!(f, "{signal} has no definition"),
+      PlanError::Duplicate(signal) => write!(f, "{signal} is queued twice"),
+      PlanError::Unsatisfied {
+        signal,
+        dependency,
+        pass,
+      } => write!(
+        f,
+        "{signal} in pass {pass} depends on {dependency}, which no earlier \
+         pass evaluates"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for PlanError {}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Check that the plan evaluates every root target, evaluates each target
+  /// once, and only after all of its dependencies.
+  ///
+  /// Plans trimmed with [`skip_targets`](Self::skip_targets) or
+  /// [`skip_evaluated`](Self::skip_evaluated) expect some values to be
+  /// supplied, so they can fail this check.
+  pub fn verify(&self) -> Result<(), PlanError> {
+    let graph = self.matrix().dependency_graph();
+    let defset = self.matrix().defset();
+    let mut evaluated = SignalSet::default();
+    for (i, pass) in self.passes().iter().enumerate() {
+      for stage in pass.stages() {
+        for target in stage.iter() {
+          if defset.get(*target).is_none() {
+            return Err(PlanError::Undefined(*target));
+          }
+          if evaluated.contains(target) {
+            return Err(PlanError::Duplicate(*target));
+          }
+          if let Some(dep) = graph
+            .dependencies(*target)
+            .iter()
+            .find(|dep| !evaluated.contains(dep))
+          {
+            return Err(PlanError::Unsatisfied {
+              signal:     *target,
+              dependency: *dep,
+              pass:       i,
+            });
+          }
+        }
+        evaluated.extend(stage.iter().copied());
+      }
+    }
+    match self.root_targets().iter().find(|r| !evaluated.contains(r)) {
+      Some(root) => Err(PlanError::MissingRoot(*root)),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Which planner a [`CrossCheckError`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannerRole {
+  Reference,
+  Candidate,
+}
+
+/// A difference between two planners found by [`cross_check_planners`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CrossCheckError {
+  /// One of the plans failed [`PlannedEvaluation::verify`].
+  InvalidPlan(PlannerRole, PlanError),
+  /// The runs produced different values for a signal. The values are
+  /// formatted with `Debug`.
+  Mismatch {
+    signal:    Signal,
+    reference: String,
+    candidate: String,
+  },
+}
+
+impl fmt::Display for CrossCheckError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CrossCheckError::InvalidPlan(role, error) => {
+        write!(f, "{role:?} plan is invalid: {error}")
+      }
+      CrossCheckError::Mismatch {
+        signal,
+        reference,
+        candidate,
+      } => write!(
+        f,
+        "{signal} evaluated to {candidate} but the reference gives {reference}"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for CrossCheckError {}
+
+/// Plan the given roots with a reference planner and a candidate, verify both
+/// plans, run them, and compare the values they both kept, roots included.
+///
+/// This is for developing planners against a known-good one like
+/// [`CustomPlanner`](crate::CustomPlanner). Returns the values of the
+/// candidate's run on success.
+pub fn cross_check_planners<T, R, C>(
+  matrix: &SignalMatrix<T>,
+  reference: &R,
+  candidate: &C,
+  roots: impl IntoIterator<Item = Signal>,
+) -> Result<EvaluationValueMap<T>, CrossCheckError>
+where
+  T: SignalDef,
+  T::Value: PartialEq,
+  R: EvaluationPlanner<T> + ?Sized,
+  C: EvaluationPlanner<T> + ?Sized,
+{
+  let span = tracing::info_span!("cross_check");
+  let _enter = span.enter();
+
+  let roots: SignalSet = roots.into_iter().collect();
+  let expected = verified_run(
+    matrix.plan_evaluation(reference, roots.iter().copied()),
+    PlannerRole::Reference,
+  )?;
+  let actual = verified_run(
+    matrix.plan_evaluation(candidate, roots.iter().copied()),
+    PlannerRole::Candidate,
+  )?;
+
+  let mut compared = 0;
+  for (signal, expected) in expected.values.iter() {
+    let (Some(expected), Some(actual)) = (expected, actual.get(signal)) else {
+      continue;
+    };
+    if expected != actual {
+      return Err(CrossCheckError::Mismatch {
+        signal,
+        reference: format!("{expected:?}"),
+        candidate: format!("{actual:?}"),
+      });
+    }
+    compared += 1;
+  }
+  tracing::info!(compared, "planners agree");
+  Ok(actual)
+}
+
+fn verified_run<T: SignalDef>(
+  plan: PlannedEvaluation<'_, T>,
+  role: PlannerRole,
+) -> Result<EvaluationValueMap<T>, CrossCheckError> {
+  plan
+    .verify()
+    .map_err(|error| CrossCheckError::InvalidPlan(role, error))?;
+  Ok(plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets())))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationPassDescriptor, MemoryMinimizingPlanner,
+    SignalDefMap,
+  };
+
+  /// Plans only the roots, leaving their dependencies out.
+  struct RootsOnly;
+
+  impl<T: SignalDef> EvaluationPlanner<T> for RootsOnly {
+    fn plan_evaluation<'m>(
+      &self,
+      matrix: &'m SignalMatrix<T>,
+      root_targets: SignalSet,
+    ) -> PlannedEvaluation<'m, T> {
+      let pass = EvaluationPassDescriptor::new(root_targets.clone());
+      PlannedEvaluation::from_passes(matrix, root_targets, vec![pass])
+    }
+  }
+
+  #[test]
+  fn test_cross_check() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 8,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+
+    let values = cross_check_planners(
+      &matrix,
+      &CustomPlanner::new(),
+      &MemoryMinimizingPlanner::new(),
+      roots.clone(),
+    )
+    .unwrap();
+    assert!(roots.iter().all(|root| values.get(*root).is_some()));
+
+    let error =
+      cross_check_planners(&matrix, &CustomPlanner::new(), &RootsOnly, roots)
+        .unwrap_err();
+    assert!(matches!(
+      error,
+      CrossCheckError::InvalidPlan(
+        PlannerRole::Candidate,
+        PlanError::Unsatisfied { pass: 0, .. }
+      )
+    ));
+  }
+}
@@ -0,0 +1,156 @@
+use fixedbitset::FixedBitSet;
+use tracing::instrument;
+
+use crate::{Signal, SignalDef, SignalDefMap, SignalSet};
+
+/// A compressed sparse row (CSR) view of the dependency graph.
+///
+/// Signal IDs are dense, so row `i` holds the deduplicated, ID-sorted
+/// dependencies of the signal with index `i`. IDs without a definition have
+/// empty rows.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DependencyGraph {
+  offsets: Vec<usize>,
+  edges:   Vec<Signal>,
+}
+
+impl DependencyGraph {
+  /// Build the dependency graph of every signal in the given map.
+  #[instrument(skip(defset))]
+  pub(crate) fn build<T: SignalDef>(defset: &SignalDefMap<T>) -> Self {
+    let id_space = defset.id_space();
+    let mut offsets = Vec::with_capacity(id_space + 1);
+    let mut edges = Vec::new();
+    let mut row = Vec::new();
+
+    offsets.push(0);
+    for index in 0..id_space {
+      if let Some(def) = defset.get(Signal::from_index(index)) {
+        row.clear();
+        def.dependencies_into(&mut row);
+        row.sort_unstable_by_key(|s| s.0);
+        row.dedup();
+        edges.extend_from_slice(&row);
+      }
+      offsets.push(edges.len());
+    }
+
+    DependencyGraph { offsets, edges }
+  }
+
+  /// Get the dependencies of the given signal.
+  pub(crate) fn dependencies(&self, signal: Signal) -> &[Signal] {
+    let index = signal.index();
+    &self.edges[self.offsets[index]..self.offsets[index + 1]]
+  }
+
+  /// Build the transposed graph, where row `i` holds the signals that depend
+  /// on signal `i`.
+  pub(crate) fn transpose(&self) -> Self {
+    let mut counts = vec![0; self.len() + 1];
+    for dep in self.edges.iter() {
+      counts[dep.index() + 1] += 1;
+    }
+    let mut offsets = counts;
+    for i in 1..offsets.len() {
+      offsets[i] += offsets[i - 1];
+    }
+
+    let mut cursor = offsets.clone();
+    let mut edges = vec![Signal(0); self.edges.len()];
+    for index in 0..self.len() {
+      for dep in self.dependencies(Signal::from_index(index)) {
+        edges[cursor[dep.index()]] = Signal::from_index(index);
+        cursor[dep.index()] += 1;
+      }
+    }
+
+    DependencyGraph { offsets, edges }
+  }
+
+  /// Get every signal reachable from the given signals by following edges,
+  /// not including the given signals unless they are reachable themselves.
+  pub(crate) fn reachable_from(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+  ) -> SignalSet {
+    let mut visited = FixedBitSet::with_capacity(self.len());
+    let mut stack: Vec<_> = signals.into_iter().collect();
+    let mut reached = SignalSet::default();
+    while let Some(signal) = stack.pop() {
+      for next in self.dependencies(signal) {
+        if !visited.put(next.index()) {
+          reached.insert(*next);
+          stack.push(*next);
+        }
+      }
+    }
+    reached
+  }
+
+  /// Get the length of the longest dependency chain below each row, or
+  /// `None` for rows on or above a cycle.
+  pub(crate) fn levels(&self) -> Vec<Option<usize>> {
+    let dependents = self.transpose();
+    let mut pending: Vec<_> = (0..self.len())
+      .map(|i| self.offsets[i + 1] - self.offsets[i])
+      .collect();
+    let mut levels = vec![None; self.len()];
+    let mut ready: Vec<_> =
+      (0..self.len()).filter(|i| pending[*i] == 0).collect();
+    for i in ready.iter() {
+      levels[*i] = Some(0);
+    }
+    while let Some(i) = ready.pop() {
+      let level = levels[i].unwrap() + 1;
+      for dependent in dependents.dependencies(Signal::from_index(i)) {
+        let j = dependent.index();
+        levels[j] = Some(levels[j].map_or(level, |l: usize| l.max(level)));
+        pending[j] -= 1;
+        if pending[j] == 0 {
+          ready.push(j);
+        }
+      }
+    }
+    // rows still pending never had all their dependencies resolved
+    for (level, pending) in levels.iter_mut().zip(pending) {
+      if pending > 0 {
+        *level = None;
+      }
+    }
+    levels
+  }
+
+  /// Get the number of rows in the graph, which is the ID space of the
+  /// signal map it was built from.
+  pub(crate) fn len(&self) -> usize { self.offsets.len() - 1 }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, UnaryOp};
+
+  #[test]
+  fn test_dependency_graph() {
+    let mut defset = SignalDefMap::new();
+
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(b, b)));
+
+    let graph = DependencyGraph::build(&defset);
+    assert_eq!(graph.len(), 3);
+    assert!(graph.dependencies(a).is_empty());
+    assert_eq!(graph.dependencies(b), &[a]);
+    // duplicate edges are collapsed
+    assert_eq!(graph.dependencies(c), &[b]);
+
+    let dependents = graph.transpose();
+    assert_eq!(dependents.dependencies(a), &[b]);
+    assert_eq!(dependents.dependencies(b), &[c]);
+    assert_eq!(dependents.reachable_from([a]), [b, c].into_iter().collect());
+    assert_eq!(graph.levels(), [Some(0), Some(1), Some(2)]);
+  }
+}
@@ -0,0 +1,334 @@
+//! A long-running daemon that keeps [`GraphConfig`] graphs and their plans
+//! warm in memory, so short-lived clients don't pay for loading and planning
+//! a graph on every invocation.
+//!
+//! Clients connect over a unix socket and exchange newline-delimited JSON: one
+//! [`DaemonRequest`] per line, each answered by one [`DaemonResponse`] line.
+//!
+//! ```text
+//! > {"op": "load", "graph": "loan", "config": {"nodes": {...}}}
+//! < {"type": "loaded", "nodes": 7}
+//! > {"op": "evaluate", "graph": "loan", "overrides": {"rate": 0.07}}
+//! < {"type": "values", "values": {"total": 1967.15}}
+//! ```
+//!
+//! Graphs live in a [`GraphRegistry`], so plans are cached per graph by their
+//! targets and overridden nodes, and dropped when the graph is reloaded.
+
+use std::{
+  collections::BTreeMap,
+  io::{self, BufRead, BufReader, Write},
+  os::unix::net::{UnixListener, UnixStream},
+  path::Path,
+  sync::Arc,
+  thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  config::GraphConfig, EvaluationValueMap, FloatMapSignalDef, GraphRegistry,
+  TenantLimits,
+};
+
+/// A request to a [`Daemon`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DaemonRequest {
+  /// Load a graph, replacing any graph of that name.
+  Load { graph: String, config: GraphConfig },
+  /// Evaluate a graph. Overrides replace the values of some nodes, and the
+  /// targets pick the nodes to report, defaulting to the graph's roots.
+  Evaluate {
+    graph:     String,
+    #[serde(default)]
+    overrides: BTreeMap<String, f64>,
+    #[serde(default)]
+    targets:   Vec<String>,
+  },
+  /// Drop a graph and its plans.
+  Unload { graph: String },
+  /// List the loaded graphs.
+  Status,
+}
+
+/// A response from a [`Daemon`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonResponse {
+  /// A graph was loaded with this many nodes, as this version.
+  Loaded { nodes: usize, version: u64 },
+  /// The values of an evaluation, by node name.
+  Values { values: BTreeMap<String, f64> },
+  /// A graph was unloaded.
+  Unloaded,
+  /// The loaded graphs, by name.
+  Status {
+    graphs: BTreeMap<String, GraphStatus>,
+  },
+  /// The request failed.
+  Error { error: String },
+}
+
+/// A loaded graph, as reported by [`DaemonRequest::Status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphStatus {
+  pub nodes:        usize,
+  pub version:      u64,
+  pub cached_plans: usize,
+}
+
+/// The registry tenant holding the daemon's graphs.
+const TENANT: &str = "daemon";
+
+/// Loaded graphs and their cached plans, answering [`DaemonRequest`]s.
+#[derive(Debug, Default)]
+pub struct Daemon {
+  graphs: GraphRegistry<FloatMapSignalDef>,
+}
+
+impl Daemon {
+  /// Create a daemon with no graphs loaded.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the graphs the daemon may hold.
+  pub fn with_limits(self, limits: TenantLimits) -> Self {
+    self.graphs.set_limits(TENANT, limits);
+    self
+  }
+
+  /// Answer a request.
+  pub fn handle(&self, request: DaemonRequest) -> DaemonResponse {
+    let result = match request {
+      DaemonRequest::Load { graph, config } => self.load(graph, &config),
+      DaemonRequest::Evaluate {
+        graph,
+        overrides,
+        targets,
+      } => self.evaluate(&graph, &overrides, &targets),
+      DaemonRequest::Unload { graph } => {
+        match self.graphs.remove(TENANT, &graph) {
+          Some(_) => Ok(DaemonResponse::Unloaded),
+          None => Err(not_found(&graph)),
+        }
+      }
+      DaemonRequest::Status => Ok(self.status()),
+    };
+    result.unwrap_or_else(|error| DaemonResponse::Error { error })
+  }
+
+  fn load(
+    &self,
+    name: String,
+    config: &GraphConfig,
+  ) -> Result<DaemonResponse, String> {
+    let graph = config.build().map_err(|e| e.to_string())?;
+    let graph = self
+      .graphs
+      .insert(TENANT, &name, graph.defset, graph.roots)
+      .map_err(|e| e.to_string())?;
+    Ok(DaemonResponse::Loaded {
+      nodes:   graph.matrix().defset().len(),
+      version: graph.version(),
+    })
+  }
+
+  fn evaluate(
+    &self,
+    name: &str,
+    overrides: &BTreeMap<String, f64>,
+    targets: &[String],
+  ) -> Result<DaemonResponse, String> {
+    let graph = self
+      .graphs
+      .get(TENANT, name)
+      .ok_or_else(|| not_found(name))?;
+    let defset = graph.matrix().defset();
+    let lookup = |node: &str| {
+      defset
+        .lookup(node)
+        .ok_or_else(|| format!("no node named `{node}`"))
+    };
+    let targets = match targets.is_empty() {
+      true => graph.roots().to_vec(),
+      false => targets
+        .iter()
+        .map(|node| lookup(node))
+        .collect::<Result<Vec<_>, _>>()?,
+    };
+    let overrides = overrides
+      .iter()
+      .map(|(node, value)| Ok((lookup(node)?, *value)))
+      .collect::<Result<Vec<_>, String>>()?;
+
+    let skipped: Vec<_> = overrides.iter().map(|(s, _)| *s).collect();
+    let plan = graph.plan(&targets, &skipped);
+
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    for (signal, value) in overrides {
+      values.insert(signal, value);
+    }
+    let values = plan.run(values);
+    let values = targets
+      .iter()
+      .map(|target| {
+        let name = defset.label(*target).unwrap().to_string();
+        (name, *values.get(*target).unwrap())
+      })
+      .collect();
+    Ok(DaemonResponse::Values { values })
+  }
+
+  fn status(&self) -> DaemonResponse {
+    let graphs = self
+      .graphs
+      .graphs(TENANT)
+      .into_iter()
+      .map(|(name, graph)| {
+        (name, GraphStatus {
+          nodes:        graph.matrix().defset().len(),
+          version:      graph.version(),
+          cached_plans: graph.cached_plans(),
+        })
+      })
+      .collect();
+    DaemonResponse::Status { graphs }
+  }
+
+  /// Answer requests on a connection until the client disconnects.
+  pub fn serve_connection(&self, stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let response = match serde_json::from_str(&line) {
+        Ok(request) => self.handle(request),
+        Err(e) => DaemonResponse::Error {
+          error: format!("invalid request: {e}"),
+        },
+      };
+      serde_json::to_writer(&mut writer, &response)?;
+      writer.write_all(b"\n")?;
+    }
+    Ok(())
+  }
+
+  /// Accept connections on a listener, each answered on its own thread, until
+  /// accepting fails.
+  pub fn serve(self: Arc<Self>, listener: UnixListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+      let stream = stream?;
+      let daemon = self.clone();
+      thread::spawn(move || {
+        if let Err(e) = daemon.serve_connection(stream) {
+          tracing::warn!("daemon connection failed: {e}");
+        }
+      });
+    }
+    Ok(())
+  }
+}
+
+fn not_found(name: &str) -> String { format!("no graph named `{name}`") }
+
+/// A connection to a [`Daemon`].
+#[derive(Debug)]
+pub struct DaemonClient {
+  reader: BufReader<UnixStream>,
+  writer: UnixStream,
+}
+
+impl DaemonClient {
+  /// Connect to a daemon listening on the given socket.
+  pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+    let writer = UnixStream::connect(path)?;
+    Ok(DaemonClient {
+      reader: BufReader::new(writer.try_clone()?),
+      writer,
+    })
+  }
+
+  /// Send a request and wait for its response.
+  pub fn request(
+    &mut self,
+    request: &DaemonRequest,
+  ) -> io::Result<DaemonResponse> {
+    serde_json::to_writer(&mut self.writer, request)?;
+    self.writer.write_all(b"\n")?;
+    let mut line = String::new();
+    if self.reader.read_line(&mut line)? == 0 {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(serde_json::from_str(&line)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::NodeConfig;
+
+  #[test]
+  fn test_daemon_over_socket() {
+    let path = std::env::temp_dir()
+      .join(format!("matrix-daemon-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || Arc::new(Daemon::new()).serve(listener));
+
+    let mut config = GraphConfig::default();
+    config.nodes.insert("a".into(), NodeConfig::Constant(2.0));
+    config.nodes.insert("b".into(), NodeConfig::Constant(3.0));
+    let product = serde_json::from_str(r#"{"op": "mul", "args": ["a", "b"]}"#);
+    config.nodes.insert("product".into(), product.unwrap());
+
+    let mut client = DaemonClient::connect(&path).unwrap();
+    let load = DaemonRequest::Load {
+      graph: "g".into(),
+      config,
+    };
+    assert_eq!(client.request(&load).unwrap(), DaemonResponse::Loaded {
+      nodes:   3,
+      version: 1,
+    });
+    let evaluate = |b: f64| DaemonRequest::Evaluate {
+      graph:     "g".into(),
+      overrides: [("b".to_string(), b)].into(),
+      targets:   vec![],
+    };
+    for b in [3.0, 5.0] {
+      assert_eq!(
+        client.request(&evaluate(b)).unwrap(),
+        DaemonResponse::Values {
+          values: [("product".to_string(), 2.0 * b)].into(),
+        }
+      );
+    }
+
+    // a second client sees the same warm graph and its one cached plan
+    let mut other = DaemonClient::connect(&path).unwrap();
+    assert_eq!(
+      other.request(&DaemonRequest::Status).unwrap(),
+      DaemonResponse::Status {
+        graphs: [("g".to_string(), GraphStatus {
+          nodes:        3,
+          version:      1,
+          cached_plans: 1,
+        })]
+        .into(),
+      }
+    );
+    other
+      .request(&DaemonRequest::Unload { graph: "g".into() })
+      .unwrap();
+    assert_eq!(
+      client.request(&evaluate(1.0)).unwrap(),
+      DaemonResponse::Error {
+        error: "no graph named `g`".into(),
+      }
+    );
+    let _ = std::fs::remove_file(&path);
+  }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::{Signal, SignalDef, SignalDefMap};
+
+/// An error from [`SignalDefMap::define`] or [`SignalDefMap::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeclarationError {
+  /// The signal wasn't declared, or has already been defined.
+  NotDeclared(Signal),
+  /// Declared signals that were never defined, sorted by ID.
+  Undefined(Vec<Signal>),
+}
+
+impl fmt::Display for DeclarationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DeclarationError::NotDeclared(signal) => {
+        write!(f, "{signal} is not a declared placeholder")
+      }
+      DeclarationError::Undefined(signals) => {
+        write!(f, "{} declared signals are undefined:", signals.len())?;
+        for signal in signals {
+          write!(f, " {signal}")?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl std::error::Error for DeclarationError {}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Reserve a signal without defining it yet, so definitions can reference
+  /// it before it has a definition of its own, e.g. for mutually-referencing
+  /// structures or graphs loaded from unordered data. Give it a definition
+  /// with [`define`](Self::define) before building a matrix.
+  pub fn declare(&mut self) -> Signal {
+    let signal = Signal(self.last_id);
+    self.last_id += 1;
+    self.declared.insert(signal);
+    signal
+  }
+
+  /// Define a signal reserved by [`declare`](Self::declare).
+  ///
+  /// # Panics
+  ///
+  /// In [strict mode](Self::with_strict_dependencies), panics if the
+  /// definition depends on a signal that isn't in the map.
+  pub fn define(
+    &mut self,
+    signal: Signal,
+    def: T,
+  ) -> Result<(), DeclarationError> {
+    if !self.declared.contains(&signal) {
+      return Err(DeclarationError::NotDeclared(signal));
+    }
+    if self.strict {
+      self
+        .check_dependencies(&def)
+        .unwrap_or_else(|e| panic!("{e}"));
+    }
+    self.declared.remove(&signal);
+    self.map.insert(signal, def);
+    Ok(())
+  }
+
+  /// Check whether a signal is declared but not yet defined.
+  pub fn is_placeholder(&self, signal: Signal) -> bool {
+    self.declared.contains(&signal)
+  }
+
+  /// Check that every declared signal has been defined.
+  pub fn validate(&self) -> Result<(), DeclarationError> {
+    if self.declared.is_empty() {
+      return Ok(());
+    }
+    let mut undefined: Vec<_> = self.declared.iter().copied().collect();
+    undefined.sort_by_key(|s| s.0);
+    Err(DeclarationError::Undefined(undefined))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalMatrix,
+  };
+
+  #[test]
+  fn test_declare_and_define() {
+    let mut defset = SignalDefMap::new();
+    let rate = defset.declare();
+    let spare = defset.declare();
+    let total = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(rate, rate)));
+    assert!(defset.is_placeholder(rate));
+    assert_eq!(
+      defset.validate(),
+      Err(DeclarationError::Undefined(vec![rate, spare]))
+    );
+    assert_eq!(
+      defset.validate().unwrap_err().to_string(),
+      "2 declared signals are undefined: #0 #1"
+    );
+
+    defset
+      .define(rate, FloatMapSignalDef::Constant(3.0))
+      .unwrap();
+    defset
+      .define(spare, FloatMapSignalDef::Constant(0.0))
+      .unwrap();
+    assert_eq!(
+      defset.define(rate, FloatMapSignalDef::Constant(4.0)),
+      Err(DeclarationError::NotDeclared(rate))
+    );
+    assert_eq!(
+      defset.define(total, FloatMapSignalDef::Constant(4.0)),
+      Err(DeclarationError::NotDeclared(total))
+    );
+    defset.validate().unwrap();
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(total), Some(&9.0));
+  }
+}
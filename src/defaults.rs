@@ -0,0 +1,79 @@
+use crate::{Signal, SignalDef, SignalMap};
+
+/// Values to use for dependencies that have no value when a signal is
+/// evaluated, for plans made lenient with
+/// [`PlannedEvaluation::with_defaults`](crate::PlannedEvaluation::with_defaults).
+#[derive(Debug)]
+pub struct Defaults<T: SignalDef> {
+  signals:  SignalMap<T::Value>,
+  fallback: Option<T::Value>,
+}
+
+impl<T: SignalDef> Default for Defaults<T> {
+  fn default() -> Self {
+    Defaults {
+      signals:  SignalMap::default(),
+      fallback: None,
+    }
+  }
+}
+
+impl<T: SignalDef> Defaults<T> {
+  /// Create an empty set of defaults.
+  pub fn new() -> Self { Self::default() }
+
+  /// Set the default value of the given signal.
+  pub fn with_signal(mut self, signal: Signal, value: T::Value) -> Self {
+    self.signals.insert(signal, value);
+    self
+  }
+
+  /// Set the default value of every signal without its own default.
+  pub fn with_fallback(mut self, value: T::Value) -> Self {
+    self.fallback = Some(value);
+    self
+  }
+
+  /// Get the default value of the given signal, if it has one.
+  pub fn get(&self, signal: Signal) -> Option<&T::Value> {
+    self.signals.get(&signal).or(self.fallback.as_ref())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_lenient_run_uses_defaults() {
+    let mut defset = SignalDefMap::new();
+    let price = defset.insert(FloatMapSignalDef::Constant(10.0));
+    let qty = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let fee = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let subtotal = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(price, qty)));
+    let total = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+      subtotal, fee,
+    )));
+    let matrix = SignalMatrix::new(defset);
+
+    // the caller was supposed to supply the inputs, but forgot
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [total])
+      .skip_targets([price, qty, fee])
+      .with_defaults(Defaults::new().with_signal(qty, 1.0).with_fallback(0.0));
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(total), Some(&0.0));
+
+    let mut inputs = EvaluationValueMap::new_empty(Default::default());
+    inputs.insert(price, 4.0);
+    let values = plan.run(inputs);
+    assert_eq!(values.get(total), Some(&4.0));
+    // defaults stand in for dependencies, but aren't stored as their values
+    assert_eq!(values.get(qty), None);
+  }
+}
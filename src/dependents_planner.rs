@@ -0,0 +1,186 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use fixedbitset::FixedBitSet;
+
+use crate::{
+  EvaluationPassDescriptor, EvaluationPlanner, PlannedEvaluation, Signal,
+  SignalDef, SignalMatrix, SignalSet,
+};
+
+/// A planner that schedules ready signals with the most dependents first, so
+/// each pass unlocks as much downstream work as it can. Pays off on irregular
+/// graphs with a pass size limit around the thread count, where filling a
+/// pass with leaf-like signals would leave the next passes starved.
+///
+/// A signal is ready once every dependency has been scheduled in an earlier
+/// pass. Each pass takes up to the limit of ready signals, ordered by their
+/// number of dependents among the planned signals and then by ID. Without a
+/// limit every ready signal is taken, which schedules each signal as early as
+/// possible.
+///
+/// This applies the priority when deciding passes, which then run behind
+/// barriers as usual. To apply it while running instead, pulling from a
+/// ready queue without barriers, use
+/// [`run_dependents_first`](PlannedEvaluation::run_dependents_first).
+///
+/// # Panics
+///
+/// Planning panics if a needed signal never becomes ready, because it is on
+/// or depends on a dependency cycle.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DependentsFirstPlanner {
+  max_pass_size: Option<usize>,
+}
+
+impl DependentsFirstPlanner {
+  /// Create a new planner with no pass size limit.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the number of targets in each pass.
+  pub fn with_max_pass_size(mut self, max_pass_size: usize) -> Self {
+    self.max_pass_size = Some(max_pass_size.max(1));
+    self
+  }
+}
+
+impl<Def: SignalDef> EvaluationPlanner<Def> for DependentsFirstPlanner {
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def> {
+    let graph = matrix.dependency_graph();
+    let dependents = matrix.dependents_graph();
+
+    let mut needed = FixedBitSet::with_capacity(graph.len());
+    for signal in matrix
+      .dependencies_closure(root_targets.iter().copied())
+      .iter()
+      .chain(root_targets.iter())
+    {
+      needed.insert(signal.index());
+    }
+
+    // dependency counts left before each signal is ready, and dependent
+    // counts within the planned signals
+    let mut pending = vec![0; graph.len()];
+    let mut unlocks = vec![0; graph.len()];
+    for index in needed.ones() {
+      let deps = graph.dependencies(Signal::from_index(index));
+      pending[index] = deps.len();
+      for dep in deps {
+        unlocks[dep.index()] += 1;
+      }
+    }
+
+    let mut ready: BinaryHeap<_> = needed
+      .ones()
+      .filter(|i| pending[*i] == 0)
+      .map(|i| (unlocks[i], Reverse(i)))
+      .collect();
+    let max_pass_size = self.max_pass_size.unwrap_or(usize::MAX);
+    let mut passes = Vec::new();
+    while !ready.is_empty() {
+      let mut pass = Vec::new();
+      while pass.len() < max_pass_size {
+        let Some((_, Reverse(index))) = ready.pop() else {
+          break;
+        };
+        pass.push(Signal::from_index(index));
+      }
+      for signal in pass.iter() {
+        for dependent in dependents.dependencies(*signal) {
+          let i = dependent.index();
+          if needed.contains(i) {
+            pending[i] -= 1;
+            if pending[i] == 0 {
+              ready.push((unlocks[i], Reverse(i)));
+            }
+          }
+        }
+      }
+      passes.push(EvaluationPassDescriptor::new(pass.into_iter().collect()));
+    }
+    if let Some(index) = needed.ones().find(|i| pending[*i] > 0) {
+      panic!(
+        "signal {} never becomes ready, as it is on or depends on a \
+         dependency cycle",
+        matrix.defset().display(Signal::from_index(index))
+      );
+    }
+
+    PlannedEvaluation::from_passes(matrix, root_targets, passes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, FloatMapSignalDef, SignalDefMap,
+    UnaryOp,
+  };
+
+  #[test]
+  fn test_dependents_first() {
+    let mut defset = SignalDefMap::new();
+    let lone = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let hub = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let users: Vec<_> = (0..3)
+      .map(|_| defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(hub))))
+      .collect();
+    let matrix = SignalMatrix::new(defset);
+
+    let planner = DependentsFirstPlanner::new().with_max_pass_size(2);
+    let roots = users.iter().copied().chain([lone]);
+    let plan = matrix.plan_evaluation(&planner, roots);
+    plan.verify().unwrap();
+    // the hub goes first; the lone input only fills the space left over
+    let first: Vec<_> = plan.passes()[0].targets().iter().copied().collect();
+    assert_eq!(first.len(), 2);
+    assert!(first.contains(&hub) && first.contains(&lone));
+
+    let plan = matrix
+      .plan_evaluation(&DependentsFirstPlanner::new().with_max_pass_size(1), [
+        users[0], lone,
+      ]);
+    assert_eq!(plan.passes()[0].targets().iter().next(), Some(&hub));
+
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     6,
+      width:      20,
+      min_fan_in: 1,
+      max_fan_in: 4,
+      reach:      3,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let expected = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected = expected
+      .run(EvaluationValueMap::new_empty(expected.all_queued_targets()));
+    let plan = matrix.plan_evaluation(
+      &DependentsFirstPlanner::new().with_max_pass_size(8),
+      roots.iter().copied(),
+    );
+    plan.verify().unwrap();
+    assert!(plan.passes().iter().all(|p| p.targets().len() <= 8));
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    for root in roots.iter() {
+      assert_eq!(values.get(*root), expected.get(*root));
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "dependency cycle")]
+  fn test_dependents_first_cycle() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    defset.replace(a, FloatMapSignalDef::UnaryOp(UnaryOp::Neg(b)));
+    let matrix = SignalMatrix::new(defset);
+    matrix.plan_evaluation(&DependentsFirstPlanner::new(), [b]);
+  }
+}
@@ -0,0 +1,155 @@
+use crate::{PlannedEvaluation, Relink, Signal, SignalDef, SignalDefMap};
+
+/// Why a signal is deprecated, and what to use instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+  pub replacement: Option<Signal>,
+  pub note:        String,
+}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Mark a signal deprecated, optionally pointing at its replacement.
+  /// Planning a graph that evaluates it logs a warning, and
+  /// [`migrate`](Self::migrate) wires its dependents to the replacement.
+  pub fn deprecate(
+    &mut self,
+    signal: Signal,
+    replacement: Option<Signal>,
+    note: impl Into<String>,
+  ) {
+    self.deprecated.insert(signal, Deprecation {
+      replacement,
+      note: note.into(),
+    });
+  }
+
+  /// Clear the deprecation of a signal, returning it if it had one.
+  pub fn undeprecate(&mut self, signal: Signal) -> Option<Deprecation> {
+    self.deprecated.remove(&signal)
+  }
+
+  /// Get the deprecation of a signal, if it's deprecated.
+  pub fn deprecation(&self, signal: Signal) -> Option<&Deprecation> {
+    self.deprecated.get(&signal)
+  }
+
+  /// Follow replacement pointers from a signal to the first one that isn't
+  /// deprecated with a replacement. Stops early on a cycle of replacements.
+  pub fn replacement(&self, mut signal: Signal) -> Signal {
+    let mut hops = 0;
+    while let Some(to) = self.deprecation(signal).and_then(|d| d.replacement) {
+      if hops == self.deprecated.len() {
+        break;
+      }
+      signal = to;
+      hops += 1;
+    }
+    signal
+  }
+}
+
+impl<T: Relink> SignalDefMap<T> {
+  /// Rewire every dependent of a deprecated signal with a replacement to
+  /// that replacement, returning the signals that were rewritten, by ID.
+  ///
+  /// A dependency isn't rewired to the signal depending on it, so a
+  /// replacement defined in terms of the signal it replaces keeps working.
+  pub fn migrate(&mut self) -> Vec<Signal> {
+    let mut signals: Vec<_> = self.iter().map(|(s, _)| s).collect();
+    signals.sort_by_key(|s| s.0);
+
+    let mut migrated = Vec::new();
+    let mut deps = Vec::new();
+    for signal in signals {
+      let def = self.get(signal).unwrap();
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      let rewired = |dep: Signal| match self.replacement(dep) {
+        to if to == signal => dep,
+        to => to,
+      };
+      if deps.iter().all(|dep| rewired(*dep) == *dep) {
+        continue;
+      }
+      let relinked = def.relink(&mut |dep| rewired(dep));
+      self.replace(signal, relinked);
+      migrated.push(signal);
+    }
+    migrated
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Log a warning for every deprecated signal the plan evaluates.
+  pub(crate) fn warn_deprecated(&self) {
+    let defset = self.matrix().defset();
+    if defset.deprecated.is_empty() {
+      return;
+    }
+    let mut signals: Vec<_> = self
+      .all_queued_targets()
+      .into_iter()
+      .filter(|s| defset.deprecation(*s).is_some())
+      .collect();
+    signals.sort_by_key(|s| s.0);
+    for signal in signals {
+      let deprecation = defset.deprecation(signal).unwrap();
+      let signal = defset.display(signal);
+      match deprecation.replacement {
+        Some(to) => tracing::warn!(
+          %signal,
+          replacement = %defset.display(to),
+          note = %deprecation.note,
+          "planned a deprecated signal"
+        ),
+        None => tracing::warn!(
+          %signal,
+          note = %deprecation.note,
+          "planned a deprecated signal"
+        ),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_deprecate_and_migrate() {
+    let mut defset = SignalDefMap::new();
+    let old = defset.insert_labeled("old", FloatMapSignalDef::Constant(1.0));
+    let new = defset
+      .insert_labeled("new", FloatMapSignalDef::UnaryOp(UnaryOp::Neg(old)));
+    let user =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(old, old)));
+    let untouched = defset.insert(FloatMapSignalDef::Constant(5.0));
+    defset.deprecate(old, Some(new), "use `new`, which flips the sign");
+    assert_eq!(defset.replacement(old), new);
+    assert_eq!(defset.replacement(untouched), untouched);
+
+    // the replacement still reads the signal it replaces
+    assert_eq!(defset.migrate(), [user]);
+    assert_eq!(defset.migrate(), []);
+    let matrix = SignalMatrix::new(defset);
+    assert!(matrix.dependencies_closure([user]).contains(&new));
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [user]);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(user), Some(&1.0));
+
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    defset.deprecate(a, Some(b), "");
+    defset.deprecate(b, Some(a), "");
+    assert_eq!(defset.replacement(a), a);
+    assert_eq!(defset.undeprecate(b).unwrap().replacement, Some(a));
+    assert_eq!(defset.replacement(a), b);
+  }
+}
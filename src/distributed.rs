@@ -0,0 +1,452 @@
+//! Evaluating a plan across worker processes over TCP.
+//!
+//! Every worker holds its own copy of the same [`SignalMatrix`] and serves
+//! requests with [`serve_worker`]. A [`Coordinator`] splits each pass of a plan
+//! across the workers, ships the values of every dependency a worker needs
+//! with its request, and merges the results into one [`EvaluationValueMap`].
+//!
+//! Messages are length-prefixed and little-endian. A request is the number of
+//! dependency values, each as a signal ID, byte length and encoded value,
+//! followed by the number of targets and their signal IDs. A response is a
+//! status byte: 0 followed by the number of values, each encoded like a
+//! dependency, or 1 followed by a length-prefixed UTF-8 error message for a
+//! request the worker refused.
+
+use std::{
+  io::{self, BufRead, BufReader, BufWriter, Read, Write},
+  net::{TcpListener, TcpStream, ToSocketAddrs},
+  thread,
+};
+
+use crate::{
+  EvaluationPassDescriptor, EvaluationValueMap, Partitioning,
+  PlannedEvaluation, Signal, SignalDef, SignalMatrix, SignalSet,
+};
+
+/// A value that can be sent between a [`Coordinator`] and its workers.
+pub trait WireValue: Sized {
+  /// Append the encoded value to `out`.
+  fn encode(&self, out: &mut Vec<u8>);
+  /// Decode a value encoded by [`encode`](Self::encode).
+  fn decode(bytes: &[u8]) -> io::Result<Self>;
+}
+
+impl WireValue for f64 {
+  fn encode(&self, out: &mut Vec<u8>) { out.extend(self.to_le_bytes()); }
+
+  fn decode(bytes: &[u8]) -> io::Result<Self> {
+    let bytes = bytes.try_into().map_err(|_| invalid("expected 8 bytes"))?;
+    Ok(f64::from_le_bytes(bytes))
+  }
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+  let mut buf = [0; 8];
+  r.read_exact(&mut buf)?;
+  Ok(u64::from_le_bytes(buf))
+}
+
+/// Read a length-prefixed byte string into `out`. The buffer grows with the
+/// bytes actually received, so a bogus length can't allocate more than the
+/// peer sends.
+pub(crate) fn read_bytes(
+  r: &mut impl Read,
+  out: &mut Vec<u8>,
+) -> io::Result<()> {
+  let len = read_u64(r)?;
+  out.clear();
+  r.by_ref().take(len).read_to_end(out)?;
+  if (out.len() as u64) < len {
+    return Err(io::ErrorKind::UnexpectedEof.into());
+  }
+  Ok(())
+}
+
+fn read_values<V: WireValue>(
+  r: &mut impl Read,
+) -> io::Result<Vec<(Signal, V)>> {
+  let count = read_u64(r)?;
+  let mut values = Vec::new();
+  let mut bytes = Vec::new();
+  for _ in 0..count {
+    let signal = Signal(read_u64(r)?);
+    read_bytes(r, &mut bytes)?;
+    values.push((signal, V::decode(&bytes)?));
+  }
+  Ok(values)
+}
+
+/// Read a worker's response, failing if it refused the request.
+fn read_response<V: WireValue>(
+  r: &mut impl Read,
+) -> io::Result<Vec<(Signal, V)>> {
+  let mut status = [0];
+  r.read_exact(&mut status)?;
+  match status[0] {
+    0 => read_values(r),
+    1 => {
+      let mut message = Vec::new();
+      read_bytes(r, &mut message)?;
+      Err(io::Error::other(format!(
+        "worker refused the request: {}",
+        String::from_utf8_lossy(&message)
+      )))
+    }
+    _ => Err(invalid("unknown response status")),
+  }
+}
+
+fn write_error(w: &mut impl Write, message: &str) -> io::Result<()> {
+  w.write_all(&[1])?;
+  w.write_all(&(message.len() as u64).to_le_bytes())?;
+  w.write_all(message.as_bytes())
+}
+
+/// Check that a request only names known targets and carries the value of
+/// every signal they read.
+fn check_request<T: SignalDef, V>(
+  matrix: &SignalMatrix<T>,
+  deps: &[(Signal, V)],
+  targets: &[Signal],
+) -> Result<(), String> {
+  if let Some(target) =
+    targets.iter().find(|t| matrix.defset().get(**t).is_none())
+  {
+    return Err(format!("unknown target {target}"));
+  }
+  let supplied: SignalSet = deps.iter().map(|(signal, _)| *signal).collect();
+  let graph = matrix.dependency_graph();
+  for target in targets {
+    let dependencies = graph.dependencies(*target);
+    if let Some(dep) = dependencies.iter().find(|d| !supplied.contains(d)) {
+      return Err(format!("missing value for dependency {dep} of {target}"));
+    }
+  }
+  Ok(())
+}
+
+fn write_values<'v, V: WireValue + 'v>(
+  w: &mut impl Write,
+  values: impl ExactSizeIterator<Item = (Signal, &'v V)>,
+) -> io::Result<()> {
+  w.write_all(&(values.len() as u64).to_le_bytes())?;
+  let mut bytes = Vec::new();
+  for (signal, value) in values {
+    bytes.clear();
+    value.encode(&mut bytes);
+    w.write_all(&signal.0.to_le_bytes())?;
+    w.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    w.write_all(&bytes)?;
+  }
+  Ok(())
+}
+
+/// Serve evaluation requests from coordinators on the given listener, one
+/// thread per connection, until accepting a connection fails.
+pub fn serve_worker<T>(
+  matrix: &SignalMatrix<T>,
+  listener: TcpListener,
+) -> io::Result<()>
+where
+  T: SignalDef,
+  T::Value: WireValue,
+{
+  thread::scope(|scope| {
+    for stream in listener.incoming() {
+      let stream = stream?;
+      scope.spawn(move || {
+        if let Err(e) = serve_connection(matrix, stream) {
+          tracing::warn!(error = %e, "worker connection failed");
+        }
+      });
+    }
+    Ok(())
+  })
+}
+
+fn serve_connection<T>(
+  matrix: &SignalMatrix<T>,
+  stream: TcpStream,
+) -> io::Result<()>
+where
+  T: SignalDef,
+  T::Value: WireValue,
+{
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut writer = BufWriter::new(stream);
+  loop {
+    // the coordinator closing between requests ends the connection cleanly
+    if reader.fill_buf()?.is_empty() {
+      return Ok(());
+    }
+    let request = read_values::<T::Value>(&mut reader).and_then(|deps| {
+      let targets: Vec<_> = (0..read_u64(&mut reader)?)
+        .map(|_| read_u64(&mut reader).map(Signal))
+        .collect::<io::Result<_>>()?;
+      Ok((deps, targets))
+    });
+    // a malformed request leaves no way to find the next one, so refuse it
+    // and close the connection
+    let (deps, targets) = match request {
+      Ok(request) => request,
+      Err(e) => {
+        write_error(&mut writer, &format!("malformed request: {e}"))?;
+        return writer.flush();
+      }
+    };
+    // refuse a bad request without evaluating it, keeping the connection
+    if let Err(message) = check_request(matrix, &deps, &targets) {
+      write_error(&mut writer, &message)?;
+      writer.flush()?;
+      continue;
+    }
+
+    let span = tracing::info_span!("worker_request", targets = targets.len());
+    let _enter = span.enter();
+
+    let pass = EvaluationPassDescriptor::new(targets.iter().copied().collect());
+    let plan =
+      PlannedEvaluation::from_passes(matrix, pass.targets().clone(), vec![
+        pass,
+      ]);
+    let mut values = EvaluationValueMap::new_empty(Default::default());
+    for (signal, value) in deps {
+      values.insert(signal, value);
+    }
+    let values = plan.run(values);
+
+    writer.write_all(&[0])?;
+    write_values(
+      &mut writer,
+      targets.iter().map(|t| (*t, values.get(*t).unwrap())),
+    )?;
+    writer.flush()?;
+  }
+}
+
+/// A connection to a set of workers serving [`serve_worker`], for running
+/// plans with [`PlannedEvaluation::run_distributed`].
+#[derive(Debug)]
+pub struct Coordinator {
+  workers:   Vec<(BufReader<TcpStream>, BufWriter<TcpStream>)>,
+  placement: Option<Partitioning>,
+}
+
+impl Coordinator {
+  /// Connect to the workers at the given addresses.
+  pub fn connect<A: ToSocketAddrs>(
+    addrs: impl IntoIterator<Item = A>,
+  ) -> io::Result<Self> {
+    let workers = addrs
+      .into_iter()
+      .map(|addr| {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok((BufReader::new(stream.try_clone()?), BufWriter::new(stream)))
+      })
+      .collect::<io::Result<Vec<_>>>()?;
+    if workers.is_empty() {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "a coordinator needs at least one worker",
+      ));
+    }
+    Ok(Coordinator {
+      workers,
+      placement: None,
+    })
+  }
+
+  /// Place signals on workers by a [`Partitioning`] of the graph, sending the
+  /// signals of part `i` to worker `i` modulo the number of workers, so fewer
+  /// values cross between workers.
+  pub fn with_partitioning(mut self, partitioning: Partitioning) -> Self {
+    self.placement = Some(partitioning);
+    self
+  }
+
+  /// Get the number of workers.
+  pub fn workers(&self) -> usize { self.workers.len() }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T>
+where
+  T::Value: WireValue,
+{
+  /// Run the planned evaluation across the coordinator's workers, updating
+  /// the given value map with the results.
+  ///
+  /// Each pass is split evenly across the workers, or by the coordinator's
+  /// partitioning if it has one, and all of its requests are sent before any
+  /// response is read, so the workers evaluate in parallel.
+  pub fn run_distributed(
+    &self,
+    mut values: EvaluationValueMap<T>,
+    coordinator: &mut Coordinator,
+  ) -> io::Result<EvaluationValueMap<T>> {
+    let graph = self.matrix().dependency_graph();
+    let workers = coordinator.workers.len();
+    values.begin_epoch();
+
+    for (i, pass) in self.passes().iter().enumerate() {
+      let pass_span = tracing::info_span!("distributed_pass", i);
+      let _enter = pass_span.enter();
+
+      for stage in pass.stages() {
+        // sort so the split is deterministic
+        let mut targets: Vec<_> = stage.iter().copied().collect();
+        targets.sort_by_key(|s| s.0);
+        let mut chunks = vec![Vec::new(); workers];
+        let per_worker = targets.len().div_ceil(workers).max(1);
+        for (i, target) in targets.into_iter().enumerate() {
+          let worker = match &coordinator.placement {
+            Some(placement) => placement.part_of(target).unwrap_or(0) % workers,
+            None => i / per_worker,
+          };
+          chunks[worker].push(target);
+        }
+
+        for (chunk, (_, writer)) in chunks.iter().zip(&mut coordinator.workers)
+        {
+          let deps: SignalSet = chunk
+            .iter()
+            .flat_map(|target| graph.dependencies(*target).iter().copied())
+            .collect();
+          let deps = deps
+            .iter()
+            .map(|dep| {
+              let value = values.get(*dep).ok_or_else(|| {
+                invalid(&format!("missing value for dependency {dep:?}"))
+              })?;
+              Ok((*dep, value))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+          write_values(writer, deps.into_iter())?;
+          writer.write_all(&(chunk.len() as u64).to_le_bytes())?;
+          for target in chunk.iter() {
+            writer.write_all(&target.0.to_le_bytes())?;
+          }
+          writer.flush()?;
+        }
+
+        for (_, (reader, _)) in chunks.iter().zip(&mut coordinator.workers) {
+          for (signal, value) in read_response::<T::Value>(reader)? {
+            values.insert_evaluated(signal, value);
+          }
+        }
+      }
+    }
+
+    Ok(values)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, FloatMapSignalDef, SignalDefMap,
+  };
+
+  fn build() -> (SignalMatrix<FloatMapSignalDef>, Vec<Signal>) {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 64,
+      arity:  2,
+    })
+    .with_seed(7)
+    .build_float(&mut defset);
+    (SignalMatrix::new(defset), roots)
+  }
+
+  #[test]
+  fn test_distributed_run() {
+    let addrs: Vec<_> = (0..3)
+      .map(|_| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve_worker(&build().0, listener));
+        addr
+      })
+      .collect();
+
+    let (matrix, roots) = build();
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let local = plan.run(EvaluationValueMap::new_empty(Default::default()));
+
+    let mut coordinator = Coordinator::connect(&addrs).unwrap();
+    let remote = plan
+      .run_distributed(
+        EvaluationValueMap::new_empty(Default::default()),
+        &mut coordinator,
+      )
+      .unwrap();
+    let partitioning = matrix.partition(3, Default::default());
+    let mut coordinator = Coordinator::connect(&addrs)
+      .unwrap()
+      .with_partitioning(partitioning);
+    let placed = plan
+      .run_distributed(
+        EvaluationValueMap::new_empty(Default::default()),
+        &mut coordinator,
+      )
+      .unwrap();
+
+    for root in roots {
+      assert_eq!(remote.get(root), local.get(root));
+      assert_eq!(placed.get(root), local.get(root));
+    }
+  }
+
+  #[test]
+  fn test_worker_refuses_bad_requests() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || serve_worker(&build().0, listener));
+    let (matrix, roots) = build();
+    let (leaf, constant) = matrix
+      .defset()
+      .iter()
+      .find_map(|(signal, def)| match def {
+        FloatMapSignalDef::Constant(c) => Some((signal, *c)),
+        _ => None,
+      })
+      .unwrap();
+    let request = |stream: &mut TcpStream, target: Signal| {
+      write_values::<f64>(stream, std::iter::empty()).unwrap();
+      stream.write_all(&1u64.to_le_bytes()).unwrap();
+      stream.write_all(&target.0.to_le_bytes()).unwrap();
+    };
+
+    // a value claiming to be huge only costs what is actually sent, and is
+    // refused once the stream ends short of it
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(&1u64.to_le_bytes()).unwrap();
+    stream.write_all(&leaf.0.to_le_bytes()).unwrap();
+    stream.write_all(&u64::MAX.to_le_bytes()).unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let error = read_response::<f64>(&mut stream).unwrap_err();
+    assert!(error.to_string().contains("malformed request"));
+
+    // the worker keeps serving new connections
+    let mut stream = TcpStream::connect(addr).unwrap();
+    request(&mut stream, leaf);
+    let values = read_response::<f64>(&mut stream).unwrap();
+    assert_eq!(values, [(leaf, constant)]);
+
+    // a missing dependency is refused, and the connection stays usable
+    let mut stream = TcpStream::connect(addr).unwrap();
+    request(&mut stream, roots[0]);
+    let error = read_response::<f64>(&mut stream).unwrap_err();
+    assert!(error.to_string().contains("missing value for dependency"));
+    request(&mut stream, leaf);
+    let values = read_response::<f64>(&mut stream).unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].0, leaf);
+  }
+}
@@ -0,0 +1,174 @@
+use std::cmp::Reverse;
+
+use crate::{Signal, SignalDef, SignalMap, SignalMatrix};
+
+/// The dominator tree of a root's dependencies, from
+/// [`SignalMatrix::dominators`].
+///
+/// A signal dominates another if every dependency path from the root to the
+/// other passes through it, so the root only sees the other through it. The
+/// dominators of a signal are single points of failure for it, and a signal
+/// dominating a large part of the graph is a prime candidate for caching or
+/// validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominatorTree {
+  root:      Signal,
+  idom:      SignalMap<Signal>,
+  dominated: SignalMap<usize>,
+}
+
+impl DominatorTree {
+  /// Get the root the tree was computed for.
+  pub fn root(&self) -> Signal { self.root }
+
+  /// Get the closest strict dominator of a signal, or `None` for the root
+  /// and for signals the root doesn't depend on.
+  pub fn immediate_dominator(&self, signal: Signal) -> Option<Signal> {
+    self.idom.get(&signal).copied()
+  }
+
+  /// Get every strict dominator of a signal, from the closest up to the
+  /// root.
+  pub fn dominators(&self, signal: Signal) -> Vec<Signal> {
+    std::iter::successors(self.immediate_dominator(signal), |s| {
+      self.immediate_dominator(*s)
+    })
+    .collect()
+  }
+
+  /// Check whether `dominator` dominates `signal`. Every signal in the tree
+  /// dominates itself.
+  pub fn dominates(&self, dominator: Signal, signal: Signal) -> bool {
+    let in_tree = |s| s == self.root || self.idom.contains_key(&s);
+    in_tree(signal)
+      && (dominator == signal || self.dominators(signal).contains(&dominator))
+  }
+
+  /// Get the number of signals a signal strictly dominates.
+  pub fn dominated_count(&self, signal: Signal) -> usize {
+    self.dominated.get(&signal).copied().unwrap_or(0)
+  }
+
+  /// Get every signal other than the root that dominates at least one other,
+  /// with its dominated count, most dominating first and then by ID.
+  pub fn bottlenecks(&self) -> Vec<(Signal, usize)> {
+    let mut bottlenecks: Vec<_> = self
+      .dominated
+      .iter()
+      .filter(|(s, count)| **s != self.root && **count > 0)
+      .map(|(s, count)| (*s, *count))
+      .collect();
+    bottlenecks.sort_by_key(|(s, count)| (Reverse(*count), s.0));
+    bottlenecks
+  }
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Compute the dominator tree of a root's dependencies, following edges
+  /// from the root down to its inputs.
+  pub fn dominators(&self, root: Signal) -> DominatorTree {
+    let graph = self.dependency_graph();
+    let dependents = self.dependents_graph();
+
+    // postorder of the signals reachable from the root
+    let mut order = vec![usize::MAX; graph.len()];
+    let mut postorder = Vec::new();
+    let mut stack = vec![(root, 0)];
+    order[root.index()] = 0;
+    while let Some((signal, next)) = stack.pop() {
+      match graph.dependencies(signal).get(next) {
+        Some(dep) => {
+          stack.push((signal, next + 1));
+          if order[dep.index()] == usize::MAX {
+            order[dep.index()] = 0;
+            stack.push((*dep, 0));
+          }
+        }
+        None => {
+          order[signal.index()] = postorder.len();
+          postorder.push(signal);
+        }
+      }
+    }
+
+    // Cooper, Harvey and Kennedy's iterative algorithm, by postorder number
+    let mut idom = vec![usize::MAX; postorder.len()];
+    let root_number = postorder.len() - 1;
+    idom[root_number] = root_number;
+    let intersect = |idom: &[usize], mut a: usize, mut b: usize| {
+      while a != b {
+        while a < b {
+          a = idom[a];
+        }
+        while b < a {
+          b = idom[b];
+        }
+      }
+      a
+    };
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for number in (0..root_number).rev() {
+        let preds = dependents
+          .dependencies(postorder[number])
+          .iter()
+          .map(|s| order[s.index()])
+          .filter(|p| *p != usize::MAX && idom[*p] != usize::MAX);
+        let Some(new) = preds.reduce(|a, b| intersect(&idom, a, b)) else {
+          continue;
+        };
+        if idom[number] != new {
+          idom[number] = new;
+          changed = true;
+        }
+      }
+    }
+
+    let mut dominated = vec![0; postorder.len()];
+    for number in 0..root_number {
+      dominated[idom[number]] += dominated[number] + 1;
+    }
+    DominatorTree {
+      root,
+      idom: (0..root_number)
+        .map(|n| (postorder[n], postorder[idom[n]]))
+        .collect(),
+      dominated: postorder.iter().copied().zip(dominated).collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap, UnaryOp};
+
+  #[test]
+  fn test_dominators() {
+    // a feeds a diamond through b and c into d; d and e feed the root
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let c = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let d =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(b, c)));
+    let e = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let root =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(d, e)));
+    let stray = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let matrix = SignalMatrix::new(defset);
+
+    let tree = matrix.dominators(root);
+    assert_eq!(tree.immediate_dominator(a), Some(d));
+    assert_eq!(tree.immediate_dominator(b), Some(d));
+    assert_eq!(tree.immediate_dominator(e), Some(root));
+    assert_eq!(tree.immediate_dominator(root), None);
+    assert_eq!(tree.immediate_dominator(stray), None);
+    assert_eq!(tree.dominators(a), [d, root]);
+    assert!(tree.dominates(d, a) && tree.dominates(a, a));
+    assert!(!tree.dominates(b, a) && !tree.dominates(root, stray));
+    assert_eq!(tree.dominated_count(root), 5);
+    assert_eq!(tree.bottlenecks(), [(d, 3)]);
+  }
+}
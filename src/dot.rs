@@ -0,0 +1,82 @@
+use std::fmt::Write;
+
+use crate::{SignalDef, SignalMatrix};
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Render the graph in Graphviz DOT format, with an edge from each
+  /// dependency to its dependent labeled by the dependency's role, if it has
+  /// one. Signals are labeled like
+  /// [`SignalDefMap::display`](crate::SignalDefMap::display) and listed by
+  /// ID.
+  pub fn to_dot(&self) -> String {
+    let mut signals: Vec<_> = self.defset.iter().map(|(s, _)| s).collect();
+    signals.sort_by_key(|s| s.0);
+
+    let mut out = String::from("digraph signals {\n");
+    for signal in signals.iter() {
+      let label = escape(&self.defset.display(*signal).to_string());
+      writeln!(out, "  n{} [label=\"{label}\"];", signal.0).unwrap();
+    }
+    for signal in signals.iter() {
+      for (dep, role) in self.dependency_edges(*signal) {
+        write!(out, "  n{} -> n{}", dep.0, signal.0).unwrap();
+        if let Some(role) = role {
+          write!(out, " [label=\"{}\"]", escape(role)).unwrap();
+        }
+        out.push_str(";\n");
+      }
+    }
+    out.push_str("}\n");
+    out
+  }
+}
+
+fn escape(label: &str) -> String {
+  label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    DefHarness, EvalContext, IntMapSignalDef, IntOp, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_roles_in_dot() {
+    let mut defset = SignalDefMap::new();
+    let a =
+      defset.insert_labeled("a", IntMapSignalDef::new(IntOp::Constant(6)));
+    let b = defset.insert(IntMapSignalDef::new(IntOp::Constant(3)));
+    let quotient = defset
+      .insert_labeled("a \"over\" b", IntMapSignalDef::new(IntOp::Div(a, b)));
+    defset.insert(IntMapSignalDef::new(IntOp::Neg(quotient)));
+    let matrix = SignalMatrix::new(defset);
+
+    assert_eq!(matrix.dependency_edges(quotient), [
+      (a, Some("numerator")),
+      (b, Some("denominator"))
+    ]);
+    assert_eq!(
+      matrix.to_dot(),
+      "digraph signals {\n  n0 [label=\"a\"];\n  n1 [label=\"#1\"];\n  n2 \
+       [label=\"a \\\"over\\\" b\"];\n  n3 [label=\"#3\"];\n  n0 -> n2 \
+       [label=\"numerator\"];\n  n1 -> n2 [label=\"denominator\"];\n  n2 -> \
+       n3;\n}\n"
+    );
+
+    // roles reach the evaluation context
+    let mut harness = DefHarness::new();
+    let (six, three) = (harness.stub(Ok(6)), harness.stub(Ok(3)));
+    harness
+      .assert_evaluates(&IntMapSignalDef::new(IntOp::Div(six, three)), Ok(2));
+    let def = IntMapSignalDef::new(IntOp::Div(three, six));
+    let ctx =
+      EvalContext::<IntMapSignalDef>::for_def(harness.target(), &def, [
+        (three, &Ok(3)),
+        (six, &Ok(6)),
+      ]);
+    assert_eq!(ctx.role("numerator"), Some(&Ok(3)));
+    assert_eq!(ctx.role_of(six), Some("denominator"));
+    assert_eq!(ctx.role("divisor"), None);
+  }
+}
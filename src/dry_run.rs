@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use crate::{PlannedEvaluation, Signal, SignalDef};
+
+/// Predicts how long evaluating a signal takes, for
+/// [`PlannedEvaluation::simulate`].
+///
+/// Implemented for closures taking the signal and its definition.
+pub trait CostModel<T: SignalDef> {
+  /// Predict the time it takes to evaluate the given signal.
+  fn cost(&self, signal: Signal, def: &T) -> Duration;
+}
+
+impl<T: SignalDef, F> CostModel<T> for F
+where
+  F: Fn(Signal, &T) -> Duration,
+{
+  fn cost(&self, signal: Signal, def: &T) -> Duration { self(signal, def) }
+}
+
+/// The predicted cost of one pass of a [`DryRun`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassForecast {
+  targets:    usize,
+  work:       Duration,
+  longest:    Duration,
+  sequential: bool,
+  live_after: usize,
+}
+
+impl PassForecast {
+  /// Get the number of targets in the pass.
+  pub fn targets(&self) -> usize { self.targets }
+
+  /// Get the summed cost of the pass's targets.
+  pub fn work(&self) -> Duration { self.work }
+
+  /// Get the cost of the pass's most expensive target, which bounds how
+  /// short the pass can get with any number of threads.
+  pub fn longest(&self) -> Duration { self.longest }
+
+  /// Whether the pass is a sequential segment, whose targets run one after
+  /// another.
+  pub fn is_sequential(&self) -> bool { self.sequential }
+
+  /// Predict the duration of the pass on the given number of threads, as the
+  /// larger of its longest target and its work split evenly over the
+  /// threads. A sequential segment takes its whole work on any number.
+  pub fn duration(&self, threads: usize) -> Duration {
+    if self.sequential {
+      return self.work;
+    }
+    self.longest.max(self.work / threads.max(1) as u32)
+  }
+
+  /// Get the number of target values alive once the pass has run.
+  pub fn live_after(&self) -> usize { self.live_after }
+}
+
+/// A prediction of a run from [`PlannedEvaluation::simulate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun {
+  passes: Vec<PassForecast>,
+}
+
+impl DryRun {
+  /// Get the forecast of each pass, in order.
+  pub fn passes(&self) -> &[PassForecast] { &self.passes }
+
+  /// Get the summed cost of every target.
+  pub fn total_work(&self) -> Duration {
+    self.passes.iter().map(|pass| pass.work).sum()
+  }
+
+  /// Predict the duration of the run on the given number of threads.
+  pub fn duration(&self, threads: usize) -> Duration {
+    self.passes.iter().map(|pass| pass.duration(threads)).sum()
+  }
+
+  /// Get the largest number of target values alive at once, counting each
+  /// pass's targets as alive while it runs.
+  pub fn peak_live_values(&self) -> usize {
+    let mut live_before = 0;
+    let mut peak = 0;
+    for pass in self.passes.iter() {
+      peak = peak.max(live_before + pass.targets);
+      live_before = pass.live_after;
+    }
+    peak
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Walk the plan without evaluating anything, predicting the cost of each
+  /// pass under the given cost model and the values alive after it.
+  ///
+  /// Intermediates are counted as freed after their last use only if the plan
+  /// [frees intermediates](Self::with_free_intermediates), as in a real run.
+  pub fn simulate(&self, model: &impl CostModel<T>) -> DryRun {
+    let defset = self.matrix().defset();
+    let releases = self.free_intermediates().then(|| self.release_schedule());
+
+    let mut live = 0;
+    let passes = self
+      .passes()
+      .iter()
+      .enumerate()
+      .map(|(i, pass)| {
+        let costs = pass
+          .targets()
+          .iter()
+          .map(|signal| model.cost(*signal, defset.get(*signal).unwrap()));
+        let (work, longest) = costs
+          .fold((Duration::ZERO, Duration::ZERO), |(work, longest), cost| {
+            (work + cost, longest.max(cost))
+          });
+        live += pass.targets().len();
+        if let Some(releases) = &releases {
+          live -= releases[i].len();
+        }
+        PassForecast {
+          targets: pass.targets().len(),
+          work,
+          longest,
+          sequential: pass.is_sequential(),
+          live_after: live,
+        }
+      })
+      .collect();
+    DryRun { passes }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, FloatMapSignalDef, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_simulate() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 8,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots);
+
+    // constants are free, operations take a millisecond
+    let model = |_, def: &FloatMapSignalDef| match def {
+      FloatMapSignalDef::Constant(_) => Duration::ZERO,
+      _ => Duration::from_millis(1),
+    };
+    let dry_run = plan.simulate(&model);
+    let widths: Vec<_> = dry_run.passes().iter().map(|p| p.targets()).collect();
+    assert_eq!(widths, [8, 4, 2, 1]);
+    assert_eq!(dry_run.total_work(), Duration::from_millis(7));
+    assert_eq!(dry_run.duration(1), Duration::from_millis(7));
+    assert_eq!(dry_run.duration(64), Duration::from_millis(3));
+    assert_eq!(dry_run.peak_live_values(), 15);
+
+    let freeing = plan.with_free_intermediates(true).simulate(&model);
+    assert_eq!(freeing.passes()[3].live_after(), 1);
+    assert_eq!(freeing.peak_live_values(), 12);
+
+    // a sequential segment costs the sum of its targets
+    let mut defset = SignalDefMap::new();
+    let chain = GraphGenerator::new(GraphShape::Chain { length: 4 })
+      .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), chain)
+      .collapse_chains();
+    let dry_run = plan.simulate(&model);
+    assert!(dry_run.passes()[0].is_sequential());
+    assert_eq!(dry_run.duration(64), Duration::from_millis(3));
+  }
+}
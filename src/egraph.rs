@@ -0,0 +1,436 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use egg::{
+  define_language, rewrite as rw, Analysis, CostFunction, DidMerge, EGraph,
+  Extractor, Id, Language, Rewrite, Runner,
+};
+
+use crate::{
+  FloatBinaryOp, FloatMapSignalDef, Signal, SignalDef, SignalDefMap, SignalMap,
+  UnaryOp,
+};
+
+/// The bits of an `f64` constant, so it can be hashed and ordered as an
+/// e-graph leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Bits(u64);
+
+impl Bits {
+  fn new(value: f64) -> Self { Bits(value.to_bits()) }
+
+  fn get(self) -> f64 { f64::from_bits(self.0) }
+}
+
+impl fmt::Display for Bits {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.get())
+  }
+}
+
+impl FromStr for Bits {
+  type Err = std::num::ParseFloatError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> { s.parse().map(Bits::new) }
+}
+
+/// A signal kept opaque in the e-graph, written `#id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Leaf(u64);
+
+impl fmt::Display for Leaf {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "#{}", self.0)
+  }
+}
+
+impl FromStr for Leaf {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.strip_prefix('#')
+      .and_then(|id| id.parse().ok())
+      .map(Leaf)
+      .ok_or(())
+  }
+}
+
+define_language! {
+  enum Arith {
+    "+" = Add([Id; 2]),
+    "-" = Sub([Id; 2]),
+    "*" = Mul([Id; 2]),
+    "/" = Div([Id; 2]),
+    "pow" = Pow([Id; 2]),
+    "neg" = Neg(Id),
+    Num(Bits),
+    Input(Leaf),
+  }
+}
+
+/// Folds e-classes whose value is known into constants.
+#[derive(Default)]
+struct ConstantFolding;
+
+impl Analysis<Arith> for ConstantFolding {
+  type Data = Option<Bits>;
+
+  fn make(
+    egraph: &mut EGraph<Arith, Self>,
+    enode: &Arith,
+    _: Id,
+  ) -> Self::Data {
+    let x = |id: &Id| egraph[*id].data.map(Bits::get);
+    let value = match enode {
+      Arith::Num(bits) => return Some(*bits),
+      Arith::Input(_) => return None,
+      Arith::Add([a, b]) => x(a)? + x(b)?,
+      Arith::Sub([a, b]) => x(a)? - x(b)?,
+      Arith::Mul([a, b]) => x(a)? * x(b)?,
+      Arith::Div([a, b]) => x(a)? / x(b)?,
+      Arith::Pow([a, b]) => x(a)?.powf(x(b)?),
+      Arith::Neg(a) => -x(a)?,
+    };
+    Some(Bits::new(value))
+  }
+
+  fn merge(&mut self, to: &mut Self::Data, from: Self::Data) -> DidMerge {
+    // classes merged by a rule that doesn't hold for every float (like
+    // `x * 0 = 0` for infinite `x`) may disagree; keep what's there
+    egg::merge_option(to, from, |_, _| DidMerge(false, false))
+  }
+
+  fn modify(egraph: &mut EGraph<Arith, Self>, id: Id) {
+    if let Some(bits) = egraph[id].data {
+      let folded = egraph.add(Arith::Num(bits));
+      egraph.union(id, folded);
+    }
+  }
+}
+
+/// Weighs each operation by its rough cost to evaluate.
+struct OpCost;
+
+impl CostFunction<Arith> for OpCost {
+  type Cost = usize;
+
+  fn cost<C>(&mut self, enode: &Arith, mut costs: C) -> Self::Cost
+  where
+    C: FnMut(Id) -> Self::Cost,
+  {
+    enode.fold(weight(enode), |sum, id| sum + costs(id))
+  }
+}
+
+/// Get the rough cost of evaluating a single node.
+fn weight(node: &Arith) -> usize {
+  match node {
+    Arith::Num(_) | Arith::Input(_) => 1,
+    Arith::Add(_) | Arith::Sub(_) | Arith::Neg(_) => 2,
+    Arith::Mul(_) => 3,
+    Arith::Div(_) => 8,
+    Arith::Pow(_) => 20,
+  }
+}
+
+/// The arithmetic rules used for saturation. They assume real arithmetic, so
+/// results can differ from the original graph in rounding, and where
+/// infinities or NaNs are involved.
+fn rules() -> Vec<Rewrite<Arith, ConstantFolding>> {
+  let mut rules = vec![
+    rw!("commute-add"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+    rw!("commute-mul"; "(* ?a ?b)" => "(* ?b ?a)"),
+    rw!("assoc-add"; "(+ ?a (+ ?b ?c))" => "(+ (+ ?a ?b) ?c)"),
+    rw!("assoc-mul"; "(* ?a (* ?b ?c))" => "(* (* ?a ?b) ?c)"),
+    rw!("add-0"; "(+ ?a 0)" => "?a"),
+    rw!("sub-0"; "(- ?a 0)" => "?a"),
+    rw!("sub-self"; "(- ?a ?a)" => "0"),
+    rw!("mul-0"; "(* ?a 0)" => "0"),
+    rw!("mul-1"; "(* ?a 1)" => "?a"),
+    rw!("mul-neg-1"; "(* ?a -1)" => "(neg ?a)"),
+    rw!("div-1"; "(/ ?a 1)" => "?a"),
+    rw!("neg-neg"; "(neg (neg ?a))" => "?a"),
+    rw!("add-neg"; "(+ ?a (neg ?b))" => "(- ?a ?b)"),
+    rw!("pow-0"; "(pow ?a 0)" => "1"),
+    rw!("pow-1"; "(pow ?a 1)" => "?a"),
+    rw!("pow-2"; "(pow ?a 2)" => "(* ?a ?a)"),
+    rw!("double"; "(+ ?a ?a)" => "(* 2 ?a)"),
+  ];
+  rules.extend(rw!("sub-canon"; "(- ?a ?b)" <=> "(+ ?a (neg ?b))"));
+  rules.extend(
+    rw!("distribute"; "(* ?a (+ ?b ?c))" <=> "(+ (* ?a ?b) (* ?a ?c))"),
+  );
+  rules
+}
+
+/// Optimizes float graphs by equality saturation: the graph is lowered into
+/// an e-graph, a standard arithmetic ruleset is applied until nothing new is
+/// found or a limit is hit, and the cheapest equivalent graph is extracted.
+///
+/// The rules assume real arithmetic, so the optimized graph can round
+/// differently, and can differ where infinities or NaNs are involved.
+#[derive(Debug, Clone)]
+pub struct EggOptimizer {
+  iter_limit: usize,
+  node_limit: usize,
+}
+
+impl Default for EggOptimizer {
+  fn default() -> Self {
+    EggOptimizer {
+      iter_limit: 30,
+      node_limit: 10_000,
+    }
+  }
+}
+
+/// The graph extracted by [`EggOptimizer::optimize`].
+#[derive(Debug)]
+pub struct OptimizedGraph {
+  defset:      SignalDefMap<FloatMapSignalDef>,
+  remap:       SignalMap<Signal>,
+  cost_before: usize,
+  cost_after:  usize,
+}
+
+impl OptimizedGraph {
+  /// Get the optimized signal definitions.
+  pub fn defset(&self) -> &SignalDefMap<FloatMapSignalDef> { &self.defset }
+
+  /// Take the optimized signal definitions.
+  pub fn into_defset(self) -> SignalDefMap<FloatMapSignalDef> { self.defset }
+
+  /// Get the signal in the optimized map standing in for the given root or
+  /// input of the original one.
+  pub fn remap(&self, signal: Signal) -> Option<Signal> {
+    self.remap.get(&signal).copied()
+  }
+
+  /// Get the estimated cost of evaluating every signal the roots depend on,
+  /// before optimizing.
+  pub fn cost_before(&self) -> usize { self.cost_before }
+
+  /// Get the estimated cost of evaluating every signal the roots depend on,
+  /// after optimizing.
+  pub fn cost_after(&self) -> usize { self.cost_after }
+}
+
+impl EggOptimizer {
+  /// Create an optimizer with the default limits.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the number of saturation iterations.
+  pub fn with_iter_limit(mut self, iter_limit: usize) -> Self {
+    self.iter_limit = iter_limit;
+    self
+  }
+
+  /// Limit the number of e-nodes the e-graph may grow to.
+  pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+    self.node_limit = node_limit;
+    self
+  }
+
+  /// Optimize the subgraph of `defset` reachable from the roots into a new
+  /// map, with fresh signal IDs.
+  ///
+  /// Constants are folded into the graph unless they're listed as inputs:
+  /// inputs are kept as opaque leaves with their definitions copied over,
+  /// so their values can still be overridden in the optimized graph. Roots
+  /// and inputs keep their labels, and [`OptimizedGraph::remap`] maps them
+  /// to their new signals.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a signal reachable from the roots has no definition, or if an
+  /// input isn't a constant.
+  pub fn optimize(
+    &self,
+    defset: &SignalDefMap<FloatMapSignalDef>,
+    roots: impl IntoIterator<Item = Signal>,
+    inputs: impl IntoIterator<Item = Signal>,
+  ) -> OptimizedGraph {
+    let span = tracing::info_span!("egg_optimize");
+    let _enter = span.enter();
+
+    let roots: Vec<_> = roots.into_iter().collect();
+    let inputs: Vec<_> = inputs.into_iter().collect();
+
+    // lower in post-order, so every dependency is added before its dependents
+    let mut egraph = EGraph::<Arith, ConstantFolding>::default();
+    let mut lowered = SignalMap::<Id>::default();
+    let mut stack: Vec<_> = roots.iter().map(|s| (*s, false)).collect();
+    stack.extend(inputs.iter().map(|s| (*s, false)));
+    let mut deps = Vec::new();
+    let mut cost_before = 0;
+    while let Some((signal, expanded)) = stack.pop() {
+      if lowered.contains_key(&signal) {
+        continue;
+      }
+      let def = defset
+        .get(signal)
+        .unwrap_or_else(|| panic!("no definition for #{}", signal.0));
+      if inputs.contains(&signal) {
+        assert!(
+          matches!(def, FloatMapSignalDef::Constant(_)),
+          "input #{} is not a constant",
+          signal.0
+        );
+        lowered.insert(signal, egraph.add(Arith::Input(Leaf(signal.0))));
+        cost_before += 1;
+        continue;
+      }
+      if !expanded {
+        deps.clear();
+        def.dependencies_into(&mut deps);
+        stack.push((signal, true));
+        stack.extend(deps.iter().map(|dep| (*dep, false)));
+        continue;
+      }
+      let id = |s: &Signal| lowered[s];
+      let node = match def {
+        FloatMapSignalDef::Constant(value) => Arith::Num(Bits::new(*value)),
+        FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => Arith::Neg(id(a)),
+        FloatMapSignalDef::BinaryOp(op) => match op {
+          FloatBinaryOp::Add(a, b) => Arith::Add([id(a), id(b)]),
+          FloatBinaryOp::Sub(a, b) => Arith::Sub([id(a), id(b)]),
+          FloatBinaryOp::Mul(a, b) => Arith::Mul([id(a), id(b)]),
+          FloatBinaryOp::Div(a, b) => Arith::Div([id(a), id(b)]),
+          FloatBinaryOp::Pow(a, b) => Arith::Pow([id(a), id(b)]),
+        },
+      };
+      cost_before += weight(&node);
+      lowered.insert(signal, egraph.add(node));
+    }
+    egraph.rebuild();
+
+    let runner = Runner::default()
+      .with_iter_limit(self.iter_limit)
+      .with_node_limit(self.node_limit)
+      .with_egraph(egraph)
+      .run(&rules());
+    tracing::info!(
+      iterations = runner.iterations.len(),
+      nodes = runner.egraph.total_number_of_nodes(),
+      stop_reason = ?runner.stop_reason,
+      "saturation complete"
+    );
+
+    // rebuild from the best node of each class, sharing classes between
+    // roots so common subexpressions are only defined once
+    let egraph = &runner.egraph;
+    let extractor = Extractor::new(egraph, OpCost);
+    let mut out = SignalDefMap::new();
+    let mut built = HashMap::<Id, Signal>::new();
+    let mut cost_after = 0;
+    let mut stack: Vec<_> = roots
+      .iter()
+      .chain(inputs.iter())
+      .map(|s| (egraph.find(lowered[s]), false))
+      .collect();
+    while let Some((class, expanded)) = stack.pop() {
+      if built.contains_key(&class) {
+        continue;
+      }
+      let node = extractor.find_best_node(class);
+      if !expanded {
+        stack.push((class, true));
+        stack.extend(node.children().iter().map(|c| (egraph.find(*c), false)));
+        continue;
+      }
+      let s = |id: &Id| built[&egraph.find(*id)];
+      let def = match node {
+        Arith::Num(bits) => FloatMapSignalDef::Constant(bits.get()),
+        Arith::Input(Leaf(id)) => match defset.get(Signal(*id)) {
+          Some(FloatMapSignalDef::Constant(value)) => {
+            FloatMapSignalDef::Constant(*value)
+          }
+          _ => unreachable!("inputs are checked while lowering"),
+        },
+        Arith::Neg(a) => FloatMapSignalDef::UnaryOp(UnaryOp::Neg(s(a))),
+        Arith::Add([a, b]) => binary(FloatBinaryOp::Add(s(a), s(b))),
+        Arith::Sub([a, b]) => binary(FloatBinaryOp::Sub(s(a), s(b))),
+        Arith::Mul([a, b]) => binary(FloatBinaryOp::Mul(s(a), s(b))),
+        Arith::Div([a, b]) => binary(FloatBinaryOp::Div(s(a), s(b))),
+        Arith::Pow([a, b]) => binary(FloatBinaryOp::Pow(s(a), s(b))),
+      };
+      cost_after += weight(node);
+      built.insert(class, out.insert(def));
+    }
+
+    let mut remap = SignalMap::default();
+    for signal in roots.iter().chain(inputs.iter()) {
+      let new = built[&egraph.find(lowered[signal])];
+      remap.insert(*signal, new);
+      if let Some(label) = defset.label(*signal) {
+        if out.label(new).is_none() {
+          out.set_label(new, label);
+        }
+      }
+    }
+
+    tracing::info!(
+      signals = out.len(),
+      cost_before,
+      cost_after,
+      "extracted optimized graph"
+    );
+    OptimizedGraph {
+      defset: out,
+      remap,
+      cost_before,
+      cost_after,
+    }
+  }
+}
+
+fn binary(op: FloatBinaryOp) -> FloatMapSignalDef {
+  FloatMapSignalDef::BinaryOp(op)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalMatrix};
+
+  #[test]
+  fn test_optimize() {
+    type Def = FloatMapSignalDef;
+    let op = Def::BinaryOp;
+
+    // (x * 1 + 0) * (y - y) + (x * 2 + x * 3) / (2 + 3)
+    let mut defset = SignalDefMap::new();
+    let x = defset.insert_labeled("x", Def::Constant(4.0));
+    let y = defset.insert(Def::Constant(7.0));
+    let zero = defset.insert(Def::Constant(0.0));
+    let one = defset.insert(Def::Constant(1.0));
+    let two = defset.insert(Def::Constant(2.0));
+    let three = defset.insert(Def::Constant(3.0));
+    let x1 = defset.insert(op(FloatBinaryOp::Mul(x, one)));
+    let x10 = defset.insert(op(FloatBinaryOp::Add(x1, zero)));
+    let yy = defset.insert(op(FloatBinaryOp::Sub(y, y)));
+    let dead = defset.insert(op(FloatBinaryOp::Mul(x10, yy)));
+    let x2 = defset.insert(op(FloatBinaryOp::Mul(x, two)));
+    let x3 = defset.insert(op(FloatBinaryOp::Mul(x, three)));
+    let sum = defset.insert(op(FloatBinaryOp::Add(x2, x3)));
+    let five = defset.insert(op(FloatBinaryOp::Add(two, three)));
+    let mean = defset.insert(op(FloatBinaryOp::Div(sum, five)));
+    let total =
+      defset.insert_labeled("total", op(FloatBinaryOp::Add(dead, mean)));
+
+    let optimized = EggOptimizer::new().optimize(&defset, [total], [x, y]);
+    assert!(optimized.cost_after() < optimized.cost_before());
+
+    // `x * 5 / 5`, with `y` kept as an input even though nothing uses it
+    let total = optimized.remap(total).unwrap();
+    let x = optimized.remap(x).unwrap();
+    let defset = optimized.into_defset();
+    assert_eq!(defset.lookup("total"), Some(total));
+    assert_eq!(defset.lookup("x"), Some(x));
+    assert_eq!(defset.len(), 5);
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(total), Some(&4.0));
+  }
+}
@@ -0,0 +1,288 @@
+use std::{
+  sync::{Arc, Condvar, Mutex, RwLock},
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use crate::{
+  EvaluationPlanner, EvaluationValueMap, HotMatrix, Signal, SignalDef,
+  SignalMap, SignalMatrix, SignalSet,
+};
+
+/// Decides when an [`Engine`] runs its next tick.
+///
+/// Implemented for [`Duration`], as a fixed interval, and for closures taking
+/// the time the last tick started and returning when the next one should.
+pub trait Schedule: Send {
+  /// Get when the tick after one started at `last` should start.
+  fn next_tick(&mut self, last: Instant) -> Instant;
+}
+
+impl Schedule for Duration {
+  fn next_tick(&mut self, last: Instant) -> Instant { last + *self }
+}
+
+impl<F: FnMut(Instant) -> Instant + Send> Schedule for F {
+  fn next_tick(&mut self, last: Instant) -> Instant { self(last) }
+}
+
+/// Re-evaluates a set of root targets on a [`Schedule`] in a background
+/// thread, merging the latest external inputs into every tick.
+///
+/// The matrix can be swapped for a new version while the engine runs, with
+/// [`EngineHandle::swap_matrix`].
+pub struct Engine<T: SignalDef, P> {
+  matrix:   Arc<HotMatrix<T>>,
+  planner:  P,
+  roots:    SignalSet,
+  schedule: Box<dyn Schedule>,
+}
+
+impl<T, P> Engine<T, P>
+where
+  T: SignalDef + Send + 'static,
+  T::Value: Clone,
+  P: EvaluationPlanner<T> + Send + 'static,
+{
+  /// Create an engine re-evaluating the given root targets once a second.
+  pub fn new(
+    matrix: impl Into<Arc<SignalMatrix<T>>>,
+    planner: P,
+    root_targets: impl IntoIterator<Item = Signal>,
+  ) -> Self {
+    Engine {
+      matrix: Arc::new(HotMatrix::new(matrix)),
+      planner,
+      roots: root_targets.into_iter().collect(),
+      schedule: Box::new(Duration::from_secs(1)),
+    }
+  }
+
+  /// Set when ticks run.
+  pub fn with_schedule(mut self, schedule: impl Schedule + 'static) -> Self {
+    self.schedule = Box::new(schedule);
+    self
+  }
+
+  /// Start ticking in a background thread. The first tick runs immediately.
+  pub fn start(self) -> EngineHandle<T> {
+    let shared = Arc::new(Shared {
+      matrix:  self.matrix.clone(),
+      control: Mutex::new(Control {
+        inputs:  SignalMap::default(),
+        stopped: false,
+      }),
+      wake:    Condvar::new(),
+      latest:  RwLock::new(None),
+      ticks:   Mutex::new(0),
+      ticked:  Condvar::new(),
+    });
+
+    let thread = thread::spawn({
+      let shared = shared.clone();
+      move || self.run(&shared)
+    });
+
+    EngineHandle {
+      shared,
+      thread: Some(thread),
+    }
+  }
+
+  fn run(mut self, shared: &Shared<T>) {
+    let mut next = Instant::now();
+    let mut matrix = shared.matrix.current();
+    loop {
+      let inputs = {
+        let control = shared.control.lock().unwrap();
+        let (control, _) = shared
+          .wake
+          .wait_timeout_while(
+            control,
+            next.saturating_duration_since(Instant::now()),
+            |control| !control.stopped && Instant::now() < next,
+          )
+          .unwrap();
+        if control.stopped {
+          return;
+        }
+        // swaps happen under the control lock, so the inputs always match
+        let current = shared.matrix.current();
+        if !Arc::ptr_eq(&current, &matrix) {
+          self.roots = self
+            .roots
+            .iter()
+            .filter_map(|s| current.defset().carry_over(matrix.defset(), *s))
+            .collect();
+          matrix = current;
+        }
+        control.inputs.clone()
+      };
+
+      let started = Instant::now();
+      let tick_span = tracing::info_span!("engine_tick");
+      let _enter = tick_span.enter();
+
+      let plan = matrix
+        .plan_evaluation(&self.planner, self.roots.iter().copied())
+        .skip_targets(inputs.keys().copied());
+      let mut values = EvaluationValueMap::new_empty(Default::default());
+      for (signal, value) in inputs {
+        values.insert(signal, value);
+      }
+      let values = plan.run(values);
+
+      *shared.latest.write().unwrap() = Some(Arc::new(values));
+      *shared.ticks.lock().unwrap() += 1;
+      shared.ticked.notify_all();
+
+      next = self.schedule.next_tick(started);
+    }
+  }
+}
+
+struct Control<T: SignalDef> {
+  inputs:  SignalMap<T::Value>,
+  stopped: bool,
+}
+
+struct Shared<T: SignalDef> {
+  matrix:  Arc<HotMatrix<T>>,
+  control: Mutex<Control<T>>,
+  wake:    Condvar,
+  latest:  RwLock<Option<Arc<EvaluationValueMap<T>>>>,
+  ticks:   Mutex<u64>,
+  ticked:  Condvar,
+}
+
+/// A handle to a running [`Engine`]. Stops the engine when dropped.
+pub struct EngineHandle<T: SignalDef> {
+  shared: Arc<Shared<T>>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl<T: SignalDef> EngineHandle<T> {
+  /// Set the value of an input signal, used from the next tick on instead of
+  /// evaluating it.
+  pub fn set_input(&self, signal: Signal, value: T::Value) {
+    let mut control = self.shared.control.lock().unwrap();
+    control.inputs.insert(signal, value);
+  }
+
+  /// Stop overriding an input signal, so it's evaluated again from the next
+  /// tick on.
+  pub fn clear_input(&self, signal: Signal) {
+    let mut control = self.shared.control.lock().unwrap();
+    control.inputs.remove(&signal);
+  }
+
+  /// Swap in a new version of the matrix. A tick in progress finishes against
+  /// the old version; later ticks use the new one. Inputs and root targets
+  /// carry over by label, and are dropped if they don't.
+  pub fn swap_matrix(
+    &self,
+    matrix: impl Into<Arc<SignalMatrix<T>>>,
+  ) -> Arc<SignalMatrix<T>> {
+    let mut control = self.shared.control.lock().unwrap();
+    let matrix = matrix.into();
+    let old = self.shared.matrix.swap(matrix.clone());
+    control.inputs = std::mem::take(&mut control.inputs)
+      .into_iter()
+      .filter_map(|(signal, value)| {
+        let signal = matrix.defset().carry_over(old.defset(), signal)?;
+        Some((signal, value))
+      })
+      .collect();
+    old
+  }
+
+  /// Get the current version of the matrix.
+  pub fn matrix(&self) -> Arc<SignalMatrix<T>> { self.shared.matrix.current() }
+
+  /// Get the values from the most recent tick, if any tick has finished. Until
+  /// a tick finishes after a swap, these are values of the old version.
+  pub fn latest(&self) -> Option<Arc<EvaluationValueMap<T>>> {
+    self.shared.latest.read().unwrap().clone()
+  }
+
+  /// Get the number of ticks finished so far.
+  pub fn ticks(&self) -> u64 { *self.shared.ticks.lock().unwrap() }
+
+  /// Block until more than `after` ticks have finished, then return the
+  /// number finished.
+  pub fn wait_for_tick(&self, after: u64) -> u64 {
+    let ticks = self.shared.ticks.lock().unwrap();
+    *self
+      .shared
+      .ticked
+      .wait_while(ticks, |ticks| *ticks <= after)
+      .unwrap()
+  }
+
+  /// Stop the engine, waiting for an in-progress tick to finish.
+  pub fn stop(mut self) { self.shutdown(); }
+
+  fn shutdown(&mut self) {
+    self.shared.control.lock().unwrap().stopped = true;
+    self.shared.wake.notify_all();
+    if let Some(thread) = self.thread.take() {
+      thread.join().expect("engine thread panicked");
+    }
+  }
+}
+
+impl<T: SignalDef> Drop for EngineHandle<T> {
+  fn drop(&mut self) { self.shutdown(); }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap};
+
+  #[test]
+  fn test_engine_ticks() {
+    let mut defset = SignalDefMap::new();
+
+    let price =
+      defset.insert_labeled("price", FloatMapSignalDef::Constant(10.0));
+    let qty = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let total = defset.insert_labeled(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(price, qty)),
+    );
+
+    let engine =
+      Engine::new(SignalMatrix::new(defset), CustomPlanner::new(), [total])
+        .with_schedule(Duration::from_millis(1));
+    let handle = engine.start();
+
+    let tick = handle.wait_for_tick(0);
+    assert_eq!(handle.latest().unwrap().get(total), Some(&20.0));
+
+    handle.set_input(price, 12.5);
+    // the input may land mid-tick, so wait for a whole tick after this one
+    let tick = handle.wait_for_tick(tick + 1);
+    assert!(tick >= 3);
+    assert_eq!(handle.latest().unwrap().get(total), Some(&25.0));
+
+    // swap in a version where `total` also adds a fee; the input carries over
+    let mut defset = SignalDefMap::new();
+    let fee = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let price =
+      defset.insert_labeled("price", FloatMapSignalDef::Constant(0.0));
+    let qty = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let subtotal = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(price, qty)));
+    let total = defset.insert_labeled(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(subtotal, fee)),
+    );
+    let tick = handle.ticks();
+    handle.swap_matrix(SignalMatrix::new(defset));
+    handle.wait_for_tick(tick + 1);
+    assert_eq!(handle.latest().unwrap().get(total), Some(&26.0));
+
+    handle.stop();
+  }
+}
@@ -0,0 +1,104 @@
+use crate::{EvaluationValueMap, Signal, SignalDef};
+
+/// The epochs of a value map: which run it's on, and which run last set
+/// each signal's value.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Epochs {
+  current: u64,
+  /// The epoch each signal was last set at, by index.
+  stamps:  Vec<Option<u64>>,
+}
+
+impl<T: SignalDef> EvaluationValueMap<T> {
+  /// Get the current epoch: the number of runs started on this map.
+  pub fn epoch(&self) -> u64 { self.epochs.current }
+
+  /// Start a new epoch, returning it. Every run does this before
+  /// evaluating anything, so the values it sets are stamped newer than any
+  /// set before it. Custom executors writing into the map should do the same,
+  /// and set values with
+  /// [`insert_evaluated`](EvaluationValueMap::insert_evaluated).
+  pub fn begin_epoch(&mut self) -> u64 {
+    self.epochs.current += 1;
+    self.epochs.current
+  }
+
+  /// Get the epoch the given signal's value was last set at, whether by a
+  /// run or by [`insert`](Self::insert), or `None` if it never was. Values
+  /// inserted between runs carry the epoch of the run after them, so
+  /// [`changed_since`](Self::changed_since) the last run includes them.
+  pub fn evaluated_at(&self, signal: Signal) -> Option<u64> {
+    self.epochs.stamps.get(signal.index()).copied().flatten()
+  }
+
+  /// Iterate over the signals with values set after the given epoch, by ID,
+  /// so subscribers that remember the last epoch they saw are only notified
+  /// of new values.
+  pub fn changed_since(&self, epoch: u64) -> impl Iterator<Item = Signal> + '_ {
+    self
+      .values
+      .iter()
+      .filter(|(_, value)| value.is_some())
+      .map(|(signal, _)| signal)
+      .filter(move |s| self.evaluated_at(*s).is_some_and(|at| at > epoch))
+  }
+
+  /// Stamp the given signal with the current epoch.
+  pub(crate) fn stamp(&mut self, signal: Signal) {
+    self.stamp_at(signal, self.epochs.current);
+  }
+
+  /// Stamp the given signal with the epoch the next run will begin.
+  pub(crate) fn stamp_next(&mut self, signal: Signal) {
+    self.stamp_at(signal, self.epochs.current + 1);
+  }
+
+  fn stamp_at(&mut self, signal: Signal, epoch: u64) {
+    let index = signal.index();
+    if index >= self.epochs.stamps.len() {
+      self.epochs.stamps.resize(index + 1, None);
+    }
+    self.epochs.stamps[index] = Some(epoch);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_epochs() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [sum]);
+
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.epoch(), 1);
+    assert_eq!(values.evaluated_at(sum), Some(1));
+    assert_eq!(values.changed_since(0).count(), 3);
+
+    // an input set between runs is stamped with the next run, and a rerun
+    // only re-evaluating what changed restamps just that
+    let mut values = values;
+    values.insert(a, 5.0);
+    assert_eq!(values.evaluated_at(a), Some(2));
+    assert_eq!(values.changed_since(1).collect::<Vec<_>>(), [a]);
+    values.invalidate([sum]);
+    let plan = plan.skip_evaluated(&values);
+    let values = plan.run_with(values, &RunOptions::new());
+    assert_eq!(values.get(sum), Some(&7.0));
+    assert_eq!(values.epoch(), 2);
+    assert_eq!(values.evaluated_at(a), Some(2));
+    assert_eq!(values.evaluated_at(b), Some(1));
+    assert_eq!(values.evaluated_at(sum), Some(2));
+    assert_eq!(values.changed_since(1).collect::<Vec<_>>(), [a, sum]);
+  }
+}
@@ -1,103 +1,359 @@
-use std::collections::{HashMap, HashSet};
-
-use rayon::prelude::*;
-use tracing::instrument;
-
-use crate::{EvalContext, Signal, SignalDef, SignalMatrix};
-
-pub trait EvaluationPlanner {
-  fn plan_evaluation<Def: SignalDef>(
-    matrix: &SignalMatrix<Def>,
-    root_targets: HashSet<Signal>,
-  ) -> PlannedEvaluation<'_, Def>;
-}
-
-pub struct CustomPlanner;
-
-impl EvaluationPlanner for CustomPlanner {
-  fn plan_evaluation<Def: SignalDef>(
-    matrix: &SignalMatrix<Def>,
-    root_targets: HashSet<Signal>,
-  ) -> PlannedEvaluation<'_, Def> {
-    let dep_registry = matrix.defset.dependency_registry();
-
-    let mut passes = vec![];
-    // targets that must be satisfied in the current pass
-    let mut unsatisfied_targets = root_targets.clone();
-
-    loop {
-      let loop_span =
-        tracing::info_span!("planning_pass", ?unsatisfied_targets);
-      let _enter = loop_span.enter();
-      let pass = EvaluationPassDescriptor {
-        targets: unsatisfied_targets.clone(),
-      };
-
-      tracing::info_span!("unsatisfied_target_deps").in_scope(|| {
-        unsatisfied_targets = unsatisfied_targets
-          .iter()
-          .flat_map(|target| dep_registry.get(target).unwrap())
-          .cloned()
-          .collect();
-      });
+use std::{
+  borrow::Cow,
+  fmt,
+  sync::Arc,
+  time::{Duration, Instant},
+};
 
-      if pass.targets.is_empty() {
-        break;
-      }
+use fixedbitset::FixedBitSet;
+use tracing::{instrument, Level};
+
+use crate::{
+  condense::variant_name, epoch::Epochs, limits::ConcurrencyLimits, par::*,
+  slots::ValueSlots, Batching, Budget, BudgetReport, ContextScratch, Defaults,
+  EvalContext, FairShare, PassProfile, PoisonReport, ProfileReport, RunOptions,
+  Signal, SignalDef, SignalMap, SignalMatrix, SignalProfile, SignalSet,
+  StatsRegistry, Validators, ValueStore, Violation,
+};
+
+/// Create a per-signal span. Without the `signal-spans` feature this compiles
+/// to a disabled span, so hot per-target code pays no tracing overhead.
+macro_rules! signal_span {
+  ($($arg:tt)*) => {{
+    #[cfg(feature = "signal-spans")]
+    let span = tracing::info_span!($($arg)*);
+    #[cfg(not(feature = "signal-spans"))]
+    let span = tracing::Span::none();
+    span
+  }};
+}
+
+/// Trait for planners, which turn a set of root targets into a
+/// [`PlannedEvaluation`].
+///
+/// Planners take `&self`, so they can carry configuration and be selected at
+/// runtime as `Box<dyn EvaluationPlanner<Def>>`.
+pub trait EvaluationPlanner<Def: SignalDef> {
+  /// Plan the evaluation of the given root targets in the given
+  /// [`SignalMatrix`].
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def>;
+}
+
+impl<Def: SignalDef, P: EvaluationPlanner<Def> + ?Sized> EvaluationPlanner<Def>
+  for Box<P>
+{
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def> {
+    (**self).plan_evaluation(matrix, root_targets)
+  }
+}
+
+/// The default planner. Walks back from the root targets one dependency level
+/// per pass, so each signal lands in the latest pass that still precedes
+/// everything depending on it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CustomPlanner {
+  max_pass_size: Option<usize>,
+}
 
-      passes.push(pass);
+impl CustomPlanner {
+  /// Create a new planner with no pass size limit.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the number of targets in each pass. Oversized passes are split into
+  /// consecutive sub-passes.
+  pub fn with_max_pass_size(mut self, max_pass_size: usize) -> Self {
+    self.max_pass_size = Some(max_pass_size.max(1));
+    self
+  }
+
+  /// Get the configured maximum number of targets per pass, if any.
+  pub fn max_pass_size(&self) -> Option<usize> { self.max_pass_size }
+}
+
+impl<Def: SignalDef> EvaluationPlanner<Def> for CustomPlanner {
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def> {
+    let passes = match matrix.frozen_order() {
+      Some(order) => tracing::info_span!("frozen_passes")
+        .in_scope(|| frozen_passes(matrix, order, &root_targets)),
+      None => walk_passes(matrix, &root_targets),
+    };
+
+    let mut passes: Vec<_> =
+      tracing::info_span!("collect_passes").in_scope(|| {
+        passes
+          .into_iter()
+          .map(|pass| {
+            EvaluationPassDescriptor::new(
+              pass.ones().map(Signal::from_index).collect(),
+            )
+          })
+          .collect()
+      });
+
+    if let Some(max_pass_size) = self.max_pass_size {
+      passes = tracing::info_span!("split_passes")
+        .in_scope(|| split_oversized_passes(passes, max_pass_size));
     }
 
-    tracing::info_span!("reverse_passes").in_scope(|| {
-      passes.reverse();
-    });
+    PlannedEvaluation::from_passes(matrix, root_targets, passes)
+  }
+}
 
-    // now go through passes start to finish and remove targets from later
-    // passes if they are satisfied by an earlier pass
-
-    tracing::info_span!("dedup_passes").in_scope(|| {
-      let mut queued_targets: HashSet<Signal> = HashSet::new();
-      for pass in passes.iter_mut() {
-        pass.targets.retain(|target| {
-          if queued_targets.contains(target) {
-            false
-          } else {
-            queued_targets.insert(*target);
-            true
-          }
-        });
+/// Build the passes for [`CustomPlanner`] by walking back from the root
+/// targets one dependency level at a time.
+fn walk_passes<T: SignalDef>(
+  matrix: &SignalMatrix<T>,
+  root_targets: &SignalSet,
+) -> Vec<FixedBitSet> {
+  let graph = matrix.dependency_graph();
+  let id_space = graph.len();
+
+  // passes are built as bitsets over signal indices; signal IDs are dense,
+  // so this beats hashing for both membership and dedup
+  let mut passes: Vec<FixedBitSet> = vec![];
+  // targets that must be satisfied in the current pass
+  let mut unsatisfied_targets = FixedBitSet::with_capacity(id_space);
+  for target in root_targets.iter() {
+    unsatisfied_targets.insert(target.index());
+  }
+
+  loop {
+    let loop_span = tracing::info_span!(
+      "planning_pass",
+      unsatisfied = unsatisfied_targets.count_ones(..)
+    );
+    let _enter = loop_span.enter();
+
+    if unsatisfied_targets.is_clear() {
+      break;
+    }
+
+    let mut next_targets = FixedBitSet::with_capacity(id_space);
+    tracing::info_span!("unsatisfied_target_deps").in_scope(|| {
+      for target in unsatisfied_targets.ones() {
+        for dep in graph.dependencies(Signal::from_index(target)) {
+          next_targets.insert(dep.index());
+        }
       }
     });
 
-    PlannedEvaluation {
-      matrix,
-      root_targets,
-      passes,
+    passes.push(std::mem::replace(&mut unsatisfied_targets, next_targets));
+  }
+
+  tracing::info_span!("reverse_passes").in_scope(|| {
+    passes.reverse();
+  });
+
+  // now go through passes start to finish and remove targets from later
+  // passes if they are satisfied by an earlier pass
+
+  tracing::info_span!("dedup_passes").in_scope(|| {
+    let mut queued_targets = FixedBitSet::with_capacity(id_space);
+    for pass in passes.iter_mut() {
+      pass.difference_with(&queued_targets);
+      queued_targets.union_with(pass);
     }
+  });
+
+  passes
+}
+
+/// Build the passes for [`CustomPlanner`] from a frozen topological order,
+/// giving the same passes as [`walk_passes`] in one sweep. Each needed
+/// signal goes in the pass its longest chain of dependents up to a root
+/// allows; visiting the order backwards finishes every signal's dependents
+/// before the signal itself.
+fn frozen_passes<T: SignalDef>(
+  matrix: &SignalMatrix<T>,
+  order: &[Signal],
+  root_targets: &SignalSet,
+) -> Vec<FixedBitSet> {
+  let graph = matrix.dependency_graph();
+  let id_space = graph.len();
+
+  let mut needed = FixedBitSet::with_capacity(id_space);
+  for target in root_targets.iter() {
+    needed.insert(target.index());
   }
+  // the number of passes between each needed signal and the last pass
+  let mut heights = vec![0; id_space];
+  for signal in order.iter().rev() {
+    if !needed.contains(signal.index()) {
+      continue;
+    }
+    let height = heights[signal.index()] + 1;
+    for dep in graph.dependencies(*signal) {
+      needed.insert(dep.index());
+      heights[dep.index()] = heights[dep.index()].max(height);
+    }
+  }
+
+  // dependencies without a definition aren't in the order, but are placed
+  // by their dependents all the same
+  let depth = needed.ones().map(|i| heights[i] + 1).max().unwrap_or(0);
+  let mut passes = vec![FixedBitSet::with_capacity(id_space); depth];
+  for index in needed.ones() {
+    passes[depth - 1 - heights[index]].insert(index);
+  }
+  passes
+}
+
+/// Split every pass with more than `max_pass_size` targets into consecutive
+/// sub-passes. Targets within a pass never depend on each other, so any split
+/// still respects dependencies. Sequential segments are kept whole.
+pub(crate) fn split_oversized_passes(
+  passes: Vec<EvaluationPassDescriptor>,
+  max_pass_size: usize,
+) -> Vec<EvaluationPassDescriptor> {
+  let mut split = Vec::with_capacity(passes.len());
+  for pass in passes {
+    if pass.is_sequential() || pass.targets.len() <= max_pass_size {
+      split.push(pass);
+      continue;
+    }
+    // sort so the split is deterministic
+    let mut targets: Vec<_> = pass.targets.into_iter().collect();
+    targets.sort_by_key(|s| s.0);
+    split.extend(targets.chunks(max_pass_size).map(|chunk| {
+      EvaluationPassDescriptor::new(chunk.iter().copied().collect())
+    }));
+  }
+  split
 }
 
 /// A planned evaluation of targets in a [`SignalMatrix`].
 #[derive(Debug)]
 pub struct PlannedEvaluation<'m, T: SignalDef> {
-  matrix:       &'m SignalMatrix<T>,
-  root_targets: HashSet<Signal>,
-  passes:       Vec<EvaluationPassDescriptor>,
+  matrix:             &'m SignalMatrix<T>,
+  root_targets:       SignalSet,
+  /// Shared with a plan cache, and copied on first edit.
+  passes:             Arc<Vec<EvaluationPassDescriptor>>,
+  free_intermediates: bool,
+  defaults:           Option<Defaults<T>>,
 }
 
 impl<'m, T: SignalDef> PlannedEvaluation<'m, T> {
   /// Create a new planned evaluation of the given root targets in the given
-  /// [`SignalMatrix`].
-  #[instrument]
-  pub fn new<P: EvaluationPlanner>(
+  /// [`SignalMatrix`], using the given planner.
+  #[instrument(
+    name = "plan",
+    skip_all,
+    fields(signals = matrix.defset().len())
+  )]
+  pub fn new<P: EvaluationPlanner<T> + ?Sized>(
     matrix: &'m SignalMatrix<T>,
-    root_targets: HashSet<Signal>,
+    planner: &P,
+    root_targets: impl IntoIterator<Item = Signal>,
   ) -> Self {
-    P::plan_evaluation(matrix, root_targets)
+    let plan =
+      planner.plan_evaluation(matrix, root_targets.into_iter().collect());
+    tracing::debug!(%plan, "planned evaluation");
+    plan.warn_deprecated();
+    plan
+  }
+
+  /// Assemble a planned evaluation from already-computed passes. This is the
+  /// constructor for [`EvaluationPlanner`] implementations.
+  ///
+  /// Every target's dependencies must be satisfied by an earlier pass.
+  pub fn from_passes(
+    matrix: &'m SignalMatrix<T>,
+    root_targets: SignalSet,
+    passes: Vec<EvaluationPassDescriptor>,
+  ) -> Self {
+    Self::from_shared_passes(matrix, root_targets, Arc::new(passes))
+  }
+
+  /// Assemble a planned evaluation from passes shared with other plans, e.g.
+  /// by a plan cache. They're only copied if the plan edits them.
+  pub(crate) fn from_shared_passes(
+    matrix: &'m SignalMatrix<T>,
+    root_targets: SignalSet,
+    passes: Arc<Vec<EvaluationPassDescriptor>>,
+  ) -> Self {
+    PlannedEvaluation {
+      matrix,
+      root_targets,
+      passes,
+      free_intermediates: false,
+      defaults: None,
+    }
+  }
+
+  /// Set whether [`run`](Self::run) drops intermediate values (anything that
+  /// isn't a root target) as soon as no later pass depends on them.
+  pub fn with_free_intermediates(mut self, free_intermediates: bool) -> Self {
+    self.free_intermediates = free_intermediates;
+    self
+  }
+
+  /// Whether [`run`](Self::run) drops intermediate values once they are no
+  /// longer needed.
+  pub fn free_intermediates(&self) -> bool { self.free_intermediates }
+
+  /// Make the run lenient: a dependency without a value is replaced by its
+  /// default instead of panicking. Plans are strict by default, and lenient
+  /// runs still panic for dependencies without a default.
+  pub fn with_defaults(mut self, defaults: Defaults<T>) -> Self {
+    self.defaults = Some(defaults);
+    self
+  }
+
+  /// Get the defaults of a lenient plan.
+  pub fn defaults(&self) -> Option<&Defaults<T>> { self.defaults.as_ref() }
+
+  /// Drop every target that already has a value in the given map, along with
+  /// any passes left empty. Combined with
+  /// [`EvaluationValueMap::invalidate`], this turns a full plan into an
+  /// incremental recalculation of only the stale signals.
+  pub fn skip_evaluated(self, values: &EvaluationValueMap<T>) -> Self {
+    self.retain_targets(|target| values.get(target).is_none())
+  }
+
+  /// Drop the given targets, along with any passes left empty. Their values
+  /// must be supplied in the value map passed to [`run`](Self::run).
+  pub fn skip_targets(self, targets: impl IntoIterator<Item = Signal>) -> Self {
+    let skipped: SignalSet = targets.into_iter().collect();
+    self.retain_targets(|target| !skipped.contains(&target))
+  }
+
+  fn retain_targets(mut self, mut keep: impl FnMut(Signal) -> bool) -> Self {
+    let passes = Arc::make_mut(&mut self.passes);
+    for pass in passes.iter_mut() {
+      pass.targets.retain(|target| keep(*target));
+      let targets = &pass.targets;
+      pass.sequence.retain(|target| targets.contains(target));
+    }
+    passes.retain(|pass| !pass.targets.is_empty());
+    self
+  }
+
+  /// Get the root targets this evaluation was planned for.
+  pub fn root_targets(&self) -> &SignalSet { &self.root_targets }
+
+  /// Get the index of the pass the given signal is evaluated in, if it's
+  /// queued.
+  pub fn pass_of(&self, signal: Signal) -> Option<usize> {
+    self
+      .passes
+      .iter()
+      .position(|pass| pass.targets.contains(&signal))
   }
 
   /// Get all targets that are queued for evaluation in this planned evaluation.
-  pub fn all_queued_targets(&self) -> HashSet<Signal> {
+  pub fn all_queued_targets(&self) -> SignalSet {
     self
       .passes
       .par_iter()
@@ -106,85 +362,671 @@ impl<'m, T: SignalDef> PlannedEvaluation<'m, T> {
       .collect()
   }
 
+  /// Compute, for each pass, the intermediate targets whose values are no
+  /// longer needed once that pass has run. Root targets are never released.
+  pub fn release_schedule(&self) -> Vec<SignalSet> {
+    let graph = self.matrix.dependency_graph();
+    let mut last_use = SignalMap::<usize>::default();
+    for (i, pass) in self.passes.iter().enumerate() {
+      for target in pass.targets.iter() {
+        last_use.insert(*target, i);
+        for dep in graph.dependencies(*target) {
+          // passes are walked in order, so this only ever moves forward
+          if let Some(last) = last_use.get_mut(dep) {
+            *last = i;
+          }
+        }
+      }
+    }
+
+    let mut schedule = vec![SignalSet::default(); self.passes.len()];
+    for (signal, i) in last_use {
+      if !self.root_targets.contains(&signal) {
+        schedule[i].insert(signal);
+      }
+    }
+    schedule
+  }
+
+  /// Get the largest number of target values that are alive at once during
+  /// the evaluation, assuming intermediates are freed as soon as possible.
+  pub fn peak_live_values(&self) -> usize {
+    let mut live = 0;
+    let mut peak = 0;
+    for (pass, released) in self.passes.iter().zip(self.release_schedule()) {
+      live += pass.targets.len();
+      peak = peak.max(live);
+      live -= released.len();
+    }
+    peak
+  }
+
   /// Run the planned evaluation, updating the given value map with the results.
-  #[instrument]
-  pub fn run(
+  pub fn run(&self, values: EvaluationValueMap<T>) -> EvaluationValueMap<T> {
+    self.run_with(values, &RunOptions::default())
+  }
+
+  /// Run the planned evaluation with the given options, updating the given
+  /// value map with the results.
+  pub fn run_with(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> EvaluationValueMap<T> {
+    self.execute(values, options, RunHooks::default())
+  }
+
+  /// Run the planned evaluation like [`run_with`](Self::run_with), also
+  /// recording the timing of every pass and signal.
+  pub fn run_profiled(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> (EvaluationValueMap<T>, ProfileReport) {
+    let mut report = ProfileReport::default();
+    let values = self.execute(values, options, RunHooks {
+      profile: Some(&mut report),
+      ..Default::default()
+    });
+    (values, report)
+  }
+
+  /// Run the planned evaluation like [`run_with`](Self::run_with), checking
+  /// the given validators as soon as their values are available and
+  /// collecting every violation.
+  pub fn run_validated(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    validators: &Validators<T>,
+  ) -> (EvaluationValueMap<T>, Vec<Violation>) {
+    let mut violations = Vec::new();
+    let values = self.execute(values, options, RunHooks {
+      validation: Some((validators, &mut violations)),
+      ..Default::default()
+    });
+    (values, violations)
+  }
+
+  #[instrument(name = "run", skip_all, fields(passes = self.passes.len()))]
+  pub(crate) fn execute(
     &self,
     mut values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    hooks: RunHooks<'_, T>,
   ) -> EvaluationValueMap<T> {
+    let RunHooks {
+      mut profile,
+      mut validation,
+      mut budget,
+      mut poison,
+      stats,
+      batching,
+      skip,
+      keep_intermediates,
+    } = hooks;
+    values.begin_epoch();
+    let mut releases = (self.free_intermediates && !keep_intermediates)
+      .then(|| self.release_schedule());
+    let due = validation
+      .as_ref()
+      .map(|(validators, _)| validators.schedule(&self.passes));
+    if let (Some((validators, _)), Some(due), Some(releases)) =
+      (&validation, &due, &mut releases)
+    {
+      validators.defer_releases(due, releases);
+    }
+    if let (Some((validators, violations)), Some(due)) = (&mut validation, &due)
+    {
+      validators.run_checks(
+        &due[0],
+        None,
+        &values,
+        &self.matrix.defset,
+        violations,
+      );
+    }
+    let mut executor = PassExecutor::new(options);
+    let limits = ConcurrencyLimits::new(options);
+    let profiling = profile.is_some();
+    // per-signal timing feeds the pass summary, so skip it if nobody listens
+    let summarize = tracing::enabled!(Level::INFO);
+    let timed = profiling || summarize || stats.is_some();
+    // only read the clock if something uses it, as there's none on wasm
+    let started = (timed || budget.is_some()).then(Instant::now);
+    let elapsed = || started.map_or(Duration::ZERO, |s| s.elapsed());
+    if let Some(stats) = stats {
+      stats.start_run();
+    }
+
     for (i, pass) in self.passes.iter().enumerate() {
+      if let Some((budget, report)) = &mut budget {
+        if !budget.admit(i, pass, elapsed(), report) {
+          break;
+        }
+      }
       let pass_span = tracing::info_span!("evaluation_pass", i);
       let _enter = pass_span.enter();
-      let evaluations: Vec<_> = pass
-        .targets
-        .par_iter()
-        .map(|target| {
-          let def = self.matrix.defset.get(*target).unwrap();
-          let deps = def.dependencies();
-
-          let context_gathering_span =
-            tracing::info_span!("gather_context", ?deps);
-          let _enter = context_gathering_span.enter();
-          let context_values = deps.into_iter().map(|dep| {
-            let value = values
-              .values
-              .get(&dep)
-              .and_then(|v| v.as_ref())
-              .unwrap_or_else(|| {
-                panic!(
-                  "Missing value for dependency {dep:?} while evaluating \
-                   {target:?} in pass {i}"
-                )
-              });
-            (dep, value)
-          });
-          let context = EvalContext {
-            values: context_values.collect(),
+      let pass_start = elapsed();
+
+      let mut tally = PassTally::new(profiling);
+      if pass.is_sequential() {
+        // a sequential segment evaluates its targets one at a time on this
+        // thread, each seeing the values of those before it
+        for target in pass.sequence().iter().copied() {
+          if skip.is_some_and(|skipped| skipped.contains(&target)) {
+            continue;
+          }
+          if let Some((_, report)) = &mut poison {
+            let causes = report
+              .causes_of(self.matrix.dependency_graph().dependencies(target));
+            if !causes.is_empty() {
+              report.poisoned.insert(target, causes);
+              tally.poisoned += 1;
+              continue;
+            }
+          }
+          let def = self.matrix.defset.get(target).unwrap();
+          let _permit = limits.acquire(def.concurrency_class());
+          let _slot = options.fair_share().map(FairShare::acquire);
+          let start = timed.then(elapsed);
+          let value = {
+            let mut scratch = ContextScratch::new(options.summation());
+            self.evaluate_target(i, target, &values, &mut scratch)
           };
-          drop(_enter);
+          let timing = start.map(|start| (start, elapsed() - start));
+          let evaluation = (target, value, timing, current_thread_index());
+          self.settle(evaluation, &mut tally, &mut values, &mut poison, stats);
+        }
+      } else {
+        let mut targets = &pass.targets;
+        let kept;
+        if let Some(skipped) = skip {
+          if targets.iter().any(|t| skipped.contains(t)) {
+            kept = targets
+              .iter()
+              .filter(|t| !skipped.contains(t))
+              .copied()
+              .collect();
+            targets = &kept;
+          }
+        }
 
-          let evaluator_span = tracing::info_span!("evaluate");
-          let _enter = evaluator_span.enter();
-          let value = def.evaluate(&context);
-          drop(_enter);
+        // targets depending on a failed or poisoned signal are poisoned too,
+        // and not evaluated
+        let healthy;
+        if let Some((_, report)) = &mut poison {
+          let before = report.poisoned.len();
+          for target in targets.iter() {
+            let causes = report
+              .causes_of(self.matrix.dependency_graph().dependencies(*target));
+            if !causes.is_empty() {
+              report.poisoned.insert(*target, causes);
+            }
+          }
+          tally.poisoned += report.poisoned.len() - before;
+          if report.poisoned.len() > before {
+            healthy = targets
+              .iter()
+              .filter(|t| !report.poisoned.contains_key(t))
+              .copied()
+              .collect();
+            targets = &healthy;
+          }
+        }
 
-          (*target, value)
-        })
-        .collect();
+        let mut batched = Vec::new();
+        let unbatched;
+        if let Some(batching) = batching {
+          let timer = started.filter(|_| timed);
+          (unbatched, batched) = self
+            .evaluate_batches(i, batching, targets, &values, timer, options);
+          targets = &unbatched;
+        }
+
+        let parallelism = options.parallelism(i, pass);
+        let mut evaluations = executor.map_collect(
+          parallelism,
+          targets,
+          || ContextScratch::new(options.summation()),
+          |scratch, target| {
+            let def = self.matrix.defset.get(target).unwrap();
+            let _permit = limits.acquire(def.concurrency_class());
+            let _slot = options.fair_share().map(FairShare::acquire);
+            let start = timed.then(elapsed);
+            let value = self.evaluate_target(i, target, &values, scratch);
+            let timing = start.map(|start| (start, elapsed() - start));
+            (target, value, timing, current_thread_index())
+          },
+        );
+        evaluations.append(&mut batched);
+
+        for evaluation in evaluations {
+          self.settle(evaluation, &mut tally, &mut values, &mut poison, stats);
+        }
+      }
+      let PassTally {
+        signals,
+        profiling: _,
+        recorded,
+        slowest,
+        failures,
+        poisoned,
+      } = tally;
+      let pass_duration = elapsed() - pass_start;
+      if let Some(stats) = stats {
+        stats.record(&recorded);
+      }
+
+      match (summarize, slowest) {
+        (true, Some((signal, slowest))) => tracing::info!(
+          pass = i,
+          targets = pass.targets.len(),
+          wall_time_us = pass_duration.as_micros() as u64,
+          slowest_signal = %self.matrix.defset.display_label(signal),
+          slowest_us = slowest.as_micros() as u64,
+          failed = failures,
+          poisoned,
+          "evaluation pass complete"
+        ),
+        // every target was skipped or poisoned
+        (true, None) => tracing::info!(
+          pass = i,
+          targets = pass.targets.len(),
+          wall_time_us = pass_duration.as_micros() as u64,
+          failed = failures,
+          poisoned,
+          "evaluation pass complete"
+        ),
+        (false, _) => {}
+      }
+
+      if let Some(report) = profile.as_deref_mut() {
+        report.passes.push(PassProfile {
+          index: i,
+          start: pass_start,
+          duration: pass_duration,
+          signals,
+        });
+      }
+
+      if let (Some((validators, violations)), Some(due)) =
+        (&mut validation, &due)
+      {
+        validators.run_checks(
+          &due[i + 1],
+          Some(i),
+          &values,
+          &self.matrix.defset,
+          violations,
+        );
+      }
 
-      for (target, value) in evaluations {
-        values.values.insert(target, Some(value));
+      if let Some(releases) = &releases {
+        for signal in releases[i].iter() {
+          values.values.remove(signal);
+        }
       }
     }
 
     values
   }
 
+  /// Record an evaluated target in the value map and the pass's tally.
+  fn settle(
+    &self,
+    (target, value, timing, thread): Evaluation<T::Value>,
+    tally: &mut PassTally,
+    values: &mut EvaluationValueMap<T>,
+    poison: &mut Option<(FailureCheck<'_, T::Value>, &mut PoisonReport)>,
+    stats: Option<&StatsRegistry<T::Value>>,
+  ) {
+    let mut poisoning = false;
+    if let Some((failed, report)) = poison {
+      if failed(&value) {
+        report.failed.insert(target);
+        poisoning = true;
+      }
+    }
+    let failed = stats.is_some_and(|stats| stats.is_failure(&value));
+    tally.failures += usize::from(poisoning || failed);
+    values.insert_evaluated(target, value);
+    let Some((start, duration)) = timing else {
+      return;
+    };
+    if stats.is_some() {
+      tally.recorded.push((target, duration, failed));
+    }
+    if tally.slowest.is_none_or(|(_, slowest)| duration > slowest) {
+      tally.slowest = Some((target, duration));
+    }
+    if tally.profiling {
+      tally.signals.push(SignalProfile {
+        signal: target,
+        label: self.matrix.defset.display_label(target),
+        variant: variant_name(self.matrix.defset.get(target).unwrap()),
+        start,
+        duration,
+        thread,
+      });
+    }
+  }
+
+  /// Gather the context for a single target in the scratch buffers and
+  /// evaluate it.
+  pub(crate) fn evaluate_target<'v>(
+    &'v self,
+    pass_index: usize,
+    target: Signal,
+    values: &'v EvaluationValueMap<T>,
+    scratch: &mut ContextScratch<'v, T>,
+  ) -> T::Value {
+    let def = self.matrix.defset.get(target).unwrap();
+    let deps = self.matrix.dependency_graph().dependencies(target);
+
+    let context_gathering_span = signal_span!("gather_context", ?deps);
+    let context_enter = context_gathering_span.enter();
+    let context_values = self.context_values(pass_index, target, deps, values);
+    let context = EvalContext::for_def_in(target, def, context_values, scratch);
+    drop(context_enter);
+
+    let evaluator_span = signal_span!("evaluate");
+    let _enter = evaluator_span.enter();
+    let value = def.evaluate(&context);
+    context.recycle(scratch);
+    value
+  }
+
+  /// Gather the values of a target's dependencies into a fresh context.
+  pub(crate) fn gather_context<'v>(
+    &'v self,
+    pass_index: usize,
+    target: Signal,
+    def: &T,
+    values: &'v EvaluationValueMap<T>,
+  ) -> EvalContext<'v, T> {
+    let deps = self.matrix.dependency_graph().dependencies(target);
+
+    let context_gathering_span = signal_span!("gather_context", ?deps);
+    let _enter = context_gathering_span.enter();
+    let context_values = self.context_values(pass_index, target, deps, values);
+    EvalContext::for_def(target, def, context_values)
+  }
+
+  /// Look up the values of a target's dependencies, falling back to the
+  /// plan's defaults.
+  fn context_values<'v>(
+    &'v self,
+    pass_index: usize,
+    target: Signal,
+    deps: &'v [Signal],
+    values: &'v EvaluationValueMap<T>,
+  ) -> impl Iterator<Item = (Signal, &'v T::Value)> + 'v {
+    deps.iter().map(move |&dep| {
+      let value = values
+        .values
+        .get(&dep)
+        .and_then(|v| v.as_ref())
+        .or_else(|| {
+          let default = self.defaults.as_ref()?.get(dep)?;
+          tracing::debug!(
+            ?dep,
+            ?target,
+            "using default for missing dependency"
+          );
+          Some(default)
+        })
+        .unwrap_or_else(|| {
+          panic!(
+            "Missing value for dependency {dep:?} while evaluating {target:?} \
+             in pass {pass_index}"
+          )
+        });
+      (dep, value)
+    })
+  }
+
   pub fn passes(&self) -> &[EvaluationPassDescriptor] { &self.passes }
+
+  /// Get mutable access to the passes, for
+  /// [`PlanMiddleware`](crate::PlanMiddleware) implementations. Every target's
+  /// dependencies must still be satisfied by an earlier pass or by the value
+  /// map passed to [`run`](Self::run).
+  pub fn passes_mut(&mut self) -> &mut Vec<EvaluationPassDescriptor> {
+    Arc::make_mut(&mut self.passes)
+  }
+
+  /// Get the passes as shared with this plan, for caching without copying
+  /// them.
+  pub(crate) fn shared_passes(&self) -> Arc<Vec<EvaluationPassDescriptor>> {
+    Arc::clone(&self.passes)
+  }
+
+  /// Get the [`SignalMatrix`] this evaluation was planned in.
+  pub fn matrix(&self) -> &'m SignalMatrix<T> { self.matrix }
 }
 
 /// Describes a pass in a planned evaluation.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EvaluationPassDescriptor {
-  targets: HashSet<Signal>,
+  targets:  SignalSet,
+  sequence: Vec<Signal>,
 }
 
 impl EvaluationPassDescriptor {
+  /// Create a pass descriptor evaluating the given targets.
+  pub fn new(targets: SignalSet) -> Self {
+    EvaluationPassDescriptor {
+      targets,
+      sequence: Vec::new(),
+    }
+  }
+
+  /// Create a sequential segment, evaluating the given targets one after
+  /// another in one pass. Each target may depend on those before it.
+  pub fn sequential(sequence: Vec<Signal>) -> Self {
+    EvaluationPassDescriptor {
+      targets: sequence.iter().copied().collect(),
+      sequence,
+    }
+  }
+
   /// Get the targets in this pass.
-  pub fn targets(&self) -> &HashSet<Signal> { &self.targets }
+  pub fn targets(&self) -> &SignalSet { &self.targets }
+
+  /// Whether this pass is a sequential segment.
+  pub fn is_sequential(&self) -> bool { !self.sequence.is_empty() }
+
+  /// Get the number of targets this pass evaluates at once: all of them for
+  /// a normal pass, and one for a sequential segment.
+  pub fn width(&self) -> usize {
+    match self.is_sequential() {
+      true => 1,
+      false => self.targets.len(),
+    }
+  }
+
+  /// Get the targets in this pass in evaluation order: the segment's order
+  /// for a sequential segment, and by ID otherwise.
+  pub fn ordered_targets(&self) -> Vec<Signal> {
+    if self.is_sequential() {
+      return self.sequence.clone();
+    }
+    let mut targets: Vec<_> = self.targets.iter().copied().collect();
+    targets.sort_by_key(|s| s.0);
+    targets
+  }
+
+  /// Get the targets of a sequential segment in order, or nothing for a
+  /// normal pass.
+  pub(crate) fn sequence(&self) -> &[Signal] { &self.sequence }
+
+  /// Get the groups of targets evaluated together, in order: every target
+  /// at once for a normal pass, and one at a time for a sequential segment.
+  pub(crate) fn stages(&self) -> Vec<Cow<'_, SignalSet>> {
+    if !self.is_sequential() {
+      return vec![Cow::Borrowed(&self.targets)];
+    }
+    self
+      .sequence
+      .iter()
+      .map(|s| Cow::Owned([*s].into_iter().collect()))
+      .collect()
+  }
+
+  /// Get a copy of the pass keeping only the targets matching the predicate,
+  /// in the same order.
+  pub(crate) fn filtered(&self, keep: impl Fn(Signal) -> bool) -> Self {
+    if self.is_sequential() {
+      return EvaluationPassDescriptor::sequential(
+        self.sequence.iter().copied().filter(|s| keep(*s)).collect(),
+      );
+    }
+    EvaluationPassDescriptor::new(
+      self.targets.iter().copied().filter(|s| keep(*s)).collect(),
+    )
+  }
+}
+
+/// The number of signals listed before the rest are elided.
+const DISPLAY_LIMIT: usize = 8;
+
+/// Write a comma-separated list of signals, eliding all but the first few.
+fn write_signals(
+  f: &mut fmt::Formatter<'_>,
+  signals: &[Signal],
+  display: impl Fn(Signal) -> String,
+) -> fmt::Result {
+  for (i, signal) in signals.iter().take(DISPLAY_LIMIT).enumerate() {
+    if i > 0 {
+      f.write_str(", ")?;
+    }
+    f.write_str(&display(*signal))?;
+  }
+  if signals.len() > DISPLAY_LIMIT {
+    write!(f, ", ... ({} more)", signals.len() - DISPLAY_LIMIT)?;
+  }
+  Ok(())
+}
+
+/// Shows the target count and the first few targets in evaluation order.
+impl fmt::Display for EvaluationPassDescriptor {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} targets", self.targets.len())?;
+    if self.is_sequential() {
+      f.write_str(" in sequence")?;
+    }
+    f.write_str(": ")?;
+    write_signals(f, &self.ordered_targets(), |s| s.to_string())
+  }
+}
+
+/// Shows a one-line summary of the plan. The alternate form (`{:#}`) adds a
+/// line for each pass, listing its first few targets by label.
+impl<T: SignalDef> fmt::Display for PlannedEvaluation<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let targets: usize = self.passes.iter().map(|p| p.targets.len()).sum();
+    let widest = self.passes.iter().map(|p| p.width()).max();
+    write!(
+      f,
+      "{} roots, {targets} targets in {} passes (widest {})",
+      self.root_targets.len(),
+      self.passes.len(),
+      widest.unwrap_or(0),
+    )?;
+    if f.alternate() {
+      let defset = self.matrix.defset();
+      for (i, pass) in self.passes.iter().enumerate() {
+        write!(f, "\n  pass {i} ({}", pass.targets.len())?;
+        if pass.is_sequential() {
+          f.write_str(", sequential")?;
+        }
+        f.write_str("): ")?;
+        write_signals(f, &pass.ordered_targets(), |s| {
+          defset.display(s).to_string()
+        })?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Decides whether a value is a failure, for poisoned runs.
+pub(crate) type FailureCheck<'h, V> = &'h dyn Fn(&V) -> bool;
+
+/// Optional instrumentation and controls for a single run.
+pub(crate) struct RunHooks<'h, T: SignalDef> {
+  pub(crate) profile:            Option<&'h mut ProfileReport>,
+  pub(crate) validation: Option<(&'h Validators<T>, &'h mut Vec<Violation>)>,
+  pub(crate) budget:             Option<(&'h Budget, &'h mut BudgetReport)>,
+  pub(crate) poison: Option<(FailureCheck<'h, T::Value>, &'h mut PoisonReport)>,
+  pub(crate) stats:              Option<&'h StatsRegistry<T::Value>>,
+  pub(crate) batching:           Option<Batching<T>>,
+  pub(crate) skip:               Option<&'h SignalSet>,
+  /// Keep every value even if the plan frees intermediates.
+  pub(crate) keep_intermediates: bool,
+}
+
+/// An evaluated target: its value, its start and duration if timed, and the
+/// thread it ran on.
+type Evaluation<V> = (Signal, V, Option<(Duration, Duration)>, usize);
+
+/// What a pass has recorded about the targets it evaluated.
+struct PassTally {
+  profiling: bool,
+  signals:   Vec<SignalProfile>,
+  recorded:  Vec<(Signal, Duration, bool)>,
+  slowest:   Option<(Signal, Duration)>,
+  failures:  usize,
+  poisoned:  usize,
+}
+
+impl PassTally {
+  fn new(profiling: bool) -> Self {
+    PassTally {
+      profiling,
+      signals: Vec::new(),
+      recorded: Vec::new(),
+      slowest: None,
+      failures: 0,
+      poisoned: 0,
+    }
+  }
+}
+
+impl<T: SignalDef> Default for RunHooks<'_, T> {
+  fn default() -> Self {
+    RunHooks {
+      profile:            None,
+      validation:         None,
+      budget:             None,
+      poison:             None,
+      stats:              None,
+      batching:           None,
+      skip:               None,
+      keep_intermediates: false,
+    }
+  }
 }
 
 /// A map of values for evaluated signals.
+///
+/// Values are stored in slots indexed by signal ID, pre-sized for the
+/// targets the map is created for.
 #[derive(Debug)]
 pub struct EvaluationValueMap<T: SignalDef> {
-  values: HashMap<Signal, Option<T::Value>>,
+  pub(crate) values: ValueSlots<T::Value>,
+  pub(crate) store:  Option<Arc<dyn ValueStore<T::Value>>>,
+  pub(crate) epochs: Epochs,
 }
 
 impl<T: SignalDef> EvaluationValueMap<T> {
   /// Create a new empty value map for the given targets.
-  pub fn new_empty(targets: HashSet<Signal>) -> Self {
+  pub fn new_empty(targets: SignalSet) -> Self {
     EvaluationValueMap {
-      values: targets.par_iter().map(|s| (*s, None)).collect(),
+      values: ValueSlots::pending(&targets),
+      store:  None,
+      epochs: Epochs::default(),
     }
   }
 
@@ -192,12 +1034,73 @@ impl<T: SignalDef> EvaluationValueMap<T> {
   pub fn get(&self, signal: Signal) -> Option<&T::Value> {
     self.values.get(&signal).and_then(|v| v.as_ref())
   }
+
+  /// Set the value for the given signal, as if it had been evaluated. The
+  /// value is stamped with the epoch of the next run, so it reads as changed
+  /// since the last one.
+  pub fn insert(&mut self, signal: Signal, value: T::Value) {
+    self.values.insert(signal, Some(value));
+    self.stamp_next(signal);
+    self.write_through(signal);
+  }
+
+  /// Set the value for the given signal as evaluated by the current run,
+  /// stamping it with the current epoch. Executors use this after
+  /// [`begin_epoch`](Self::begin_epoch).
+  pub fn insert_evaluated(&mut self, signal: Signal, value: T::Value) {
+    self.values.insert(signal, Some(value));
+    self.stamp(signal);
+    self.write_through(signal);
+  }
+
+  /// Move the value for the given signal out of the map, leaving it
+  /// unevaluated. Avoids cloning large values when extracting results.
+  pub fn take(&mut self, signal: Signal) -> Option<T::Value> {
+    self.values.get_mut(&signal).and_then(Option::take)
+  }
+
+  /// Call `f` with a borrow of every value in the map, by ID, e.g. to
+  /// stream results to an exporter without cloning them.
+  pub fn for_each(&self, mut f: impl FnMut(Signal, &T::Value)) {
+    for (signal, value) in self.values.iter() {
+      if let Some(value) = value {
+        f(signal, value);
+      }
+    }
+  }
+
+  /// Call `f` with a borrow of the value of each given signal that has one,
+  /// in the order given.
+  pub fn for_each_of(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+    mut f: impl FnMut(Signal, &T::Value),
+  ) {
+    for signal in signals {
+      if let Some(value) = self.get(signal) {
+        f(signal, value);
+      }
+    }
+  }
+
+  /// Clear the values of the given signals, so they are re-evaluated by a
+  /// plan passed through [`PlannedEvaluation::skip_evaluated`].
+  pub fn invalidate(&mut self, signals: impl IntoIterator<Item = Signal>) {
+    for signal in signals {
+      if let Some(value) = self.values.get_mut(&signal) {
+        *value = None;
+      }
+      self.remove_stored(signal);
+    }
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap, UnaryOp};
+  use crate::{
+    FloatBinaryOp, FloatMapSignalDef, PassParallelism, SignalDefMap, UnaryOp,
+  };
 
   #[test]
   fn test_planned_evaluation() {
@@ -211,10 +1114,9 @@ mod tests {
     let e =
       defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(c, d)));
 
-    let root_targets = vec![e].into_iter().collect();
     let matrix = SignalMatrix::new(defset);
 
-    let planned_eval = matrix.plan_evaluation::<CustomPlanner>(root_targets);
+    let planned_eval = matrix.plan_evaluation(&CustomPlanner::new(), [e]);
 
     // phase 0: a, b
     // phase 1: c
@@ -240,4 +1142,211 @@ mod tests {
 
     assert_eq!(values.get(e).unwrap(), &-9.0);
   }
+
+  #[test]
+  fn test_plan_structure() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let d = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(c)));
+    let e =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(c, d)));
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix.plan_evaluation(&CustomPlanner::new(), [e]);
+
+    assert_eq!(planned_eval.pass_of(d), Some(2));
+    assert_eq!(planned_eval.pass_of(a), Some(0));
+    assert_eq!(matrix.direct_dependencies(e), &[c, d]);
+    assert_eq!(matrix.direct_dependents(c), &[d, e]);
+    let levels = matrix.levels();
+    assert_eq!((levels[&a], levels[&c], levels[&e]), (0, 1, 3));
+  }
+
+  #[test]
+  fn test_display() {
+    let mut defset = SignalDefMap::new();
+    let leaves: Vec<_> = (0..10)
+      .map(|i| defset.insert(FloatMapSignalDef::Constant(i as f64)))
+      .collect();
+    let total = defset.insert_labeled(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(leaves[0], leaves[9])),
+    );
+    assert_eq!(leaves[3].to_string(), "#3");
+    assert_eq!(defset.display(total).to_string(), "total");
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    assert_eq!(
+      plan.to_string(),
+      "1 roots, 3 targets in 2 passes (widest 2)"
+    );
+    assert_eq!(
+      format!("{plan:#}"),
+      "1 roots, 3 targets in 2 passes (widest 2)\n  pass 0 (2): #0, #9\n  \
+       pass 1 (1): total"
+    );
+
+    let wide = EvaluationPassDescriptor::new(leaves.iter().copied().collect());
+    assert_eq!(
+      wide.to_string(),
+      "10 targets: #0, #1, #2, #3, #4, #5, #6, #7, ... (2 more)"
+    );
+  }
+
+  #[test]
+  fn test_context_scratch_reuse() {
+    let def =
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(Signal(0), Signal(1)));
+    let mut scratch = ContextScratch::<FloatMapSignalDef>::default();
+    let context = EvalContext::for_def_in(
+      Signal(2),
+      &def,
+      [(Signal(0), &1.0), (Signal(1), &2.0)],
+      &mut scratch,
+    );
+    assert_eq!(def.evaluate(&context), 3.0);
+    context.recycle(&mut scratch);
+    // the buffers come back empty, with their capacity
+    assert!(scratch.values.is_empty() && scratch.values.capacity() >= 2);
+
+    let def = FloatMapSignalDef::UnaryOp(UnaryOp::Neg(Signal(2)));
+    let context = EvalContext::for_def_in(
+      Signal(3),
+      &def,
+      [(Signal(2), &3.0)],
+      &mut scratch,
+    );
+    assert_eq!(context.get(Signal(0)), None);
+    assert_eq!(def.evaluate(&context), -3.0);
+  }
+
+  #[test]
+  fn test_boxed_planner() {
+    let mut defset = SignalDefMap::new();
+
+    let a = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let b = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+
+    let matrix = SignalMatrix::new(defset);
+    let planner: Box<dyn EvaluationPlanner<FloatMapSignalDef>> =
+      Box::new(CustomPlanner::new());
+
+    let planned_eval = matrix.plan_evaluation(&planner, [b]);
+    assert_eq!(planned_eval.passes().len(), 2);
+
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+    assert_eq!(values.get(b).unwrap(), &-3.0);
+  }
+
+  #[test]
+  fn test_max_pass_size() {
+    let mut defset = SignalDefMap::new();
+
+    let negs: SignalSet = (0..5)
+      .map(|i| {
+        let leaf = defset.insert(FloatMapSignalDef::Constant(i as f64));
+        defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(leaf)))
+      })
+      .collect();
+
+    let matrix = SignalMatrix::new(defset);
+    let planner = CustomPlanner::new().with_max_pass_size(2);
+    let planned_eval = matrix.plan_evaluation(&planner, negs.iter().copied());
+
+    // two passes of five, each split into 2 + 2 + 1
+    assert_eq!(planned_eval.passes().len(), 6);
+    assert!(planned_eval
+      .passes()
+      .iter()
+      .all(|pass| pass.targets().len() <= 2));
+
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+    let total: f64 = negs.iter().map(|s| values.get(*s).unwrap()).sum();
+    assert_eq!(total, -10.0);
+  }
+
+  #[test]
+  fn test_parallelism_policy() {
+    use std::sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc,
+    };
+
+    let mut defset = SignalDefMap::new();
+
+    let negs: Vec<_> = (0..8)
+      .map(|i| {
+        let leaf = defset.insert(FloatMapSignalDef::Constant(i as f64));
+        defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(leaf)))
+      })
+      .collect();
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval =
+      matrix.plan_evaluation(&CustomPlanner::new(), negs.iter().copied());
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let options = RunOptions::new().with_parallelism_policy({
+      let calls = calls.clone();
+      move |i: usize, _: &EvaluationPassDescriptor| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        if i == 0 {
+          PassParallelism::Limited(2)
+        } else {
+          PassParallelism::Full
+        }
+      }
+    });
+
+    let values = planned_eval.run_with(
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets()),
+      &options,
+    );
+    assert_eq!(calls.load(Ordering::Relaxed), planned_eval.passes().len());
+    assert_eq!(values.get(negs[3]).unwrap(), &-3.0);
+    // the limited pool outlives the run, for the next one to reuse
+    #[cfg(feature = "rayon")]
+    options
+      .thread_pools()
+      .get_or_build(2, || panic!("limited pool was not kept"));
+  }
+
+  #[test]
+  fn test_incremental_recalculation() {
+    let mut defset = SignalDefMap::new();
+
+    let a = defset.insert_labeled("a", FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let d = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(b)));
+
+    let mut matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [c, d]);
+    let mut values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(c).unwrap(), &3.0);
+
+    let a = matrix.defset().lookup("a").unwrap();
+    matrix.update(a, FloatMapSignalDef::Constant(5.0));
+    let mut stale = matrix.dependents_closure([a]);
+    stale.insert(a);
+    values.invalidate(stale);
+
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [c, d])
+      .skip_evaluated(&values);
+    // only `a` and `c` are stale
+    assert_eq!(plan.all_queued_targets(), [a, c].into_iter().collect());
+    let values = plan.run(values);
+    assert_eq!(values.get(c).unwrap(), &7.0);
+    assert_eq!(values.get(d).unwrap(), &-2.0);
+  }
 }
@@ -0,0 +1,250 @@
+use std::{fmt, str::FromStr};
+
+use crate::{ArithmeticError, EvalContext, Signal, SignalDef};
+
+/// The number of fractional digits a [`Decimal`] stores.
+pub const DECIMAL_PLACES: u32 = 9;
+
+const ONE: i128 = 10i128.pow(DECIMAL_PLACES);
+
+/// A fixed-point decimal number with [`DECIMAL_PLACES`] fractional digits,
+/// stored as a scaled `i128`. Results that need more digits are rounded half to
+/// even (banker's rounding).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Decimal {
+  units: i128,
+}
+
+impl Decimal {
+  pub const ZERO: Decimal = Decimal { units: 0 };
+
+  /// Create a decimal from an integer.
+  pub fn from_int(n: i64) -> Self {
+    Decimal {
+      units: n as i128 * ONE,
+    }
+  }
+
+  /// Create a decimal equal to `mantissa * 10^-scale`, rounding half to even
+  /// if `scale` exceeds [`DECIMAL_PLACES`]. Returns `None` on overflow.
+  pub fn from_parts(mantissa: i128, scale: u32) -> Option<Self> {
+    let units = if scale <= DECIMAL_PLACES {
+      mantissa.checked_mul(10i128.checked_pow(DECIMAL_PLACES - scale)?)?
+    } else {
+      div_half_even(mantissa, 10i128.checked_pow(scale - DECIMAL_PLACES)?)
+    };
+    Some(Decimal { units })
+  }
+
+  /// Round to the given number of fractional digits, half to even.
+  pub fn round_dp(self, dp: u32) -> Self {
+    if dp >= DECIMAL_PLACES {
+      return self;
+    }
+    let factor = 10i128.pow(DECIMAL_PLACES - dp);
+    Decimal {
+      units: div_half_even(self.units, factor) * factor,
+    }
+  }
+
+  /// Negate, returning `None` on overflow.
+  pub fn checked_neg(self) -> Option<Self> {
+    self.units.checked_neg().map(|units| Decimal { units })
+  }
+
+  /// Add, returning `None` on overflow.
+  pub fn checked_add(self, rhs: Self) -> Option<Self> {
+    self
+      .units
+      .checked_add(rhs.units)
+      .map(|units| Decimal { units })
+  }
+
+  /// Subtract, returning `None` on overflow.
+  pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+    self
+      .units
+      .checked_sub(rhs.units)
+      .map(|units| Decimal { units })
+  }
+
+  /// Multiply, rounding half to even, returning `None` on overflow.
+  pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+    let product = self.units.checked_mul(rhs.units)?;
+    Some(Decimal {
+      units: div_half_even(product, ONE),
+    })
+  }
+
+  /// Divide, rounding half to even, returning `None` on overflow or division
+  /// by zero.
+  pub fn checked_div(self, rhs: Self) -> Option<Self> {
+    if rhs.units == 0 {
+      return None;
+    }
+    let scaled = self.units.checked_mul(ONE)?;
+    Some(Decimal {
+      units: div_half_even(scaled, rhs.units),
+    })
+  }
+}
+
+/// Divide, rounding the quotient half to even.
+fn div_half_even(n: i128, d: i128) -> i128 {
+  let (q, r) = (n / d, n % d);
+  if r == 0 {
+    return q;
+  }
+  let away = if (n < 0) == (d < 0) { 1 } else { -1 };
+  // compare |r| against |d| / 2 without overflowing
+  let (r, half) = (r.unsigned_abs(), d.unsigned_abs() - r.unsigned_abs());
+  match r.cmp(&half) {
+    std::cmp::Ordering::Less => q,
+    std::cmp::Ordering::Greater => q + away,
+    std::cmp::Ordering::Equal if q % 2 == 0 => q,
+    std::cmp::Ordering::Equal => q + away,
+  }
+}
+
+impl fmt::Display for Decimal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let sign = if self.units < 0 { "-" } else { "" };
+    let units = self.units.unsigned_abs();
+    let (int, frac) = (units / ONE as u128, units % ONE as u128);
+    if frac == 0 {
+      return write!(f, "{sign}{int}");
+    }
+    let frac = format!("{frac:0width$}", width = DECIMAL_PLACES as usize);
+    write!(f, "{sign}{int}.{}", frac.trim_end_matches('0'))
+  }
+}
+
+/// An error parsing a [`Decimal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError(String);
+
+impl fmt::Display for ParseDecimalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid decimal `{}`", self.0)
+  }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl FromStr for Decimal {
+  type Err = ParseDecimalError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = || ParseDecimalError(s.to_string());
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    let digits = format!("{int}{frac}");
+    if frac.starts_with(['+', '-'])
+      || !digits.ends_with(|c: char| c.is_ascii_digit())
+    {
+      return Err(err());
+    }
+    let mantissa: i128 = digits.parse().map_err(|_| err())?;
+    Decimal::from_parts(mantissa, frac.len() as u32).ok_or_else(err)
+  }
+}
+
+/// A signal definition for a fixed-point [`Decimal`] value or operation, for
+/// graphs over money where `f64` rounding is unacceptable.
+///
+/// Evaluates to a `Result`, like [`IntMapSignalDef`](crate::IntMapSignalDef).
+#[derive(Debug)]
+pub enum DecimalSignalDef {
+  Constant(Decimal),
+  Neg(Signal),
+  Add(Signal, Signal),
+  Sub(Signal, Signal),
+  Mul(Signal, Signal),
+  Div(Signal, Signal),
+  /// Round to the given number of fractional digits, half to even.
+  Round(Signal, u32),
+}
+
+impl SignalDef for DecimalSignalDef {
+  type Value = Result<Decimal, ArithmeticError>;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self {
+      DecimalSignalDef::Constant(_) => {}
+      DecimalSignalDef::Neg(s) | DecimalSignalDef::Round(s, _) => out.push(*s),
+      DecimalSignalDef::Add(a, b)
+      | DecimalSignalDef::Sub(a, b)
+      | DecimalSignalDef::Mul(a, b)
+      | DecimalSignalDef::Div(a, b) => out.extend([*a, *b]),
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    let value = |s: &Signal| *ctx.values[s];
+    let signal = ctx.signal();
+    let overflow = |op| ArithmeticError::Overflow { signal, op };
+
+    match self {
+      DecimalSignalDef::Constant(d) => Ok(*d),
+      DecimalSignalDef::Neg(a) => {
+        value(a)?.checked_neg().ok_or(overflow("neg"))
+      }
+      DecimalSignalDef::Add(a, b) => {
+        value(a)?.checked_add(value(b)?).ok_or(overflow("add"))
+      }
+      DecimalSignalDef::Sub(a, b) => {
+        value(a)?.checked_sub(value(b)?).ok_or(overflow("sub"))
+      }
+      DecimalSignalDef::Mul(a, b) => {
+        value(a)?.checked_mul(value(b)?).ok_or(overflow("mul"))
+      }
+      DecimalSignalDef::Div(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        if b == Decimal::ZERO {
+          return Err(ArithmeticError::DivisionByZero { signal });
+        }
+        a.checked_div(b).ok_or(overflow("div"))
+      }
+      DecimalSignalDef::Round(a, dp) => Ok(value(a)?.round_dp(*dp)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalDefMap, SignalMatrix};
+
+  fn dec(s: &str) -> Decimal { s.parse().unwrap() }
+
+  #[test]
+  fn test_bankers_rounding() {
+    assert_eq!(dec("2.345").round_dp(2), dec("2.34"));
+    assert_eq!(dec("2.355").round_dp(2), dec("2.36"));
+    assert_eq!(dec("-2.345").round_dp(2), dec("-2.34"));
+    assert_eq!(dec("0.1").checked_add(dec("0.2")), Some(dec("0.3")));
+    assert_eq!(dec("-12.50").to_string(), "-12.5");
+
+    let mut defset = SignalDefMap::new();
+    let price = defset.insert(DecimalSignalDef::Constant(dec("19.99")));
+    let rate = defset.insert(DecimalSignalDef::Constant(dec("0.0725")));
+    let tax = defset.insert(DecimalSignalDef::Mul(price, rate));
+    let tax = defset.insert(DecimalSignalDef::Round(tax, 2));
+    let total = defset.insert(DecimalSignalDef::Add(price, tax));
+    let zero = defset.insert(DecimalSignalDef::Constant(Decimal::ZERO));
+    let bad = defset.insert(DecimalSignalDef::Div(total, zero));
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval =
+      matrix.plan_evaluation(&CustomPlanner::new(), [total, bad]);
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+
+    // 19.99 * 0.0725 = 1.449275, rounded to 1.45
+    assert_eq!(values.get(total), Some(&Ok(dec("21.44"))));
+    assert_eq!(
+      values.get(bad),
+      Some(&Err(ArithmeticError::DivisionByZero { signal: bad }))
+    );
+  }
+}
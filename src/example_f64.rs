@@ -1,11 +1,16 @@
-use std::collections::HashSet;
+use std::fmt::Debug;
+
+use num_traits::Float;
 
 use crate::{EvalContext, Signal, SignalDef};
 
 /// A signal definition for a floating-point value or operation.
+///
+/// Generic over any [`Float`], so graphs can trade precision for footprint
+/// with `f32` or a user type like `half::f16`. Defaults to `f64`.
 #[derive(Debug)]
-pub enum FloatMapSignalDef {
-  Constant(f64),
+pub enum FloatMapSignalDef<F = f64> {
+  Constant(F),
   UnaryOp(UnaryOp),
   BinaryOp(FloatBinaryOp),
 }
@@ -26,21 +31,21 @@ pub enum UnaryOp {
   Neg(Signal),
 }
 
-impl SignalDef for FloatMapSignalDef {
-  type Value = f64;
+impl<F: Float + Debug + Send + Sync> SignalDef for FloatMapSignalDef<F> {
+  type Value = F;
 
-  fn dependencies(&self) -> HashSet<Signal> {
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
     match self {
-      FloatMapSignalDef::Constant(_) => HashSet::new(),
+      FloatMapSignalDef::Constant(_) => {}
       FloatMapSignalDef::UnaryOp(op) => match op {
-        UnaryOp::Neg(s) => vec![*s].into_iter().collect(),
+        UnaryOp::Neg(s) => out.push(*s),
       },
       FloatMapSignalDef::BinaryOp(op) => match op {
-        FloatBinaryOp::Add(a, b) => vec![*a, *b].into_iter().collect(),
-        FloatBinaryOp::Sub(a, b) => vec![*a, *b].into_iter().collect(),
-        FloatBinaryOp::Mul(a, b) => vec![*a, *b].into_iter().collect(),
-        FloatBinaryOp::Div(a, b) => vec![*a, *b].into_iter().collect(),
-        FloatBinaryOp::Pow(a, b) => vec![*a, *b].into_iter().collect(),
+        FloatBinaryOp::Add(a, b)
+        | FloatBinaryOp::Sub(a, b)
+        | FloatBinaryOp::Mul(a, b)
+        | FloatBinaryOp::Div(a, b)
+        | FloatBinaryOp::Pow(a, b) => out.extend([*a, *b]),
       },
     }
   }
@@ -49,15 +54,40 @@ impl SignalDef for FloatMapSignalDef {
     match self {
       FloatMapSignalDef::Constant(value) => *value,
       FloatMapSignalDef::UnaryOp(op) => match op {
-        UnaryOp::Neg(s) => -ctx.values[s],
+        UnaryOp::Neg(s) => -*ctx.values[s],
       },
       FloatMapSignalDef::BinaryOp(op) => match op {
-        FloatBinaryOp::Add(a, b) => ctx.values[a] + ctx.values[b],
-        FloatBinaryOp::Sub(a, b) => ctx.values[a] - ctx.values[b],
-        FloatBinaryOp::Mul(a, b) => ctx.values[a] * ctx.values[b],
-        FloatBinaryOp::Div(a, b) => ctx.values[a] / ctx.values[b],
+        FloatBinaryOp::Add(a, b) => *ctx.values[a] + *ctx.values[b],
+        FloatBinaryOp::Sub(a, b) => *ctx.values[a] - *ctx.values[b],
+        FloatBinaryOp::Mul(a, b) => *ctx.values[a] * *ctx.values[b],
+        FloatBinaryOp::Div(a, b) => *ctx.values[a] / *ctx.values[b],
         FloatBinaryOp::Pow(a, b) => ctx.values[a].powf(*ctx.values[b]),
       },
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_f32_graph() {
+    let mut defset = SignalDefMap::<FloatMapSignalDef<f32>>::new();
+
+    let a = defset.insert(FloatMapSignalDef::Constant(1.5));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(a, b)));
+    let d = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(c)));
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix.plan_evaluation(&CustomPlanner::new(), [d]);
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+
+    assert_eq!(values.get(d), Some(&-2.25f32));
+  }
+}
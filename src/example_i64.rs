@@ -0,0 +1,208 @@
+use std::fmt;
+
+use crate::{EvalContext, Signal, SignalDef};
+
+/// How an integer operation handles overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+  /// Wrap around at the bounds of `i64`.
+  Wrap,
+  /// Clamp to the bounds of `i64`.
+  Saturate,
+  /// Evaluate to an [`ArithmeticError::Overflow`] naming the signal.
+  #[default]
+  Checked,
+}
+
+/// An integer operation.
+#[derive(Debug)]
+pub enum IntOp {
+  Constant(i64),
+  Neg(Signal),
+  Add(Signal, Signal),
+  Sub(Signal, Signal),
+  Mul(Signal, Signal),
+  Div(Signal, Signal),
+}
+
+/// A signal definition for an integer value or operation, with a configurable
+/// [`OverflowPolicy`].
+///
+/// Evaluates to a `Result`: a failed signal holds an [`ArithmeticError`] naming
+/// it, and every signal depending on it evaluates to that same error.
+#[derive(Debug)]
+pub struct IntMapSignalDef {
+  op:       IntOp,
+  overflow: OverflowPolicy,
+}
+
+impl IntMapSignalDef {
+  /// Create a definition for the given operation, with checked overflow.
+  pub fn new(op: IntOp) -> Self {
+    IntMapSignalDef {
+      op,
+      overflow: OverflowPolicy::default(),
+    }
+  }
+
+  /// Set the overflow policy.
+  pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+    self.overflow = overflow;
+    self
+  }
+
+  /// Get the operation.
+  pub fn op(&self) -> &IntOp { &self.op }
+
+  /// Get the overflow policy.
+  pub fn overflow(&self) -> OverflowPolicy { self.overflow }
+}
+
+/// An arithmetic error evaluating an [`IntMapSignalDef`] or
+/// [`DecimalSignalDef`](crate::DecimalSignalDef).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+  /// The operation overflowed under [`OverflowPolicy::Checked`].
+  Overflow {
+    signal: Signal,
+    op:     &'static str,
+  },
+  /// The operation divided by zero, which fails under every policy.
+  DivisionByZero { signal: Signal },
+}
+
+impl ArithmeticError {
+  /// Get the signal where the error originated.
+  pub fn signal(&self) -> Signal {
+    match self {
+      ArithmeticError::Overflow { signal, .. }
+      | ArithmeticError::DivisionByZero { signal } => *signal,
+    }
+  }
+}
+
+impl fmt::Display for ArithmeticError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ArithmeticError::Overflow { signal, op } => {
+        write!(f, "signal #{} overflowed in {op}", signal.0)
+      }
+      ArithmeticError::DivisionByZero { signal } => {
+        write!(f, "signal #{} divided by zero", signal.0)
+      }
+    }
+  }
+}
+
+impl std::error::Error for ArithmeticError {}
+
+impl SignalDef for IntMapSignalDef {
+  type Value = Result<i64, ArithmeticError>;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self.op {
+      IntOp::Constant(_) => {}
+      IntOp::Neg(s) => out.push(s),
+      IntOp::Add(a, b)
+      | IntOp::Sub(a, b)
+      | IntOp::Mul(a, b)
+      | IntOp::Div(a, b) => out.extend([a, b]),
+    }
+  }
+
+  fn dependency_roles_into(&self, out: &mut Vec<(Signal, &'static str)>) {
+    if let IntOp::Div(a, b) = self.op {
+      out.extend([(a, "numerator"), (b, "denominator")]);
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    let value = |s: &Signal| ctx.values[s].map(i128::from);
+    let signal = ctx.signal();
+
+    // every operation on two `i64`s fits in an `i128`, so compute exactly and
+    // apply the policy on the way back down
+    let (exact, op) = match &self.op {
+      IntOp::Constant(n) => return Ok(*n),
+      IntOp::Neg(a) => (-value(a)?, "neg"),
+      IntOp::Add(a, b) => (value(a)? + value(b)?, "add"),
+      IntOp::Sub(a, b) => (value(a)? - value(b)?, "sub"),
+      IntOp::Mul(a, b) => (value(a)? * value(b)?, "mul"),
+      IntOp::Div(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        if b == 0 {
+          return Err(ArithmeticError::DivisionByZero { signal });
+        }
+        (a / b, "div")
+      }
+    };
+
+    match self.overflow {
+      OverflowPolicy::Wrap => Ok(exact as i64),
+      OverflowPolicy::Saturate => {
+        Ok(exact.clamp(i64::MIN.into(), i64::MAX.into()) as i64)
+      }
+      OverflowPolicy::Checked => i64::try_from(exact)
+        .map_err(|_| ArithmeticError::Overflow { signal, op }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_overflow_policy() {
+    let mut defset = SignalDefMap::new();
+
+    let max = defset.insert(IntMapSignalDef::new(IntOp::Constant(i64::MAX)));
+    let one = defset.insert(IntMapSignalDef::new(IntOp::Constant(1)));
+    let wrapped = defset.insert(
+      IntMapSignalDef::new(IntOp::Add(max, one))
+        .with_overflow(OverflowPolicy::Wrap),
+    );
+    let saturated = defset.insert(
+      IntMapSignalDef::new(IntOp::Add(max, one))
+        .with_overflow(OverflowPolicy::Saturate),
+    );
+    let checked = defset.insert(IntMapSignalDef::new(IntOp::Add(max, one)));
+    let downstream = defset.insert(IntMapSignalDef::new(IntOp::Neg(checked)));
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix
+      .plan_evaluation(&CustomPlanner::new(), [wrapped, saturated, downstream]);
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+
+    assert_eq!(values.get(wrapped), Some(&Ok(i64::MIN)));
+    assert_eq!(values.get(saturated), Some(&Ok(i64::MAX)));
+    let error = ArithmeticError::Overflow {
+      signal: checked,
+      op:     "add",
+    };
+    assert_eq!(values.get(downstream), Some(&Err(error)));
+    assert_eq!(error.signal(), checked);
+  }
+
+  #[test]
+  fn test_evaluate_in_isolation() {
+    let (a, b, out) = (
+      Signal::from_index(0),
+      Signal::from_index(1),
+      Signal::from_index(2),
+    );
+    let def = IntMapSignalDef::new(IntOp::Div(a, b));
+    let (six, zero) = (Ok(6), Ok(0));
+
+    let ctx = EvalContext::from_values(out, [(a, &six), (b, &six)]);
+    assert_eq!(def.evaluate(&ctx), Ok(1));
+    let ctx = EvalContext::from_values(out, [(a, &six), (b, &zero)]);
+    assert_eq!(
+      def.evaluate(&ctx),
+      Err(ArithmeticError::DivisionByZero { signal: out })
+    );
+  }
+}
@@ -0,0 +1,175 @@
+use std::{borrow::Borrow, fmt};
+
+use crate::{Signal, SignalDef, SignalDefMap};
+
+/// A user-supplied ID for a signal that stays the same across rebuilds of
+/// the graph, unlike [`Signal`]s, which are handed out in insertion order.
+/// Any string works; [`from_uuid`](Self::from_uuid) formats a UUID.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(transparent)
+)]
+pub struct ExternalId(String);
+
+impl ExternalId {
+  /// Create an ID from a string.
+  pub fn new(id: impl Into<String>) -> Self { ExternalId(id.into()) }
+
+  /// Create an ID from a UUID, in its hyphenated lowercase form.
+  pub fn from_uuid(uuid: u128) -> Self {
+    let hex = format!("{uuid:032x}");
+    ExternalId(format!(
+      "{}-{}-{}-{}-{}",
+      &hex[..8],
+      &hex[8..12],
+      &hex[12..16],
+      &hex[16..20],
+      &hex[20..]
+    ))
+  }
+
+  /// Get the ID as a string.
+  pub fn as_str(&self) -> &str { &self.0 }
+}
+
+impl From<&str> for ExternalId {
+  fn from(id: &str) -> Self { ExternalId::new(id) }
+}
+
+impl From<String> for ExternalId {
+  fn from(id: String) -> Self { ExternalId(id) }
+}
+
+impl Borrow<str> for ExternalId {
+  fn borrow(&self) -> &str { &self.0 }
+}
+
+impl fmt::Display for ExternalId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+/// An external ID is already held by another signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateExternalId {
+  pub id:     ExternalId,
+  pub holder: Signal,
+}
+
+impl fmt::Display for DuplicateExternalId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "external ID `{}` is already held by {}",
+      self.id, self.holder
+    )
+  }
+}
+
+impl std::error::Error for DuplicateExternalId {}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Insert a new signal definition with an external ID.
+  pub fn insert_with_external_id(
+    &mut self,
+    id: impl Into<ExternalId>,
+    def: T,
+  ) -> Result<Signal, DuplicateExternalId> {
+    let id = id.into();
+    if let Some(holder) = self.lookup_external_id(id.as_str()) {
+      return Err(DuplicateExternalId { id, holder });
+    }
+    let signal = self.insert(def);
+    self.set_external_id(signal, id)?;
+    Ok(signal)
+  }
+
+  /// Set the external ID of a signal, replacing any it had before. Fails if
+  /// another signal holds the ID.
+  pub fn set_external_id(
+    &mut self,
+    signal: Signal,
+    id: impl Into<ExternalId>,
+  ) -> Result<(), DuplicateExternalId> {
+    let id = id.into();
+    match self.by_external_id.get(&id) {
+      Some(holder) if *holder != signal => {
+        return Err(DuplicateExternalId {
+          id,
+          holder: *holder,
+        });
+      }
+      _ => {}
+    }
+    if let Some(old) = self.external_ids.insert(signal, id.clone()) {
+      self.by_external_id.remove(&old);
+    }
+    self.by_external_id.insert(id, signal);
+    Ok(())
+  }
+
+  /// Get the external ID of a signal, if it has one.
+  pub fn external_id(&self, signal: Signal) -> Option<&ExternalId> {
+    self.external_ids.get(&signal)
+  }
+
+  /// Find the signal with the given external ID.
+  pub fn lookup_external_id(&self, id: &str) -> Option<Signal> {
+    self.by_external_id.get(id).copied()
+  }
+
+  /// Iterate over every external ID and its signal, sorted by ID.
+  pub fn external_ids(&self) -> impl Iterator<Item = (&ExternalId, Signal)> {
+    self.by_external_id.iter().map(|(id, signal)| (id, *signal))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{EvaluationValueMap, FloatMapSignalDef};
+
+  #[test]
+  fn test_external_ids() {
+    let uuid = ExternalId::from_uuid(0x6f1c_29ab_0000_4e7f_8a1d_0123_4567_89ab);
+    assert_eq!(uuid.as_str(), "6f1c29ab-0000-4e7f-8a1d-0123456789ab");
+
+    let mut v1 = SignalDefMap::new();
+    let rate = v1
+      .insert_with_external_id(uuid.clone(), FloatMapSignalDef::Constant(0.05))
+      .unwrap();
+    let other = v1.insert(FloatMapSignalDef::Constant(1.0));
+    assert_eq!(
+      v1.set_external_id(other, uuid.clone()),
+      Err(DuplicateExternalId {
+        id:     uuid.clone(),
+        holder: rate,
+      })
+    );
+    v1.set_external_id(other, "other").unwrap();
+    v1.set_external_id(other, "renamed").unwrap();
+    assert_eq!(v1.lookup_external_id("other"), None);
+    assert_eq!(v1.external_id(other).unwrap().as_str(), "renamed");
+    assert_eq!(v1.external_ids().count(), 2);
+
+    // a rebuild in another order still finds the signal, without a label
+    let mut v2 = SignalDefMap::new();
+    v2.insert(FloatMapSignalDef::Constant(2.0));
+    let rate2 = v2
+      .insert_with_external_id(uuid.as_str(), FloatMapSignalDef::Constant(0.05))
+      .unwrap();
+    assert_ne!(rate, rate2);
+    assert_eq!(v2.lookup_external_id(uuid.as_str()), Some(rate2));
+    assert_eq!(v2.carry_over(&v1, rate), Some(rate2));
+    assert_eq!(v2.carry_over(&v1, other), None);
+
+    let mut values = EvaluationValueMap::<FloatMapSignalDef>::new_empty(
+      [rate].into_iter().collect(),
+    );
+    values.insert(rate, 0.05);
+    assert_eq!(values.migrate(&v1, &v2).get(rate2), Some(&0.05));
+  }
+}
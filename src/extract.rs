@@ -0,0 +1,101 @@
+use crate::{EvaluationValueMap, Signal, SignalDef};
+
+impl<T: SignalDef> EvaluationValueMap<T> {
+  /// Clone the values of the given signals into a `Vec`, in the order given,
+  /// or `None` if any of them hasn't been evaluated.
+  pub fn extract_vec(&self, signals: &[Signal]) -> Option<Vec<T::Value>>
+  where
+    T::Value: Clone,
+  {
+    signals.iter().map(|s| self.get(*s).cloned()).collect()
+  }
+
+  /// Move the values of the given signals out of the map into a `Vec`, in the
+  /// order given, leaving them unevaluated. Returns `None` and leaves the map
+  /// untouched if any of them hasn't been evaluated.
+  ///
+  /// A signal listed twice is only evaluated once, so it makes this fail.
+  pub fn take_vec(&mut self, signals: &[Signal]) -> Option<Vec<T::Value>> {
+    let mut seen = Vec::with_capacity(signals.len());
+    for signal in signals {
+      if self.get(*signal).is_none() || seen.contains(signal) {
+        return None;
+      }
+      seen.push(*signal);
+    }
+    signals.iter().map(|s| self.take(*s)).collect()
+  }
+
+  /// Clone the values of the given signals into a 1-D array, in the order
+  /// given, or `None` if any of them hasn't been evaluated.
+  #[cfg(feature = "ndarray")]
+  pub fn extract_array(
+    &self,
+    signals: &[Signal],
+  ) -> Option<ndarray::Array1<T::Value>>
+  where
+    T::Value: Clone,
+  {
+    self.extract_vec(signals).map(ndarray::Array1::from)
+  }
+
+  /// Copy the values of the given signals into an Arrow column of primitive
+  /// type `A`, in the order given. Signals that haven't been evaluated are
+  /// null.
+  #[cfg(feature = "arrow")]
+  pub fn extract_column<A>(
+    &self,
+    signals: &[Signal],
+  ) -> arrow::array::PrimitiveArray<A>
+  where
+    A: arrow::datatypes::ArrowPrimitiveType<Native = T::Value>,
+    T::Value: Copy,
+  {
+    signals.iter().map(|s| self.get(*s).copied()).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_extract_columns() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let stray = defset.insert(FloatMapSignalDef::Constant(0.0));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [sum]);
+    let mut values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    assert_eq!(values.extract_vec(&[sum, a, b]), Some(vec![5.0, 2.0, 3.0]));
+    assert_eq!(values.extract_vec(&[a, stray]), None);
+    #[cfg(feature = "ndarray")]
+    assert_eq!(
+      values.extract_array(&[b, sum]),
+      Some(ndarray::arr1(&[3.0, 5.0]))
+    );
+    #[cfg(feature = "arrow")]
+    {
+      use arrow::{array::Array, datatypes::Float64Type};
+      let column = values.extract_column::<Float64Type>(&[a, stray, sum]);
+      assert_eq!(column.len(), 3);
+      assert!(column.is_null(1));
+      assert_eq!(column.value(2), 5.0);
+    }
+
+    // taking fails as a whole, then moves every value out
+    assert_eq!(values.take_vec(&[a, a]), None);
+    assert_eq!(values.take_vec(&[b, stray]), None);
+    assert_eq!(values.get(b), Some(&3.0));
+    assert_eq!(values.take_vec(&[b, a]), Some(vec![3.0, 2.0]));
+    assert_eq!(values.get(a), None);
+  }
+}
@@ -0,0 +1,244 @@
+use std::{
+  collections::BTreeMap,
+  fmt,
+  sync::{Arc, Condvar, Mutex},
+};
+
+/// A pool of evaluation slots shared by concurrent evaluations, handed out
+/// fairly by weight so a huge background run can't starve small interactive
+/// ones sharing the thread pool.
+///
+/// Each evaluation takes a [`FairShare`] and runs with it through
+/// [`RunOptions::with_fair_share`](crate::RunOptions::with_fair_share). Every
+/// target then waits for a slot; free slots go to the waiting share that has
+/// been served the least relative to its weight, so a share of weight 4 gets
+/// four slots for every one a share of weight 1 gets while both are busy.
+/// Shares joining later start level with the least served busy share, rather
+/// than owed everything they missed.
+#[derive(Clone)]
+pub struct FairScheduler {
+  shared: Arc<Shared>,
+}
+
+struct Shared {
+  state:    Mutex<State>,
+  released: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+  free:    usize,
+  next_id: u64,
+  shares:  BTreeMap<u64, Share>,
+}
+
+struct Share {
+  weight:      u32,
+  max_slots:   usize,
+  running:     usize,
+  waiting:     usize,
+  /// Slots taken divided by weight.
+  virtual_use: f64,
+}
+
+impl State {
+  fn eligible(&self) -> impl Iterator<Item = (&u64, &Share)> {
+    self
+      .shares
+      .iter()
+      .filter(|(_, s)| s.waiting > 0 && s.running < s.max_slots)
+  }
+
+  /// Check whether the given share is next in line for a free slot.
+  fn is_next(&self, id: u64) -> bool {
+    self.free > 0
+      && self
+        .eligible()
+        .min_by(|(_, a), (_, b)| a.virtual_use.total_cmp(&b.virtual_use))
+        .is_some_and(|(next, _)| *next == id)
+  }
+}
+
+impl FairScheduler {
+  /// Create a scheduler with the given number of slots, usually the thread
+  /// count of the shared pool.
+  pub fn new(slots: usize) -> Self {
+    FairScheduler {
+      shared: Arc::new(Shared {
+        state:    Mutex::new(State {
+          free: slots.max(1),
+          ..Default::default()
+        }),
+        released: Condvar::new(),
+      }),
+    }
+  }
+
+  /// Register an evaluation with the given weight, which is at least 1.
+  pub fn share(&self, weight: u32) -> FairShare {
+    let mut state = self.shared.state.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    let virtual_use = state
+      .eligible()
+      .map(|(_, s)| s.virtual_use)
+      .min_by(f64::total_cmp)
+      .unwrap_or(0.0);
+    state.shares.insert(id, Share {
+      weight: weight.max(1),
+      max_slots: usize::MAX,
+      running: 0,
+      waiting: 0,
+      virtual_use,
+    });
+    FairShare {
+      shared: self.shared.clone(),
+      id,
+    }
+  }
+
+  /// Get the number of slots not in use.
+  pub fn free_slots(&self) -> usize { self.shared.state.lock().unwrap().free }
+}
+
+impl fmt::Debug for FairScheduler {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let state = self.shared.state.lock().unwrap();
+    f.debug_struct("FairScheduler")
+      .field("free", &state.free)
+      .field("shares", &state.shares.len())
+      .finish()
+  }
+}
+
+/// An evaluation's claim on a [`FairScheduler`], released when dropped.
+pub struct FairShare {
+  shared: Arc<Shared>,
+  id:     u64,
+}
+
+/// A slot of a [`FairScheduler`], given back when dropped.
+pub(crate) struct FairPermit<'s>(&'s FairShare);
+
+impl FairShare {
+  /// Let this share hold at most this many slots at once, however many are
+  /// free, e.g. to keep a batch job from ever taking the whole pool.
+  pub fn with_max_slots(self, max_slots: usize) -> Self {
+    let mut state = self.shared.state.lock().unwrap();
+    state.shares.get_mut(&self.id).unwrap().max_slots = max_slots.max(1);
+    drop(state);
+    self
+  }
+
+  /// Wait for a slot.
+  pub(crate) fn acquire(&self) -> FairPermit<'_> {
+    let mut state = self.shared.state.lock().unwrap();
+    state.shares.get_mut(&self.id).unwrap().waiting += 1;
+    while !state.is_next(self.id) {
+      state = self.shared.released.wait(state).unwrap();
+    }
+    state.free -= 1;
+    let share = state.shares.get_mut(&self.id).unwrap();
+    share.waiting -= 1;
+    share.running += 1;
+    share.virtual_use += 1.0 / share.weight as f64;
+    // another share may be next for a remaining slot
+    self.shared.released.notify_all();
+    FairPermit(self)
+  }
+
+  #[cfg(test)]
+  fn waiting(&self) -> usize {
+    self.shared.state.lock().unwrap().shares[&self.id].waiting
+  }
+}
+
+impl Drop for FairPermit<'_> {
+  fn drop(&mut self) {
+    let mut state = self.0.shared.state.lock().unwrap();
+    state.free += 1;
+    state.shares.get_mut(&self.0.id).unwrap().running -= 1;
+    self.0.shared.released.notify_all();
+  }
+}
+
+impl Drop for FairShare {
+  fn drop(&mut self) {
+    self.shared.state.lock().unwrap().shares.remove(&self.id);
+    self.shared.released.notify_all();
+  }
+}
+
+impl fmt::Debug for FairShare {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("FairShare").field(&self.id).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::Duration};
+
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_fair_scheduling() {
+    let scheduler = FairScheduler::new(1);
+    let background = scheduler.share(1);
+    let interactive = scheduler.share(1);
+    let order = Mutex::new(Vec::new());
+
+    let held = background.acquire();
+    thread::scope(|scope| {
+      scope.spawn(|| {
+        let _permit = background.acquire();
+        order.lock().unwrap().push("background");
+      });
+      while background.waiting() == 0 {
+        thread::sleep(Duration::from_millis(1));
+      }
+      scope.spawn(|| {
+        let _permit = interactive.acquire();
+        order.lock().unwrap().push("interactive");
+      });
+      while interactive.waiting() == 0 {
+        thread::sleep(Duration::from_millis(1));
+      }
+      // background has been served already, so interactive goes first
+      drop(held);
+    });
+    assert_eq!(*order.lock().unwrap(), ["interactive", "background"]);
+    assert_eq!(scheduler.free_slots(), 1);
+
+    // evaluations sharing the scheduler still produce their values
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 32,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let scheduler = FairScheduler::new(2);
+    thread::scope(|scope| {
+      for weight in [1, 8] {
+        let share = scheduler.share(weight).with_max_slots(1);
+        let (plan, roots, expected) = (&plan, &roots, &expected);
+        scope.spawn(move || {
+          let options = RunOptions::new().with_fair_share(share);
+          let values = plan.run_with(
+            EvaluationValueMap::new_empty(plan.all_queued_targets()),
+            &options,
+          );
+          assert_eq!(values.get(roots[0]), expected.get(roots[0]));
+        });
+      }
+    });
+  }
+}
@@ -0,0 +1,260 @@
+use std::{mem, ops::Deref};
+
+use crate::{Signal, SignalDef, SignalDefMap, SignalMap, SignalMatrix};
+
+/// A [`SignalMatrix`] that can no longer be edited, laid out for planning and
+/// running. Created by [`SignalMatrix::freeze`].
+///
+/// Freezing builds the matrix's CSR dependency and dependents graphs up
+/// front, moves the definitions into a flat array indexed by signal, and
+/// computes the level of every signal and a topological order. The executor
+/// looks definitions up by index instead of hashing, [`CustomPlanner`]
+/// places targets in one sweep of the frozen order instead of walking back a
+/// level at a time, and [`levels`](SignalMatrix::levels) reads the frozen
+/// levels.
+///
+/// Call [`thaw`](Self::thaw) to edit the graph again.
+///
+/// [`CustomPlanner`]: crate::CustomPlanner
+#[derive(Debug)]
+pub struct FrozenMatrix<T: SignalDef> {
+  matrix: SignalMatrix<T>,
+}
+
+/// The structure precomputed by freezing a matrix.
+#[derive(Debug)]
+pub(crate) struct FrozenLayout {
+  /// The level of each signal by index, or `None` on or above a cycle.
+  levels: Vec<Option<usize>>,
+  /// Every signal not on or above a cycle, by level and then by ID.
+  order:  Vec<Signal>,
+}
+
+/// The definitions of a frozen [`SignalDefMap`], indexed by signal.
+#[derive(Debug)]
+pub(crate) struct DenseDefs<T> {
+  defs: Vec<Option<T>>,
+  len:  usize,
+}
+
+impl<T> DenseDefs<T> {
+  pub(crate) fn get(&self, signal: Signal) -> Option<&T> {
+    self.defs.get(signal.index()).and_then(Option::as_ref)
+  }
+
+  pub(crate) fn iter(&self) -> impl Iterator<Item = (Signal, &T)> {
+    self.defs.iter().enumerate().filter_map(|(index, def)| {
+      Some((Signal::from_index(index), def.as_ref()?))
+    })
+  }
+
+  pub(crate) fn len(&self) -> usize { self.len }
+}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Move the definitions into a flat array.
+  fn densify(&mut self) {
+    let mut defs: Vec<_> = (0..self.id_space()).map(|_| None).collect();
+    let len = self.map.len();
+    for (signal, def) in mem::take(&mut self.map) {
+      defs[signal.index()] = Some(def);
+    }
+    self.dense = Some(DenseDefs { defs, len });
+  }
+
+  /// Move the definitions back into the map, for editing.
+  fn sparsify(&mut self) {
+    let Some(dense) = self.dense.take() else {
+      return;
+    };
+    let mut map = SignalMap::default();
+    map.reserve(dense.len);
+    for (index, def) in dense.defs.into_iter().enumerate() {
+      if let Some(def) = def {
+        map.insert(Signal::from_index(index), def);
+      }
+    }
+    self.map = map;
+  }
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Freeze the matrix, building its cached graphs, moving its definitions
+  /// into a flat array, and computing levels and a topological order.
+  pub fn freeze(mut self) -> FrozenMatrix<T> {
+    let span = tracing::info_span!("freeze", signals = self.defset.len());
+    let _enter = span.enter();
+
+    self.dependents_graph();
+    let levels = self.dependency_graph().levels();
+    let mut order: Vec<_> = self
+      .defset
+      .iter()
+      .filter_map(|(signal, _)| Some((levels[signal.index()]?, signal)))
+      .collect();
+    order.sort_unstable_by_key(|(level, signal)| (*level, signal.0));
+    self.defset.densify();
+    self.frozen = Some(FrozenLayout {
+      levels,
+      order: order.into_iter().map(|(_, signal)| signal).collect(),
+    });
+    FrozenMatrix { matrix: self }
+  }
+
+  /// Get the frozen level of every signal by index, if the matrix is frozen.
+  pub(crate) fn frozen_levels(&self) -> Option<&[Option<usize>]> {
+    self.frozen.as_ref().map(|frozen| frozen.levels.as_slice())
+  }
+
+  /// Get the frozen topological order, if the matrix is frozen and has no
+  /// cycles, so the order holds every signal.
+  pub(crate) fn frozen_order(&self) -> Option<&[Signal]> {
+    self
+      .frozen
+      .as_ref()
+      .filter(|frozen| frozen.order.len() == self.defset.len())
+      .map(|frozen| frozen.order.as_slice())
+  }
+}
+
+impl<T: SignalDef> FrozenMatrix<T> {
+  /// Get the frozen matrix.
+  pub fn matrix(&self) -> &SignalMatrix<T> { &self.matrix }
+
+  fn layout(&self) -> &FrozenLayout {
+    self
+      .matrix
+      .frozen
+      .as_ref()
+      .expect("frozen matrix has a layout")
+  }
+
+  /// Get the level of a signal: 0 without dependencies, and otherwise one
+  /// more than its highest dependency. `None` for unknown signals and
+  /// signals on or above a cycle.
+  pub fn level(&self, signal: Signal) -> Option<usize> {
+    self.layout().levels.get(signal.index()).copied().flatten()
+  }
+
+  /// Get every signal not on or above a cycle, by level and then by ID, so
+  /// each comes after all its dependencies.
+  pub fn order(&self) -> &[Signal] { &self.layout().order }
+
+  /// Get the number of levels, which is the length of the longest
+  /// dependency chain.
+  pub fn depth(&self) -> usize {
+    self
+      .order()
+      .last()
+      .and_then(|s| self.level(*s))
+      .map_or(0, |l| l + 1)
+  }
+
+  /// Unfreeze the matrix for editing. Its cached graphs are kept until the
+  /// first edit.
+  pub fn thaw(mut self) -> SignalMatrix<T> {
+    self.matrix.frozen = None;
+    self.matrix.defset.sparsify();
+    self.matrix
+  }
+}
+
+impl<T: SignalDef> Deref for FrozenMatrix<T> {
+  type Target = SignalMatrix<T>;
+
+  fn deref(&self) -> &SignalMatrix<T> { &self.matrix }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, FloatMapSignalDef, PlannedEvaluation,
+    SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_freeze() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 8,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let levels = matrix.levels();
+    let frozen = matrix.freeze();
+
+    assert_eq!(frozen.order().len(), frozen.defset().len());
+    assert_eq!(frozen.depth(), 4);
+    assert_eq!(frozen.level(roots[0]), Some(3));
+    for (i, signal) in frozen.order().iter().enumerate() {
+      assert_eq!(frozen.level(*signal), levels.get(signal).copied());
+      for dep in frozen.direct_dependencies(*signal) {
+        assert!(frozen.order()[..i].contains(dep));
+      }
+    }
+
+    let plan = frozen.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let total = *values.get(roots[0]).unwrap();
+
+    let mut matrix = frozen.thaw();
+    let leaf = matrix.direct_dependencies(roots[0])[0];
+    matrix.update(leaf, FloatMapSignalDef::Constant(0.0));
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_ne!(values.get(roots[0]), Some(&total));
+  }
+
+  #[test]
+  fn test_frozen_planning() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     6,
+      width:      8,
+      min_fan_in: 1,
+      max_fan_in: 3,
+      reach:      3,
+    })
+    .with_seed(7)
+    .build_float(&mut defset);
+    let roots = &roots[..roots.len() / 2];
+    let matrix = SignalMatrix::new(defset);
+    let passes = |plan: &PlannedEvaluation<'_, FloatMapSignalDef>| {
+      plan
+        .passes()
+        .iter()
+        .map(|pass| {
+          let mut targets: Vec<_> = pass.targets().iter().copied().collect();
+          targets.sort_by_key(|s| s.0);
+          targets
+        })
+        .collect::<Vec<_>>()
+    };
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.to_vec());
+    let expected_passes = passes(&plan);
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let len = matrix.defset().len();
+
+    // the frozen sweep places every target where the walk does, and the
+    // executor finds every definition in the flat array
+    let frozen = matrix.freeze();
+    assert_eq!(frozen.defset().len(), len);
+    assert_eq!(frozen.defset().iter().count(), len);
+    let plan = frozen.plan_evaluation(&CustomPlanner::new(), roots.to_vec());
+    assert_eq!(passes(&plan), expected_passes);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    for root in roots {
+      assert_eq!(values.get(*root), expected.get(*root));
+    }
+
+    let matrix = frozen.thaw();
+    assert_eq!(matrix.defset().len(), len);
+    assert!(matrix.defset().get(roots[0]).is_some());
+  }
+}
@@ -0,0 +1,325 @@
+use std::fmt::Debug;
+
+use num_traits::Float;
+
+use crate::{
+  EvalContext, EvaluationPassDescriptor, FloatBinaryOp, FloatMapSignalDef,
+  PlannedEvaluation, Signal, SignalDef, SignalDefMap, SignalMap, SignalMatrix,
+  SignalSet, UnaryOp,
+};
+
+/// An instruction of a [`FusedFloatDef`]'s stack machine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instr<F> {
+  Const(F),
+  /// Push the value of the input at this index.
+  Load(usize),
+  Neg,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Pow,
+}
+
+/// A fused subgraph of [`FloatMapSignalDef`]s, evaluated as one small
+/// stack-machine program over the values of its inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusedFloatDef<F = f64> {
+  inputs:    Vec<Signal>,
+  code:      Vec<Instr<F>>,
+  max_depth: usize,
+  fused:     usize,
+}
+
+impl<F> FusedFloatDef<F> {
+  /// Get the signals the program reads, in load order.
+  pub fn inputs(&self) -> &[Signal] { &self.inputs }
+
+  /// Get the number of original signals the program computes, including the
+  /// one it stands for.
+  pub fn fused(&self) -> usize { self.fused }
+}
+
+impl<F: Float + Debug + Send + Sync> SignalDef for FusedFloatDef<F> {
+  type Value = F;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    out.extend(self.inputs.iter().copied());
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> F {
+    let mut stack: Vec<F> = Vec::with_capacity(self.max_depth);
+    for instr in self.code.iter() {
+      let value = match *instr {
+        Instr::Const(value) => value,
+        Instr::Load(i) => *ctx.values[&self.inputs[i]],
+        Instr::Neg => -stack.pop().unwrap(),
+        op => {
+          let b = stack.pop().unwrap();
+          let a = stack.pop().unwrap();
+          match op {
+            Instr::Add => a + b,
+            Instr::Sub => a - b,
+            Instr::Mul => a * b,
+            Instr::Div => a / b,
+            _ => a.powf(b),
+          }
+        }
+      };
+      stack.push(value);
+    }
+    stack.pop().unwrap()
+  }
+}
+
+/// A plan lowered by [`PlannedEvaluation::fuse`], with its own matrix of
+/// [`FusedFloatDef`]s under the original signal IDs.
+#[derive(Debug)]
+pub struct FusedGraph<F: Float + Debug + Send + Sync = f64> {
+  matrix: SignalMatrix<FusedFloatDef<F>>,
+  roots:  SignalSet,
+  passes: Vec<SignalSet>,
+}
+
+impl<F: Float + Debug + Send + Sync> FusedGraph<F> {
+  /// Get the matrix of fused definitions. Signals fused into another are
+  /// left as undefined placeholders.
+  pub fn matrix(&self) -> &SignalMatrix<FusedFloatDef<F>> { &self.matrix }
+
+  /// Plan the fused evaluation, which keeps the original plan's pass order
+  /// for the signals that weren't fused away.
+  pub fn plan(&self) -> PlannedEvaluation<'_, FusedFloatDef<F>> {
+    let passes = self
+      .passes
+      .iter()
+      .map(|targets| EvaluationPassDescriptor::new(targets.clone()))
+      .collect();
+    PlannedEvaluation::from_passes(&self.matrix, self.roots.clone(), passes)
+  }
+
+  /// Get the number of signals that were fused into a dependent.
+  pub fn fused_away(&self) -> usize {
+    self
+      .matrix
+      .defset()
+      .iter()
+      .map(|(_, def)| def.fused - 1)
+      .sum()
+  }
+}
+
+enum Task<F> {
+  Visit(Signal),
+  Emit(Instr<F>),
+}
+
+impl<F: Float + Debug + Send + Sync>
+  PlannedEvaluation<'_, FloatMapSignalDef<F>>
+{
+  /// Lower the plan into fused programs: every target used exactly once by
+  /// another target and not a root is inlined into its user, so deep chains
+  /// and trees of arithmetic evaluate as one program each instead of paying
+  /// scheduling and context gathering per node.
+  ///
+  /// Targets used more than once stay separate signals, so nothing is
+  /// computed twice. Signals outside the plan become program inputs, to be
+  /// provided in the value map as with the original plan.
+  pub fn fuse(&self) -> FusedGraph<F> {
+    let defset = self.matrix().defset();
+    let queued = self.all_queued_targets();
+    // count every operand, so a signal read twice by one user isn't inlined
+    // and then emitted once per read
+    let mut uses: SignalMap<usize> = SignalMap::default();
+    let mut deps = Vec::new();
+    for target in queued.iter() {
+      deps.clear();
+      defset.get(*target).unwrap().dependencies_into(&mut deps);
+      for dep in deps.iter() {
+        *uses.entry(*dep).or_default() += 1;
+      }
+    }
+    let inlined = |s: Signal| {
+      queued.contains(&s)
+        && !self.root_targets().contains(&s)
+        && uses.get(&s) == Some(&1)
+    };
+
+    let mut fused = SignalDefMap::new();
+    let mut passes = Vec::new();
+    let mut next = 0;
+    let mut define = |fused: &mut SignalDefMap<_>, signal: Signal, def| {
+      while next <= signal.0 {
+        fused.declare();
+        next += 1;
+      }
+      fused.define(signal, def).unwrap();
+      if let Some(label) = defset.label(signal) {
+        fused.set_label(signal, label);
+      }
+    };
+    let mut heads: Vec<_> =
+      queued.iter().copied().filter(|s| !inlined(*s)).collect();
+    heads.sort_by_key(|s| s.0);
+    for head in heads {
+      define(&mut fused, head, self.compile(head, &inlined));
+    }
+    let kept: SignalSet = fused.iter().map(|(s, _)| s).collect();
+    // sequential segments come apart into a pass per target, as the kept
+    // targets in a segment may still depend on each other
+    for stage in self.passes().iter().flat_map(|pass| pass.stages()) {
+      let targets: SignalSet =
+        stage.iter().filter(|s| kept.contains(s)).copied().collect();
+      if !targets.is_empty() {
+        passes.push(targets);
+      }
+    }
+
+    FusedGraph {
+      matrix: SignalMatrix::new(fused),
+      roots: self.root_targets().clone(),
+      passes,
+    }
+  }
+
+  /// Compile the program computing `head`, inlining the signals `inlined`
+  /// accepts.
+  fn compile(
+    &self,
+    head: Signal,
+    inlined: &impl Fn(Signal) -> bool,
+  ) -> FusedFloatDef<F> {
+    let defset = self.matrix().defset();
+    let mut program = FusedFloatDef {
+      inputs:    Vec::new(),
+      code:      Vec::new(),
+      max_depth: 0,
+      fused:     0,
+    };
+    let mut depth = 0;
+    let mut tasks = vec![Task::Visit(head)];
+    while let Some(task) = tasks.pop() {
+      let instr = match task {
+        Task::Emit(instr) => instr,
+        Task::Visit(signal) if signal != head && !inlined(signal) => {
+          let index = match program.inputs.iter().position(|s| *s == signal) {
+            Some(index) => index,
+            None => {
+              program.inputs.push(signal);
+              program.inputs.len() - 1
+            }
+          };
+          Instr::Load(index)
+        }
+        Task::Visit(signal) => {
+          program.fused += 1;
+          match defset.get(signal).unwrap() {
+            FloatMapSignalDef::Constant(value) => Instr::Const(*value),
+            FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => {
+              tasks.extend([Task::Emit(Instr::Neg), Task::Visit(*a)]);
+              continue;
+            }
+            FloatMapSignalDef::BinaryOp(op) => {
+              let (instr, a, b) = match op {
+                FloatBinaryOp::Add(a, b) => (Instr::Add, a, b),
+                FloatBinaryOp::Sub(a, b) => (Instr::Sub, a, b),
+                FloatBinaryOp::Mul(a, b) => (Instr::Mul, a, b),
+                FloatBinaryOp::Div(a, b) => (Instr::Div, a, b),
+                FloatBinaryOp::Pow(a, b) => (Instr::Pow, a, b),
+              };
+              tasks.extend([
+                Task::Emit(instr),
+                Task::Visit(*b),
+                Task::Visit(*a),
+              ]);
+              continue;
+            }
+          }
+        }
+      };
+      depth = match instr {
+        Instr::Const(_) | Instr::Load(_) => depth + 1,
+        Instr::Neg => depth,
+        _ => depth - 1,
+      };
+      program.max_depth = program.max_depth.max(depth);
+      program.code.push(instr);
+    }
+    program
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap};
+
+  #[test]
+  fn test_fuse_chains() {
+    // a long chain alternating negation and addition of a shared input, fed
+    // into two roots
+    let mut defset = SignalDefMap::new();
+    let shared = defset.insert(FloatMapSignalDef::Constant(0.5));
+    let mut tail = defset.insert(FloatMapSignalDef::Constant(1.0));
+    for i in 0..1000 {
+      tail = defset.insert(match i % 2 {
+        0 => FloatMapSignalDef::UnaryOp(UnaryOp::Neg(tail)),
+        _ => FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(tail, shared)),
+      });
+    }
+    let square = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(tail, tail)));
+    let halved = defset.insert(FloatMapSignalDef::BinaryOp(
+      FloatBinaryOp::Div(tail, shared),
+    ));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [square, halved]);
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let fused = plan.fuse();
+    let fused_plan = fused.plan();
+    // the chain collapses into `tail`, which is used three times
+    assert_eq!(fused_plan.all_queued_targets().len(), 4);
+    assert_eq!(fused.fused_away(), 1000);
+    assert_eq!(fused.matrix().defset().get(tail).unwrap().inputs(), [
+      shared
+    ]);
+    assert!(fused_plan.passes().len() < plan.passes().len());
+    let values = fused_plan.run(EvaluationValueMap::new_empty(
+      fused_plan.all_queued_targets(),
+    ));
+    assert_eq!(values.get(square), expected.get(square));
+    assert_eq!(values.get(halved), expected.get(halved));
+  }
+
+  #[test]
+  fn test_fuse_repeated_operand() {
+    // every signal is read twice by its user, so inlining would double the
+    // program at every step
+    let mut defset = SignalDefMap::new();
+    let mut x = defset.insert(FloatMapSignalDef::Constant(1.0001));
+    for _ in 0..20 {
+      x = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(x, x)));
+    }
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [x]);
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let fused = plan.fuse();
+    assert_eq!(fused.fused_away(), 0);
+    let code: usize = fused
+      .matrix()
+      .defset()
+      .iter()
+      .map(|(_, def)| def.code.len())
+      .sum();
+    assert!(code <= 3 * 21);
+    let fused_plan = fused.plan();
+    let values = fused_plan.run(EvaluationValueMap::new_empty(
+      fused_plan.all_queued_targets(),
+    ));
+    assert_eq!(values.get(x), expected.get(x));
+  }
+}
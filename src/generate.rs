@@ -0,0 +1,260 @@
+//! Deterministic, seeded graph generators for stress-testing [`SignalDef`]
+//! implementations and writing reproducible performance reports.
+//!
+//! A [`GraphGenerator`] decides the shape of a graph (which nodes depend on
+//! which), and a caller-supplied closure decides what each node is.
+
+use crate::{
+  FloatBinaryOp, FloatMapSignalDef, Signal, SignalDef, SignalDefMap, UnaryOp,
+};
+
+/// A small, seeded pseudo-random number generator (SplitMix64).
+///
+/// Output is stable across platforms and releases, so a seed fully
+/// reproduces a generated graph.
+#[derive(Debug, Clone)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  /// Create a new generator from the given seed.
+  pub fn new(seed: u64) -> Self { Rng { state: seed } }
+
+  /// Get the next 64 random bits.
+  pub fn next_u64(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  /// Get a random float in `[0, 1)`.
+  pub fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// Get a random integer in `[0, n)`. `n` must be non-zero.
+  pub fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+
+  /// Get a random integer in `[min, max]`.
+  pub fn between(&mut self, min: usize, max: usize) -> usize {
+    min + self.below(max.saturating_sub(min) + 1)
+  }
+}
+
+/// The shape of a generated graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphShape {
+  /// A reduction tree over `leaves` inputs, where each node combines up to
+  /// `arity` nodes of the level below. Has a single root.
+  Tree { leaves: usize, arity: usize },
+  /// A single chain of `length` nodes, each depending on the previous one.
+  Chain { length: usize },
+  /// One source node with `width` independent consumers, each of which is a
+  /// root.
+  FanOut { width: usize },
+  /// `layers` layers of `width` nodes each. Nodes outside the first layer
+  /// depend on between `min_fan_in` and `max_fan_in` distinct nodes chosen
+  /// from the previous `reach` layers.
+  LayeredDag {
+    layers:     usize,
+    width:      usize,
+    min_fan_in: usize,
+    max_fan_in: usize,
+    reach:      usize,
+  },
+}
+
+/// Generates graphs of a given [`GraphShape`] from a seed.
+#[derive(Debug, Clone)]
+pub struct GraphGenerator {
+  shape: GraphShape,
+  seed:  u64,
+}
+
+impl GraphGenerator {
+  /// Create a new generator for the given shape, with a seed of 0.
+  pub fn new(shape: GraphShape) -> Self { GraphGenerator { shape, seed: 0 } }
+
+  /// Set the seed.
+  pub fn with_seed(mut self, seed: u64) -> Self {
+    self.seed = seed;
+    self
+  }
+
+  /// Get the shape.
+  pub fn shape(&self) -> &GraphShape { &self.shape }
+
+  /// Generate the graph into `defset`, calling `make` with each node's
+  /// dependencies (in dependency order) to get its definition. Returns the
+  /// roots: every generated node that no other generated node depends on.
+  pub fn build<T: SignalDef>(
+    &self,
+    defset: &mut SignalDefMap<T>,
+    mut make: impl FnMut(&[Signal], &mut Rng) -> T,
+  ) -> Vec<Signal> {
+    self.build_with(defset, |defset, deps, rng| {
+      let def = make(deps, rng);
+      defset.insert(def)
+    })
+  }
+
+  /// Like [`build`](Self::build), but `make` inserts the node itself and
+  /// returns its handle, so it may insert helper nodes along the way.
+  pub fn build_with<T: SignalDef>(
+    &self,
+    defset: &mut SignalDefMap<T>,
+    mut make: impl FnMut(&mut SignalDefMap<T>, &[Signal], &mut Rng) -> Signal,
+  ) -> Vec<Signal> {
+    let mut rng = Rng::new(self.seed);
+    // every generated node alongside whether something depends on it
+    let mut nodes: Vec<(Signal, bool)> = Vec::new();
+    let mut node = |defset: &mut SignalDefMap<T>,
+                    rng: &mut Rng,
+                    deps: &[usize],
+                    nodes: &mut Vec<(Signal, bool)>| {
+      let dep_signals: Vec<_> = deps.iter().map(|&d| nodes[d].0).collect();
+      for &d in deps {
+        nodes[d].1 = true;
+      }
+      let signal = make(defset, &dep_signals, rng);
+      nodes.push((signal, false));
+      nodes.len() - 1
+    };
+
+    match self.shape {
+      GraphShape::Tree { leaves, arity } => {
+        let arity = arity.max(2);
+        let mut level: Vec<_> = (0..leaves)
+          .map(|_| node(defset, &mut rng, &[], &mut nodes))
+          .collect();
+        while level.len() > 1 {
+          level = level
+            .chunks(arity)
+            .map(|chunk| node(defset, &mut rng, chunk, &mut nodes))
+            .collect();
+        }
+      }
+      GraphShape::Chain { length } => {
+        let mut last = None;
+        for _ in 0..length {
+          let deps: Vec<_> = last.into_iter().collect();
+          last = Some(node(defset, &mut rng, &deps, &mut nodes));
+        }
+      }
+      GraphShape::FanOut { width } => {
+        let source = node(defset, &mut rng, &[], &mut nodes);
+        for _ in 0..width {
+          node(defset, &mut rng, &[source], &mut nodes);
+        }
+      }
+      GraphShape::LayeredDag {
+        layers,
+        width,
+        min_fan_in,
+        max_fan_in,
+        reach,
+      } => {
+        let reach = reach.max(1);
+        let mut layer_starts = Vec::new();
+        for layer in 0..layers {
+          layer_starts.push(nodes.len());
+          let pool_start = layer_starts[layer.saturating_sub(reach)];
+          let pool_end = nodes.len();
+          for _ in 0..width {
+            let mut deps = Vec::new();
+            if layer > 0 {
+              // rejection sampling picks distinct deps without materializing
+              // the candidate pool for every node
+              let pool_len = pool_end - pool_start;
+              let fan_in = rng.between(min_fan_in, max_fan_in).min(pool_len);
+              let mut picked: Vec<usize> = Vec::with_capacity(fan_in);
+              while picked.len() < fan_in {
+                let candidate = pool_start + rng.below(pool_len);
+                if !picked.contains(&candidate) {
+                  picked.push(candidate);
+                }
+              }
+              picked.sort_unstable();
+              deps = picked;
+            }
+            node(defset, &mut rng, &deps, &mut nodes);
+          }
+        }
+      }
+    }
+
+    nodes
+      .into_iter()
+      .filter(|(_, has_dependents)| !has_dependents)
+      .map(|(signal, _)| signal)
+      .collect()
+  }
+
+  /// Generate a graph of [`FloatMapSignalDef`]s. Inputs are random constants
+  /// in `[0, 1)`, single-dependency nodes negate, and wider nodes combine
+  /// their dependencies with random `Add`, `Sub` and `Mul` operations.
+  pub fn build_float(
+    &self,
+    defset: &mut SignalDefMap<FloatMapSignalDef>,
+  ) -> Vec<Signal> {
+    self.build_with(defset, |defset, deps, rng| match deps {
+      [] => defset.insert(FloatMapSignalDef::Constant(rng.next_f64())),
+      [a] => defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(*a))),
+      [first, rest @ ..] => rest.iter().fold(*first, |acc, dep| {
+        let op = match rng.below(3) {
+          0 => FloatBinaryOp::Add(acc, *dep),
+          1 => FloatBinaryOp::Sub(acc, *dep),
+          _ => FloatBinaryOp::Mul(acc, *dep),
+        };
+        defset.insert(FloatMapSignalDef::BinaryOp(op))
+      }),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn layered() -> GraphGenerator {
+    GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     6,
+      width:      20,
+      min_fan_in: 1,
+      max_fan_in: 4,
+      reach:      2,
+    })
+  }
+
+  #[test]
+  fn test_generation_is_deterministic() {
+    let render = |seed| {
+      let mut defset = SignalDefMap::new();
+      let roots = layered().with_seed(seed).build_float(&mut defset);
+      format!("{roots:?} {defset:?}")
+    };
+    assert_eq!(render(7), render(7));
+    assert_ne!(render(7), render(8));
+  }
+
+  #[test]
+  fn test_shapes() {
+    let mut defset = SignalDefMap::<FloatMapSignalDef>::new();
+    let tree = GraphGenerator::new(GraphShape::Tree {
+      leaves: 9,
+      arity:  3,
+    });
+    assert_eq!(tree.build_float(&mut defset).len(), 1);
+
+    let chain = GraphGenerator::new(GraphShape::Chain { length: 10 });
+    assert_eq!(chain.build_float(&mut defset).len(), 1);
+
+    let fan_out = GraphGenerator::new(GraphShape::FanOut { width: 5 });
+    assert_eq!(fan_out.build_float(&mut defset).len(), 5);
+  }
+}
@@ -0,0 +1,52 @@
+use std::{
+  collections::{HashMap, HashSet},
+  hash::{BuildHasherDefault, Hasher},
+};
+
+use crate::Signal;
+
+/// A fast, non-cryptographic hasher for [`Signal`] keys.
+///
+/// Signals are plain sequential IDs, so there is nothing for SipHash's DoS
+/// resistance to protect against. This uses the multiply-rotate scheme from
+/// FxHash, which is a single multiply per key.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SignalHasher {
+  hash: u64,
+}
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl SignalHasher {
+  #[inline]
+  fn add_to_hash(&mut self, word: u64) {
+    self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+  }
+}
+
+impl Hasher for SignalHasher {
+  #[inline]
+  fn write(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+      let mut word = [0; 8];
+      word[..chunk.len()].copy_from_slice(chunk);
+      self.add_to_hash(u64::from_le_bytes(word));
+    }
+  }
+
+  #[inline]
+  fn write_u64(&mut self, n: u64) { self.add_to_hash(n); }
+
+  #[inline]
+  fn write_usize(&mut self, n: usize) { self.add_to_hash(n as u64); }
+
+  #[inline]
+  fn finish(&self) -> u64 { self.hash }
+}
+
+/// A [`BuildHasher`](std::hash::BuildHasher) for [`SignalHasher`].
+pub type BuildSignalHasher = BuildHasherDefault<SignalHasher>;
+/// A hash map keyed by [`Signal`], using [`SignalHasher`].
+pub type SignalMap<V> = HashMap<Signal, V, BuildSignalHasher>;
+/// A hash set of [`Signal`]s, using [`SignalHasher`].
+pub type SignalSet = HashSet<Signal, BuildSignalHasher>;
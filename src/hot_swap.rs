@@ -0,0 +1,148 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+  ContentHash, EvaluationValueMap, Signal, SignalDef, SignalDefMap,
+  SignalMatrix,
+};
+
+/// A [`SignalMatrix`] that can be atomically replaced by a new version while
+/// evaluations are in flight.
+///
+/// Runs take a snapshot with [`current`](Self::current) and keep evaluating
+/// against it, so a swap only affects runs that start afterwards.
+#[derive(Debug)]
+pub struct HotMatrix<T: SignalDef> {
+  current: RwLock<(u64, Arc<SignalMatrix<T>>)>,
+}
+
+impl<T: SignalDef> HotMatrix<T> {
+  /// Create a hot matrix starting at version 0.
+  pub fn new(matrix: impl Into<Arc<SignalMatrix<T>>>) -> Self {
+    HotMatrix {
+      current: RwLock::new((0, matrix.into())),
+    }
+  }
+
+  /// Get the current version of the matrix.
+  pub fn current(&self) -> Arc<SignalMatrix<T>> {
+    self.current.read().unwrap().1.clone()
+  }
+
+  /// Get the current version number, starting at 0 and increasing by one per
+  /// swap.
+  pub fn version(&self) -> u64 { self.current.read().unwrap().0 }
+
+  /// Replace the matrix with a new version, returning the old one.
+  pub fn swap(
+    &self,
+    matrix: impl Into<Arc<SignalMatrix<T>>>,
+  ) -> Arc<SignalMatrix<T>> {
+    let mut current = self.current.write().unwrap();
+    let version = current.0 + 1;
+    std::mem::replace(&mut *current, (version, matrix.into())).1
+  }
+}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Find the signal in this map that carries over the given signal from
+  /// another version, by [external ID](SignalDefMap::external_id) if it has
+  /// one and by label otherwise. Signals with neither never carry over.
+  pub fn carry_over(
+    &self,
+    from: &SignalDefMap<T>,
+    signal: Signal,
+  ) -> Option<Signal> {
+    match from.external_id(signal) {
+      Some(id) => self.lookup_external_id(id.as_str()),
+      None => from.label(signal).and_then(|label| self.lookup(label)),
+    }
+  }
+}
+
+impl<T: SignalDef + ContentHash> EvaluationValueMap<T>
+where
+  T::Value: ContentHash,
+{
+  /// Move the values of every signal that carries over from one version of a
+  /// definition map to another (see [`SignalDefMap::carry_over`]) into a new
+  /// value map, as long as both versions compute it the same way: its
+  /// definition and everything upstream of it have the same
+  /// [content key](SignalMatrix::content_keys). Values of signals that don't
+  /// carry over, or whose definitions changed, are dropped to be evaluated
+  /// again. Signals without a definition in either version are inputs, and
+  /// carry over as they are.
+  pub fn migrate(self, from: &SignalDefMap<T>, to: &SignalDefMap<T>) -> Self {
+    let carried: Vec<_> = self
+      .values
+      .into_iter()
+      .filter_map(|(signal, value)| {
+        Some((signal, to.carry_over(from, signal)?, value?))
+      })
+      .collect();
+    let empty = EvaluationValueMap::new_empty(Default::default());
+    let before = from.content_keys(carried.iter().map(|(s, ..)| *s), &empty);
+    let after = to.content_keys(carried.iter().map(|(_, s, _)| *s), &empty);
+
+    let mut migrated = EvaluationValueMap::new_empty(Default::default());
+    for (old, new, value) in carried {
+      if before.get(&old) == after.get(&new) {
+        migrated.insert(new, value);
+      }
+    }
+    migrated
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, FloatBinaryOp, FloatMapSignalDef, UnaryOp};
+
+  #[test]
+  fn test_hot_swap() {
+    let mut v1 = SignalDefMap::new();
+    let base = v1.insert_labeled("base", FloatMapSignalDef::Constant(2.0));
+    let rate = v1.insert_labeled("rate", FloatMapSignalDef::Constant(0.05));
+    let neg =
+      v1.insert_labeled("neg", FloatMapSignalDef::UnaryOp(UnaryOp::Neg(rate)));
+    let scaled = v1.insert_labeled(
+      "scaled",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(base, base)),
+    );
+    let hot = HotMatrix::new(SignalMatrix::new(v1));
+
+    // a run in flight keeps its snapshot across the swap
+    let old = hot.current();
+    let plan = old.plan_evaluation(&CustomPlanner::new(), [neg, scaled]);
+
+    let mut v2 = SignalDefMap::new();
+    let one = v2.insert(FloatMapSignalDef::Constant(1.0));
+    let rate2 = v2.insert_labeled("rate", FloatMapSignalDef::Constant(0.07));
+    let neg2 =
+      v2.insert_labeled("neg", FloatMapSignalDef::UnaryOp(UnaryOp::Neg(rate2)));
+    let base2 = v2.insert_labeled("base", FloatMapSignalDef::Constant(2.0));
+    let scaled2 = v2.insert_labeled(
+      "scaled",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(base2, base2)),
+    );
+    v2.insert_labeled(
+      "growth",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(one, rate2)),
+    );
+    let swapped = hot.swap(SignalMatrix::new(v2));
+    assert!(Arc::ptr_eq(&swapped, &old));
+    assert_eq!(hot.version(), 1);
+
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(neg), Some(&-0.05));
+
+    // values only carry over if nothing they're computed from changed
+    let new = hot.current();
+    assert_eq!(new.defset().carry_over(old.defset(), neg), Some(neg2));
+    let migrated = values.migrate(old.defset(), new.defset());
+    assert_eq!(migrated.get(rate2), None);
+    assert_eq!(migrated.get(neg2), None);
+    assert_eq!(migrated.get(base2), Some(&2.0));
+    assert_eq!(migrated.get(scaled2), Some(&4.0));
+  }
+}
@@ -0,0 +1,330 @@
+use std::fmt;
+
+use cranelift_codegen::{
+  ir::{types, AbiParam, InstBuilder, MemFlags},
+  settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+use crate::{
+  EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef, PlannedEvaluation,
+  Signal, SignalSet, UnaryOp,
+};
+
+/// An error compiling a plan with [`PlannedEvaluation::compile_jit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitError(String);
+
+impl fmt::Display for JitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "failed to compile plan: {}", self.0)
+  }
+}
+
+impl std::error::Error for JitError {}
+
+/// A value map passed to [`JitPlan::run`] is missing one of the plan's
+/// [inputs](JitPlan::inputs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingJitInput {
+  pub signal: Signal,
+}
+
+impl fmt::Display for MissingJitInput {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "missing value for input {} of the compiled plan",
+      self.signal
+    )
+  }
+}
+
+impl std::error::Error for MissingJitInput {}
+
+fn jit_error(error: impl fmt::Display) -> JitError {
+  JitError(error.to_string())
+}
+
+extern "C" fn powf(base: f64, exponent: f64) -> f64 { base.powf(exponent) }
+
+type Compiled = extern "C" fn(*mut f64);
+
+/// A plan compiled to one native function over a flat buffer of values
+/// indexed by signal ID, from [`PlannedEvaluation::compile_jit`].
+pub struct JitPlan {
+  module:             Option<JITModule>,
+  function:           Compiled,
+  slots:              usize,
+  inputs:             Vec<Signal>,
+  targets:            Vec<Signal>,
+  root_targets:       SignalSet,
+  free_intermediates: bool,
+}
+
+impl JitPlan {
+  /// Get the signals the compiled code reads but doesn't compute, which the
+  /// value map passed to [`run`](Self::run) must provide.
+  pub fn inputs(&self) -> &[Signal] { &self.inputs }
+
+  /// Evaluate the compiled plan on the calling thread, like
+  /// [`PlannedEvaluation::run`].
+  ///
+  /// Fails if the value map is missing an [input](Self::inputs).
+  pub fn run(
+    &self,
+    mut values: EvaluationValueMap<FloatMapSignalDef>,
+  ) -> Result<EvaluationValueMap<FloatMapSignalDef>, MissingJitInput> {
+    let mut buffer = vec![0.0; self.slots];
+    for input in self.inputs.iter() {
+      let value = values
+        .get(*input)
+        .ok_or(MissingJitInput { signal: *input })?;
+      buffer[input.index()] = *value;
+    }
+    (self.function)(buffer.as_mut_ptr());
+    values.begin_epoch();
+    for target in self.targets.iter() {
+      if !self.free_intermediates || self.root_targets.contains(target) {
+        values.insert_evaluated(*target, buffer[target.index()]);
+      }
+    }
+    Ok(values)
+  }
+}
+
+impl Drop for JitPlan {
+  fn drop(&mut self) {
+    if let Some(module) = self.module.take() {
+      // SAFETY: the compiled function is only reachable through `self`
+      unsafe { module.free_memory() };
+    }
+  }
+}
+
+impl fmt::Debug for JitPlan {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("JitPlan")
+      .field("inputs", &self.inputs.len())
+      .field("targets", &self.targets.len())
+      .finish()
+  }
+}
+
+impl PlannedEvaluation<'_, FloatMapSignalDef> {
+  /// Compile the plan's passes, in order, to native code with cranelift.
+  /// Every target becomes a few instructions loading its dependencies from
+  /// and storing its value to a flat buffer, with no scheduling or context
+  /// gathering at run time.
+  ///
+  /// Fails if cranelift doesn't support the host.
+  pub fn compile_jit(&self) -> Result<JitPlan, JitError> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(jit_error)?;
+    let isa = cranelift_native::builder()
+      .map_err(jit_error)?
+      .finish(settings::Flags::new(flags))
+      .map_err(jit_error)?;
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbol("matrix_powf", powf as *const u8);
+    let mut module = JITModule::new(builder);
+
+    let pointer = module.target_config().pointer_type();
+    let mut pow_signature = module.make_signature();
+    pow_signature.params.push(AbiParam::new(types::F64));
+    pow_signature.params.push(AbiParam::new(types::F64));
+    pow_signature.returns.push(AbiParam::new(types::F64));
+    let pow = module
+      .declare_function("matrix_powf", Linkage::Import, &pow_signature)
+      .map_err(jit_error)?;
+
+    let mut context = module.make_context();
+    context.func.signature.params.push(AbiParam::new(pointer));
+    let evaluate = module
+      .declare_function("evaluate", Linkage::Local, &context.func.signature)
+      .map_err(jit_error)?;
+
+    let defset = self.matrix().defset();
+    let mut targets = Vec::new();
+    let mut function_context = FunctionBuilderContext::new();
+    let mut b = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let pow = module.declare_func_in_func(pow, b.func);
+    let block = b.create_block();
+    b.append_block_params_for_function_params(block);
+    b.switch_to_block(block);
+    b.seal_block(block);
+    let base = b.block_params(block)[0];
+    let flags = MemFlags::trusted();
+    let slot = |b: &mut FunctionBuilder, signal: Signal| {
+      b.ins().iadd_imm(base, signal.index() as i64 * 8)
+    };
+    let load = |b: &mut FunctionBuilder, signal: Signal| {
+      let address = slot(b, signal);
+      b.ins().load(types::F64, flags, address, 0)
+    };
+    for pass in self.passes() {
+      for target in pass.ordered_targets() {
+        let value = match defset.get(target).unwrap() {
+          FloatMapSignalDef::Constant(value) => b.ins().f64const(*value),
+          FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => {
+            let a = load(&mut b, *a);
+            b.ins().fneg(a)
+          }
+          FloatMapSignalDef::BinaryOp(op) => match op {
+            FloatBinaryOp::Add(x, y) => {
+              let (x, y) = (load(&mut b, *x), load(&mut b, *y));
+              b.ins().fadd(x, y)
+            }
+            FloatBinaryOp::Sub(x, y) => {
+              let (x, y) = (load(&mut b, *x), load(&mut b, *y));
+              b.ins().fsub(x, y)
+            }
+            FloatBinaryOp::Mul(x, y) => {
+              let (x, y) = (load(&mut b, *x), load(&mut b, *y));
+              b.ins().fmul(x, y)
+            }
+            FloatBinaryOp::Div(x, y) => {
+              let (x, y) = (load(&mut b, *x), load(&mut b, *y));
+              b.ins().fdiv(x, y)
+            }
+            FloatBinaryOp::Pow(x, y) => {
+              let (x, y) = (load(&mut b, *x), load(&mut b, *y));
+              let call = b.ins().call(pow, &[x, y]);
+              b.inst_results(call)[0]
+            }
+          },
+        };
+        let address = slot(&mut b, target);
+        b.ins().store(flags, value, address, 0);
+        targets.push(target);
+      }
+    }
+    b.ins().return_(&[]);
+    b.finalize();
+
+    module
+      .define_function(evaluate, &mut context)
+      .map_err(jit_error)?;
+    module.clear_context(&mut context);
+    module.finalize_definitions().map_err(jit_error)?;
+    let code = module.get_finalized_function(evaluate);
+    // SAFETY: the function was declared with this signature, and `run` passes
+    // a buffer covering every signal ID it reads or writes
+    let function = unsafe { std::mem::transmute::<*const u8, Compiled>(code) };
+
+    // only what the compiled code loads, which for a trimmed plan is less
+    // than everything below its roots
+    let queued = self.all_queued_targets();
+    let graph = self.matrix().dependency_graph();
+    let mut inputs: Vec<_> = queued
+      .iter()
+      .flat_map(|target| graph.dependencies(*target).iter().copied())
+      .filter(|s| !queued.contains(s))
+      .collect();
+    inputs.sort_by_key(|s| s.0);
+    inputs.dedup();
+    Ok(JitPlan {
+      module: Some(module),
+      function,
+      slots: self.matrix().defset().id_space(),
+      inputs,
+      targets,
+      root_targets: self.root_targets().clone(),
+      free_intermediates: self.free_intermediates(),
+    })
+  }
+
+  /// Compile the plan with [`compile_jit`](Self::compile_jit) and run it,
+  /// falling back to [`run`](Self::run) if it can't be compiled.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value map is missing a value the plan reads, like
+  /// [`run`](Self::run).
+  pub fn run_jit(
+    &self,
+    values: EvaluationValueMap<FloatMapSignalDef>,
+  ) -> EvaluationValueMap<FloatMapSignalDef> {
+    match self.compile_jit() {
+      Ok(compiled) => compiled.run(values).unwrap_or_else(|e| panic!("{e}")),
+      Err(error) => {
+        tracing::warn!(%error, "falling back to the executor");
+        self.run(values)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_jit_matches_executor() {
+    let mut defset = SignalDefMap::new();
+    let mut roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     6,
+      width:      40,
+      min_fan_in: 1,
+      max_fan_in: 3,
+      reach:      2,
+    })
+    .build_float(&mut defset);
+    let base = defset.insert(FloatMapSignalDef::Constant(1.5));
+    let input = defset.insert(FloatMapSignalDef::Constant(2.0));
+    roots.push(
+      defset
+        .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(base, input))),
+    );
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let compiled = plan.compile_jit().unwrap();
+    assert!(compiled.inputs().is_empty());
+    let values = compiled
+      .run(EvaluationValueMap::new_empty(plan.all_queued_targets()))
+      .unwrap();
+    for root in roots.iter() {
+      assert_eq!(values.get(*root), expected.get(*root));
+    }
+    assert_eq!(values.get(*roots.last().unwrap()), Some(&2.25));
+
+    // signals outside the plan are read from the value map
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [*roots.last().unwrap()])
+      .skip_targets([input]);
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(input, 3.0);
+    let values = plan.run_jit(values);
+    assert_eq!(values.get(*roots.last().unwrap()), Some(&3.375));
+
+    // only direct reads are inputs, not whatever sits below them
+    let mut defset = SignalDefMap::new();
+    let x = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let y = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(x)));
+    let z = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(y)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [z])
+      .skip_targets([x, y]);
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(y, 4.0);
+    let compiled = plan.compile_jit().unwrap();
+    assert_eq!(compiled.inputs(), [y]);
+    assert_eq!(
+      compiled
+        .run(EvaluationValueMap::new_empty(plan.all_queued_targets()))
+        .unwrap_err(),
+      MissingJitInput { signal: y }
+    );
+    assert_eq!(compiled.run(values).unwrap().get(z), Some(&-4.0));
+  }
+}
@@ -1,65 +1,480 @@
+#[cfg(feature = "affinity")]
+mod affinity;
+mod attribution;
+mod audit;
+mod batch;
+mod budget;
+mod cache;
+mod call;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod components;
+mod condense;
+mod condition;
+#[cfg(feature = "config")]
+pub mod config;
+mod contract;
+mod cross_check;
+mod csr;
+#[cfg(all(unix, feature = "daemon"))]
+pub mod daemon;
+mod declare;
+mod defaults;
+mod dependents_planner;
+mod deprecate;
+pub mod distributed;
+mod dominators;
+mod dot;
+mod dry_run;
+#[cfg(feature = "egg")]
+mod egraph;
+mod engine;
+mod epoch;
 mod eval;
+mod example_decimal;
 mod example_f64;
+mod example_i64;
+mod external_id;
+mod extract;
+mod fair;
+mod freeze;
+mod fuse;
+pub mod generate;
+mod hash;
+mod hot_swap;
+#[cfg(feature = "jit")]
+mod jit;
+mod limits;
+mod lint;
+mod memory_planner;
+mod middleware;
+mod namespace;
+mod nodes;
+mod overrides;
+mod par;
+mod partition;
+mod pipelines;
+mod poison;
+#[cfg(feature = "portable")]
+pub mod portable;
+mod profile;
+mod ready_queue;
+mod registry;
+mod replay;
+mod rewrite;
+mod rounding;
+mod run_options;
+mod sampled;
+mod segment;
+mod sensitivity;
+#[cfg(feature = "server")]
+pub mod server;
+mod shadow;
+mod simulation;
+mod siphash;
+#[cfg(feature = "sled")]
+mod sled_store;
+mod slots;
+mod snapshot;
+mod stats;
+mod step;
+mod store;
+mod strict;
+mod summation;
+mod tags;
+mod template;
+#[cfg(feature = "ndarray")]
+mod tensor;
+mod test_support;
+mod timeline;
+mod traverse;
+mod units;
+mod validate;
+mod visibility;
+mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use std::{
-  collections::{HashMap, HashSet},
-  fmt::Debug,
+  collections::BTreeMap,
+  fmt::{self, Debug},
+  sync::OnceLock,
 };
 
+#[cfg(feature = "affinity")]
+pub use affinity::*;
+pub use attribution::*;
+pub use audit::*;
+pub use batch::*;
+pub use budget::*;
+pub use cache::*;
+pub use call::*;
+#[cfg(feature = "arrow")]
+pub use columnar::*;
+pub use components::*;
+pub use condense::*;
+pub use condition::*;
+pub use contract::*;
+pub use cross_check::*;
+pub use declare::*;
+pub use defaults::*;
+pub use dependents_planner::*;
+pub use deprecate::*;
+pub use dominators::*;
+pub use dry_run::*;
+#[cfg(feature = "egg")]
+pub use egraph::*;
+pub use engine::*;
 pub use eval::*;
+pub use example_decimal::*;
 pub use example_f64::*;
-use tracing::instrument;
+pub use example_i64::*;
+pub use external_id::*;
+pub use fair::*;
+pub use freeze::*;
+pub use fuse::*;
+pub use hash::*;
+pub use hot_swap::*;
+#[cfg(feature = "jit")]
+pub use jit::*;
+pub use lint::*;
+pub use memory_planner::*;
+pub use middleware::*;
+pub use namespace::*;
+pub use nodes::*;
+pub use partition::*;
+pub use poison::*;
+pub use profile::*;
+pub use registry::*;
+pub use replay::*;
+pub use rewrite::*;
+pub use rounding::*;
+pub use run_options::*;
+pub use sampled::*;
+pub use sensitivity::*;
+pub use shadow::*;
+pub use simulation::*;
+#[cfg(feature = "sled")]
+pub use sled_store::*;
+pub use stats::*;
+pub use step::*;
+pub use store::*;
+pub use strict::*;
+pub use summation::*;
+pub use template::*;
+#[cfg(feature = "ndarray")]
+pub use tensor::*;
+pub use test_support::*;
+pub use traverse::*;
+pub use units::*;
+pub use validate::*;
+pub use visibility::*;
+pub use visit::*;
+
+use self::{
+  csr::DependencyGraph,
+  freeze::{DenseDefs, FrozenLayout},
+};
 
 /// A map of signal definitions.
 #[derive(Debug, Default)]
 pub struct SignalDefMap<T: SignalDef> {
-  map:     HashMap<Signal, T>,
-  last_id: u64,
+  map:            SignalMap<T>,
+  labels:         SignalMap<String>,
+  by_label:       BTreeMap<String, Signal>,
+  tags:           BTreeMap<String, SignalSet>,
+  visibility:     SignalMap<Visibility>,
+  deprecated:     SignalMap<Deprecation>,
+  external_ids:   SignalMap<ExternalId>,
+  by_external_id: BTreeMap<ExternalId, Signal>,
+  declared:       SignalSet,
+  strict:         bool,
+  last_id:        u64,
+  /// The definitions in a flat array, moved out of `map` while the matrix
+  /// holding this map is frozen and can't edit it.
+  dense:          Option<DenseDefs<T>>,
 }
 
 impl<T: SignalDef> SignalDefMap<T> {
   pub fn new() -> Self {
     SignalDefMap {
-      map:     HashMap::new(),
-      last_id: 0,
+      map:            SignalMap::default(),
+      labels:         SignalMap::default(),
+      by_label:       BTreeMap::new(),
+      tags:           BTreeMap::new(),
+      visibility:     SignalMap::default(),
+      deprecated:     SignalMap::default(),
+      external_ids:   SignalMap::default(),
+      by_external_id: BTreeMap::new(),
+      declared:       SignalSet::default(),
+      strict:         false,
+      last_id:        0,
+      dense:          None,
     }
   }
 
+  /// Insert a signal definition.
+  ///
+  /// # Panics
+  ///
+  /// In [strict mode](Self::with_strict_dependencies), panics if the
+  /// definition depends on a signal that isn't in the map.
   pub fn insert(&mut self, def: T) -> Signal {
+    if self.strict {
+      self
+        .check_dependencies(&def)
+        .unwrap_or_else(|e| panic!("{e}"));
+    }
     let id = Signal(self.last_id);
     self.last_id += 1;
     self.map.insert(id, def);
     id
   }
 
-  pub fn get(&self, signal: Signal) -> Option<&T> { self.map.get(&signal) }
+  /// Insert a signal definition under the given label. If the label is
+  /// already taken, it moves to the new signal.
+  pub fn insert_labeled(&mut self, label: impl Into<String>, def: T) -> Signal {
+    let signal = self.insert(def);
+    self.set_label(signal, label);
+    signal
+  }
+
+  /// Replace the definition of an existing signal, returning the old one.
+  /// Returns `None` and does nothing if the signal doesn't exist.
+  ///
+  /// # Panics
+  ///
+  /// In [strict mode](Self::with_strict_dependencies), panics if the new
+  /// definition depends on a signal that isn't in the map.
+  pub fn replace(&mut self, signal: Signal, def: T) -> Option<T> {
+    if self.strict {
+      self
+        .check_dependencies(&def)
+        .unwrap_or_else(|e| panic!("{e}"));
+    }
+    let slot = self.map.get_mut(&signal)?;
+    Some(std::mem::replace(slot, def))
+  }
+
+  pub fn get(&self, signal: Signal) -> Option<&T> {
+    match &self.dense {
+      Some(dense) => dense.get(signal),
+      None => self.map.get(&signal),
+    }
+  }
+
+  /// Set the label of a signal, replacing any label it had before.
+  pub fn set_label(&mut self, signal: Signal, label: impl Into<String>) {
+    let label = label.into();
+    if let Some(old) = self.labels.insert(signal, label.clone()) {
+      self.by_label.remove(&old);
+    }
+    if let Some(previous_owner) = self.by_label.insert(label, signal) {
+      if previous_owner != signal {
+        self.labels.remove(&previous_owner);
+      }
+    }
+  }
+
+  /// Get the label of a signal, if it has one.
+  pub fn label(&self, signal: Signal) -> Option<&str> {
+    self.labels.get(&signal).map(String::as_str)
+  }
+
+  /// Get the label of a signal, or `#id` if it has none.
+  pub(crate) fn display_label(&self, signal: Signal) -> String {
+    self.display(signal).to_string()
+  }
+
+  /// Display a signal by its label, falling back to `#id`.
+  pub fn display(&self, signal: Signal) -> LabeledSignal<'_> {
+    LabeledSignal {
+      signal,
+      label: self.label(signal),
+    }
+  }
+
+  /// Find the signal with the given label.
+  pub fn lookup(&self, label: &str) -> Option<Signal> {
+    self.by_label.get(label).copied()
+  }
 
-  #[instrument]
-  fn dependency_registry(&self) -> HashMap<Signal, HashSet<Signal>> {
+  /// Iterate over every label and its signal, sorted by label.
+  pub fn labels(&self) -> impl Iterator<Item = (&str, Signal)> {
+    self
+      .by_label
+      .iter()
+      .map(|(label, signal)| (label.as_str(), *signal))
+  }
+
+  /// Iterate over all signals and their definitions, in no particular order.
+  pub fn iter(&self) -> impl Iterator<Item = (Signal, &T)> {
+    let dense = self.dense.iter().flat_map(DenseDefs::iter);
     self
       .map
       .iter()
-      .map(|(signal, def)| (*signal, def.dependencies()))
-      .collect()
+      .map(|(signal, def)| (*signal, def))
+      .chain(dense)
   }
+
+  /// Get the number of signal definitions.
+  pub fn len(&self) -> usize {
+    self.dense.as_ref().map_or(self.map.len(), DenseDefs::len)
+  }
+
+  /// Whether there are no signal definitions.
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+  /// Get the number of signal IDs handed out so far. Every signal's
+  /// [`index`](Signal::index) is below this.
+  fn id_space(&self) -> usize { self.last_id as usize }
 }
 
 /// The core graph type. Contains a signal map.
 #[derive(Debug)]
 pub struct SignalMatrix<T: SignalDef> {
-  defset: SignalDefMap<T>,
+  defset:     SignalDefMap<T>,
+  graph:      OnceLock<DependencyGraph>,
+  dependents: OnceLock<DependencyGraph>,
+  /// The precomputed levels and order, while the matrix is
+  /// [frozen](Self::freeze).
+  frozen:     Option<FrozenLayout>,
 }
 
 impl<T: SignalDef> SignalMatrix<T> {
   /// Create a new signal matrix with the given signal definition map.
-  pub fn new(defset: SignalDefMap<T>) -> Self { SignalMatrix { defset } }
+  pub fn new(defset: SignalDefMap<T>) -> Self {
+    SignalMatrix {
+      defset,
+      graph: OnceLock::new(),
+      dependents: OnceLock::new(),
+      frozen: None,
+    }
+  }
+
+  /// Get the signal definition map.
+  pub fn defset(&self) -> &SignalDefMap<T> { &self.defset }
+
+  /// Get mutable access to the signal definition map, for editing the graph.
+  /// Cached graph structure is rebuilt on next use.
+  pub fn defset_mut(&mut self) -> &mut SignalDefMap<T> {
+    self.graph = OnceLock::new();
+    self.dependents = OnceLock::new();
+    &mut self.defset
+  }
+
+  /// Replace the definition of an existing signal, returning the old one.
+  pub fn update(&mut self, signal: Signal, def: T) -> Option<T> {
+    self.defset_mut().replace(signal, def)
+  }
+
+  /// Get the CSR dependency graph, building it on first use.
+  fn dependency_graph(&self) -> &DependencyGraph {
+    self
+      .graph
+      .get_or_init(|| DependencyGraph::build(&self.defset))
+  }
+
+  /// Get the transposed dependency graph, whose rows hold each signal's
+  /// dependents instead of its dependencies.
+  fn dependents_graph(&self) -> &DependencyGraph {
+    self
+      .dependents
+      .get_or_init(|| self.dependency_graph().transpose())
+  }
+
+  /// Get the direct dependencies of a signal, deduplicated and sorted by ID.
+  pub fn direct_dependencies(&self, signal: Signal) -> &[Signal] {
+    let graph = self.dependency_graph();
+    if signal.index() < graph.len() {
+      graph.dependencies(signal)
+    } else {
+      &[]
+    }
+  }
+
+  /// Get the direct dependencies of a signal along with their roles, sorted
+  /// by ID. A dependency appears once for each of its roles, or once without
+  /// a role if it has none.
+  pub fn dependency_edges(
+    &self,
+    signal: Signal,
+  ) -> Vec<(Signal, Option<&'static str>)> {
+    let mut roles = Vec::new();
+    if let Some(def) = self.defset.get(signal) {
+      def.dependency_roles_into(&mut roles);
+    }
+    let mut edges = Vec::new();
+    for dep in self.direct_dependencies(signal) {
+      let before = edges.len();
+      for (_, role) in roles.iter().filter(|(s, _)| s == dep) {
+        if !edges[before..].contains(&(*dep, Some(*role))) {
+          edges.push((*dep, Some(*role)));
+        }
+      }
+      if edges.len() == before {
+        edges.push((*dep, None));
+      }
+    }
+    edges
+  }
+
+  /// Get the signals that directly depend on a signal, sorted by ID.
+  pub fn direct_dependents(&self, signal: Signal) -> &[Signal] {
+    let graph = self.dependents_graph();
+    if signal.index() < graph.len() {
+      graph.dependencies(signal)
+    } else {
+      &[]
+    }
+  }
+
+  /// Get the level of every signal: 0 for signals without dependencies, and
+  /// otherwise one more than the highest level among its dependencies.
+  /// Signals in the same level never depend on each other.
+  ///
+  /// Dependencies without a definition count as level 0. Signals on a
+  /// dependency cycle, or depending on one, have no level.
+  pub fn levels(&self) -> SignalMap<usize> {
+    let computed;
+    let levels = match self.frozen_levels() {
+      Some(levels) => levels,
+      None => {
+        computed = self.dependency_graph().levels();
+        &computed
+      }
+    };
+    self
+      .defset
+      .iter()
+      .filter_map(|(signal, _)| Some((signal, levels[signal.index()]?)))
+      .collect()
+  }
+
+  /// Get every signal the given signals transitively depend on.
+  pub fn dependencies_closure(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+  ) -> SignalSet {
+    self.dependency_graph().reachable_from(signals)
+  }
+
+  /// Get every signal that transitively depends on the given signals. These
+  /// are the signals whose values go stale when the given signals change.
+  pub fn dependents_closure(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+  ) -> SignalSet {
+    self.dependents_graph().reachable_from(signals)
+  }
 
   /// Build a [`PlannedEvaluation`] of the given root targets.
-  pub fn plan_evaluation<P: EvaluationPlanner>(
+  pub fn plan_evaluation<P: EvaluationPlanner<T> + ?Sized>(
     &self,
-    root_targets: HashSet<Signal>,
-  ) -> PlannedEvaluation<T> {
-    PlannedEvaluation::new::<P>(self, root_targets)
+    planner: &P,
+    root_targets: impl IntoIterator<Item = Signal>,
+  ) -> PlannedEvaluation<'_, T> {
+    PlannedEvaluation::new(self, planner, root_targets)
   }
 }
 
@@ -67,9 +482,161 @@ impl<T: SignalDef> SignalMatrix<T> {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Signal(u64);
 
+impl Signal {
+  /// Get this signal's ID as a dense index, for bitsets and flat arrays.
+  fn index(self) -> usize { self.0 as usize }
+
+  fn from_index(index: usize) -> Self { Signal(index as u64) }
+}
+
+impl fmt::Display for Signal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "#{}", self.0)
+  }
+}
+
+/// A signal displayed by its label, from [`SignalDefMap::display`].
+#[derive(Debug, Clone, Copy)]
+pub struct LabeledSignal<'a> {
+  signal: Signal,
+  label:  Option<&'a str>,
+}
+
+impl fmt::Display for LabeledSignal<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.label {
+      Some(label) => f.write_str(label),
+      None => fmt::Display::fmt(&self.signal, f),
+    }
+  }
+}
+
 /// Context given to an evaluator function. For providing dependencies.
 pub struct EvalContext<'c, T: SignalDef> {
-  values: HashMap<Signal, &'c T::Value>,
+  signal:    Signal,
+  values:    SignalMap<&'c T::Value>,
+  roles:     Vec<(Signal, &'static str)>,
+  summation: Option<Summation>,
+}
+
+impl<'c, T: SignalDef> EvalContext<'c, T> {
+  /// Create a context for evaluating `signal` with the given dependency
+  /// values, e.g. to test a definition's
+  /// [`evaluate`](SignalDef::evaluate) without planning a graph.
+  pub fn from_values(
+    signal: Signal,
+    values: impl IntoIterator<Item = (Signal, &'c T::Value)>,
+  ) -> Self {
+    EvalContext {
+      signal,
+      values: values.into_iter().collect(),
+      roles: Vec::new(),
+      summation: None,
+    }
+  }
+
+  /// Set the roles of the dependencies, as given by
+  /// [`SignalDef::dependency_roles_into`].
+  pub fn with_roles(
+    mut self,
+    roles: impl IntoIterator<Item = (Signal, &'static str)>,
+  ) -> Self {
+    self.roles = roles.into_iter().collect();
+    self
+  }
+
+  /// Sum dependencies in a fixed order, like a run with
+  /// [`RunOptions::with_reproducible_summation`].
+  pub fn with_summation(mut self, summation: Summation) -> Self {
+    self.summation = Some(summation);
+    self
+  }
+
+  /// Create a context for evaluating `def` as `signal`, with the roles it
+  /// gives its dependencies.
+  pub(crate) fn for_def(
+    signal: Signal,
+    def: &T,
+    values: impl IntoIterator<Item = (Signal, &'c T::Value)>,
+  ) -> Self {
+    let mut roles = Vec::new();
+    def.dependency_roles_into(&mut roles);
+    Self::from_values(signal, values).with_roles(roles)
+  }
+
+  /// Like [`for_def`](Self::for_def), but built in the buffers of a scratch
+  /// context instead of fresh allocations. Give the buffers back with
+  /// [`recycle`](Self::recycle).
+  pub(crate) fn for_def_in(
+    signal: Signal,
+    def: &T,
+    values: impl IntoIterator<Item = (Signal, &'c T::Value)>,
+    scratch: &mut ContextScratch<'c, T>,
+  ) -> Self {
+    let mut context = EvalContext {
+      signal,
+      values: std::mem::take(&mut scratch.values),
+      roles: std::mem::take(&mut scratch.roles),
+      summation: scratch.summation,
+    };
+    context.values.extend(values);
+    def.dependency_roles_into(&mut context.roles);
+    context
+  }
+
+  /// Clear the context and return its buffers to a scratch context.
+  pub(crate) fn recycle(mut self, scratch: &mut ContextScratch<'c, T>) {
+    self.values.clear();
+    self.roles.clear();
+    scratch.values = self.values;
+    scratch.roles = self.roles;
+  }
+
+  /// Get the signal being evaluated, e.g. to name it in an error value.
+  pub fn signal(&self) -> Signal { self.signal }
+
+  /// Get the fixed order dependencies are summed in, if the run has one.
+  pub fn summation(&self) -> Option<Summation> { self.summation }
+
+  /// Get the value of the given dependency.
+  pub fn get(&self, signal: Signal) -> Option<&'c T::Value> {
+    self.values.get(&signal).copied()
+  }
+
+  /// Get the value of the first dependency with the given role.
+  pub fn role(&self, role: &str) -> Option<&'c T::Value> {
+    let (signal, _) = self.roles.iter().find(|(_, r)| *r == role)?;
+    self.get(*signal)
+  }
+
+  /// Get the first role of the given dependency, if it has one.
+  pub fn role_of(&self, dependency: Signal) -> Option<&'static str> {
+    let (_, role) = self.roles.iter().find(|(s, _)| *s == dependency)?;
+    Some(role)
+  }
+}
+
+/// The buffers of an [`EvalContext`], kept by each worker between targets so
+/// evaluating millions of cheap signals doesn't allocate a map for each one.
+pub(crate) struct ContextScratch<'c, T: SignalDef> {
+  values:    SignalMap<&'c T::Value>,
+  roles:     Vec<(Signal, &'static str)>,
+  summation: Option<Summation>,
+}
+
+impl<T: SignalDef> ContextScratch<'_, T> {
+  /// Create empty buffers for contexts summing in the given order.
+  pub(crate) fn new(summation: Option<Summation>) -> Self {
+    ContextScratch {
+      values: SignalMap::default(),
+      roles: Vec::new(),
+      summation,
+    }
+  }
+}
+
+impl<T: SignalDef> Default for ContextScratch<'_, T> {
+  fn default() -> Self { Self::new(None) }
 }
 
 /// Trait for signal definitions.
@@ -77,8 +644,25 @@ pub trait SignalDef: Debug + Sync + Sized {
   /// The type of value that this signal definition evaluates to.
   type Value: Debug + Send + Sync + Sized;
 
-  /// Get the dependencies of this signal definition.
-  fn dependencies(&self) -> HashSet<Signal>;
+  /// Push the dependencies of this signal definition onto `out`. Duplicates
+  /// are allowed. This is what the planners and executor use, since it lets
+  /// them reuse one buffer instead of allocating a set per signal.
+  fn dependencies_into(&self, out: &mut Vec<Signal>);
+  /// Get the dependencies of this signal definition as a set.
+  fn dependencies(&self) -> SignalSet {
+    let mut deps = Vec::new();
+    self.dependencies_into(&mut deps);
+    deps.into_iter().collect()
+  }
+  /// Push the role of each dependency that has one onto `out`, e.g.
+  /// `"numerator"` and `"denominator"`, so defs with several inputs of one
+  /// type needn't rely on positional conventions. A dependency may appear
+  /// with several roles. Defaults to no roles.
+  fn dependency_roles_into(&self, _out: &mut Vec<(Signal, &'static str)>) {}
+  /// Get the class this signal counts against for
+  /// [`RunOptions::with_concurrency_limit`], e.g. `"http_fetch"`. Defaults to
+  /// no class, so the signal is never limited.
+  fn concurrency_class(&self) -> Option<&'static str> { None }
   /// Evaluate this signal definition with the given context.
   fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value;
 }
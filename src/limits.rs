@@ -0,0 +1,109 @@
+use std::{
+  collections::HashMap,
+  sync::{Condvar, Mutex},
+};
+
+use crate::RunOptions;
+
+/// A counting semaphore.
+#[derive(Debug)]
+struct Semaphore {
+  available: Mutex<usize>,
+  released:  Condvar,
+}
+
+/// A slot of a [`ConcurrencyLimits`] class, given back when dropped.
+pub(crate) struct Permit<'s>(&'s Semaphore);
+
+impl Drop for Permit<'_> {
+  fn drop(&mut self) {
+    *self.0.available.lock().unwrap() += 1;
+    self.0.released.notify_one();
+  }
+}
+
+/// The semaphores enforcing a run's
+/// [`with_concurrency_limit`](RunOptions::with_concurrency_limit)s.
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyLimits {
+  classes: HashMap<&'static str, Semaphore>,
+}
+
+impl ConcurrencyLimits {
+  pub(crate) fn new(options: &RunOptions) -> Self {
+    let classes = options
+      .concurrency_limits()
+      .iter()
+      .map(|(class, limit)| {
+        (*class, Semaphore {
+          available: Mutex::new(*limit),
+          released:  Condvar::new(),
+        })
+      })
+      .collect();
+    ConcurrencyLimits { classes }
+  }
+
+  /// Wait for a slot of the given class, if it is limited.
+  pub(crate) fn acquire(&self, class: Option<&str>) -> Option<Permit<'_>> {
+    let semaphore = self.classes.get(class?)?;
+    let mut available = semaphore.available.lock().unwrap();
+    while *available == 0 {
+      available = semaphore.released.wait(available).unwrap();
+    }
+    *available -= 1;
+    Some(Permit(semaphore))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+  };
+
+  use crate::{
+    CustomPlanner, EvalContext, EvaluationValueMap, RunOptions, Signal,
+    SignalDef, SignalDefMap, SignalMatrix,
+  };
+
+  static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+  static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+  #[derive(Debug)]
+  struct Fetch;
+
+  impl SignalDef for Fetch {
+    type Value = ();
+
+    fn dependencies_into(&self, _out: &mut Vec<Signal>) {}
+
+    fn concurrency_class(&self) -> Option<&'static str> { Some("fetch") }
+
+    fn evaluate(&self, _ctx: &EvalContext<Self>) {
+      let active = ACTIVE.fetch_add(1, Ordering::SeqCst) + 1;
+      PEAK.fetch_max(active, Ordering::SeqCst);
+      thread::sleep(Duration::from_millis(5));
+      ACTIVE.fetch_sub(1, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  fn test_concurrency_limit() {
+    let mut defset = SignalDefMap::new();
+    let fetches: Vec<_> = (0..16).map(|_| defset.insert(Fetch)).collect();
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), fetches.clone());
+    assert_eq!(plan.passes().len(), 1);
+
+    let options = RunOptions::new().with_concurrency_limit("fetch", 2);
+    let values = plan.run_with(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &options,
+    );
+    assert!(fetches.iter().all(|s| values.get(*s).is_some()));
+    assert!(PEAK.load(Ordering::SeqCst) <= 2);
+  }
+}
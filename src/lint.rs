@@ -0,0 +1,360 @@
+use std::fmt::{self, Debug};
+
+use num_traits::Float;
+
+use crate::{
+  FloatBinaryOp, FloatMapSignalDef, Signal, SignalDef, SignalMatrix, SignalSet,
+};
+
+/// What a [`Lint`] sees of the graph being linted.
+#[derive(Debug)]
+pub struct LintContext<'a, T: SignalDef> {
+  pub matrix: &'a SignalMatrix<T>,
+  /// The signals the graph is evaluated for. Signals nothing reaches from
+  /// them are unused.
+  pub roots:  &'a SignalSet,
+}
+
+impl<F: Float + Debug + Send + Sync> LintContext<'_, FloatMapSignalDef<F>> {
+  /// Get the value of a signal if it's a constant.
+  fn constant(&self, signal: Signal) -> Option<F> {
+    match self.matrix.defset().get(signal)? {
+      FloatMapSignalDef::Constant(value) => Some(*value),
+      _ => None,
+    }
+  }
+}
+
+/// A check run against every signal of a graph by a [`Linter`].
+pub trait Lint<T: SignalDef>: Send + Sync {
+  /// Get the name the lint's diagnostics are reported under, e.g.
+  /// `"unused_signal"`.
+  fn name(&self) -> &str;
+
+  /// Check one signal, returning a message describing the problem, if any.
+  fn check(
+    &self,
+    cx: &LintContext<'_, T>,
+    signal: Signal,
+    def: &T,
+  ) -> Option<String>;
+}
+
+/// A problem with a signal found by a [`Lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub lint:    String,
+  pub signal:  Signal,
+  /// The label of the signal, or `#id` if it has none.
+  pub label:   String,
+  pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {} [{}]", self.label, self.message, self.lint)
+  }
+}
+
+type CheckFn<T> =
+  Box<dyn Fn(&LintContext<'_, T>, Signal, &T) -> Option<String> + Send + Sync>;
+
+struct FnLint<T: SignalDef> {
+  name:  String,
+  check: CheckFn<T>,
+}
+
+impl<T: SignalDef> Lint<T> for FnLint<T> {
+  fn name(&self) -> &str { &self.name }
+
+  fn check(
+    &self,
+    cx: &LintContext<'_, T>,
+    signal: Signal,
+    def: &T,
+  ) -> Option<String> {
+    (self.check)(cx, signal, def)
+  }
+}
+
+/// A set of lints checked against a whole graph.
+pub struct Linter<T: SignalDef> {
+  lints: Vec<Box<dyn Lint<T>>>,
+}
+
+impl<T: SignalDef> Default for Linter<T> {
+  fn default() -> Self { Linter { lints: Vec::new() } }
+}
+
+impl<T: SignalDef> fmt::Debug for Linter<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_list()
+      .entries(self.lints.iter().map(|lint| lint.name()))
+      .finish()
+  }
+}
+
+impl<T: SignalDef> Linter<T> {
+  /// Create a linter with no lints.
+  pub fn new() -> Self { Self::default() }
+
+  /// Create a linter with the lints that apply to any graph:
+  /// [`UnusedSignal`] and [`ExcessiveFanIn`] with its default limit.
+  pub fn generic() -> Self {
+    Self::new()
+      .with_lint(UnusedSignal)
+      .with_lint(ExcessiveFanIn::default())
+  }
+
+  /// Add a lint.
+  pub fn with_lint(mut self, lint: impl Lint<T> + 'static) -> Self {
+    self.lints.push(Box::new(lint));
+    self
+  }
+
+  /// Add a lint from a closure, reported under the given name.
+  pub fn with_check(
+    self,
+    name: impl Into<String>,
+    check: impl Fn(&LintContext<'_, T>, Signal, &T) -> Option<String>
+      + Send
+      + Sync
+      + 'static,
+  ) -> Self
+  where
+    T: 'static,
+  {
+    self.with_lint(FnLint {
+      name:  name.into(),
+      check: Box::new(check),
+    })
+  }
+
+  /// Remove every lint with the given name.
+  pub fn allow(mut self, name: &str) -> Self {
+    self.lints.retain(|lint| lint.name() != name);
+    self
+  }
+
+  /// Check every signal of the graph, with the given roots, against every
+  /// lint. Diagnostics are ordered by signal ID, then by lint.
+  pub fn check(
+    &self,
+    matrix: &SignalMatrix<T>,
+    roots: impl IntoIterator<Item = Signal>,
+  ) -> Vec<Diagnostic> {
+    let roots = roots.into_iter().collect();
+    let cx = LintContext {
+      matrix,
+      roots: &roots,
+    };
+    let mut signals: Vec<_> = matrix.defset().iter().collect();
+    signals.sort_by_key(|(s, _)| s.0);
+
+    let mut diagnostics = Vec::new();
+    for (signal, def) in signals {
+      for lint in self.lints.iter() {
+        if let Some(message) = lint.check(&cx, signal, def) {
+          diagnostics.push(Diagnostic {
+            lint: lint.name().to_owned(),
+            signal,
+            label: matrix.defset().display(signal).to_string(),
+            message,
+          });
+        }
+      }
+    }
+    diagnostics
+  }
+}
+
+impl<F: Float + Debug + Send + Sync + 'static> Linter<FloatMapSignalDef<F>> {
+  /// Create a linter with the [`generic`](Self::generic) lints plus the float
+  /// lints: [`DivideByZero`] and [`IntegerPow`].
+  pub fn float() -> Self {
+    Self::generic()
+      .with_lint(DivideByZero)
+      .with_lint(IntegerPow::default())
+  }
+}
+
+/// Flags signals that aren't a root and that no root depends on, so they
+/// are never evaluated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnusedSignal;
+
+impl<T: SignalDef> Lint<T> for UnusedSignal {
+  fn name(&self) -> &str { "unused_signal" }
+
+  fn check(
+    &self,
+    cx: &LintContext<'_, T>,
+    signal: Signal,
+    _def: &T,
+  ) -> Option<String> {
+    if cx.roots.contains(&signal) {
+      return None;
+    }
+    let dependents = cx.matrix.dependents_closure([signal]);
+    let used = dependents.iter().any(|s| cx.roots.contains(s));
+    (!used).then(|| "no root depends on this signal".to_owned())
+  }
+}
+
+/// Flags signals with more distinct dependencies than a limit, which
+/// serialize a lot of work behind one target.
+#[derive(Debug, Clone, Copy)]
+pub struct ExcessiveFanIn {
+  max: usize,
+}
+
+impl ExcessiveFanIn {
+  /// Flag signals with more than `max` distinct dependencies.
+  pub fn new(max: usize) -> Self { ExcessiveFanIn { max } }
+}
+
+/// Flags more than 64 dependencies.
+impl Default for ExcessiveFanIn {
+  fn default() -> Self { ExcessiveFanIn::new(64) }
+}
+
+impl<T: SignalDef> Lint<T> for ExcessiveFanIn {
+  fn name(&self) -> &str { "excessive_fan_in" }
+
+  fn check(
+    &self,
+    _cx: &LintContext<'_, T>,
+    _signal: Signal,
+    def: &T,
+  ) -> Option<String> {
+    let fan_in = def.dependencies().len();
+    (fan_in > self.max).then(|| {
+      format!("{fan_in} dependencies, more than the {} allowed", self.max)
+    })
+  }
+}
+
+/// Flags float divisions by a constant zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DivideByZero;
+
+impl<F: Float + Debug + Send + Sync> Lint<FloatMapSignalDef<F>>
+  for DivideByZero
+{
+  fn name(&self) -> &str { "divide_by_zero" }
+
+  fn check(
+    &self,
+    cx: &LintContext<'_, FloatMapSignalDef<F>>,
+    _signal: Signal,
+    def: &FloatMapSignalDef<F>,
+  ) -> Option<String> {
+    let FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(_, b)) = def else {
+      return None;
+    };
+    let divisor = cx.constant(*b)?;
+    divisor.is_zero().then(|| {
+      format!(
+        "divides by {}, a constant zero",
+        cx.matrix.defset().display(*b)
+      )
+    })
+  }
+}
+
+/// Flags float powers with a small constant integer exponent, which are
+/// cheaper and more exact as repeated multiplication.
+#[derive(Debug, Clone, Copy)]
+pub struct IntegerPow {
+  max_exponent: u32,
+}
+
+impl IntegerPow {
+  /// Flag integer exponents from 2 up to `max_exponent`.
+  pub fn new(max_exponent: u32) -> Self { IntegerPow { max_exponent } }
+}
+
+/// Flags exponents up to 4.
+impl Default for IntegerPow {
+  fn default() -> Self { IntegerPow::new(4) }
+}
+
+impl<F: Float + Debug + Send + Sync> Lint<FloatMapSignalDef<F>> for IntegerPow {
+  fn name(&self) -> &str { "integer_pow" }
+
+  fn check(
+    &self,
+    cx: &LintContext<'_, FloatMapSignalDef<F>>,
+    _signal: Signal,
+    def: &FloatMapSignalDef<F>,
+  ) -> Option<String> {
+    let FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(_, b)) = def else {
+      return None;
+    };
+    let exponent = cx.constant(*b)?;
+    let n = exponent
+      .to_u32()
+      .filter(|n| F::from(*n) == Some(exponent))?;
+    (2..=self.max_exponent).contains(&n).then(|| {
+      format!(
+        "raises to the constant {n}, better as {} multiplications",
+        n - 1
+      )
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{SignalDefMap, UnaryOp};
+
+  #[test]
+  fn test_lints() {
+    let mut defset = SignalDefMap::new();
+    let x = defset.insert_labeled("x", FloatMapSignalDef::Constant(3.0));
+    let zero = defset.insert(FloatMapSignalDef::Constant(0.0));
+    let two = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let half = defset.insert(FloatMapSignalDef::Constant(0.5));
+    let ratio = defset.insert_labeled(
+      "ratio",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(x, zero)),
+    );
+    let square =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(x, two)));
+    let root =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(x, half)));
+    let stray = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(x)));
+    let matrix = SignalMatrix::new(defset);
+
+    let diagnostics = Linter::float().check(&matrix, [ratio, square, root]);
+    let found: Vec<_> = diagnostics
+      .iter()
+      .map(|d| (d.lint.as_str(), d.signal))
+      .collect();
+    assert_eq!(found, [
+      ("divide_by_zero", ratio),
+      ("integer_pow", square),
+      ("unused_signal", stray),
+    ]);
+    assert_eq!(
+      diagnostics[0].to_string(),
+      "ratio: divides by #1, a constant zero [divide_by_zero]"
+    );
+
+    // custom lints run alongside the built-ins, which can be allowed
+    let diagnostics = Linter::float()
+      .allow("unused_signal")
+      .with_lint(ExcessiveFanIn::new(1))
+      .with_check("labeled", |cx, signal, _| {
+        cx.matrix
+          .defset()
+          .label(signal)
+          .is_none()
+          .then(|| "has no label".to_owned())
+      })
+      .check(&matrix, [ratio, square, root]);
+    assert_eq!(diagnostics.len(), 3 + 2 + 6);
+    assert!(diagnostics.iter().all(|d| d.lint != "unused_signal"));
+  }
+}
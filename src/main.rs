@@ -1,68 +1,219 @@
+//! Benchmarks planning and evaluating a generated float graph.
+//!
+//! Prints one JSON object with the graph's size and the timings, so reports
+//! can be compared across machines and versions:
+//!
+//! ```text
+//! matrix --shape dag --size 200 --width 500 --planner memory --threads 4
+//! ```
+
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
 use matrix::{
-  CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+  generate::{GraphGenerator, GraphShape},
+  CustomPlanner, EvaluationPlanner, EvaluationValueMap, FloatBinaryOp,
+  FloatMapSignalDef, MemoryMinimizingPlanner, PassParallelism, RunOptions,
   SignalDefMap, SignalMatrix,
 };
 use tracing_chrome::ChromeLayerBuilder;
 use tracing_subscriber::prelude::*;
 
-fn main() {
-  let (chrome_layer, _guard) = ChromeLayerBuilder::new().build();
-  tracing_subscriber::registry().with(chrome_layer).init();
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Shape {
+  /// A reduction tree over `size` inputs.
+  Tree,
+  /// A chain of `size` nodes.
+  Chain,
+  /// One input with `size` consumers.
+  FanOut,
+  /// `size` layers of `width` nodes with random dependencies.
+  Dag,
+}
 
-  let mut defset = SignalDefMap::new();
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Ops {
+  /// Every operation is an addition.
+  Add,
+  /// Random additions, subtractions, multiplications and negations.
+  Mixed,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Planner {
+  Custom,
+  Memory,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Executor {
+  /// Evaluate each pass across threads.
+  Parallel,
+  /// Evaluate every pass on the calling thread.
+  Sequential,
+}
+
+/// Benchmark planning and evaluating a generated graph.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+  /// The shape of the graph.
+  #[arg(long, value_enum, default_value_t = Shape::Tree)]
+  shape:    Shape,
+  /// The size of the graph; its meaning depends on the shape.
+  #[arg(long, default_value_t = 10_000)]
+  size:     usize,
+  /// The arity of tree nodes.
+  #[arg(long, default_value_t = 2)]
+  arity:    usize,
+  /// The width of each DAG layer.
+  #[arg(long, default_value_t = 100)]
+  width:    usize,
+  /// The most dependencies of a DAG node.
+  #[arg(long, default_value_t = 4)]
+  fan_in:   usize,
+  /// The seed for random shapes and constants.
+  #[arg(long, default_value_t = 0)]
+  seed:     u64,
+  /// The operations the graph is built from.
+  #[arg(long, value_enum, default_value_t = Ops::Mixed)]
+  ops:      Ops,
+  /// The planner to plan the evaluation with.
+  #[arg(long, value_enum, default_value_t = Planner::Custom)]
+  planner:  Planner,
+  /// How passes are executed.
+  #[arg(long, value_enum, default_value_t = Executor::Parallel)]
+  executor: Executor,
+  /// The most threads a parallel pass may use; defaults to all of them.
+  #[arg(long)]
+  threads:  Option<usize>,
+  /// How many times to run the evaluation.
+  #[arg(long, default_value_t = 5)]
+  runs:     usize,
+  /// Write a Chrome trace of the run to the working directory.
+  #[arg(long)]
+  trace:    bool,
+}
 
-  // let a = defset.insert(FloatMapSignalDef::Constant(1.0));
-  // let b = defset.insert(FloatMapSignalDef::Constant(2.0));
-  // let c = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a,
-  // b))); let d = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(c)));
-  // let e = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(c,
-  // d)));
-
-  // create a symetric binary tree of addition operations
-  let depth: i32 = 100;
-  let mut last_level = (0..depth.pow(2))
-    .map(|i| defset.insert(FloatMapSignalDef::Constant(i as f64)))
-    .collect::<Vec<_>>();
-
-  for _ in 0..depth {
-    let mut new_level = Vec::new();
-    for (i, a) in last_level.iter().step_by(2).enumerate() {
-      let b = last_level.get(i * 2 + 1).unwrap_or(a);
-      new_level.push(
-        defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(*a, *b))),
-      );
+impl Args {
+  fn generator(&self) -> GraphGenerator {
+    let shape = match self.shape {
+      Shape::Tree => GraphShape::Tree {
+        leaves: self.size,
+        arity:  self.arity,
+      },
+      Shape::Chain => GraphShape::Chain { length: self.size },
+      Shape::FanOut => GraphShape::FanOut { width: self.size },
+      Shape::Dag => GraphShape::LayeredDag {
+        layers:     self.size,
+        width:      self.width,
+        min_fan_in: 1,
+        max_fan_in: self.fan_in,
+        reach:      2,
+      },
+    };
+    GraphGenerator::new(shape).with_seed(self.seed)
+  }
+
+  fn planner(&self) -> Box<dyn EvaluationPlanner<FloatMapSignalDef>> {
+    match self.planner {
+      Planner::Custom => Box::new(CustomPlanner::new()),
+      Planner::Memory => Box::new(MemoryMinimizingPlanner::new()),
     }
-    last_level = new_level;
   }
-  let root = last_level[0];
 
-  let root_targets = vec![root].into_iter().collect();
-  let matrix = SignalMatrix::new(defset);
+  fn run_options(&self) -> RunOptions {
+    let parallelism = match (self.executor, self.threads) {
+      (Executor::Sequential, _) => PassParallelism::Limited(1),
+      (Executor::Parallel, Some(threads)) => PassParallelism::Limited(threads),
+      (Executor::Parallel, None) => PassParallelism::Full,
+    };
+    RunOptions::new().with_parallelism_policy(move |_, _: &_| parallelism)
+  }
+}
+
+fn micros(duration: Duration) -> u128 { duration.as_micros() }
 
-  let now = std::time::Instant::now();
-  let planned_eval = matrix.plan_evaluation::<CustomPlanner>(root_targets);
-  println!("planning took {:?}", now.elapsed());
+/// The option's command-line name as a JSON string.
+fn quoted(value: impl ValueEnum) -> String {
+  format!("\"{}\"", value.to_possible_value().unwrap().get_name())
+}
 
-  println!(
-    "first pass size: {}",
-    planned_eval.passes().first().unwrap().targets().len()
-  );
-  // println!("passes:");
-  // planned_eval
-  //   .passes()
-  //   .iter()
-  //   .enumerate()
-  //   .for_each(|(i, pass)| {
-  //     println!("  {i}:\t{:?}", pass);
-  //   });
+fn main() {
+  let args = Args::parse();
+  let _guard = args.trace.then(|| {
+    let (chrome_layer, guard) = ChromeLayerBuilder::new().build();
+    tracing_subscriber::registry().with(chrome_layer).init();
+    guard
+  });
 
-  let values = EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+  let mut defset = SignalDefMap::new();
+  let generator = args.generator();
+  let now = Instant::now();
+  let roots = match args.ops {
+    Ops::Mixed => generator.build_float(&mut defset),
+    Ops::Add => {
+      let mut next_constant = 0.0;
+      generator.build_with(&mut defset, |defset, deps, _| match deps {
+        [] => {
+          next_constant += 1.0;
+          defset.insert(FloatMapSignalDef::Constant(next_constant - 1.0))
+        }
+        [a] => {
+          defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(*a, *a)))
+        }
+        [first, rest @ ..] => rest.iter().fold(*first, |acc, dep| {
+          defset
+            .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(acc, *dep)))
+        }),
+      })
+    }
+  };
+  let signals = defset.len();
+  let matrix = SignalMatrix::new(defset);
+  let build = now.elapsed();
 
-  let now = std::time::Instant::now();
-  let values = planned_eval.run(values);
-  println!("evaluation took {:?}", now.elapsed());
+  let now = Instant::now();
+  let planned_eval = matrix.plan_evaluation(&*args.planner(), roots.clone());
+  let plan = now.elapsed();
+  let widest = planned_eval.passes().iter().map(|p| p.width()).max();
 
-  // dbg!(&values);
+  let options = args.run_options();
+  let mut runs = Vec::with_capacity(args.runs);
+  for _ in 0..args.runs {
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let now = Instant::now();
+    let values = planned_eval.run_with(values, &options);
+    runs.push(now.elapsed());
+    assert!(roots.iter().all(|root| values.get(*root).is_some()));
+  }
+  let mut sorted = runs.clone();
+  sorted.sort_unstable();
+  let median = sorted.get(sorted.len() / 2).copied().unwrap_or_default();
 
-  println!("final answer: {}", values.get(root).unwrap());
+  let runs: Vec<_> = runs.iter().map(|run| micros(*run).to_string()).collect();
+  let fields = [
+    ("shape", quoted(args.shape)),
+    ("ops", quoted(args.ops)),
+    ("signals", signals.to_string()),
+    ("roots", roots.len().to_string()),
+    ("passes", planned_eval.passes().len().to_string()),
+    ("widest_pass", widest.unwrap_or(0).to_string()),
+    ("planner", quoted(args.planner)),
+    ("executor", quoted(args.executor)),
+    (
+      "threads",
+      args.threads.map_or("null".into(), |t| t.to_string()),
+    ),
+    ("build_us", micros(build).to_string()),
+    ("plan_us", micros(plan).to_string()),
+    ("eval_median_us", micros(median).to_string()),
+    ("eval_us", format!("[{}]", runs.join(","))),
+  ];
+  let fields: Vec<_> = fields
+    .iter()
+    .map(|(key, value)| format!("\"{key}\":{value}"))
+    .collect();
+  println!("{{{}}}", fields.join(","));
 }
@@ -0,0 +1,255 @@
+use std::{fmt, sync::Arc};
+
+use fixedbitset::FixedBitSet;
+
+use crate::{
+  csr::DependencyGraph, EvaluationPassDescriptor, EvaluationPlanner,
+  PlannedEvaluation, Signal, SignalDef, SignalMap, SignalMatrix, SignalSet,
+};
+
+type SizeHint = Arc<dyn Fn(Signal) -> usize + Send + Sync>;
+
+/// A planner that orders evaluation to keep as few intermediate values alive
+/// at once as possible, at the cost of producing many more (and narrower)
+/// passes than [`CustomPlanner`](crate::CustomPlanner).
+///
+/// Signals are scheduled in depth-first post-order from the root targets, so
+/// each subgraph is finished and folded into its consumer before the next one
+/// is started. Consecutive signals in that order share a pass when none of
+/// them depends on another. Plans produced by this planner free intermediates
+/// during [`run`](PlannedEvaluation::run).
+#[derive(Clone, Default)]
+pub struct MemoryMinimizingPlanner {
+  max_pass_size: Option<usize>,
+  memory_cap:    Option<(usize, SizeHint)>,
+}
+
+impl fmt::Debug for MemoryMinimizingPlanner {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("MemoryMinimizingPlanner")
+      .field("max_pass_size", &self.max_pass_size)
+      .field("memory_cap", &self.memory_cap.as_ref().map(|(cap, _)| cap))
+      .finish()
+  }
+}
+
+impl MemoryMinimizingPlanner {
+  /// Create a new memory-minimizing planner with no pass size limit.
+  pub fn new() -> Self { Self::default() }
+
+  /// Limit the number of targets in each pass. A limit of 1 yields a fully
+  /// sequential schedule with the lowest peak of live values.
+  pub fn with_max_pass_size(mut self, max_pass_size: usize) -> Self {
+    self.max_pass_size = Some(max_pass_size.max(1));
+    self
+  }
+
+  /// Keep the bytes of live values under a cap, with the given estimate of
+  /// each signal's value size. A pass is cut short when adding a target would
+  /// push the values alive during it over the cap, so the values it frees
+  /// are released before the next target is evaluated.
+  ///
+  /// The cap is best effort: a pass always gets at least one target, so a
+  /// cap that a single target's dependencies already exceed will be too.
+  pub fn with_memory_cap(
+    mut self,
+    max_bytes: usize,
+    size_hint: impl Fn(Signal) -> usize + Send + Sync + 'static,
+  ) -> Self {
+    self.memory_cap = Some((max_bytes, Arc::new(size_hint)));
+    self
+  }
+}
+
+impl<Def: SignalDef> EvaluationPlanner<Def> for MemoryMinimizingPlanner {
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def> {
+    let graph = matrix.dependency_graph();
+
+    // iterative post-order DFS, so deep chains don't overflow the stack
+    let order = tracing::info_span!("post_order").in_scope(|| {
+      let mut roots: Vec<_> = root_targets.iter().copied().collect();
+      roots.sort_by_key(|s| s.0);
+
+      let mut visited = FixedBitSet::with_capacity(graph.len());
+      let mut order = Vec::new();
+      for root in roots {
+        if visited.put(root.index()) {
+          continue;
+        }
+        let mut stack = vec![(root, graph.dependencies(root), 0)];
+        while let Some((signal, deps, next)) = stack.last_mut() {
+          if let Some(dep) = deps.get(*next).copied() {
+            *next += 1;
+            if !visited.put(dep.index()) {
+              stack.push((dep, graph.dependencies(dep), 0));
+            }
+          } else {
+            order.push(*signal);
+            stack.pop();
+          }
+        }
+      }
+      order
+    });
+
+    let passes = tracing::info_span!("group_passes").in_scope(|| {
+      let max_pass_size = self.max_pass_size.unwrap_or(usize::MAX);
+      let mut memory = self
+        .memory_cap
+        .as_ref()
+        .map(|(cap, size)| LiveMemory::new(graph, &order, *cap, size.as_ref()));
+      let mut passes = Vec::new();
+      let mut current = SignalSet::default();
+      for signal in order.iter().copied() {
+        let depends_on_current = graph
+          .dependencies(signal)
+          .iter()
+          .any(|dep| current.contains(dep));
+        let over_cap = memory
+          .as_ref()
+          .is_some_and(|memory| memory.would_exceed(signal));
+        if !current.is_empty()
+          && (depends_on_current || over_cap || current.len() >= max_pass_size)
+        {
+          if let Some(memory) = memory.as_mut() {
+            memory.finish_pass(graph, &current, &root_targets);
+          }
+          passes
+            .push(EvaluationPassDescriptor::new(std::mem::take(&mut current)));
+        }
+        if let Some(memory) = memory.as_mut() {
+          memory.add(signal);
+        }
+        current.insert(signal);
+      }
+      if !current.is_empty() {
+        passes.push(EvaluationPassDescriptor::new(current));
+      }
+      passes
+    });
+
+    PlannedEvaluation::from_passes(matrix, root_targets, passes)
+      .with_free_intermediates(true)
+  }
+}
+
+/// Tracks the bytes of live values while passes are grouped, for
+/// [`MemoryMinimizingPlanner::with_memory_cap`].
+struct LiveMemory<'h> {
+  cap:       usize,
+  size:      &'h (dyn Fn(Signal) -> usize + Send + Sync),
+  /// Bytes of values alive before the current pass, plus its own outputs.
+  live:      usize,
+  /// How many queued signals still have to read each value.
+  consumers: SignalMap<usize>,
+}
+
+impl<'h> LiveMemory<'h> {
+  fn new(
+    graph: &DependencyGraph,
+    order: &[Signal],
+    cap: usize,
+    size: &'h (dyn Fn(Signal) -> usize + Send + Sync),
+  ) -> Self {
+    let mut consumers = SignalMap::default();
+    for signal in order {
+      for dep in graph.dependencies(*signal) {
+        *consumers.entry(*dep).or_default() += 1;
+      }
+    }
+    LiveMemory {
+      cap,
+      size,
+      live: 0,
+      consumers,
+    }
+  }
+
+  fn would_exceed(&self, signal: Signal) -> bool {
+    self.live + (self.size)(signal) > self.cap
+  }
+
+  fn add(&mut self, signal: Signal) { self.live += (self.size)(signal); }
+
+  /// Release the values whose last consumer is in the finished pass.
+  fn finish_pass(
+    &mut self,
+    graph: &DependencyGraph,
+    pass: &SignalSet,
+    roots: &SignalSet,
+  ) {
+    for signal in pass {
+      for dep in graph.dependencies(*signal) {
+        let remaining = self.consumers.get_mut(dep).unwrap();
+        *remaining -= 1;
+        if *remaining == 0 && !roots.contains(dep) {
+          self.live -= (self.size)(*dep);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalDefMap,
+  };
+
+  #[test]
+  fn test_memory_minimizing_planner() {
+    let mut defset = SignalDefMap::new();
+
+    // a balanced addition tree over 16 constants
+    let leaves: Vec<_> = (0..16)
+      .map(|i| defset.insert(FloatMapSignalDef::Constant(i as f64)))
+      .collect();
+    let mut level = leaves.clone();
+    while level.len() > 1 {
+      level = level
+        .chunks(2)
+        .map(|pair| {
+          defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+            pair[0], pair[1],
+          )))
+        })
+        .collect();
+    }
+    let root = level[0];
+    let matrix = SignalMatrix::new(defset);
+
+    let wide = matrix.plan_evaluation(&CustomPlanner::new(), [root]);
+    let narrow = matrix.plan_evaluation(
+      &MemoryMinimizingPlanner::new().with_max_pass_size(1),
+      [root],
+    );
+    assert!(narrow.passes().len() > wide.passes().len());
+    assert!(narrow.peak_live_values() < wide.peak_live_values());
+
+    let values =
+      narrow.run(EvaluationValueMap::new_empty(narrow.all_queued_targets()));
+    assert_eq!(values.get(root).unwrap(), &120.0);
+    // intermediates were freed as soon as their consumer ran
+    assert!(leaves.iter().all(|leaf| values.get(*leaf).is_none()));
+
+    // six values of 8 bytes fit, which is all the last pair of leaves needs:
+    // three partial sums, the leaves, and their own sum
+    let capped = matrix.plan_evaluation(
+      &MemoryMinimizingPlanner::new().with_memory_cap(48, |_| 8),
+      [root],
+    );
+    let unbounded =
+      matrix.plan_evaluation(&MemoryMinimizingPlanner::new(), [root]);
+    assert_eq!(capped.peak_live_values(), 6);
+    assert_eq!(unbounded.peak_live_values(), 7);
+    let values =
+      capped.run(EvaluationValueMap::new_empty(capped.all_queued_targets()));
+    assert_eq!(values.get(root).unwrap(), &120.0);
+  }
+}
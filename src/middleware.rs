@@ -0,0 +1,117 @@
+use crate::{
+  EvaluationPlanner, PlannedEvaluation, SignalDef, SignalMatrix, SignalSet,
+};
+
+/// A hook that inspects and transforms a [`PlannedEvaluation`] before it runs,
+/// e.g. to drop debug-only signals or to enforce a scheduling policy.
+///
+/// Implemented for closures taking `&mut PlannedEvaluation`. Attach one to a
+/// planner with [`WithMiddleware`], or apply it to a plan directly.
+pub trait PlanMiddleware<Def: SignalDef> {
+  /// Transform the given plan in place.
+  fn apply(&self, plan: &mut PlannedEvaluation<'_, Def>);
+}
+
+impl<Def: SignalDef, F> PlanMiddleware<Def> for F
+where
+  F: Fn(&mut PlannedEvaluation<'_, Def>),
+{
+  fn apply(&self, plan: &mut PlannedEvaluation<'_, Def>) { self(plan) }
+}
+
+/// A planner that runs a [`PlanMiddleware`] over every plan produced by
+/// another planner. Nest these to chain several middlewares; the innermost
+/// one is applied first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WithMiddleware<P, M> {
+  planner:    P,
+  middleware: M,
+}
+
+impl<P, M> WithMiddleware<P, M> {
+  /// Wrap the given planner with the given middleware.
+  pub fn new(planner: P, middleware: M) -> Self {
+    WithMiddleware {
+      planner,
+      middleware,
+    }
+  }
+}
+
+impl<Def, P, M> EvaluationPlanner<Def> for WithMiddleware<P, M>
+where
+  Def: SignalDef,
+  P: EvaluationPlanner<Def>,
+  M: PlanMiddleware<Def>,
+{
+  fn plan_evaluation<'m>(
+    &self,
+    matrix: &'m SignalMatrix<Def>,
+    root_targets: SignalSet,
+  ) -> PlannedEvaluation<'m, Def> {
+    let mut plan = self.planner.plan_evaluation(matrix, root_targets);
+    tracing::info_span!("plan_middleware")
+      .in_scope(|| self.middleware.apply(&mut plan));
+    plan
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationPassDescriptor, EvaluationValueMap,
+    FloatMapSignalDef, SignalDefMap, UnaryOp,
+  };
+
+  #[test]
+  fn test_plan_middleware() {
+    let mut defset = SignalDefMap::new();
+
+    let negs: Vec<_> = (0..6)
+      .map(|i| {
+        let leaf = defset.insert(FloatMapSignalDef::Constant(i as f64));
+        defset.insert_labeled(
+          format!("neg{i}"),
+          FloatMapSignalDef::UnaryOp(UnaryOp::Neg(leaf)),
+        )
+      })
+      .collect();
+
+    let matrix = SignalMatrix::new(defset);
+
+    // policy: at most two negations per pass
+    let limit_negs = |plan: &mut PlannedEvaluation<'_, FloatMapSignalDef>| {
+      let matrix = plan.matrix();
+      let mut passes = Vec::new();
+      for pass in plan.passes_mut().drain(..) {
+        let (negs, rest): (SignalSet, SignalSet) =
+          pass.targets().iter().partition(|s| {
+            matches!(
+              matrix.defset().get(**s),
+              Some(FloatMapSignalDef::UnaryOp(_))
+            )
+          });
+        let mut negs: Vec<_> = negs.into_iter().collect();
+        negs.sort_by_key(|s| s.0);
+        if !rest.is_empty() {
+          passes.push(EvaluationPassDescriptor::new(rest));
+        }
+        passes.extend(negs.chunks(2).map(|chunk| {
+          EvaluationPassDescriptor::new(chunk.iter().copied().collect())
+        }));
+      }
+      *plan.passes_mut() = passes;
+    };
+    let planner = WithMiddleware::new(CustomPlanner::new(), limit_negs);
+
+    let planned_eval = matrix.plan_evaluation(&planner, negs.iter().copied());
+    assert_eq!(planned_eval.passes().len(), 4);
+
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+    let total: f64 = negs.iter().map(|s| values.get(*s).unwrap()).sum();
+    assert_eq!(total, -15.0);
+  }
+}
@@ -0,0 +1,118 @@
+use std::ops::Bound;
+
+use crate::{Signal, SignalDef, SignalDefMap, SignalMatrix, SignalSet};
+
+/// The separator between segments of a namespaced label, as in
+/// `portfolio.us.equities.total`.
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+/// A handle for inserting signals under a namespace of a [`SignalDefMap`].
+///
+/// Signals inserted through a namespace are labeled with the namespace path
+/// followed by their name.
+pub struct Namespace<'a, T: SignalDef> {
+  defset: &'a mut SignalDefMap<T>,
+  path:   String,
+}
+
+impl<'a, T: SignalDef> Namespace<'a, T> {
+  /// Get the full path of this namespace.
+  pub fn path(&self) -> &str { &self.path }
+
+  /// Insert a signal definition named `name` in this namespace.
+  pub fn insert(&mut self, name: &str, def: T) -> Signal {
+    let label = join(&self.path, name);
+    self.defset.insert_labeled(label, def)
+  }
+
+  /// Get a handle to a child namespace.
+  pub fn namespace(&mut self, name: &str) -> Namespace<'_, T> {
+    Namespace {
+      path:   join(&self.path, name),
+      defset: self.defset,
+    }
+  }
+}
+
+fn join(path: &str, name: &str) -> String {
+  match path.is_empty() {
+    true => name.to_string(),
+    false => format!("{path}{NAMESPACE_SEPARATOR}{name}"),
+  }
+}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Get a handle for inserting signals under the given namespace path.
+  pub fn namespace(&mut self, path: &str) -> Namespace<'_, T> {
+    Namespace {
+      defset: self,
+      path:   path.to_string(),
+    }
+  }
+
+  /// Iterate over every labeled signal under the given namespace, including
+  /// nested namespaces, in label order. A signal labeled exactly `prefix` is
+  /// included too.
+  pub fn signals_under<'s>(
+    &'s self,
+    prefix: &'s str,
+  ) -> impl Iterator<Item = (&'s str, Signal)> + 's {
+    self
+      .by_label
+      .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+      .take_while(move |(label, _)| label.starts_with(prefix))
+      .filter(move |(label, _)| {
+        prefix.is_empty()
+          || label.len() == prefix.len()
+          || label[prefix.len()..].starts_with(NAMESPACE_SEPARATOR)
+      })
+      .map(|(label, signal)| (label.as_str(), *signal))
+  }
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Select every signal under the given namespace, for use as root targets.
+  pub fn targets_under(&self, prefix: &str) -> SignalSet {
+    self
+      .defset
+      .signals_under(prefix)
+      .map(|(_, signal)| signal)
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::FloatMapSignalDef;
+
+  #[test]
+  fn test_namespaces() {
+    let mut defset = SignalDefMap::new();
+
+    let mut us = defset.namespace("portfolio.us");
+    let equities = us
+      .namespace("equities")
+      .insert("total", FloatMapSignalDef::Constant(1.0));
+    let bonds = us.insert("bonds", FloatMapSignalDef::Constant(2.0));
+    let eu = defset
+      .namespace("portfolio.eu")
+      .insert("total", FloatMapSignalDef::Constant(3.0));
+    // shares a prefix with `portfolio.us`, but isn't in it
+    defset.insert_labeled("portfolio.usd", FloatMapSignalDef::Constant(4.0));
+
+    assert_eq!(defset.lookup("portfolio.us.equities.total"), Some(equities));
+    let us: Vec<_> = defset.signals_under("portfolio.us").collect();
+    assert_eq!(us, vec![
+      ("portfolio.us.bonds", bonds),
+      ("portfolio.us.equities.total", equities),
+    ]);
+
+    let matrix = SignalMatrix::new(defset);
+    assert_eq!(matrix.targets_under("portfolio").len(), 4);
+    assert_eq!(
+      matrix.targets_under("portfolio.eu"),
+      [eu].into_iter().collect()
+    );
+  }
+}
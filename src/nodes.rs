@@ -0,0 +1,157 @@
+use std::{cell::RefCell, collections::HashMap, fmt, hash::Hash};
+
+use crate::{Signal, SignalDef, SignalDefMap};
+
+/// An error building a [`SignalDefMap`] with [`SignalDefMap::from_nodes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromNodesError<K> {
+  /// Two nodes have the same external ID.
+  DuplicateId(K),
+  /// A node references an external ID no node has.
+  UnknownId { node: K, reference: K },
+}
+
+impl<K: fmt::Debug> fmt::Display for FromNodesError<K> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FromNodesError::DuplicateId(id) => {
+        write!(f, "more than one node has the ID {id:?}")
+      }
+      FromNodesError::UnknownId { node, reference } => {
+        write!(f, "node {node:?} references unknown ID {reference:?}")
+      }
+    }
+  }
+}
+
+impl<K: fmt::Debug> std::error::Error for FromNodesError<K> {}
+
+/// Resolves external IDs to signals while building a node's definition in
+/// [`SignalDefMap::from_nodes`].
+pub struct NodeIds<'a, K> {
+  signals: &'a HashMap<K, Signal>,
+  unknown: RefCell<Option<K>>,
+}
+
+impl<K: Hash + Eq + Clone> NodeIds<'_, K> {
+  /// Get the signal of the node with the given external ID. Nodes may
+  /// reference any node, including ones later in the input.
+  ///
+  /// An unknown ID returns a placeholder signal and fails the build with
+  /// [`FromNodesError::UnknownId`].
+  pub fn get(&self, id: &K) -> Signal {
+    self.signals.get(id).copied().unwrap_or_else(|| {
+      self.unknown.borrow_mut().get_or_insert_with(|| id.clone());
+      Signal(u64::MAX)
+    })
+  }
+}
+
+impl<K> fmt::Debug for NodeIds<'_, K> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("NodeIds")
+      .field("nodes", &self.signals.len())
+      .finish()
+  }
+}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Build a map from nodes identified by external IDs, such as rows loaded
+  /// from a database, whose definitions reference each other by those IDs.
+  ///
+  /// Every node gets a signal, in input order, before any definition is
+  /// built, so `build` can resolve references to any node through
+  /// [`NodeIds::get`] regardless of order. Returns the map and the signal of
+  /// each external ID.
+  pub fn from_nodes<K, N>(
+    nodes: impl IntoIterator<Item = (K, N)>,
+    mut build: impl FnMut(N, &NodeIds<'_, K>) -> T,
+  ) -> Result<(Self, HashMap<K, Signal>), FromNodesError<K>>
+  where
+    K: Hash + Eq + Clone,
+  {
+    let nodes: Vec<_> = nodes.into_iter().collect();
+    let mut signals = HashMap::with_capacity(nodes.len());
+    for (i, (id, _)) in nodes.iter().enumerate() {
+      if signals.insert(id.clone(), Signal(i as u64)).is_some() {
+        return Err(FromNodesError::DuplicateId(id.clone()));
+      }
+    }
+
+    let mut defset = SignalDefMap::new();
+    for (id, node) in nodes {
+      let ids = NodeIds {
+        signals: &signals,
+        unknown: RefCell::new(None),
+      };
+      let def = build(node, &ids);
+      if let Some(reference) = ids.unknown.into_inner() {
+        return Err(FromNodesError::UnknownId {
+          node: id,
+          reference,
+        });
+      }
+      defset.insert(def);
+    }
+    Ok((defset, signals))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalMatrix, UnaryOp,
+  };
+
+  enum Row {
+    Constant(f64),
+    Neg(&'static str),
+    Add(&'static str, &'static str),
+  }
+
+  fn build(row: Row, ids: &NodeIds<'_, &'static str>) -> FloatMapSignalDef {
+    match row {
+      Row::Constant(value) => FloatMapSignalDef::Constant(value),
+      Row::Neg(a) => FloatMapSignalDef::UnaryOp(UnaryOp::Neg(ids.get(&a))),
+      Row::Add(a, b) => FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+        ids.get(&a),
+        ids.get(&b),
+      )),
+    }
+  }
+
+  #[test]
+  fn test_from_nodes() {
+    // references point forward and backward
+    let rows = [
+      ("total", Row::Add("x", "y")),
+      ("x", Row::Constant(5.0)),
+      ("y", Row::Neg("z")),
+      ("z", Row::Constant(2.0)),
+    ];
+    let (defset, ids) = SignalDefMap::from_nodes(rows, build).unwrap();
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [ids["total"]]);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(ids["total"]), Some(&3.0));
+
+    let rows = [("a", Row::Neg("missing")), ("b", Row::Constant(1.0))];
+    let error = SignalDefMap::from_nodes(rows, build).unwrap_err();
+    assert_eq!(error, FromNodesError::UnknownId {
+      node:      "a",
+      reference: "missing",
+    });
+    assert_eq!(
+      error.to_string(),
+      "node \"a\" references unknown ID \"missing\""
+    );
+    let rows = [("a", Row::Constant(1.0)), ("a", Row::Constant(2.0))];
+    assert_eq!(
+      SignalDefMap::from_nodes(rows, build).unwrap_err(),
+      FromNodesError::DuplicateId("a")
+    );
+  }
+}
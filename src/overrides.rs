@@ -0,0 +1,118 @@
+use fixedbitset::FixedBitSet;
+
+use crate::{
+  eval::RunHooks, par::*, EvaluationValueMap, PlannedEvaluation, RunOptions,
+  Signal, SignalDef, SignalSet,
+};
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Run the planned evaluation with the given signals pinned to the given
+  /// values, as if they had already been evaluated, without touching the
+  /// graph. Their definitions aren't evaluated, and neither are signals only
+  /// needed through them, so what-if scenarios can pin intermediate signals
+  /// to hypothetical values.
+  pub fn run_with_overrides(
+    &self,
+    mut values: EvaluationValueMap<T>,
+    overrides: impl IntoIterator<Item = (Signal, T::Value)>,
+  ) -> EvaluationValueMap<T> {
+    let mut pinned = SignalSet::default();
+    for (signal, value) in overrides {
+      values.insert(signal, value);
+      pinned.insert(signal);
+    }
+
+    // everything still needed when the search stops at pinned signals
+    let graph = self.matrix().dependency_graph();
+    let mut needed = FixedBitSet::with_capacity(graph.len());
+    let mut stack: Vec<_> = self
+      .root_targets()
+      .iter()
+      .filter(|s| !pinned.contains(s))
+      .copied()
+      .collect();
+    while let Some(signal) = stack.pop() {
+      if signal.index() >= graph.len() || needed.put(signal.index()) {
+        continue;
+      }
+      let deps = graph.dependencies(signal).iter();
+      stack.extend(deps.filter(|dep| !pinned.contains(dep)));
+    }
+    let skipped: SignalSet = self
+      .all_queued_targets()
+      .into_iter()
+      .filter(|s| s.index() >= graph.len() || !needed.contains(s.index()))
+      .collect();
+
+    self.execute(values, &RunOptions::default(), RunHooks {
+      skip: Some(&skipped),
+      ..Default::default()
+    })
+  }
+
+  /// Run the planned evaluation once per scenario, each a set of overrides
+  /// as in [`run_with_overrides`](Self::run_with_overrides), returning one
+  /// value map per scenario in order. Scenarios run in parallel, and so do
+  /// the passes within each run.
+  pub fn run_scenarios<S>(
+    &self,
+    scenarios: impl IntoIterator<Item = S>,
+  ) -> Vec<EvaluationValueMap<T>>
+  where
+    S: IntoIterator<Item = (Signal, T::Value)> + Send,
+  {
+    let scenarios: Vec<_> = scenarios.into_iter().collect();
+    scenarios
+      .into_par_iter()
+      .map(|overrides| {
+        let values = EvaluationValueMap::new_empty(self.all_queued_targets());
+        self.run_with_overrides(values, overrides)
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap,
+    SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_overrides() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let neg = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(neg, b)));
+    let other = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [sum, other]);
+
+    let values = plan.run_with_overrides(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      [(neg, 10.0)],
+    );
+    assert_eq!(values.get(sum), Some(&12.0));
+    assert_eq!(values.get(neg), Some(&10.0));
+    // `a` is still needed by `other`, but `neg`'s definition never ran
+    assert_eq!(values.get(other), Some(&-1.0));
+    assert_eq!(values.get(a), Some(&1.0));
+
+    // pinning `other` too leaves nothing needing `a`
+    let values = plan.run_with_overrides(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      [(neg, 10.0), (other, 0.0)],
+    );
+    assert_eq!(values.get(a), None);
+    assert_eq!(values.get(sum), Some(&12.0));
+
+    let scenarios = [vec![], vec![(b, 5.0)], vec![(neg, 0.0), (b, 0.0)]];
+    let results = plan.run_scenarios(scenarios);
+    let sums: Vec<_> = results.iter().map(|v| v.get(sum).copied()).collect();
+    assert_eq!(sums, [Some(1.0), Some(4.0), Some(0.0)]);
+  }
+}
@@ -0,0 +1,177 @@
+//! Parallel iteration shims. With the `rayon` feature these are rayon's own
+//! traits; without it they fall back to sequential std iterators with the same
+//! method names, so call sites compile either way.
+
+#[cfg(feature = "rayon")]
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "rayon")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(feature = "affinity")]
+use crate::ThreadPlacement;
+use crate::{PassParallelism, RunOptions, Signal, SignalSet};
+
+/// Sequential stand-in for rayon's `IntoParallelRefIterator`.
+#[cfg(not(feature = "rayon"))]
+pub(crate) trait IntoParallelRefIterator<'a> {
+  type Iter: Iterator;
+
+  fn par_iter(&'a self) -> Self::Iter;
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<'a, C: ?Sized + 'a> IntoParallelRefIterator<'a> for C
+where
+  &'a C: IntoIterator,
+{
+  type Iter = <&'a C as IntoIterator>::IntoIter;
+
+  fn par_iter(&'a self) -> Self::Iter { self.into_iter() }
+}
+
+/// Sequential stand-in for rayon's `IntoParallelIterator`.
+#[cfg(not(feature = "rayon"))]
+pub(crate) trait IntoParallelIterator {
+  type Iter: Iterator;
+
+  fn into_par_iter(self) -> Self::Iter;
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<C: IntoIterator> IntoParallelIterator for C {
+  type Iter = C::IntoIter;
+
+  fn into_par_iter(self) -> Self::Iter { self.into_iter() }
+}
+
+/// Get the index of the current worker thread: 0 for the calling thread, and
+/// rayon workers numbered from 1.
+pub(crate) fn current_thread_index() -> usize {
+  #[cfg(feature = "rayon")]
+  return rayon::current_thread_index().map_or(0, |i| i + 1);
+  #[cfg(not(feature = "rayon"))]
+  0
+}
+
+/// Thread pools by thread cap, where a cap of 0 is a full-width pool of its
+/// own. Clones share the same pools.
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ThreadPools(
+  Arc<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>>,
+);
+
+#[cfg(feature = "rayon")]
+impl ThreadPools {
+  /// Get the pool for the given thread cap, building it on first use.
+  pub(crate) fn get_or_build(
+    &self,
+    threads: usize,
+    build: impl FnOnce() -> rayon::ThreadPool,
+  ) -> Arc<rayon::ThreadPool> {
+    let mut pools = self.0.lock().unwrap();
+    pools
+      .entry(threads)
+      .or_insert_with(|| Arc::new(build()))
+      .clone()
+  }
+}
+
+/// Runs pass targets under a [`PassParallelism`], with one thread pool per
+/// thread cap. The pools are kept by the [`RunOptions`], so every run with the
+/// same options reuses them.
+#[derive(Default)]
+pub(crate) struct PassExecutor {
+  #[cfg(feature = "rayon")]
+  pools:     ThreadPools,
+  #[cfg(feature = "affinity")]
+  placement: Option<ThreadPlacement>,
+}
+
+impl PassExecutor {
+  /// Create an executor for a run with the given options.
+  pub(crate) fn new(options: &RunOptions) -> Self {
+    #[cfg(not(feature = "rayon"))]
+    let _ = options;
+    PassExecutor {
+      #[cfg(feature = "rayon")]
+      pools:                                  options.thread_pools().clone(),
+      #[cfg(feature = "affinity")]
+      placement:                              options.placement().cloned(),
+    }
+  }
+
+  /// Get the pool for the given thread cap, building it on first use.
+  #[cfg(feature = "rayon")]
+  fn pool(&mut self, threads: usize) -> Arc<rayon::ThreadPool> {
+    #[cfg(feature = "affinity")]
+    let placement = self.placement.clone();
+    self.pools.get_or_build(threads, || {
+      let mut builder = rayon::ThreadPoolBuilder::new();
+      #[cfg(feature = "affinity")]
+      let threads = match (threads, &placement) {
+        (0, Some(placement)) => placement.threads().unwrap_or(0),
+        _ => threads,
+      };
+      if threads > 0 {
+        builder = builder.num_threads(threads);
+      }
+      #[cfg(feature = "affinity")]
+      if let Some(placement) = placement {
+        builder = builder.start_handler(move |worker| placement.apply(worker));
+      }
+      builder.build().expect("failed to build thread pool")
+    })
+  }
+
+  /// Check whether full-width passes need a pool of their own instead of the
+  /// global one.
+  #[cfg(feature = "rayon")]
+  fn dedicated(&self) -> bool {
+    #[cfg(feature = "affinity")]
+    return self.placement.is_some();
+    #[cfg(not(feature = "affinity"))]
+    false
+  }
+
+  /// Map `f` over `targets`, honoring the given parallelism. Each worker
+  /// gets scratch state from `init`, reused across the targets it maps.
+  pub(crate) fn map_collect<S, O: Send>(
+    &mut self,
+    parallelism: PassParallelism,
+    targets: &SignalSet,
+    init: impl Fn() -> S + Sync + Send,
+    f: impl Fn(&mut S, Signal) -> O + Sync + Send,
+  ) -> Vec<O> {
+    let sequential = || {
+      let mut scratch = init();
+      targets
+        .iter()
+        .map(|target| f(&mut scratch, *target))
+        .collect()
+    };
+    #[cfg(feature = "rayon")]
+    let parallel = || {
+      targets
+        .par_iter()
+        .map_init(&init, |scratch, target| f(scratch, *target))
+        .collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let parallel = sequential;
+    match parallelism {
+      PassParallelism::Limited(0 | 1) => sequential(),
+      #[cfg(feature = "rayon")]
+      PassParallelism::Limited(threads) => self.pool(threads).install(parallel),
+      #[cfg(feature = "rayon")]
+      PassParallelism::Full if self.dedicated() => {
+        self.pool(0).install(parallel)
+      }
+      _ => parallel(),
+    }
+  }
+}
@@ -0,0 +1,223 @@
+use crate::{Signal, SignalDef, SignalMap, SignalMatrix, SignalSet};
+
+/// How [`SignalMatrix::partition`] splits the graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionStrategy {
+  /// Grow each part breadth-first from a seed signal, following edges in both
+  /// directions, until it's full.
+  Bfs,
+  /// Start from [`Bfs`](Self::Bfs), then refine with up to `passes` rounds of
+  /// Kernighan-Lin style moves and swaps, each of which lowers the number of
+  /// cut edges while keeping the parts balanced.
+  KernighanLin { passes: usize },
+}
+
+impl Default for PartitionStrategy {
+  fn default() -> Self { PartitionStrategy::KernighanLin { passes: 8 } }
+}
+
+/// A split of a graph's signals into balanced parts, from
+/// [`SignalMatrix::partition`].
+#[derive(Debug, Clone)]
+pub struct Partitioning {
+  parts:      Vec<SignalSet>,
+  assignment: SignalMap<usize>,
+  cut_edges:  usize,
+}
+
+impl Partitioning {
+  /// Get the signals in each part.
+  pub fn parts(&self) -> &[SignalSet] { &self.parts }
+
+  /// Get the index of the part holding the given signal.
+  pub fn part_of(&self, signal: Signal) -> Option<usize> {
+    self.assignment.get(&signal).copied()
+  }
+
+  /// Get the number of dependency edges between signals in different parts.
+  pub fn cut_edges(&self) -> usize { self.cut_edges }
+}
+
+const UNASSIGNED: usize = usize::MAX;
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Split the graph's signals into `n` parts, with sizes differing by at most
+  /// one, while keeping few dependency edges between parts.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `n` is zero.
+  pub fn partition(
+    &self,
+    n: usize,
+    strategy: PartitionStrategy,
+  ) -> Partitioning {
+    assert!(n > 0, "can't partition into zero parts");
+    let span = tracing::info_span!("partition", n, ?strategy);
+    let _enter = span.enter();
+
+    let graph = self.dependency_graph();
+    let dependents = self.dependents_graph();
+    let neighbors = |index: usize| {
+      let signal = Signal::from_index(index);
+      graph
+        .dependencies(signal)
+        .iter()
+        .chain(dependents.dependencies(signal))
+        .filter(|s| self.defset().get(**s).is_some())
+        .map(|s| s.index())
+    };
+
+    let mut signals: Vec<_> = self.defset().iter().map(|(s, _)| s).collect();
+    signals.sort_by_key(|s| s.0);
+    let (min, max) = (signals.len() / n, signals.len().div_ceil(n));
+
+    // grow parts breadth-first, reseeding from the lowest unassigned signal
+    let mut part = vec![UNASSIGNED; graph.len()];
+    let mut sizes = vec![0; n];
+    let mut seeds = signals.iter().map(|s| s.index());
+    for (p, size) in sizes.iter_mut().enumerate() {
+      let target = if p < signals.len() % n { max } else { min };
+      let mut queue = std::collections::VecDeque::new();
+      while *size < target {
+        let next = queue.pop_front().or_else(|| {
+          seeds
+            .by_ref()
+            .find(|index: &usize| part[*index] == UNASSIGNED)
+        });
+        let Some(index) = next else { break };
+        if part[index] != UNASSIGNED {
+          continue;
+        }
+        part[index] = p;
+        *size += 1;
+        queue.extend(neighbors(index).filter(|i| part[*i] == UNASSIGNED));
+      }
+    }
+
+    if let PartitionStrategy::KernighanLin { passes } = strategy {
+      // links[i * n + p] counts the neighbors of signal `i` in part `p`
+      let mut links = vec![0isize; graph.len() * n];
+      for signal in signals.iter() {
+        for other in neighbors(signal.index()) {
+          links[signal.index() * n + part[other]] += 1;
+        }
+      }
+      let gain = |links: &[isize], index: usize, from: usize, to: usize| {
+        links[index * n + to] - links[index * n + from]
+      };
+      let relink = |links: &mut [isize], index: usize, from, to| {
+        for other in neighbors(index) {
+          links[other * n + from] -= 1;
+          links[other * n + to] += 1;
+        }
+      };
+
+      for _ in 0..passes {
+        let mut improved = false;
+        for signal in signals.iter() {
+          let v = signal.index();
+          let p = part[v];
+          let Some((q, v_gain)) = (0..n)
+            .filter(|q| *q != p)
+            .map(|q| (q, gain(&links, v, p, q)))
+            .max_by_key(|(_, gain)| *gain)
+            .filter(|(_, gain)| *gain > 0)
+          else {
+            continue;
+          };
+
+          if sizes[q] < max && sizes[p] > min {
+            part[v] = q;
+            (sizes[p], sizes[q]) = (sizes[p] - 1, sizes[q] + 1);
+            relink(&mut links, v, p, q);
+            improved = true;
+            continue;
+          }
+
+          // otherwise swap with the member of `q` that gains most by moving
+          let shared = |u: usize| neighbors(v).filter(|w| *w == u).count();
+          let best = signals
+            .iter()
+            .map(|s| s.index())
+            .filter(|u| part[*u] == q)
+            .map(|u| {
+              let total =
+                v_gain + gain(&links, u, q, p) - 2 * shared(u) as isize;
+              (u, total)
+            })
+            .max_by_key(|(_, total)| *total);
+          if let Some((u, _)) = best.filter(|(_, total)| *total > 0) {
+            part[v] = q;
+            relink(&mut links, v, p, q);
+            part[u] = p;
+            relink(&mut links, u, q, p);
+            improved = true;
+          }
+        }
+        if !improved {
+          break;
+        }
+      }
+    }
+
+    let mut parts = vec![SignalSet::default(); n];
+    let mut assignment = SignalMap::default();
+    let mut cut_edges = 0;
+    for signal in signals {
+      let p = part[signal.index()];
+      parts[p].insert(signal);
+      assignment.insert(signal, p);
+      cut_edges += graph
+        .dependencies(signal)
+        .iter()
+        .filter(|dep| ![p, UNASSIGNED].contains(&part[dep.index()]))
+        .count();
+    }
+    tracing::info!(cut_edges, "partitioned graph");
+
+    Partitioning {
+      parts,
+      assignment,
+      cut_edges,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap};
+
+  #[test]
+  fn test_partition() {
+    // two chains of adds, with interleaved IDs, joined by one edge
+    let mut defset = SignalDefMap::new();
+    let mut heads = [
+      defset.insert(FloatMapSignalDef::Constant(1.0)),
+      defset.insert(FloatMapSignalDef::Constant(2.0)),
+    ];
+    for _ in 0..5 {
+      for head in heads.iter_mut() {
+        *head = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+          *head, *head,
+        )));
+      }
+    }
+    defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+      heads[0], heads[1],
+    )));
+    let matrix = SignalMatrix::new(defset);
+
+    let bfs = matrix.partition(2, PartitionStrategy::Bfs);
+    let kl = matrix.partition(2, PartitionStrategy::default());
+    for partitioning in [&bfs, &kl] {
+      let sizes: Vec<_> =
+        partitioning.parts().iter().map(|p| p.len()).collect();
+      assert_eq!(sizes, [7, 6]);
+    }
+    assert!(kl.cut_edges() <= bfs.cut_edges());
+    assert_eq!(kl.cut_edges(), 1);
+    assert_ne!(kl.part_of(heads[0]), kl.part_of(heads[1]));
+  }
+}
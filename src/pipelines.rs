@@ -0,0 +1,177 @@
+use crate::{
+  par::*, EvaluationValueMap, PlannedEvaluation, RunOptions, Signal, SignalDef,
+  SignalSet,
+};
+
+impl<'m, T: SignalDef> PlannedEvaluation<'m, T> {
+  /// Split the plan into one plan per
+  /// [component](crate::SignalMatrix::components) of its roots, each keeping
+  /// its targets' passes. A deep group then gets as few passes as its own
+  /// depth needs, instead of waiting at every barrier of a shallow, wide
+  /// group planned alongside it.
+  ///
+  /// The plans free intermediates if this one does, but don't carry its
+  /// [defaults](Self::with_defaults).
+  pub fn pipelines(&self) -> Vec<PlannedEvaluation<'m, T>> {
+    let components = self
+      .matrix()
+      .components(self.root_targets().iter().copied());
+    components
+      .components()
+      .iter()
+      .map(|component| {
+        let signals = component.signals();
+        let passes = self
+          .passes()
+          .iter()
+          .map(|pass| pass.filtered(|s| signals.contains(&s)))
+          .filter(|pass| !pass.targets().is_empty())
+          .collect();
+        let roots = component.roots().iter().copied().collect();
+        PlannedEvaluation::from_passes(self.matrix(), roots, passes)
+          .with_free_intermediates(self.free_intermediates())
+      })
+      .collect()
+  }
+
+  /// Run the planned evaluation like [`run_with`](Self::run_with), running
+  /// its [`pipelines`](Self::pipelines) concurrently as tasks on the global
+  /// thread pool, so independent groups of roots progress through their
+  /// passes at their own pace without a thread each. Each pipeline's passes
+  /// still run in parallel as the options allow. Without the `rayon`
+  /// feature the pipelines run one after another.
+  ///
+  /// Plans with a single pipeline, and lenient plans, run as one.
+  pub fn run_pipelined(
+    &self,
+    mut values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> EvaluationValueMap<T> {
+    let pipelines = self.pipelines();
+    if pipelines.len() < 2 || self.defaults().is_some() {
+      return self.run_with(values, options);
+    }
+    let span =
+      tracing::info_span!("pipelined_run", pipelines = pipelines.len());
+    let _enter = span.enter();
+
+    // hand each pipeline the values it reads
+    let needs: Vec<SignalSet> = pipelines
+      .iter()
+      .map(|plan| {
+        let targets = plan.all_queued_targets();
+        let mut needs = self.matrix().dependencies_closure(targets.clone());
+        needs.extend(targets);
+        needs
+      })
+      .collect();
+    let mut inputs: Vec<_> = pipelines
+      .iter()
+      .map(|plan| EvaluationValueMap::new_empty(plan.all_queued_targets()))
+      .collect();
+    let present: Vec<Signal> = values
+      .values
+      .iter()
+      .filter(|(_, value)| value.is_some())
+      .map(|(signal, _)| signal)
+      .collect();
+    for signal in present {
+      if let Some(i) = needs.iter().position(|needs| needs.contains(&signal)) {
+        inputs[i].insert(signal, values.take(signal).unwrap());
+      }
+    }
+
+    let results: Vec<_> = pipelines
+      .par_iter()
+      .zip(inputs.into_par_iter())
+      .map(|(plan, input)| plan.run_with(input, options))
+      .collect();
+
+    values.begin_epoch();
+    for (plan, result) in pipelines.iter().zip(results) {
+      let queued = plan.all_queued_targets();
+      for (signal, value) in result.values {
+        let Some(value) = value else { continue };
+        if queued.contains(&signal) {
+          values.insert_evaluated(signal, value);
+        } else {
+          // inputs go back as they were, keeping their epochs
+          values.values.insert(signal, Some(value));
+        }
+      }
+    }
+    values
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_independent_pipelines() {
+    let mut defset = SignalDefMap::new();
+    let deep = GraphGenerator::new(GraphShape::Chain { length: 12 })
+      .build_float(&mut defset);
+    let wide = GraphGenerator::new(GraphShape::FanOut { width: 16 })
+      .build_float(&mut defset);
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let joined = [0, 1].map(|_| {
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, a)))
+    });
+    let matrix = SignalMatrix::new(defset);
+    let roots: Vec<_> =
+      deep.iter().chain(&wide).chain(&joined).copied().collect();
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), roots.clone())
+      .with_free_intermediates(true);
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let pipelines = plan.pipelines();
+    assert_eq!(pipelines.len(), 3);
+    assert_eq!(pipelines[0].passes().len(), plan.passes().len());
+    assert!(pipelines[1].passes().len() < plan.passes().len());
+    assert_eq!(pipelines[2].root_targets().len(), 2);
+
+    let values = plan.run_pipelined(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    for root in roots.iter() {
+      assert_eq!(values.get(*root), expected.get(*root));
+    }
+    assert_eq!(values.get(a), None);
+  }
+
+  #[test]
+  fn test_pipelined_epochs() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, a)));
+    let c = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let d =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(c, c)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [b, d])
+      .skip_targets([a]);
+    assert_eq!(plan.pipelines().len(), 2);
+
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(a, 2.0);
+    let stamped = values.evaluated_at(a);
+    let before = values.epoch();
+    let values = plan.run_pipelined(values, &RunOptions::new());
+    assert_eq!(values.get(b), Some(&4.0));
+    // the supplied input keeps the stamp it was inserted with
+    assert_eq!(values.evaluated_at(a), stamped);
+    let changed: Vec<_> = values.changed_since(before).collect();
+    assert_eq!(changed, [a, b, c, d]);
+  }
+}
@@ -0,0 +1,126 @@
+use crate::{
+  eval::RunHooks, EvaluationValueMap, PlannedEvaluation, RunOptions, Signal,
+  SignalDef, SignalMap, SignalSet,
+};
+
+/// A value that may represent a failed evaluation, for
+/// [`PlannedEvaluation::run_poisoning`].
+pub trait Fallible {
+  /// Check whether this value is a failure.
+  fn is_failure(&self) -> bool;
+}
+
+impl<V, E> Fallible for Result<V, E> {
+  fn is_failure(&self) -> bool { self.is_err() }
+}
+
+/// The failures of a run with
+/// [`PlannedEvaluation::run_poisoning`], and the signals they poisoned.
+#[derive(Debug, Clone, Default)]
+pub struct PoisonReport {
+  pub(crate) failed:   SignalSet,
+  pub(crate) poisoned: SignalMap<SignalSet>,
+}
+
+impl PoisonReport {
+  /// Check whether nothing failed.
+  pub fn is_clean(&self) -> bool { self.failed.is_empty() }
+
+  /// Get the signals whose values are failures, including failed inputs.
+  pub fn failed(&self) -> &SignalSet { &self.failed }
+
+  /// Get the signals that weren't evaluated because they depend on a failure.
+  pub fn poisoned(&self) -> impl Iterator<Item = Signal> + '_ {
+    self.poisoned.keys().copied()
+  }
+
+  /// Get the failed signals that the given poisoned signal depends on.
+  pub fn root_causes(&self, signal: Signal) -> Option<&SignalSet> {
+    self.poisoned.get(&signal)
+  }
+
+  /// Get the root causes a signal with the given dependencies inherits.
+  pub(crate) fn causes_of(&self, deps: &[Signal]) -> SignalSet {
+    let mut causes = SignalSet::default();
+    for dep in deps {
+      if self.failed.contains(dep) {
+        causes.insert(*dep);
+      } else if let Some(inherited) = self.poisoned.get(dep) {
+        causes.extend(inherited.iter().copied());
+      }
+    }
+    causes
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T>
+where
+  T::Value: Fallible,
+{
+  /// Run the planned evaluation like [`run_with`](Self::run_with), but
+  /// without evaluating anything that depends on a failed value.
+  ///
+  /// Signals whose dependencies failed, directly or through other skipped
+  /// signals, are poisoned: they're left without a value and reported with
+  /// the failures they trace back to, while unaffected signals still
+  /// evaluate.
+  pub fn run_poisoning(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> (EvaluationValueMap<T>, PoisonReport) {
+    let mut report = PoisonReport::default();
+    for (signal, value) in values.values.iter() {
+      if value.as_ref().is_some_and(Fallible::is_failure) {
+        report.failed.insert(signal);
+      }
+    }
+    let failed = |value: &T::Value| value.is_failure();
+    let values = self.execute(values, options, RunHooks {
+      poison: Some((&failed, &mut report)),
+      ..Default::default()
+    });
+    tracing::info!(
+      failed = report.failed.len(),
+      poisoned = report.poisoned.len(),
+      "poisoned run complete"
+    );
+    (values, report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, IntMapSignalDef, IntOp, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_poison_propagation() {
+    let mut defset = SignalDefMap::new();
+    let zero = defset.insert(IntMapSignalDef::new(IntOp::Constant(0)));
+    let ten = defset.insert(IntMapSignalDef::new(IntOp::Constant(10)));
+    let bad = defset.insert(IntMapSignalDef::new(IntOp::Div(ten, zero)));
+    let worse = defset.insert(IntMapSignalDef::new(IntOp::Neg(bad)));
+    let mixed = defset.insert(IntMapSignalDef::new(IntOp::Add(worse, ten)));
+    let fine = defset.insert(IntMapSignalDef::new(IntOp::Add(ten, ten)));
+    let matrix = SignalMatrix::new(defset);
+
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [mixed, fine]);
+    let (values, report) = plan.run_poisoning(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+
+    assert_eq!(values.get(fine), Some(&Ok(20)));
+    assert!(values.get(bad).unwrap().is_err());
+    assert_eq!(values.get(worse), None);
+    assert_eq!(values.get(mixed), None);
+
+    assert_eq!(report.failed().iter().collect::<Vec<_>>(), [&bad]);
+    assert_eq!(report.poisoned().count(), 2);
+    assert!(report.root_causes(mixed).unwrap().contains(&bad));
+    assert_eq!(report.root_causes(fine), None);
+  }
+}
@@ -0,0 +1,408 @@
+//! Exchanging [`FloatMapSignalDef`] graphs with other dataflow tools in a
+//! portable operator-graph format: a documented subset of ONNX's graph
+//! structure, written as JSON.
+//!
+//! A model holds one graph of named values. Constants are initializers,
+//! values provided from outside are graph inputs, and every operation is a
+//! node applying an ONNX operator (`Neg`, `Add`, `Sub`, `Mul`, `Div` or `Pow`)
+//! to earlier values and producing exactly one output. Nodes are listed in
+//! topological order, as in ONNX, and the graph outputs are the roots:
+//!
+//! ```json
+//! {
+//!   "ir_version": 1,
+//!   "producer_name": "matrix",
+//!   "graph": {
+//!     "inputs": ["rate"],
+//!     "initializers": [{ "name": "one", "value": 1.0 }],
+//!     "nodes": [
+//!       { "name": "growth", "op_type": "Add",
+//!         "inputs": ["one", "rate"], "outputs": ["growth"] }
+//!     ],
+//!     "outputs": ["growth"]
+//!   }
+//! }
+//! ```
+//!
+//! Every value is scalar `f64`. Signals are named by their labels, falling
+//! back to `s<id>`.
+
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  FloatBinaryOp, FloatMapSignalDef, Signal, SignalDefMap, SignalMatrix, UnaryOp,
+};
+
+/// The format version written by [`PortableModel::export`].
+pub const IR_VERSION: u32 = 1;
+
+/// A portable model: a versioned graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortableModel {
+  /// The format version.
+  pub ir_version:    u32,
+  /// The tool that wrote the model.
+  pub producer_name: String,
+  /// The graph.
+  pub graph:         PortableGraph,
+}
+
+/// A portable graph of named values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortableGraph {
+  /// Values provided from outside the graph.
+  #[serde(default)]
+  pub inputs:       Vec<String>,
+  /// Constant values.
+  #[serde(default)]
+  pub initializers: Vec<Initializer>,
+  /// Operations, each after the nodes producing its inputs.
+  #[serde(default)]
+  pub nodes:        Vec<PortableNode>,
+  /// The values the graph computes.
+  pub outputs:      Vec<String>,
+}
+
+/// A named constant of a [`PortableGraph`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Initializer {
+  pub name:  String,
+  pub value: f64,
+}
+
+/// An operation of a [`PortableGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortableNode {
+  /// The name of the node.
+  pub name:    String,
+  /// The ONNX operator the node applies.
+  pub op_type: String,
+  /// The names of the operands.
+  pub inputs:  Vec<String>,
+  /// The name of the value the node produces.
+  pub outputs: Vec<String>,
+}
+
+/// An error reading or importing a [`PortableModel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortableError {
+  /// The JSON couldn't be parsed.
+  Parse(String),
+  /// The model has a newer format version than this reader.
+  UnsupportedVersion(u32),
+  /// A node applies an operator outside the supported subset.
+  UnsupportedOp { node: String, op_type: String },
+  /// A node has the wrong number of inputs or outputs for its operator.
+  Arity { node: String, expected: usize },
+  /// A node or output references a value not defined before it.
+  UnknownValue {
+    node:      String,
+    reference: String,
+  },
+  /// Two values share a name.
+  DuplicateName(String),
+}
+
+impl fmt::Display for PortableError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PortableError::Parse(e) => write!(f, "failed to parse model: {e}"),
+      PortableError::UnsupportedVersion(version) => {
+        write!(f, "unsupported model version {version}")
+      }
+      PortableError::UnsupportedOp { node, op_type } => {
+        write!(f, "node `{node}` applies unsupported operator `{op_type}`")
+      }
+      PortableError::Arity { node, expected } => write!(
+        f,
+        "node `{node}` takes {expected} inputs and produces one output"
+      ),
+      PortableError::UnknownValue { node, reference } => write!(
+        f,
+        "`{node}` references `{reference}`, which isn't defined before it"
+      ),
+      PortableError::DuplicateName(name) => {
+        write!(f, "more than one value is named `{name}`")
+      }
+    }
+  }
+}
+
+impl std::error::Error for PortableError {}
+
+/// A graph imported with [`PortableModel::import`].
+#[derive(Debug)]
+pub struct ImportedGraph {
+  /// The signal definitions, each labeled with its value name. Graph inputs
+  /// are [declared](SignalDefMap::declare) placeholders, to be
+  /// [defined](SignalDefMap::define) before evaluation.
+  pub defset:  SignalDefMap<FloatMapSignalDef>,
+  /// The placeholders of the graph inputs, in order.
+  pub inputs:  Vec<Signal>,
+  /// The signals of the graph outputs, in order.
+  pub outputs: Vec<Signal>,
+}
+
+fn op_type(def: &FloatMapSignalDef) -> (&'static str, Vec<Signal>) {
+  match def {
+    FloatMapSignalDef::Constant(_) => {
+      unreachable!("constants are initializers")
+    }
+    FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => ("Neg", vec![*a]),
+    FloatMapSignalDef::BinaryOp(op) => match op {
+      FloatBinaryOp::Add(a, b) => ("Add", vec![*a, *b]),
+      FloatBinaryOp::Sub(a, b) => ("Sub", vec![*a, *b]),
+      FloatBinaryOp::Mul(a, b) => ("Mul", vec![*a, *b]),
+      FloatBinaryOp::Div(a, b) => ("Div", vec![*a, *b]),
+      FloatBinaryOp::Pow(a, b) => ("Pow", vec![*a, *b]),
+    },
+  }
+}
+
+/// Name a value, failing if the name is taken.
+fn define<'g>(
+  values: &mut HashMap<&'g str, Signal>,
+  name: &'g str,
+  signal: Signal,
+) -> Result<(), PortableError> {
+  match values.insert(name, signal) {
+    Some(_) => Err(PortableError::DuplicateName(name.to_string())),
+    None => Ok(()),
+  }
+}
+
+impl PortableModel {
+  /// Export the given roots and everything they depend on.
+  pub fn export(
+    matrix: &SignalMatrix<FloatMapSignalDef>,
+    roots: &[Signal],
+  ) -> Self {
+    let defset = matrix.defset();
+    let name = |signal: Signal| match defset.label(signal) {
+      Some(label) => label.to_string(),
+      None => format!("s{}", signal.0),
+    };
+    let mut needed = matrix.dependencies_closure(roots.iter().copied());
+    needed.extend(roots.iter().copied());
+
+    let mut graph = PortableGraph {
+      outputs: roots.iter().map(|s| name(*s)).collect(),
+      ..Default::default()
+    };
+    for (signal, def) in matrix.topo_iter().filter(|(s, _)| needed.contains(s))
+    {
+      if let FloatMapSignalDef::Constant(value) = def {
+        graph.initializers.push(Initializer {
+          name:  name(signal),
+          value: *value,
+        });
+        continue;
+      }
+      let (op_type, inputs) = op_type(def);
+      graph.nodes.push(PortableNode {
+        name:    name(signal),
+        op_type: op_type.to_string(),
+        inputs:  inputs.into_iter().map(name).collect(),
+        outputs: vec![name(signal)],
+      });
+    }
+    PortableModel {
+      ir_version: IR_VERSION,
+      producer_name: "matrix".to_string(),
+      graph,
+    }
+  }
+
+  /// Write the model as JSON.
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).expect("models always serialize")
+  }
+
+  /// Read a model from JSON.
+  pub fn from_json(source: &str) -> Result<Self, PortableError> {
+    let model: Self = serde_json::from_str(source)
+      .map_err(|e| PortableError::Parse(e.to_string()))?;
+    if model.ir_version > IR_VERSION {
+      return Err(PortableError::UnsupportedVersion(model.ir_version));
+    }
+    Ok(model)
+  }
+
+  /// Build the model's graph.
+  pub fn import(&self) -> Result<ImportedGraph, PortableError> {
+    let mut defset = SignalDefMap::new();
+    let mut values: HashMap<&str, Signal> = HashMap::new();
+    let graph = &self.graph;
+
+    let mut inputs = Vec::with_capacity(graph.inputs.len());
+    for name in graph.inputs.iter() {
+      let signal = defset.declare();
+      defset.set_label(signal, name);
+      define(&mut values, name, signal)?;
+      inputs.push(signal);
+    }
+    for initializer in graph.initializers.iter() {
+      let signal = defset.insert_labeled(
+        &initializer.name,
+        FloatMapSignalDef::Constant(initializer.value),
+      );
+      define(&mut values, &initializer.name, signal)?;
+    }
+
+    let resolve = |values: &HashMap<&str, Signal>, node: &str, name: &str| {
+      values
+        .get(name)
+        .copied()
+        .ok_or_else(|| PortableError::UnknownValue {
+          node:      node.to_string(),
+          reference: name.to_string(),
+        })
+    };
+    for node in graph.nodes.iter() {
+      let expected = match node.op_type.as_str() {
+        "Neg" => 1,
+        "Add" | "Sub" | "Mul" | "Div" | "Pow" => 2,
+        op_type => {
+          return Err(PortableError::UnsupportedOp {
+            node:    node.name.clone(),
+            op_type: op_type.to_string(),
+          })
+        }
+      };
+      if node.inputs.len() != expected || node.outputs.len() != 1 {
+        return Err(PortableError::Arity {
+          node: node.name.clone(),
+          expected,
+        });
+      }
+      let args = node
+        .inputs
+        .iter()
+        .map(|input| resolve(&values, &node.name, input))
+        .collect::<Result<Vec<_>, _>>()?;
+      let def = match (node.op_type.as_str(), args.as_slice()) {
+        ("Neg", [a]) => FloatMapSignalDef::UnaryOp(UnaryOp::Neg(*a)),
+        ("Add", [a, b]) => {
+          FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(*a, *b))
+        }
+        ("Sub", [a, b]) => {
+          FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(*a, *b))
+        }
+        ("Mul", [a, b]) => {
+          FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(*a, *b))
+        }
+        ("Div", [a, b]) => {
+          FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(*a, *b))
+        }
+        (_, [a, b]) => FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(*a, *b)),
+        _ => unreachable!("arity was checked above"),
+      };
+      let output = &node.outputs[0];
+      let signal = defset.insert_labeled(output, def);
+      define(&mut values, output, signal)?;
+    }
+
+    let outputs = graph
+      .outputs
+      .iter()
+      .map(|output| resolve(&values, "outputs", output))
+      .collect::<Result<_, _>>()?;
+    Ok(ImportedGraph {
+      defset,
+      inputs,
+      outputs,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap};
+
+  #[test]
+  fn test_portable_round_trip() {
+    let mut defset = SignalDefMap::new();
+    let principal =
+      defset.insert_labeled("principal", FloatMapSignalDef::Constant(1000.0));
+    let rate = defset.insert(FloatMapSignalDef::Constant(0.5));
+    let years = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let growth = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let growth = defset.insert_labeled(
+      "growth",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(growth, rate)),
+    );
+    let factor = defset.insert(FloatMapSignalDef::BinaryOp(
+      FloatBinaryOp::Pow(growth, years),
+    ));
+    let total = defset.insert_labeled(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(principal, factor)),
+    );
+    defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(total)));
+    let matrix = SignalMatrix::new(defset);
+
+    let model = PortableModel::export(&matrix, &[total]);
+    assert_eq!(model.graph.initializers.len(), 4);
+    assert_eq!(model.graph.nodes.len(), 3);
+    assert_eq!(model.graph.nodes[1].op_type, "Pow");
+    assert_eq!(model.graph.nodes[1].inputs, ["growth", "s2"]);
+    let model = PortableModel::from_json(&model.to_json()).unwrap();
+
+    let imported = model.import().unwrap();
+    let matrix = SignalMatrix::new(imported.defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), imported.outputs);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let total = matrix.defset().lookup("total").unwrap();
+    assert_eq!(values.get(total), Some(&2250.0));
+  }
+
+  #[test]
+  fn test_portable_inputs_and_errors() {
+    let model = PortableModel::from_json(
+      r#"{
+        "ir_version": 1,
+        "producer_name": "numpy-tools",
+        "graph": {
+          "inputs": ["x"],
+          "nodes": [
+            { "name": "n0", "op_type": "Neg", "inputs": ["x"], "outputs": ["y"] }
+          ],
+          "outputs": ["y"]
+        }
+      }"#,
+    )
+    .unwrap();
+    let mut imported = model.import().unwrap();
+    assert!(imported.defset.validate().is_err());
+    let x = imported.inputs[0];
+    imported
+      .defset
+      .define(x, FloatMapSignalDef::Constant(4.0))
+      .unwrap();
+    let matrix = SignalMatrix::new(imported.defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), imported.outputs);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(
+      values.get(matrix.defset().lookup("y").unwrap()),
+      Some(&-4.0)
+    );
+
+    let mut model = model;
+    model.graph.nodes[0].op_type = "Relu".to_string();
+    assert_eq!(model.import().unwrap_err(), PortableError::UnsupportedOp {
+      node:    "n0".to_string(),
+      op_type: "Relu".to_string(),
+    });
+    model.graph.nodes[0].op_type = "Neg".to_string();
+    model.graph.nodes[0].inputs = vec!["z".to_string()];
+    assert_eq!(
+      model.import().unwrap_err().to_string(),
+      "`n0` references `z`, which isn't defined before it"
+    );
+  }
+}
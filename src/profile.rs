@@ -0,0 +1,273 @@
+use std::{
+  fmt::Write as _,
+  io::{self, Write},
+  time::Duration,
+};
+
+use crate::Signal;
+
+/// Timings recorded by
+/// [`PlannedEvaluation::run_profiled`](crate::PlannedEvaluation::run_profiled).
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+  pub(crate) passes: Vec<PassProfile>,
+}
+
+/// The timing of one pass.
+#[derive(Debug, Clone)]
+pub struct PassProfile {
+  pub(crate) index:    usize,
+  pub(crate) start:    Duration,
+  pub(crate) duration: Duration,
+  pub(crate) signals:  Vec<SignalProfile>,
+}
+
+/// The timing of one signal's evaluation.
+#[derive(Debug, Clone)]
+pub struct SignalProfile {
+  pub(crate) signal:   Signal,
+  pub(crate) label:    String,
+  pub(crate) variant:  String,
+  pub(crate) start:    Duration,
+  pub(crate) duration: Duration,
+  pub(crate) thread:   usize,
+}
+
+impl ProfileReport {
+  /// Get the profiles of each pass, in execution order.
+  pub fn passes(&self) -> &[PassProfile] { &self.passes }
+
+  /// Get the total wall time of the run.
+  pub fn total_duration(&self) -> Duration {
+    self
+      .passes
+      .last()
+      .map_or(Duration::ZERO, |pass| pass.start + pass.duration)
+  }
+
+  /// Iterate over every signal profile in the run.
+  pub fn signals(&self) -> impl Iterator<Item = &SignalProfile> {
+    self.passes.iter().flat_map(|pass| pass.signals.iter())
+  }
+
+  /// Write the run as a Chrome trace (the JSON trace event format), viewable
+  /// in `chrome://tracing` or Perfetto. Passes are drawn on their own track
+  /// and signals on one track per worker thread.
+  pub fn write_chrome_trace(&self, mut w: impl Write) -> io::Result<()> {
+    let mut events = Vec::new();
+    for pass in self.passes.iter() {
+      events.push(chrome_event(
+        &format!("pass {}", pass.index),
+        "pass",
+        pass.start,
+        pass.duration,
+        0,
+      ));
+      for signal in pass.signals.iter() {
+        events.push(chrome_event(
+          &signal.label,
+          "signal",
+          signal.start,
+          signal.duration,
+          signal.thread + 1,
+        ));
+      }
+    }
+    write!(
+      w,
+      "{{\"traceEvents\":[{}],\"displayTimeUnit\":\"ms\"}}",
+      events.join(",")
+    )
+  }
+
+  /// Write the run as a speedscope profile, viewable at speedscope.app. Each
+  /// worker thread becomes one evented profile, plus one for the passes.
+  pub fn write_speedscope(&self, mut w: impl Write) -> io::Result<()> {
+    let mut frames: Vec<String> = Vec::new();
+    let mut frame_index = std::collections::HashMap::new();
+    let mut frame = |name: &str| {
+      *frame_index.entry(name.to_string()).or_insert_with(|| {
+        frames.push(format!("{{\"name\":{}}}", json_string(name)));
+        frames.len() - 1
+      })
+    };
+
+    // named tracks of (frame, start, end) spans, with passes first
+    let mut tracks: Vec<(String, Vec<Span>)> =
+      vec![("passes".to_string(), Vec::new())];
+    for pass in self.passes.iter() {
+      let f = frame(&format!("pass {}", pass.index));
+      tracks[0]
+        .1
+        .push((f, pass.start, pass.start + pass.duration));
+      for signal in pass.signals.iter() {
+        let f = frame(&signal.label);
+        let track = signal.thread + 1;
+        while tracks.len() <= track {
+          let name = format!("thread {}", tracks.len() - 1);
+          tracks.push((name, Vec::new()));
+        }
+        tracks[track]
+          .1
+          .push((f, signal.start, signal.start + signal.duration));
+      }
+    }
+
+    let end = micros(self.total_duration());
+    let mut profiles = Vec::new();
+    for (name, mut spans) in tracks {
+      if spans.is_empty() {
+        continue;
+      }
+      spans.sort_by_key(|(_, start, _)| *start);
+      let mut events = String::new();
+      let mut last = 0.0f64;
+      for (f, start, stop) in spans {
+        // keep events monotonic even if timer readings overlap slightly
+        let open = micros(start).max(last);
+        last = micros(stop).max(open);
+        if !events.is_empty() {
+          events.push(',');
+        }
+        let _ = write!(
+          events,
+          "{{\"type\":\"O\",\"frame\":{f},\"at\":{open}}},{{\"type\":\"C\",\"\
+           frame\":{f},\"at\":{last}}}"
+        );
+      }
+      profiles.push(format!(
+        "{{\"type\":\"evented\",\"name\":{},\"unit\":\"microseconds\",\"\
+         startValue\":0,\"endValue\":{},\"events\":[{events}]}}",
+        json_string(&name),
+        end.max(last)
+      ));
+    }
+
+    write!(
+      w,
+      "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\
+       \"shared\":{{\"frames\":[{}]}},\"profiles\":[{}]}}",
+      frames.join(","),
+      profiles.join(",")
+    )
+  }
+}
+
+impl PassProfile {
+  /// Get the index of this pass in the plan.
+  pub fn index(&self) -> usize { self.index }
+
+  /// Get the time from the start of the run to the start of this pass.
+  pub fn start(&self) -> Duration { self.start }
+
+  /// Get the wall time of this pass.
+  pub fn duration(&self) -> Duration { self.duration }
+
+  /// Get the profiles of the signals evaluated in this pass.
+  pub fn signals(&self) -> &[SignalProfile] { &self.signals }
+}
+
+impl SignalProfile {
+  /// Get the signal.
+  pub fn signal(&self) -> Signal { self.signal }
+
+  /// Get the signal's label, or `#id` if it has none.
+  pub fn label(&self) -> &str { &self.label }
+
+  /// Get the name of the definition's variant, as the leading name of its
+  /// `Debug` output.
+  pub fn variant(&self) -> &str { &self.variant }
+
+  /// Get the time from the start of the run to the start of evaluation.
+  pub fn start(&self) -> Duration { self.start }
+
+  /// Get the time spent gathering context and evaluating.
+  pub fn duration(&self) -> Duration { self.duration }
+
+  /// Get the worker thread that evaluated the signal. Thread 0 is the calling
+  /// thread; rayon workers are numbered from 1.
+  pub fn thread(&self) -> usize { self.thread }
+}
+
+/// A frame index with its start and end times.
+type Span = (usize, Duration, Duration);
+
+fn micros(d: Duration) -> f64 { d.as_secs_f64() * 1_000_000.0 }
+
+fn chrome_event(
+  name: &str,
+  category: &str,
+  start: Duration,
+  duration: Duration,
+  tid: usize,
+) -> String {
+  format!(
+    "{{\"name\":{},\"cat\":\"{category}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"\
+     pid\":1,\"tid\":{tid}}}",
+    json_string(name),
+    micros(start),
+    micros(duration)
+  )
+}
+
+/// Quote and escape a string for JSON.
+pub(crate) fn json_string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      }
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_profile_export() {
+    let mut defset = SignalDefMap::new();
+
+    let a =
+      defset.insert_labeled("a \"quoted\"", FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [c]);
+    let (values, report) = plan.run_profiled(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    assert_eq!(values.get(c).unwrap(), &3.0);
+    assert_eq!(report.passes().len(), 2);
+    assert_eq!(report.signals().count(), 3);
+
+    let mut chrome = Vec::new();
+    report.write_chrome_trace(&mut chrome).unwrap();
+    let chrome = String::from_utf8(chrome).unwrap();
+    assert!(chrome.starts_with("{\"traceEvents\":["));
+    assert!(chrome.contains(r#""name":"a \"quoted\"""#));
+    assert!(chrome.contains(r##""name":"#2""##));
+
+    let mut speedscope = Vec::new();
+    report.write_speedscope(&mut speedscope).unwrap();
+    let speedscope = String::from_utf8(speedscope).unwrap();
+    assert!(speedscope.contains("\"profiles\":[{\"type\":\"evented\""));
+  }
+}
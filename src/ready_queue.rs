@@ -0,0 +1,251 @@
+use std::{
+  cmp::Reverse,
+  collections::BinaryHeap,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Condvar, Mutex, OnceLock,
+  },
+};
+
+use crate::{
+  limits::ConcurrencyLimits, ContextScratch, EvalContext, EvaluationValueMap,
+  FairShare, PlannedEvaluation, RunOptions, SignalDef,
+};
+
+/// The ready queue shared by the workers of a
+/// [`run_dependents_first`](PlannedEvaluation::run_dependents_first).
+struct Queue {
+  /// Ready targets by queued dependents, then lowest ID first.
+  ready:   BinaryHeap<(usize, Reverse<usize>)>,
+  /// Targets being evaluated.
+  running: usize,
+  /// Set when a worker panics, so the others stop waiting.
+  aborted: bool,
+}
+
+/// Marks a target as running until dropped, waking the other workers.
+struct InFlight<'q> {
+  queue: &'q Mutex<Queue>,
+  wake:  &'q Condvar,
+}
+
+impl Drop for InFlight<'_> {
+  fn drop(&mut self) {
+    let mut queue = self.queue.lock().unwrap();
+    queue.running -= 1;
+    queue.aborted |= std::thread::panicking();
+    self.wake.notify_all();
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Run the planned evaluation like [`run_with`](Self::run_with), but
+  /// without barriers between passes: workers pull targets from a shared
+  /// ready queue as soon as their dependencies are evaluated, taking the
+  /// ready target with the most dependents among the queued targets first,
+  /// then the lowest ID. Unlocking the most downstream work first keeps
+  /// threads busy on irregular graphs, where a pass barrier would leave them
+  /// waiting on its slowest target.
+  ///
+  /// With the `rayon` feature there is one worker per thread of the current
+  /// pool, and without it the calling thread evaluates everything in the
+  /// same order. Concurrency limits, fair shares and reproducible summation
+  /// apply as in [`run_with`](Self::run_with); the pass parallelism policy
+  /// doesn't, as there are no passes to apply it to.
+  ///
+  /// Use [`DependentsFirstPlanner`](crate::DependentsFirstPlanner) instead to
+  /// apply the same priority when deciding passes.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a target depends on a signal that is neither queued nor in
+  /// the value map, nor covered by the plan's defaults.
+  pub fn run_dependents_first(
+    &self,
+    mut values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> EvaluationValueMap<T> {
+    let mut targets: Vec<_> = self.all_queued_targets().into_iter().collect();
+    targets.sort_by_key(|s| s.0);
+    let span =
+      tracing::info_span!("dependents_first_run", targets = targets.len());
+    let _enter = span.enter();
+
+    // targets by position, and the positions of their queued dependents
+    let graph = self.matrix().dependency_graph();
+    let mut position = vec![None; graph.len()];
+    for (i, target) in targets.iter().enumerate() {
+      position[target.index()] = Some(i);
+    }
+    let mut dependents = vec![Vec::new(); targets.len()];
+    let pending: Vec<_> = targets
+      .iter()
+      .enumerate()
+      .map(|(i, target)| {
+        let mut count = 0;
+        for dep in graph.dependencies(*target) {
+          if let Some(j) = position[dep.index()] {
+            dependents[j].push(i);
+            count += 1;
+          }
+        }
+        AtomicUsize::new(count)
+      })
+      .collect();
+    let ready = (0..targets.len())
+      .filter(|i| pending[*i].load(Ordering::Relaxed) == 0)
+      .map(|i| (dependents[i].len(), Reverse(i)))
+      .collect();
+
+    let queue = Mutex::new(Queue {
+      ready,
+      running: 0,
+      aborted: false,
+    });
+    let wake = Condvar::new();
+    let slots: Vec<OnceLock<T::Value>> =
+      targets.iter().map(|_| OnceLock::new()).collect();
+    let limits = ConcurrencyLimits::new(options);
+    let inputs = &values;
+
+    let worker = || {
+      let mut scratch = ContextScratch::new(options.summation());
+      loop {
+        let next = {
+          let mut queue = queue.lock().unwrap();
+          loop {
+            if queue.aborted {
+              return;
+            }
+            if let Some((_, Reverse(i))) = queue.ready.pop() {
+              queue.running += 1;
+              break i;
+            }
+            if queue.running == 0 {
+              return;
+            }
+            queue = wake.wait(queue).unwrap();
+          }
+        };
+        let in_flight = InFlight {
+          queue: &queue,
+          wake:  &wake,
+        };
+
+        let target = targets[next];
+        let def = self.matrix().defset().get(target).unwrap();
+        let deps = graph.dependencies(target);
+        let context_values = deps.iter().map(|&dep| {
+          let value = match position[dep.index()] {
+            Some(j) => slots[j].get(),
+            None => inputs.get(dep).or_else(|| self.defaults()?.get(dep)),
+          };
+          let value = value.unwrap_or_else(|| {
+            panic!(
+              "Missing value for dependency {dep:?} while evaluating \
+               {target:?}"
+            )
+          });
+          (dep, value)
+        });
+        let context =
+          EvalContext::for_def_in(target, def, context_values, &mut scratch);
+        let value = {
+          let _permit = limits.acquire(def.concurrency_class());
+          let _slot = options.fair_share().map(FairShare::acquire);
+          def.evaluate(&context)
+        };
+        context.recycle(&mut scratch);
+        if slots[next].set(value).is_err() {
+          unreachable!("{target} evaluated twice");
+        }
+
+        let unlocked: Vec<_> = dependents[next]
+          .iter()
+          .filter(|j| pending[**j].fetch_sub(1, Ordering::AcqRel) == 1)
+          .map(|j| (dependents[*j].len(), Reverse(*j)))
+          .collect();
+        queue.lock().unwrap().ready.extend(unlocked);
+        drop(in_flight);
+      }
+    };
+
+    #[cfg(feature = "rayon")]
+    rayon::scope(|scope| {
+      for _ in 0..rayon::current_num_threads() {
+        scope.spawn(|_| worker());
+      }
+    });
+    #[cfg(not(feature = "rayon"))]
+    worker();
+
+    values.begin_epoch();
+    let free = self.free_intermediates();
+    for (target, slot) in targets.into_iter().zip(slots) {
+      let Some(value) = slot.into_inner() else {
+        continue;
+      };
+      if !free || self.root_targets().contains(&target) {
+        values.insert_evaluated(target, value);
+      }
+    }
+    values
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_run_dependents_first() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::LayeredDag {
+      layers:     8,
+      width:      24,
+      min_fan_in: 1,
+      max_fan_in: 4,
+      reach:      3,
+    })
+    .with_seed(11)
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let values = plan.run_dependents_first(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    assert_eq!(values.epoch(), 1);
+    for signal in plan.all_queued_targets() {
+      assert_eq!(values.get(signal), expected.get(signal));
+    }
+
+    // inputs come from the map, and intermediates are freed if planned so
+    let input = roots
+      .iter()
+      .find_map(|root| matrix.direct_dependencies(*root).first().copied())
+      .unwrap();
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), roots.clone())
+      .skip_targets([input])
+      .with_free_intermediates(true);
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(input, *expected.get(input).unwrap());
+    let values = plan.run_dependents_first(values, &RunOptions::new());
+    for root in roots.iter() {
+      assert_eq!(values.get(*root), expected.get(*root));
+    }
+    assert_eq!(values.get(input), expected.get(input));
+    assert!(plan
+      .all_queued_targets()
+      .iter()
+      .filter(|s| !roots.contains(s))
+      .all(|s| values.get(*s).is_none()));
+  }
+}
@@ -0,0 +1,396 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt,
+  sync::{Arc, Mutex, RwLock},
+};
+
+use crate::{
+  CustomPlanner, EvaluationPassDescriptor, PlannedEvaluation, Signal,
+  SignalDef, SignalDefMap, SignalMatrix,
+};
+
+/// Resource limits for one tenant of a [`GraphRegistry`]. Unlimited by
+/// default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TenantLimits {
+  max_graphs:       Option<usize>,
+  max_signals:      Option<usize>,
+  max_cached_plans: Option<usize>,
+}
+
+impl TenantLimits {
+  /// Create unlimited tenant limits.
+  pub fn new() -> Self { Self::default() }
+
+  /// Allow at most this many graphs.
+  pub fn with_max_graphs(mut self, max_graphs: usize) -> Self {
+    self.max_graphs = Some(max_graphs);
+    self
+  }
+
+  /// Allow at most this many signals across all of the tenant's graphs.
+  pub fn with_max_signals(mut self, max_signals: usize) -> Self {
+    self.max_signals = Some(max_signals);
+    self
+  }
+
+  /// Cache at most this many plans per graph. Plans past the limit are made
+  /// for each evaluation instead.
+  pub fn with_max_cached_plans(mut self, max_cached_plans: usize) -> Self {
+    self.max_cached_plans = Some(max_cached_plans);
+    self
+  }
+}
+
+/// An error registering a graph in a [`GraphRegistry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+  /// The tenant already has its maximum number of graphs.
+  TooManyGraphs { tenant: String, max: usize },
+  /// The graph would take the tenant over its maximum number of signals.
+  TooManySignals {
+    tenant:    String,
+    requested: usize,
+    max:       usize,
+  },
+}
+
+impl fmt::Display for RegistryError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RegistryError::TooManyGraphs { tenant, max } => {
+        write!(f, "tenant `{tenant}` may have at most {max} graphs")
+      }
+      RegistryError::TooManySignals {
+        tenant,
+        requested,
+        max,
+      } => write!(
+        f,
+        "tenant `{tenant}` would have {requested} signals, more than the \
+         {max} allowed"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// The targets and skipped signals a plan was made for.
+type PlanKey = (Vec<Signal>, Vec<Signal>);
+
+/// A graph in a [`GraphRegistry`]: its matrix, its roots, its version, and
+/// the plans made for it.
+pub struct RegisteredGraph<T: SignalDef> {
+  matrix:           SignalMatrix<T>,
+  roots:            Vec<Signal>,
+  version:          u64,
+  max_cached_plans: Option<usize>,
+  plans:            Mutex<HashMap<PlanKey, Arc<Vec<EvaluationPassDescriptor>>>>,
+}
+
+impl<T: SignalDef> fmt::Debug for RegisteredGraph<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("RegisteredGraph")
+      .field("signals", &self.matrix.defset().len())
+      .field("roots", &self.roots)
+      .field("version", &self.version)
+      .field("cached_plans", &self.cached_plans())
+      .finish()
+  }
+}
+
+impl<T: SignalDef> RegisteredGraph<T> {
+  /// Get the graph's matrix.
+  pub fn matrix(&self) -> &SignalMatrix<T> { &self.matrix }
+
+  /// Get the graph's root targets.
+  pub fn roots(&self) -> &[Signal] { &self.roots }
+
+  /// Get the graph's version. The first graph registered under a name is
+  /// version 1, and each replacement gets the next version.
+  pub fn version(&self) -> u64 { self.version }
+
+  /// Get the number of cached plans.
+  pub fn cached_plans(&self) -> usize { self.plans.lock().unwrap().len() }
+
+  /// Plan evaluating the given targets with a [`CustomPlanner`], skipping
+  /// the given signals, e.g. ones whose values are overridden. Plans are
+  /// cached by their targets and skipped signals, and cached plans share
+  /// their passes instead of copying them. Deprecated signals in the plan
+  /// are warned about on every call.
+  pub fn plan(
+    &self,
+    targets: &[Signal],
+    skipped: &[Signal],
+  ) -> PlannedEvaluation<'_, T> {
+    let key = (targets.to_vec(), skipped.to_vec());
+    let cached = self.plans.lock().unwrap().get(&key).cloned();
+    if let Some(passes) = cached {
+      let plan = PlannedEvaluation::from_shared_passes(
+        &self.matrix,
+        targets.iter().copied().collect(),
+        passes,
+      );
+      plan.warn_deprecated();
+      return plan;
+    }
+    let plan = self
+      .matrix
+      .plan_evaluation(&CustomPlanner::new(), targets.iter().copied())
+      .skip_targets(skipped.iter().copied());
+    let mut plans = self.plans.lock().unwrap();
+    if self.max_cached_plans.is_none_or(|max| plans.len() < max) {
+      plans.insert(key, plan.shared_passes());
+    }
+    plan
+  }
+}
+
+struct Tenant<T: SignalDef> {
+  limits:   Option<TenantLimits>,
+  graphs:   BTreeMap<String, Arc<RegisteredGraph<T>>>,
+  versions: BTreeMap<String, u64>,
+}
+
+impl<T: SignalDef> Tenant<T> {
+  fn new() -> Self {
+    Tenant {
+      limits:   None,
+      graphs:   BTreeMap::new(),
+      versions: BTreeMap::new(),
+    }
+  }
+}
+
+/// Named graphs for many tenants, each graph with its own version and cached
+/// plans, and each tenant with its own [`TenantLimits`].
+///
+/// Graphs are shared as [`Arc`]s, so a graph can be evaluated while it's
+/// being replaced; the evaluation finishes on the old version.
+pub struct GraphRegistry<T: SignalDef> {
+  default_limits: TenantLimits,
+  tenants:        RwLock<HashMap<String, Tenant<T>>>,
+}
+
+impl<T: SignalDef> Default for GraphRegistry<T> {
+  fn default() -> Self {
+    GraphRegistry {
+      default_limits: TenantLimits::default(),
+      tenants:        RwLock::default(),
+    }
+  }
+}
+
+impl<T: SignalDef> fmt::Debug for GraphRegistry<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let tenants = self.tenants.read().unwrap();
+    let mut map = f.debug_map();
+    for (name, tenant) in tenants.iter() {
+      map.entry(name, &tenant.graphs);
+    }
+    map.finish()
+  }
+}
+
+impl<T: SignalDef> GraphRegistry<T> {
+  /// Create an empty registry with unlimited tenants.
+  pub fn new() -> Self { Self::default() }
+
+  /// Use the given limits for tenants without limits of their own.
+  pub fn with_default_limits(mut self, limits: TenantLimits) -> Self {
+    self.default_limits = limits;
+    self
+  }
+
+  /// Set a tenant's limits. Graphs it already has are kept, even past the
+  /// new limits.
+  pub fn set_limits(&self, tenant: &str, limits: TenantLimits) {
+    let mut tenants = self.tenants.write().unwrap();
+    let tenant = tenants
+      .entry(tenant.to_string())
+      .or_insert_with(Tenant::new);
+    tenant.limits = Some(limits);
+  }
+
+  /// Get a tenant's limits.
+  pub fn limits(&self, tenant: &str) -> TenantLimits {
+    let tenants = self.tenants.read().unwrap();
+    tenants
+      .get(tenant)
+      .and_then(|t| t.limits)
+      .unwrap_or(self.default_limits)
+  }
+
+  /// Register a graph, replacing any graph the tenant has of that name,
+  /// returning it.
+  pub fn insert(
+    &self,
+    tenant: &str,
+    name: &str,
+    defset: SignalDefMap<T>,
+    roots: Vec<Signal>,
+  ) -> Result<Arc<RegisteredGraph<T>>, RegistryError> {
+    let mut tenants = self.tenants.write().unwrap();
+    let entry = tenants
+      .entry(tenant.to_string())
+      .or_insert_with(Tenant::new);
+    let limits = entry.limits.unwrap_or(self.default_limits);
+
+    let replaced = entry.graphs.contains_key(name);
+    if let Some(max) = limits.max_graphs {
+      if !replaced && entry.graphs.len() >= max {
+        return Err(RegistryError::TooManyGraphs {
+          tenant: tenant.to_string(),
+          max,
+        });
+      }
+    }
+    if let Some(max) = limits.max_signals {
+      let requested = entry
+        .graphs
+        .iter()
+        .filter(|(n, _)| *n != name)
+        .map(|(_, graph)| graph.matrix.defset().len())
+        .sum::<usize>()
+        + defset.len();
+      if requested > max {
+        return Err(RegistryError::TooManySignals {
+          tenant: tenant.to_string(),
+          requested,
+          max,
+        });
+      }
+    }
+
+    let version = entry.versions.entry(name.to_string()).or_default();
+    *version += 1;
+    let graph = Arc::new(RegisteredGraph {
+      matrix: SignalMatrix::new(defset),
+      roots,
+      version: *version,
+      max_cached_plans: limits.max_cached_plans,
+      plans: Mutex::default(),
+    });
+    entry.graphs.insert(name.to_string(), graph.clone());
+    Ok(graph)
+  }
+
+  /// Get a tenant's graph by name.
+  pub fn get(
+    &self,
+    tenant: &str,
+    name: &str,
+  ) -> Option<Arc<RegisteredGraph<T>>> {
+    let tenants = self.tenants.read().unwrap();
+    tenants.get(tenant)?.graphs.get(name).cloned()
+  }
+
+  /// Remove a tenant's graph, returning it. Its version keeps counting if
+  /// the name is registered again.
+  pub fn remove(
+    &self,
+    tenant: &str,
+    name: &str,
+  ) -> Option<Arc<RegisteredGraph<T>>> {
+    let mut tenants = self.tenants.write().unwrap();
+    tenants.get_mut(tenant)?.graphs.remove(name)
+  }
+
+  /// Get a tenant's graphs, by name.
+  pub fn graphs(&self, tenant: &str) -> Vec<(String, Arc<RegisteredGraph<T>>)> {
+    let tenants = self.tenants.read().unwrap();
+    let Some(tenant) = tenants.get(tenant) else {
+      return Vec::new();
+    };
+    tenant
+      .graphs
+      .iter()
+      .map(|(name, graph)| (name.clone(), graph.clone()))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef};
+
+  fn graph(constant: f64) -> (SignalDefMap<FloatMapSignalDef>, Vec<Signal>) {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(constant));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    (defset, vec![sum])
+  }
+
+  #[test]
+  fn test_registry() {
+    let registry = GraphRegistry::new()
+      .with_default_limits(TenantLimits::new().with_max_cached_plans(1));
+    registry.set_limits("small", TenantLimits::new().with_max_graphs(1));
+
+    let (defset, roots) = graph(1.0);
+    let first = registry.insert("acme", "g", defset, roots).unwrap();
+    let (defset, roots) = graph(5.0);
+    registry.insert("acme", "g", defset, roots).unwrap();
+    let (defset, roots) = graph(0.0);
+    registry.insert("other", "g", defset, roots).unwrap();
+
+    // replacing bumps the version without disturbing the old graph or other
+    // tenants
+    let graph_g = registry.get("acme", "g").unwrap();
+    assert_eq!((first.version(), graph_g.version()), (1, 2));
+    assert_eq!(registry.get("other", "g").unwrap().version(), 1);
+    let root = graph_g.roots()[0];
+    for _ in 0..2 {
+      let plan = graph_g.plan(&[root], &[]);
+      let values =
+        plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+      assert_eq!(values.get(root), Some(&7.0));
+    }
+    // cached plans share their passes, copying them only when edited
+    let cached = graph_g.plan(&[root], &[]);
+    let mut edited = graph_g.plan(&[root], &[]);
+    assert!(Arc::ptr_eq(
+      &cached.shared_passes(),
+      &edited.shared_passes()
+    ));
+    edited.passes_mut().pop();
+    assert!(!Arc::ptr_eq(
+      &cached.shared_passes(),
+      &edited.shared_passes()
+    ));
+    assert_eq!(cached.passes().len(), 2);
+    let plan = graph_g.plan(&[root], &[Signal::from_index(0)]);
+    assert_eq!(plan.all_queued_targets().len(), 2);
+    assert_eq!(graph_g.cached_plans(), 1);
+
+    let (defset, roots) = graph(1.0);
+    registry.insert("small", "a", defset, roots).unwrap();
+    let (defset, roots) = graph(1.0);
+    assert_eq!(
+      registry.insert("small", "b", defset, roots).unwrap_err(),
+      RegistryError::TooManyGraphs {
+        tenant: "small".into(),
+        max:    1,
+      }
+    );
+    registry.set_limits("small", TenantLimits::new().with_max_signals(5));
+    let (defset, roots) = graph(1.0);
+    assert_eq!(
+      registry
+        .insert("small", "b", defset, roots)
+        .unwrap_err()
+        .to_string(),
+      "tenant `small` would have 6 signals, more than the 5 allowed"
+    );
+
+    assert!(registry.remove("acme", "g").is_some());
+    assert!(registry.graphs("acme").is_empty());
+    let (defset, roots) = graph(1.0);
+    let again = registry.insert("acme", "g", defset, roots).unwrap();
+    assert_eq!(again.version(), 3);
+  }
+}
@@ -0,0 +1,327 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+  distributed::{read_bytes, read_u64, WireValue},
+  eval::RunHooks,
+  EvaluationPassDescriptor, EvaluationValueMap, PlannedEvaluation, RunOptions,
+  Signal, SignalDef, SignalMatrix,
+};
+
+/// Values as signals and their [`WireValue`] encodings, sorted by ID.
+type Encoded = Vec<(Signal, Vec<u8>)>;
+
+/// A recorded evaluation: its root targets, the inputs it started from, and
+/// the value of every target, pass by pass, along with which passes were
+/// [sequential segments](EvaluationPassDescriptor::sequential). Recorded with
+/// [`PlannedEvaluation::run_logged`] and checked with
+/// [`replay`](Self::replay), to verify determinism or reproduce a production
+/// incident locally.
+///
+/// Values are stored encoded, so the log isn't tied to a signal type. It is
+/// written as little-endian counts, signal IDs and length-prefixed values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayLog {
+  roots:  Vec<Signal>,
+  inputs: Encoded,
+  passes: Vec<RecordedPass>,
+}
+
+/// One recorded pass: its targets' values in evaluation order, and whether
+/// it ran as a sequential segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RecordedPass {
+  sequential: bool,
+  values:     Encoded,
+}
+
+/// A target whose replayed value differs from the recorded one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayMismatch {
+  pub signal: Signal,
+  pub pass:   usize,
+}
+
+/// The result of [`ReplayLog::replay`].
+#[derive(Debug)]
+pub struct Replay<T: SignalDef> {
+  values:     EvaluationValueMap<T>,
+  mismatches: Vec<ReplayMismatch>,
+}
+
+impl<T: SignalDef> Replay<T> {
+  /// Get the values of the replayed run.
+  pub fn values(&self) -> &EvaluationValueMap<T> { &self.values }
+
+  /// Take the values of the replayed run.
+  pub fn into_values(self) -> EvaluationValueMap<T> { self.values }
+
+  /// Get every target whose value differs from the log, in pass order.
+  pub fn mismatches(&self) -> &[ReplayMismatch] { &self.mismatches }
+
+  /// Check whether every target reproduced its recorded value.
+  pub fn is_faithful(&self) -> bool { self.mismatches.is_empty() }
+}
+
+fn encode<'v, V: WireValue + 'v>(
+  values: impl Iterator<Item = (Signal, &'v V)>,
+) -> Encoded {
+  values
+    .map(|(signal, value)| {
+      let mut bytes = Vec::new();
+      value.encode(&mut bytes);
+      (signal, bytes)
+    })
+    .collect()
+}
+
+fn encode_sorted<'v, V: WireValue + 'v>(
+  values: impl Iterator<Item = (Signal, &'v V)>,
+) -> Encoded {
+  let mut encoded = encode(values);
+  encoded.sort_by_key(|(s, _)| s.0);
+  encoded
+}
+
+fn write_encoded(w: &mut impl Write, values: &Encoded) -> io::Result<()> {
+  w.write_all(&(values.len() as u64).to_le_bytes())?;
+  for (signal, bytes) in values {
+    w.write_all(&signal.0.to_le_bytes())?;
+    w.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    w.write_all(bytes)?;
+  }
+  Ok(())
+}
+
+fn read_encoded(r: &mut impl Read) -> io::Result<Encoded> {
+  let count = read_u64(r)?;
+  let mut values = Vec::new();
+  for _ in 0..count {
+    let signal = Signal(read_u64(r)?);
+    let mut bytes = Vec::new();
+    read_bytes(r, &mut bytes)?;
+    values.push((signal, bytes));
+  }
+  Ok(values)
+}
+
+impl ReplayLog {
+  /// Get the root targets of the recorded run.
+  pub fn roots(&self) -> &[Signal] { &self.roots }
+
+  /// Get the number of recorded passes.
+  pub fn passes(&self) -> usize { self.passes.len() }
+
+  /// Write the log to a writer.
+  pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+    w.write_all(&(self.roots.len() as u64).to_le_bytes())?;
+    for root in self.roots.iter() {
+      w.write_all(&root.0.to_le_bytes())?;
+    }
+    write_encoded(&mut w, &self.inputs)?;
+    w.write_all(&(self.passes.len() as u64).to_le_bytes())?;
+    for pass in self.passes.iter() {
+      w.write_all(&[pass.sequential as u8])?;
+      write_encoded(&mut w, &pass.values)?;
+    }
+    Ok(())
+  }
+
+  /// Read a log written by [`write_to`](Self::write_to).
+  pub fn read_from(mut r: impl Read) -> io::Result<Self> {
+    let roots = (0..read_u64(&mut r)?)
+      .map(|_| read_u64(&mut r).map(Signal))
+      .collect::<io::Result<_>>()?;
+    let inputs = read_encoded(&mut r)?;
+    let passes = (0..read_u64(&mut r)?)
+      .map(|_| {
+        let mut sequential = [0];
+        r.read_exact(&mut sequential)?;
+        Ok(RecordedPass {
+          sequential: sequential[0] != 0,
+          values:     read_encoded(&mut r)?,
+        })
+      })
+      .collect::<io::Result<_>>()?;
+    Ok(ReplayLog {
+      roots,
+      inputs,
+      passes,
+    })
+  }
+
+  /// Re-run the recorded passes against the given matrix from the recorded
+  /// inputs, comparing every target's encoded value with the log.
+  ///
+  /// Fails if a recorded value can't be decoded.
+  pub fn replay<T>(&self, matrix: &SignalMatrix<T>) -> io::Result<Replay<T>>
+  where
+    T: SignalDef,
+    T::Value: WireValue,
+  {
+    let passes = self
+      .passes
+      .iter()
+      .map(|pass| {
+        let targets = pass.values.iter().map(|(s, _)| *s);
+        match pass.sequential {
+          true => EvaluationPassDescriptor::sequential(targets.collect()),
+          false => EvaluationPassDescriptor::new(targets.collect()),
+        }
+      })
+      .collect();
+    let plan = PlannedEvaluation::from_passes(
+      matrix,
+      self.roots.iter().copied().collect(),
+      passes,
+    );
+
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    for (signal, bytes) in self.inputs.iter() {
+      values.insert(*signal, T::Value::decode(bytes)?);
+    }
+    let values = plan.run(values);
+
+    let mut mismatches = Vec::new();
+    let mut bytes = Vec::new();
+    for (pass, recorded) in self.passes.iter().enumerate() {
+      for (signal, expected) in recorded.values.iter() {
+        bytes.clear();
+        if let Some(value) = values.get(*signal) {
+          value.encode(&mut bytes);
+        }
+        if bytes != *expected {
+          mismatches.push(ReplayMismatch {
+            signal: *signal,
+            pass,
+          });
+        }
+      }
+    }
+    Ok(Replay { values, mismatches })
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T>
+where
+  T::Value: WireValue,
+{
+  /// Run the planned evaluation like [`run_with`](Self::run_with), recording
+  /// the starting values and every target's value to a [`ReplayLog`].
+  ///
+  /// Intermediates the plan frees are kept until the run ends, so they can be
+  /// recorded.
+  pub fn run_logged(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> (EvaluationValueMap<T>, ReplayLog) {
+    let inputs = encode_sorted(
+      values
+        .values
+        .iter()
+        .filter_map(|(s, v)| Some((s, v.as_ref()?))),
+    );
+    let mut values = self.execute(values, options, RunHooks {
+      keep_intermediates: true,
+      ..Default::default()
+    });
+
+    let passes = self
+      .passes()
+      .iter()
+      .map(|pass| RecordedPass {
+        sequential: pass.is_sequential(),
+        values:     encode(
+          pass
+            .ordered_targets()
+            .into_iter()
+            .filter_map(|s| Some((s, values.get(s)?))),
+        ),
+      })
+      .collect();
+    let mut roots: Vec<_> = self.root_targets().iter().copied().collect();
+    roots.sort_by_key(|s| s.0);
+
+    if self.free_intermediates() {
+      for signal in self.all_queued_targets().iter() {
+        if !self.root_targets().contains(signal) {
+          values.values.remove(signal);
+        }
+      }
+    }
+    (values, ReplayLog {
+      roots,
+      inputs,
+      passes,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, FloatMapSignalDef, SignalDefMap,
+  };
+
+  #[test]
+  fn test_record_and_replay() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 16,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), roots.clone())
+      .skip_targets([Signal(0)])
+      .with_free_intermediates(true);
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(Signal(0), 100.0);
+    let (values, log) = plan.run_logged(values, &RunOptions::new());
+    assert_eq!(values.get(Signal(1)), None);
+
+    let mut bytes = Vec::new();
+    log.write_to(&mut bytes).unwrap();
+    let log = ReplayLog::read_from(bytes.as_slice()).unwrap();
+    assert_eq!(log.passes(), plan.passes().len());
+    let replay = log.replay(&matrix).unwrap();
+    assert!(replay.is_faithful());
+    assert_eq!(replay.values().get(roots[0]), values.get(roots[0]));
+
+    // the same log against a changed graph points at the first divergence
+    let mut matrix = matrix;
+    matrix.update(Signal(1), FloatMapSignalDef::Constant(-1.0));
+    let replay = log.replay(&matrix).unwrap();
+    assert_eq!(replay.mismatches()[0], ReplayMismatch {
+      signal: Signal(1),
+      pass:   0,
+    });
+    assert_eq!(replay.mismatches().last().unwrap().signal, roots[0]);
+  }
+
+  #[test]
+  fn test_replay_sequential_segment() {
+    let mut defset = SignalDefMap::new();
+    let chain = GraphGenerator::new(GraphShape::Chain { length: 6 })
+      .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), chain.clone())
+      .collapse_chains();
+    let (values, log) = plan.run_logged(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+
+    let mut bytes = Vec::new();
+    log.write_to(&mut bytes).unwrap();
+    let log = ReplayLog::read_from(bytes.as_slice()).unwrap();
+    assert_eq!(log.passes(), 1);
+    let replay = log.replay(&matrix).unwrap();
+    assert!(replay.is_faithful());
+    assert_eq!(replay.values().get(chain[0]), values.get(chain[0]));
+  }
+}
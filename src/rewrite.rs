@@ -0,0 +1,244 @@
+use crate::{Relink, Signal, SignalDef, SignalDefMap, SignalMap, SignalSet};
+
+/// What a [`RewriteRule`] does with a matched signal.
+#[derive(Debug)]
+pub enum Rewrite<T> {
+  /// Replace the signal's definition.
+  Replace(T),
+  /// The signal is equivalent to another one, so wire its dependents to that
+  /// one instead. The signal itself keeps its definition.
+  Forward(Signal),
+}
+
+/// A rule that matches the definition of a signal and rewrites it, applied by
+/// a [`Rewriter`].
+///
+/// Implemented for closures taking the signal, its definition and the whole
+/// map, so rules can use Rust patterns to match the definition and look up
+/// its dependencies, as in matching `Mul(x, one)` where `one` is
+/// `Constant(1.0)` and forwarding to `x`.
+pub trait RewriteRule<T: SignalDef>: Sync {
+  /// Rewrite the given signal, or return `None` if the rule doesn't match.
+  fn rewrite(
+    &self,
+    signal: Signal,
+    def: &T,
+    defset: &SignalDefMap<T>,
+  ) -> Option<Rewrite<T>>;
+}
+
+impl<T: SignalDef, F> RewriteRule<T> for F
+where
+  F: Fn(Signal, &T, &SignalDefMap<T>) -> Option<Rewrite<T>> + Sync,
+{
+  fn rewrite(
+    &self,
+    signal: Signal,
+    def: &T,
+    defset: &SignalDefMap<T>,
+  ) -> Option<Rewrite<T>> {
+    self(signal, def, defset)
+  }
+}
+
+/// Applies a set of [`RewriteRule`]s to a [`SignalDefMap`] until none of them
+/// match anymore.
+pub struct Rewriter<T: SignalDef> {
+  rules:      Vec<Box<dyn RewriteRule<T>>>,
+  max_sweeps: usize,
+}
+
+impl<T: SignalDef> Default for Rewriter<T> {
+  fn default() -> Self {
+    Rewriter {
+      rules:      Vec::new(),
+      max_sweeps: 64,
+    }
+  }
+}
+
+/// The outcome of [`Rewriter::run`].
+#[derive(Debug, Clone, Default)]
+pub struct RewriteReport {
+  applied:   usize,
+  sweeps:    usize,
+  converged: bool,
+  forwarded: SignalMap<Signal>,
+}
+
+impl RewriteReport {
+  /// Get the number of rewrites applied.
+  pub fn applied(&self) -> usize { self.applied }
+
+  /// Get the number of sweeps over the map.
+  pub fn sweeps(&self) -> usize { self.sweeps }
+
+  /// Check whether the rules reached a fixpoint within the sweep limit.
+  pub fn converged(&self) -> bool { self.converged }
+
+  /// Get the signal that now stands in for the given one: the end of its
+  /// chain of forwards, or the signal itself. Use this to update root
+  /// targets after a rewrite.
+  pub fn resolve(&self, mut signal: Signal) -> Signal {
+    // bounded, in case rules forwarded signals in a cycle
+    for _ in 0..=self.forwarded.len() {
+      match self.forwarded.get(&signal) {
+        Some(next) if *next != signal => signal = *next,
+        _ => break,
+      }
+    }
+    signal
+  }
+}
+
+impl<T: Relink> Rewriter<T> {
+  /// Create a rewriter with no rules.
+  pub fn new() -> Self { Self::default() }
+
+  /// Add a rule. Rules are tried in the order they were added, and the first
+  /// that matches a signal is applied.
+  pub fn with_rule(mut self, rule: impl RewriteRule<T> + 'static) -> Self {
+    self.rules.push(Box::new(rule));
+    self
+  }
+
+  /// Limit the number of sweeps, for rule sets that don't reach a fixpoint.
+  pub fn with_max_sweeps(mut self, max_sweeps: usize) -> Self {
+    self.max_sweeps = max_sweeps;
+    self
+  }
+
+  /// Rewrite the map until no rule matches, sweeping over every signal in ID
+  /// order and then rewiring dependents of forwarded signals.
+  pub fn run(&self, defset: &mut SignalDefMap<T>) -> RewriteReport {
+    let span = tracing::info_span!("rewrite", rules = self.rules.len());
+    let _enter = span.enter();
+
+    let mut report = RewriteReport::default();
+    let mut signals: Vec<_> = defset.iter().map(|(s, _)| s).collect();
+    signals.sort_by_key(|s| s.0);
+    let mut deps = Vec::new();
+
+    while report.sweeps < self.max_sweeps {
+      report.sweeps += 1;
+      let mut changed = false;
+      let mut forwarded = SignalSet::default();
+
+      for signal in signals.iter() {
+        let def = defset.get(*signal).unwrap();
+        let rewrite = self
+          .rules
+          .iter()
+          .find_map(|rule| rule.rewrite(*signal, def, defset));
+        match rewrite {
+          Some(Rewrite::Replace(def)) => {
+            defset.replace(*signal, def);
+          }
+          Some(Rewrite::Forward(to)) => {
+            if to == *signal || report.forwarded.get(signal) == Some(&to) {
+              continue;
+            }
+            report.forwarded.insert(*signal, to);
+            forwarded.insert(*signal);
+          }
+          None => continue,
+        }
+        report.applied += 1;
+        changed = true;
+      }
+
+      if !forwarded.is_empty() {
+        for signal in signals.iter() {
+          let def = defset.get(*signal).unwrap();
+          deps.clear();
+          def.dependencies_into(&mut deps);
+          if deps.iter().any(|dep| report.forwarded.contains_key(dep)) {
+            let relinked = def.relink(&mut |dep| report.resolve(dep));
+            defset.replace(*signal, relinked);
+          }
+        }
+      }
+
+      if !changed {
+        report.converged = true;
+        break;
+      }
+    }
+
+    tracing::info!(
+      applied = report.applied,
+      sweeps = report.sweeps,
+      converged = report.converged,
+      "rewrite complete"
+    );
+    report
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_rewrite_to_fixpoint() {
+    type Def = FloatMapSignalDef;
+    let is = |defset: &SignalDefMap<Def>, s: Signal, value: f64| matches!(defset.get(s), Some(Def::Constant(v)) if *v == value);
+
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(Def::Constant(1.0));
+    let b = defset.insert(Def::Constant(2.0));
+    let x = defset.insert(Def::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let one = defset.insert(Def::Constant(1.0));
+    let minus_one = defset.insert(Def::UnaryOp(UnaryOp::Neg(one)));
+    let neg = defset.insert(Def::UnaryOp(UnaryOp::Neg(x)));
+    let negneg = defset.insert(Def::UnaryOp(UnaryOp::Neg(neg)));
+    let times_one =
+      defset.insert(Def::BinaryOp(FloatBinaryOp::Mul(negneg, one)));
+    let total =
+      defset.insert(Def::BinaryOp(FloatBinaryOp::Add(times_one, times_one)));
+
+    let rewriter = Rewriter::new()
+      // x * 1 => x
+      .with_rule(move |_, def: &Def, defset: &SignalDefMap<Def>| match def {
+        Def::BinaryOp(FloatBinaryOp::Mul(a, b)) if is(defset, *b, 1.0) => {
+          Some(Rewrite::Forward(*a))
+        }
+        _ => None,
+      })
+      // --x => x
+      .with_rule(|_, def: &Def, defset: &SignalDefMap<Def>| match def {
+        Def::UnaryOp(UnaryOp::Neg(a)) => match defset.get(*a)? {
+          Def::UnaryOp(UnaryOp::Neg(inner)) => Some(Rewrite::Forward(*inner)),
+          _ => None,
+        },
+        _ => None,
+      })
+      // -c => the negated constant
+      .with_rule(|_, def: &Def, defset: &SignalDefMap<Def>| match def {
+        Def::UnaryOp(UnaryOp::Neg(a)) => match defset.get(*a)? {
+          Def::Constant(v) => Some(Rewrite::Replace(Def::Constant(-v))),
+          _ => None,
+        },
+        _ => None,
+      });
+    let report = rewriter.run(&mut defset);
+
+    assert!(report.converged());
+    assert_eq!(report.resolve(times_one), x);
+    assert!(matches!(
+      defset.get(total),
+      Some(Def::BinaryOp(FloatBinaryOp::Add(a, b))) if *a == x && *b == x
+    ));
+    assert!(is(&defset, minus_one, -1.0));
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    assert!(!plan.all_queued_targets().contains(&times_one));
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(total), Some(&6.0));
+  }
+}
@@ -0,0 +1,287 @@
+use crate::{
+  EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef, PlannedEvaluation,
+  Signal, SignalMap, UnaryOp,
+};
+
+/// A double-double number: an unevaluated sum of two `f64`s, with about 106
+/// bits of precision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dd {
+  hi: f64,
+  lo: f64,
+}
+
+fn two_sum(a: f64, b: f64) -> Dd {
+  let hi = a + b;
+  let bb = hi - a;
+  Dd {
+    hi,
+    lo: (a - (hi - bb)) + (b - bb),
+  }
+}
+
+fn fast_two_sum(a: f64, b: f64) -> Dd {
+  let hi = a + b;
+  Dd {
+    hi,
+    lo: b - (hi - a),
+  }
+}
+
+impl Dd {
+  fn exact(value: f64) -> Self { Dd { hi: value, lo: 0.0 } }
+
+  /// Keep non-finite results from turning into NaN through their error term.
+  fn normalize(self) -> Self {
+    match self.hi.is_finite() && self.lo.is_finite() {
+      true => fast_two_sum(self.hi, self.lo),
+      false => Dd::exact(self.hi),
+    }
+  }
+
+  fn neg(self) -> Self {
+    Dd {
+      hi: -self.hi,
+      lo: -self.lo,
+    }
+  }
+
+  fn add(self, other: Self) -> Self {
+    let s = two_sum(self.hi, other.hi);
+    Dd {
+      hi: s.hi,
+      lo: s.lo + self.lo + other.lo,
+    }
+    .normalize()
+  }
+
+  fn mul(self, other: Self) -> Self {
+    let hi = self.hi * other.hi;
+    let lo =
+      self.hi.mul_add(other.hi, -hi) + self.hi * other.lo + self.lo * other.hi;
+    Dd { hi, lo }.normalize()
+  }
+
+  fn div(self, other: Self) -> Self {
+    let q1 = self.hi / other.hi;
+    let r = self.add(other.mul(Dd::exact(q1)).neg());
+    Dd {
+      hi: q1,
+      lo: r.hi / other.hi,
+    }
+    .normalize()
+  }
+
+  /// `powf` of the high parts, corrected to first order for the low parts.
+  /// The rounding of `powf` itself isn't captured.
+  fn pow(self, other: Self) -> Self {
+    let hi = self.hi.powf(other.hi);
+    let lo = hi * (other.hi * self.lo / self.hi + self.hi.ln() * other.lo);
+    Dd { hi, lo }.normalize()
+  }
+}
+
+/// The rounding error of one signal, from
+/// [`PlannedEvaluation::rounding_errors`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundingError {
+  value:     f64,
+  reference: f64,
+  relative:  f64,
+  growth:    f64,
+}
+
+impl RoundingError {
+  /// Get the value computed in `f64`.
+  pub fn value(&self) -> f64 { self.value }
+
+  /// Get the value computed in extended precision, rounded to `f64`.
+  pub fn reference(&self) -> f64 { self.reference }
+
+  /// Get the absolute error of the `f64` value.
+  pub fn absolute(&self) -> f64 { (self.value - self.reference).abs() }
+
+  /// Get the error of the `f64` value relative to the reference, or the
+  /// absolute error if the reference is zero.
+  pub fn relative(&self) -> f64 { self.relative }
+
+  /// Get how many times larger the relative error is than the largest
+  /// relative error of the signal's dependencies, or than one unit roundoff
+  /// if they're exact. Large growth marks the operation that made a value
+  /// unstable.
+  pub fn growth(&self) -> f64 { self.growth }
+}
+
+/// Per-signal rounding errors of a run, from
+/// [`PlannedEvaluation::rounding_errors`].
+#[derive(Debug, Clone, Default)]
+pub struct RoundingReport {
+  roots:  Vec<Signal>,
+  errors: SignalMap<RoundingError>,
+}
+
+impl RoundingReport {
+  /// Get the error of a signal, if it was evaluated.
+  pub fn get(&self, signal: Signal) -> Option<&RoundingError> {
+    self.errors.get(&signal)
+  }
+
+  /// Get the errors of the root targets, sorted by ID.
+  pub fn roots(&self) -> impl Iterator<Item = (Signal, &RoundingError)> {
+    self.roots.iter().map(|s| (*s, &self.errors[s]))
+  }
+
+  /// Get the signals whose error [grew](RoundingError::growth) by at least
+  /// the given factor, largest growth first, to find the operations worth
+  /// restructuring.
+  pub fn unstable(&self, min_growth: f64) -> Vec<(Signal, &RoundingError)> {
+    let mut unstable: Vec<_> = self
+      .errors
+      .iter()
+      .filter(|(_, e)| e.growth >= min_growth)
+      .map(|(s, e)| (*s, e))
+      .collect();
+    unstable.sort_by(|(a, x), (b, y)| {
+      y.growth.total_cmp(&x.growth).then(a.0.cmp(&b.0))
+    });
+    unstable
+  }
+}
+
+impl PlannedEvaluation<'_, FloatMapSignalDef<f64>> {
+  /// Run the plan sequentially with every operation computed both in `f64`
+  /// and in double-double precision, reporting how far each signal's `f64`
+  /// value is from its extended-precision reference.
+  ///
+  /// Constants and values in the given map are taken as exact, so the report
+  /// shows the error accumulated by the graph's operations alone.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a dependency has neither been evaluated nor given a value.
+  pub fn rounding_errors(
+    &self,
+    values: &EvaluationValueMap<FloatMapSignalDef<f64>>,
+  ) -> RoundingReport {
+    let defset = self.matrix().defset();
+    let mut shadows: SignalMap<(f64, Dd)> = values
+      .values
+      .iter()
+      .filter_map(|(s, v)| Some((s, (*v.as_ref()?, Dd::exact(*v.as_ref()?)))))
+      .collect();
+    let mut errors = SignalMap::default();
+
+    for pass in self.passes() {
+      for target in pass.ordered_targets() {
+        if shadows.contains_key(&target) {
+          continue;
+        }
+        let get = |s: &Signal| shadows[s];
+        let rel = |s: &Signal| {
+          errors.get(s).map_or(0.0, |e: &RoundingError| e.relative)
+        };
+        let ((value, shadow), input_error) = match defset.get(target).unwrap() {
+          FloatMapSignalDef::Constant(c) => ((*c, Dd::exact(*c)), 0.0),
+          FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => {
+            let (v, d) = get(a);
+            ((-v, d.neg()), rel(a))
+          }
+          FloatMapSignalDef::BinaryOp(op) => {
+            let (FloatBinaryOp::Add(a, b)
+            | FloatBinaryOp::Sub(a, b)
+            | FloatBinaryOp::Mul(a, b)
+            | FloatBinaryOp::Div(a, b)
+            | FloatBinaryOp::Pow(a, b)) = op;
+            let ((x, dx), (y, dy)) = (get(a), get(b));
+            let result = match op {
+              FloatBinaryOp::Add(..) => (x + y, dx.add(dy)),
+              FloatBinaryOp::Sub(..) => (x - y, dx.add(dy.neg())),
+              FloatBinaryOp::Mul(..) => (x * y, dx.mul(dy)),
+              FloatBinaryOp::Div(..) => (x / y, dx.div(dy)),
+              FloatBinaryOp::Pow(..) => (x.powf(y), dx.pow(dy)),
+            };
+            (result, rel(a).max(rel(b)))
+          }
+        };
+
+        let reference = shadow.hi + shadow.lo;
+        let absolute = ((value - shadow.hi) - shadow.lo).abs();
+        let relative = match reference == 0.0 || !reference.is_finite() {
+          true => absolute,
+          false => absolute / reference.abs(),
+        };
+        let growth = relative / input_error.max(f64::EPSILON / 2.0);
+        errors.insert(target, RoundingError {
+          value,
+          reference,
+          relative,
+          growth,
+        });
+        shadows.insert(target, (value, shadow));
+      }
+    }
+
+    let mut roots: Vec<_> = self
+      .root_targets()
+      .iter()
+      .copied()
+      .filter(|s| errors.contains_key(s))
+      .collect();
+    roots.sort_by_key(|s| s.0);
+    RoundingReport { roots, errors }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_rounding_errors() {
+    let mut defset = SignalDefMap::new();
+    let big = defset.insert(FloatMapSignalDef::Constant(1e16));
+    let one = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let three = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(big, one)));
+    // (1e16 + 1) - 1e16 cancels catastrophically
+    let diff =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(sum, big)));
+    let third = defset
+      .insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(one, three)));
+    let scaled = defset.insert(FloatMapSignalDef::BinaryOp(
+      FloatBinaryOp::Mul(third, three),
+    ));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [diff, scaled]);
+    let report = plan.rounding_errors(&EvaluationValueMap::new_empty(
+      plan.all_queued_targets(),
+    ));
+
+    let diff_error = report.get(diff).unwrap();
+    assert_eq!((diff_error.value(), diff_error.reference()), (0.0, 1.0));
+    assert_eq!(diff_error.relative(), 1.0);
+    let third_error = report.get(third).unwrap();
+    assert!(third_error.relative() > 0.0 && third_error.relative() < 1e-16);
+    assert!(report.get(scaled).unwrap().relative() < 1e-15);
+    assert_eq!(report.roots().map(|(s, _)| s).collect::<Vec<_>>(), [
+      diff, scaled
+    ]);
+
+    // the subtraction is what blew the error up
+    let unstable = report.unstable(1e6);
+    assert_eq!(unstable.len(), 1);
+    assert_eq!(unstable[0].0, diff);
+
+    // given values are exact inputs
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [diff])
+      .skip_targets([sum]);
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    values.insert(sum, 1e16 + 2.0);
+    let report = plan.rounding_errors(&values);
+    assert_eq!(report.get(diff).unwrap().relative(), 0.0);
+    assert!(report.get(sum).is_none());
+  }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+
+#[cfg(feature = "rayon")]
+use crate::par::ThreadPools;
+#[cfg(feature = "affinity")]
+use crate::ThreadPlacement;
+use crate::{EvaluationPassDescriptor, FairShare, Summation};
+
+/// How many threads a single pass may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PassParallelism {
+  /// Use the whole global thread pool.
+  #[default]
+  Full,
+  /// Use at most this many threads. `Limited(1)` evaluates the pass
+  /// sequentially on the calling thread.
+  Limited(usize),
+}
+
+/// Decides the [`PassParallelism`] of each pass in a run.
+///
+/// Implemented for closures taking the pass index and descriptor. Without the
+/// `rayon` feature every pass is sequential and the policy is ignored.
+pub trait ParallelismPolicy: Sync {
+  /// Get the parallelism for the pass at the given index.
+  fn parallelism(
+    &self,
+    pass_index: usize,
+    pass: &EvaluationPassDescriptor,
+  ) -> PassParallelism;
+}
+
+impl<F> ParallelismPolicy for F
+where
+  F: Fn(usize, &EvaluationPassDescriptor) -> PassParallelism + Sync,
+{
+  fn parallelism(
+    &self,
+    pass_index: usize,
+    pass: &EvaluationPassDescriptor,
+  ) -> PassParallelism {
+    self(pass_index, pass)
+  }
+}
+
+/// Options for
+/// [`PlannedEvaluation::run_with`](crate::PlannedEvaluation::run_with).
+#[derive(Default)]
+pub struct RunOptions {
+  parallelism: Option<Box<dyn ParallelismPolicy>>,
+  limits:      Vec<(&'static str, usize)>,
+  fair_share:  Option<FairShare>,
+  summation:   Option<Summation>,
+  #[cfg(feature = "affinity")]
+  placement:   Option<ThreadPlacement>,
+  /// The pools of limited and pinned passes, built on first use and kept
+  /// for every run with these options.
+  #[cfg(feature = "rayon")]
+  pools:       ThreadPools,
+}
+
+impl RunOptions {
+  /// Create the default run options.
+  pub fn new() -> Self { Self::default() }
+
+  /// Set the policy deciding how many threads each pass may use. Passes
+  /// limited to the same number of threads share a pool, built by the first
+  /// run that needs it and kept for every later run with these options.
+  pub fn with_parallelism_policy(
+    mut self,
+    policy: impl ParallelismPolicy + 'static,
+  ) -> Self {
+    self.parallelism = Some(Box::new(policy));
+    self
+  }
+
+  /// Let at most `limit` signals of the given
+  /// [concurrency class](crate::SignalDef::concurrency_class) evaluate at once,
+  /// e.g. to cap simultaneous HTTP fetches independently of the thread count.
+  /// Setting a class again replaces its limit.
+  ///
+  /// Threads over the limit block until a signal of the class finishes.
+  pub fn with_concurrency_limit(
+    mut self,
+    class: &'static str,
+    limit: usize,
+  ) -> Self {
+    self.limits.retain(|(c, _)| *c != class);
+    self.limits.push((class, limit.max(1)));
+    self
+  }
+
+  /// Get the concurrency limits of the run, by class.
+  pub(crate) fn concurrency_limits(&self) -> &[(&'static str, usize)] {
+    &self.limits
+  }
+
+  /// Take a slot of the share's [`FairScheduler`](crate::FairScheduler) for
+  /// every target, so the run shares the pool fairly with other evaluations
+  /// by weight.
+  pub fn with_fair_share(mut self, share: FairShare) -> Self {
+    self.fair_share = Some(share);
+    self
+  }
+
+  /// Get the fair share of the run, if it has one.
+  pub(crate) fn fair_share(&self) -> Option<&FairShare> {
+    self.fair_share.as_ref()
+  }
+
+  /// Sum dependencies in a fixed order, so
+  /// [`EvalContext::sum_dependencies`](crate::EvalContext::sum_dependencies)
+  /// gives bit-for-bit the same results whatever the thread count.
+  pub fn with_reproducible_summation(mut self, summation: Summation) -> Self {
+    self.summation = Some(summation);
+    self
+  }
+
+  /// Get the fixed summation order of the run, if it has one.
+  pub(crate) fn summation(&self) -> Option<Summation> { self.summation }
+
+  /// Pin the run's evaluation threads to cores and set their priority. The
+  /// pinned pools are built by the first run and reused by every later run
+  /// with these options.
+  #[cfg(feature = "affinity")]
+  pub fn with_thread_placement(mut self, placement: ThreadPlacement) -> Self {
+    self.placement = Some(placement);
+    self.pools = ThreadPools::default();
+    self
+  }
+
+  /// Get the thread placement of the run, if it has one.
+  #[cfg(feature = "affinity")]
+  pub(crate) fn placement(&self) -> Option<&ThreadPlacement> {
+    self.placement.as_ref()
+  }
+
+  /// Get the thread pools of runs with these options.
+  #[cfg(feature = "rayon")]
+  pub(crate) fn thread_pools(&self) -> &ThreadPools { &self.pools }
+
+  /// Get the parallelism for the pass at the given index.
+  pub(crate) fn parallelism(
+    &self,
+    pass_index: usize,
+    pass: &EvaluationPassDescriptor,
+  ) -> PassParallelism {
+    self
+      .parallelism
+      .as_ref()
+      .map_or(PassParallelism::Full, |p| p.parallelism(pass_index, pass))
+  }
+}
+
+impl fmt::Debug for RunOptions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut debug = f.debug_struct("RunOptions");
+    debug.field("parallelism", &self.parallelism.is_some());
+    debug.field("limits", &self.limits);
+    debug.field("fair_share", &self.fair_share);
+    debug.field("summation", &self.summation);
+    #[cfg(feature = "affinity")]
+    debug.field("placement", &self.placement);
+    debug.finish()
+  }
+}
@@ -0,0 +1,116 @@
+use crate::{EvaluationPassDescriptor, PlannedEvaluation, SignalDef};
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Collapse every run of two or more consecutive passes holding a single
+  /// target into one [sequential
+  /// segment](EvaluationPassDescriptor::sequential). A chain thousands deep
+  /// then plans as one pass instead of thousands, each with its own
+  /// descriptor, span and barrier.
+  ///
+  /// Per-pass hooks, stats and profiles see a segment as one pass.
+  pub fn collapse_chains(mut self) -> Self {
+    let passes = std::mem::take(self.passes_mut());
+    let before = passes.len();
+    let mut collapsed = Vec::with_capacity(passes.len());
+    let mut run = Vec::new();
+    let flush =
+      |run: &mut Vec<_>, collapsed: &mut Vec<EvaluationPassDescriptor>| {
+        match run.len() {
+          0 => {}
+          1 => collapsed
+            .push(EvaluationPassDescriptor::new(run.drain(..).collect())),
+          _ => collapsed
+            .push(EvaluationPassDescriptor::sequential(std::mem::take(run))),
+        }
+      };
+    for pass in passes {
+      if pass.is_sequential() || pass.targets().len() == 1 {
+        run.extend(pass.ordered_targets());
+        continue;
+      }
+      flush(&mut run, &mut collapsed);
+      collapsed.push(pass);
+    }
+    flush(&mut run, &mut collapsed);
+    tracing::debug!(before, after = collapsed.len(), "collapsed chains");
+    *self.passes_mut() = collapsed;
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    IntMapSignalDef, IntOp, RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_collapse_chains() {
+    let mut defset = SignalDefMap::new();
+    let chain = GraphGenerator::new(GraphShape::Chain { length: 64 })
+      .build_float(&mut defset);
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let matrix = SignalMatrix::new(defset);
+
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), chain.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let depth = plan.passes().len();
+    let collapsed = plan.collapse_chains();
+    assert_eq!(collapsed.passes().len(), 1);
+    assert!(collapsed.passes()[0].is_sequential());
+    assert_eq!(collapsed.passes()[0].targets().len(), depth);
+    collapsed.verify().unwrap();
+    let values = collapsed.run(EvaluationValueMap::new_empty(
+      collapsed.all_queued_targets(),
+    ));
+    assert_eq!(values.get(chain[0]), expected.get(chain[0]));
+
+    // wide passes are kept, and end the chain's segment
+    let roots = chain.iter().copied().chain([sum]);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), roots)
+      .collapse_chains();
+    plan.verify().unwrap();
+    assert_eq!(plan.passes().len(), 3);
+    assert!(plan.passes()[0].is_sequential());
+    assert!(!plan.passes()[1].is_sequential());
+    assert!(!plan.passes()[2].is_sequential());
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    assert_eq!(values.get(chain[0]), expected.get(chain[0]));
+    assert_eq!(values.get(sum), Some(&3.0));
+  }
+
+  #[test]
+  fn test_segment_poisoning() {
+    let mut defset = SignalDefMap::new();
+    let zero = defset.insert(IntMapSignalDef::new(IntOp::Constant(0)));
+    let ten = defset.insert(IntMapSignalDef::new(IntOp::Constant(10)));
+    let bad = defset.insert(IntMapSignalDef::new(IntOp::Div(ten, zero)));
+    let worse = defset.insert(IntMapSignalDef::new(IntOp::Neg(bad)));
+    let worst = defset.insert(IntMapSignalDef::new(IntOp::Neg(worse)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [worst])
+      .collapse_chains();
+    assert_eq!(plan.passes().len(), 2);
+    assert!(plan.passes()[1].is_sequential());
+
+    // a failure stops the rest of its segment
+    let (values, report) = plan.run_poisoning(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    assert!(values.get(bad).unwrap().is_err());
+    assert_eq!(values.get(worse), None);
+    assert_eq!(values.get(worst), None);
+    assert_eq!(report.poisoned().count(), 2);
+    assert!(report.root_causes(worst).unwrap().contains(&bad));
+  }
+}
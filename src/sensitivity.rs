@@ -0,0 +1,135 @@
+use num_traits::Float;
+
+use crate::{
+  eval::RunHooks, EvaluationValueMap, PlannedEvaluation, RunOptions, Signal,
+  SignalDef,
+};
+
+/// Finite-difference sensitivities of output signals to input signals, from
+/// [`PlannedEvaluation::sensitivity`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensitivities<F> {
+  inputs:      Vec<Signal>,
+  outputs:     Vec<Signal>,
+  /// Row-major, one row per output.
+  derivatives: Vec<F>,
+}
+
+impl<F: Copy> Sensitivities<F> {
+  /// Get the perturbed inputs, in the order given.
+  pub fn inputs(&self) -> &[Signal] { &self.inputs }
+
+  /// Get the observed outputs, in the order given.
+  pub fn outputs(&self) -> &[Signal] { &self.outputs }
+
+  /// Get the estimated derivative of `output` with respect to `input`, if
+  /// both were part of the analysis.
+  pub fn get(&self, output: Signal, input: Signal) -> Option<F> {
+    let row = self.outputs.iter().position(|s| *s == output)?;
+    let column = self.inputs.iter().position(|s| *s == input)?;
+    Some(self.derivatives[row * self.inputs.len() + column])
+  }
+
+  /// Get the derivatives of `output` with respect to every input, in input
+  /// order.
+  pub fn row(&self, output: Signal) -> Option<&[F]> {
+    let row = self.outputs.iter().position(|s| *s == output)?;
+    let width = self.inputs.len();
+    Some(&self.derivatives[row * width..(row + 1) * width])
+  }
+}
+
+impl<T, F> PlannedEvaluation<'_, T>
+where
+  T: SignalDef<Value = F>,
+  F: Float + Send,
+{
+  /// Estimate how sensitive each output is to each input by forward
+  /// differences: every input is nudged by `epsilon` in its own run, and the
+  /// change in each output is divided by `epsilon`.
+  ///
+  /// Each perturbed run pins every signal the input can't affect to its
+  /// value from one unperturbed run, so only the input's dependents are
+  /// re-evaluated, and the runs execute in parallel.
+  ///
+  /// # Panics
+  ///
+  /// Panics if an input or output isn't evaluated by the plan.
+  pub fn sensitivity(
+    &self,
+    inputs: &[Signal],
+    outputs: &[Signal],
+    epsilon: F,
+  ) -> Sensitivities<F> {
+    let base = self.execute(
+      EvaluationValueMap::new_empty(self.all_queued_targets()),
+      &RunOptions::default(),
+      RunHooks {
+        keep_intermediates: true,
+        ..Default::default()
+      },
+    );
+    let value = |values: &EvaluationValueMap<T>, signal: Signal| {
+      *values
+        .get(signal)
+        .unwrap_or_else(|| panic!("{signal} isn't evaluated by the plan"))
+    };
+
+    let scenarios = inputs.iter().map(|input| {
+      let stale = self.matrix().dependents_closure([*input]);
+      let pinned = base
+        .values
+        .iter()
+        .filter(|(s, _)| !stale.contains(s) && s != input)
+        .filter_map(|(s, v)| Some((s, (*v)?)));
+      let nudged = (*input, value(&base, *input) + epsilon);
+      pinned.chain([nudged]).collect::<Vec<_>>()
+    });
+    let perturbed = self.run_scenarios(scenarios.collect::<Vec<_>>());
+
+    let mut derivatives = Vec::with_capacity(inputs.len() * outputs.len());
+    for output in outputs {
+      let before = value(&base, *output);
+      for values in perturbed.iter() {
+        derivatives.push((value(values, *output) - before) / epsilon);
+      }
+    }
+    Sensitivities {
+      inputs: inputs.to_vec(),
+      outputs: outputs.to_vec(),
+      derivatives,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_sensitivity() {
+    let mut defset = SignalDefMap::new();
+    let x = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let y = defset.insert(FloatMapSignalDef::Constant(4.0));
+    // x * y and x * x
+    let product =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(x, y)));
+    let square =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(x, x)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [product, square])
+      .with_free_intermediates(true);
+
+    let report = plan.sensitivity(&[x, y], &[product, square], 1e-6);
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-4;
+    assert!(close(report.get(product, x).unwrap(), 4.0));
+    assert!(close(report.get(product, y).unwrap(), 3.0));
+    assert!(close(report.get(square, x).unwrap(), 6.0));
+    assert_eq!(report.get(square, y), Some(0.0));
+    assert_eq!(report.row(square).unwrap().len(), 2);
+    assert_eq!(report.get(x, x), None);
+  }
+}
@@ -0,0 +1,488 @@
+//! An HTTP service for uploading [`GraphConfig`] graphs and evaluating them,
+//! for deploying the crate as a calculation service.
+//!
+//! Graphs are uploaded as the JSON form of a [`GraphConfig`] and stored in
+//! memory by name:
+//!
+//! - `PUT /graphs/{name}` uploads a graph, replacing any graph of that name.
+//!   Needs restricted clearance, as the graph carries its own visibility.
+//! - `POST /graphs/{name}/evaluate` evaluates a graph. The optional JSON body
+//!   `{"overrides": {"rate": 0.07}, "targets": ["total"]}` replaces the values
+//!   of some nodes and picks the nodes to report, which default to the graph's
+//!   roots. Responds with `{"values": {"total": 1967.15}}`.
+//! - `GET /graphs/{name}/results` gets the values of the latest evaluation.
+//!
+//! Requests are cleared for the [`Visibility`] their API token is granted in
+//! the server's [`AccessTokens`], sent as `Authorization: Bearer <token>`.
+//! Requests without a token are public, and unknown tokens are unauthorized.
+//! Requesting or overriding a node the request isn't cleared for is
+//! forbidden, as is overriding a node that feeds one it isn't cleared for,
+//! which could otherwise be solved for from the visible results. The default
+//! roots are narrowed to the visible ones, and results only include visible
+//! nodes. The latest results are kept per clearance, so
+//! results computed with restricted overrides are never served to callers
+//! with less clearance.
+//!
+//! Errors respond with `{"error": "..."}`.
+
+use std::{
+  collections::BTreeMap,
+  io,
+  sync::{Arc, RwLock},
+};
+
+use axum::{
+  extract::{Path, State},
+  http::{header, HeaderMap, StatusCode},
+  response::{IntoResponse, Response},
+  routing::{get, post, put},
+  Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  config::GraphConfig, EvaluationValueMap, FloatMapSignalDef, GraphRegistry,
+  Visibility,
+};
+
+/// A request to evaluate a graph.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvaluateRequest {
+  /// Values to use for the named nodes instead of evaluating them.
+  #[serde(default)]
+  pub overrides: BTreeMap<String, f64>,
+  /// The nodes to evaluate and report. If empty, the graph's roots.
+  #[serde(default)]
+  pub targets:   Vec<String>,
+}
+
+/// The values of an evaluation, by node name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvaluateResponse {
+  pub values: BTreeMap<String, f64>,
+}
+
+/// The registry tenant holding the uploaded graphs.
+const TENANT: &str = "server";
+
+/// The clearance granted to each API token, configured on the server.
+#[derive(Debug, Clone, Default)]
+pub struct AccessTokens {
+  tokens: BTreeMap<String, Visibility>,
+}
+
+impl AccessTokens {
+  /// Create a set of tokens with none granted, so every request is public.
+  pub fn new() -> Self { Self::default() }
+
+  /// Grant a token the given clearance.
+  pub fn with_token(
+    mut self,
+    token: impl Into<String>,
+    clearance: Visibility,
+  ) -> Self {
+    self.tokens.insert(token.into(), clearance);
+    self
+  }
+
+  /// Get the clearance of a request from its bearer token.
+  fn clearance(&self, headers: &HeaderMap) -> Result<Visibility, ServerError> {
+    let Some(value) = headers.get(header::AUTHORIZATION) else {
+      return Ok(Visibility::Public);
+    };
+    value
+      .to_str()
+      .ok()
+      .and_then(|value| value.strip_prefix("Bearer "))
+      .and_then(|token| self.tokens.get(token))
+      .copied()
+      .ok_or_else(|| {
+        ServerError(StatusCode::UNAUTHORIZED, "unknown API token".to_string())
+      })
+  }
+}
+
+#[derive(Default)]
+struct Graphs {
+  registry: GraphRegistry<FloatMapSignalDef>,
+  tokens:   AccessTokens,
+  /// The latest results of each graph per clearance, with the version
+  /// they're for.
+  results:  RwLock<BTreeMap<(String, Visibility), (u64, EvaluateResponse)>>,
+}
+
+type SharedGraphs = Arc<Graphs>;
+
+struct ServerError(StatusCode, String);
+
+impl IntoResponse for ServerError {
+  fn into_response(self) -> Response {
+    let body = serde_json::json!({ "error": self.1 });
+    (self.0, Json(body)).into_response()
+  }
+}
+
+fn not_found(name: &str) -> ServerError {
+  ServerError(StatusCode::NOT_FOUND, format!("no graph named `{name}`"))
+}
+
+/// Build the service's router, with no graphs uploaded and no API tokens,
+/// so every request is public.
+pub fn router() -> Router { router_with_tokens(AccessTokens::new()) }
+
+/// Build the service's router, with no graphs uploaded, clearing requests
+/// by the given API tokens.
+pub fn router_with_tokens(tokens: AccessTokens) -> Router {
+  Router::new()
+    .route("/graphs/{name}", put(upload))
+    .route("/graphs/{name}/evaluate", post(evaluate))
+    .route("/graphs/{name}/results", get(results))
+    .with_state(Arc::new(Graphs {
+      tokens,
+      ..Default::default()
+    }))
+}
+
+/// Serve the service on the given listener until the process ends.
+pub async fn serve(
+  listener: tokio::net::TcpListener,
+  tokens: AccessTokens,
+) -> io::Result<()> {
+  axum::serve(listener, router_with_tokens(tokens)).await
+}
+
+async fn upload(
+  State(graphs): State<SharedGraphs>,
+  Path(name): Path<String>,
+  headers: HeaderMap,
+  Json(config): Json<GraphConfig>,
+) -> Result<StatusCode, ServerError> {
+  if graphs.tokens.clearance(&headers)? < Visibility::Restricted {
+    return Err(ServerError(
+      StatusCode::FORBIDDEN,
+      "uploading graphs needs restricted clearance".to_string(),
+    ));
+  }
+  let graph = config.build().map_err(|e| {
+    ServerError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+  })?;
+  graphs
+    .registry
+    .insert(TENANT, &name, graph.defset, graph.roots)
+    .map_err(|e| {
+      ServerError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+    })?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+async fn evaluate(
+  State(graphs): State<SharedGraphs>,
+  Path(name): Path<String>,
+  headers: HeaderMap,
+  request: Option<Json<EvaluateRequest>>,
+) -> Result<Json<EvaluateResponse>, ServerError> {
+  let request = request.map(|Json(r)| r).unwrap_or_default();
+  let clearance = graphs.tokens.clearance(&headers)?;
+  let graph = graphs
+    .registry
+    .get(TENANT, &name)
+    .ok_or_else(|| not_found(&name))?;
+
+  let defset = graph.matrix().defset();
+  let lookup = |node: &str| {
+    defset.lookup(node).ok_or_else(|| {
+      ServerError(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        format!("no node named `{node}`"),
+      )
+    })
+  };
+  let targets = match request.targets.is_empty() {
+    true => graph
+      .roots()
+      .iter()
+      .copied()
+      .filter(|root| defset.is_visible(*root, clearance))
+      .collect(),
+    false => request
+      .targets
+      .iter()
+      .map(|node| lookup(node))
+      .collect::<Result<Vec<_>, _>>()?,
+  };
+  let overrides = request
+    .overrides
+    .iter()
+    .map(|(node, value)| Ok((lookup(node)?, *value)))
+    .collect::<Result<Vec<_>, _>>()?;
+  defset
+    .check_access(
+      targets
+        .iter()
+        .chain(overrides.iter().map(|(s, _)| s))
+        .copied(),
+      clearance,
+    )
+    .map_err(|e| ServerError(StatusCode::FORBIDDEN, e.to_string()))?;
+  for (signal, _) in overrides.iter() {
+    let affected = graph.matrix().dependents_closure([*signal]);
+    defset.check_access(affected, clearance).map_err(|e| {
+      ServerError(
+        StatusCode::FORBIDDEN,
+        format!("overriding `{}` would affect {e}", defset.display(*signal)),
+      )
+    })?;
+  }
+
+  // evaluation is CPU-bound, so keep it off the async workers
+  let version = graph.version();
+  let response = tokio::task::spawn_blocking(move || {
+    let skipped: Vec<_> = overrides.iter().map(|(signal, _)| *signal).collect();
+    let plan = graph.plan(&targets, &skipped);
+    let mut values = EvaluationValueMap::new_empty(Default::default());
+    for (signal, value) in overrides {
+      values.insert(signal, value);
+    }
+    let values = plan.run(values);
+
+    let values = targets
+      .iter()
+      .map(|target| {
+        let name = graph.matrix().defset().label(*target).unwrap().to_string();
+        (name, *values.get(*target).unwrap())
+      })
+      .collect();
+    EvaluateResponse { values }
+  })
+  .await
+  .map_err(|e| ServerError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+  // the graph may have been replaced while evaluating
+  let current = graphs.registry.get(TENANT, &name);
+  if current.is_some_and(|graph| graph.version() == version) {
+    let mut results = graphs.results.write().unwrap();
+    results.insert((name, clearance), (version, response.clone()));
+  }
+  Ok(Json(response))
+}
+
+async fn results(
+  State(graphs): State<SharedGraphs>,
+  Path(name): Path<String>,
+  headers: HeaderMap,
+) -> Result<Json<EvaluateResponse>, ServerError> {
+  let clearance = graphs.tokens.clearance(&headers)?;
+  let graph = graphs
+    .registry
+    .get(TENANT, &name)
+    .ok_or_else(|| not_found(&name))?;
+  let results = graphs.results.read().unwrap();
+  let results = results.get(&(name.clone(), clearance));
+  // results of a replaced graph don't count
+  let results = results.filter(|(version, _)| *version == graph.version());
+  let (_, results) = results.ok_or_else(|| {
+    ServerError(
+      StatusCode::NOT_FOUND,
+      format!("graph `{name}` hasn't been evaluated"),
+    )
+  })?;
+  let defset = graph.matrix().defset();
+  let values = results
+    .values
+    .iter()
+    .filter(|(node, _)| {
+      let signal = defset.lookup(node).unwrap();
+      defset.is_visible(signal, clearance)
+    })
+    .map(|(node, value)| (node.clone(), *value))
+    .collect();
+  Ok(Json(EvaluateResponse { values }))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{body::Body, http::Request};
+  use http_body_util::BodyExt;
+  use tower::ServiceExt;
+
+  use super::*;
+
+  async fn call(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    body: serde_json::Value,
+  ) -> (StatusCode, serde_json::Value) {
+    send(router, Request::builder(), method, uri, body).await
+  }
+
+  async fn call_as(
+    router: &Router,
+    token: &str,
+    method: &str,
+    uri: &str,
+    body: serde_json::Value,
+  ) -> (StatusCode, serde_json::Value) {
+    let builder = Request::builder()
+      .header(header::AUTHORIZATION, format!("Bearer {token}"));
+    send(router, builder, method, uri, body).await
+  }
+
+  async fn send(
+    router: &Router,
+    builder: axum::http::request::Builder,
+    method: &str,
+    uri: &str,
+    body: serde_json::Value,
+  ) -> (StatusCode, serde_json::Value) {
+    let request = builder
+      .method(method)
+      .uri(uri)
+      .header("content-type", "application/json")
+      .body(Body::from(body.to_string()))
+      .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = match body.is_empty() {
+      true => serde_json::Value::Null,
+      false => serde_json::from_slice(&body).unwrap(),
+    };
+    (status, body)
+  }
+
+  #[tokio::test]
+  async fn test_server() {
+    let router = router_with_tokens(
+      AccessTokens::new()
+        .with_token("hr", Visibility::Internal)
+        .with_token("payroll", Visibility::Restricted),
+    );
+    let graph = serde_json::json!({
+      "roots": ["total"],
+      "nodes": {
+        "principal": 1000.0,
+        "rate": 0.5,
+        "one": 1.0,
+        "growth": { "op": "add", "args": ["one", "rate"] },
+        "total": { "op": "mul", "args": ["principal", "growth"] },
+      },
+    });
+
+    // only a restricted caller may upload, since graphs carry visibility
+    let (status, _) = call(&router, "PUT", "/graphs/loan", graph.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) =
+      call_as(&router, "hr", "PUT", "/graphs/loan", graph.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) =
+      call_as(&router, "payroll", "PUT", "/graphs/loan", graph).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, body) = call(
+      &router,
+      "GET",
+      "/graphs/loan/results",
+      serde_json::Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].is_string());
+
+    let request = serde_json::json!({
+      "overrides": { "rate": 0.25 },
+      "targets": ["total", "growth"],
+    });
+    let (status, body) =
+      call(&router, "POST", "/graphs/loan/evaluate", request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+      body,
+      serde_json::json!({ "values": { "growth": 1.25, "total": 1250.0 } })
+    );
+
+    let (_, results) = call(
+      &router,
+      "GET",
+      "/graphs/loan/results",
+      serde_json::Value::Null,
+    )
+    .await;
+    assert_eq!(results, body);
+
+    let request = serde_json::json!({ "overrides": { "nope": 1.0 } });
+    let (status, _) =
+      call(&router, "POST", "/graphs/loan/evaluate", request).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // one graph serves clients with different clearances
+    let graph = serde_json::json!({
+      "roots": ["net", "tax"],
+      "nodes": {
+        "salary": 100.0,
+        "rate": 0.25,
+        "tax": { "op": "mul", "args": ["salary", "rate"] },
+        "net": { "op": "sub", "args": ["salary", "tax"] },
+      },
+      "visibility": { "salary": "restricted", "tax": "internal" },
+    });
+    call_as(&router, "payroll", "PUT", "/graphs/pay", graph).await;
+    let evaluate = "/graphs/pay/evaluate";
+    let (status, body) =
+      call(&router, "POST", evaluate, serde_json::json!({})).await;
+    assert_eq!(
+      (status, body),
+      (
+        StatusCode::OK,
+        serde_json::json!({ "values": { "net": 75.0 } })
+      )
+    );
+    let request = serde_json::json!({ "targets": ["tax"] });
+    let (status, _) = call(&router, "POST", evaluate, request.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // zeroing the public rate would make `net` equal the restricted salary
+    let leak = serde_json::json!({ "overrides": { "rate": 0.0 } });
+    let (status, body) = call(&router, "POST", evaluate, leak).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"]
+      .as_str()
+      .unwrap()
+      .contains("overriding `rate`"));
+    let (status, _) = call_as(&router, "hr", "POST", evaluate, request).await;
+    assert_eq!(status, StatusCode::OK);
+    let request = serde_json::json!({ "overrides": { "salary": 200.0 } });
+    let (status, _) =
+      call_as(&router, "hr", "POST", evaluate, request.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // clearance comes from the token, not from anything the client claims
+    let claimed = Request::builder().header("x-clearance", "restricted");
+    let (status, _) =
+      send(&router, claimed, "POST", evaluate, request.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, body) =
+      call_as(&router, "payroll", "POST", evaluate, request).await;
+    assert_eq!(
+      body,
+      serde_json::json!({ "values": { "net": 150.0, "tax": 50.0 } })
+    );
+
+    // public callers keep seeing their own latest results, not ones computed
+    // from a restricted override
+    let results = "/graphs/pay/results";
+    let (_, body) =
+      call(&router, "GET", results, serde_json::Value::Null).await;
+    assert_eq!(body, serde_json::json!({ "values": { "net": 75.0 } }));
+    let (_, body) =
+      call_as(&router, "payroll", "GET", results, serde_json::Value::Null)
+        .await;
+    assert_eq!(
+      body,
+      serde_json::json!({ "values": { "net": 150.0, "tax": 50.0 } })
+    );
+    let (status, _) =
+      call_as(&router, "root", "GET", results, serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+  }
+}
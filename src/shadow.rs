@@ -0,0 +1,212 @@
+use std::{fmt, thread};
+
+use crate::{
+  CustomPlanner, EvaluationValueMap, Signal, SignalDef, SignalMatrix,
+};
+
+type Distance<'a, V> = Box<dyn Fn(&V, &V) -> f64 + Sync + 'a>;
+
+/// Runs two matrices, e.g. the old and new versions of a production graph,
+/// against the same inputs concurrently and compares every signal the two
+/// share a label with, for migrating a graph safely.
+///
+/// Inputs are given by label and pin the signal in both matrices, as in
+/// [`run_with_overrides`](crate::PlannedEvaluation::run_with_overrides).
+pub struct ShadowRun<'a, T: SignalDef> {
+  primary:   &'a SignalMatrix<T>,
+  shadow:    &'a SignalMatrix<T>,
+  distance:  Distance<'a, T::Value>,
+  tolerance: f64,
+  inputs:    Vec<(String, T::Value)>,
+}
+
+impl<T: SignalDef> fmt::Debug for ShadowRun<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ShadowRun")
+      .field("tolerance", &self.tolerance)
+      .field("inputs", &self.inputs)
+      .finish_non_exhaustive()
+  }
+}
+
+/// A shared signal whose values differ by more than the tolerance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+  pub label:    String,
+  pub distance: f64,
+  /// The debug-formatted primary value.
+  pub primary:  String,
+  /// The debug-formatted shadow value.
+  pub shadow:   String,
+}
+
+/// The result of a [`ShadowRun`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShadowReport {
+  /// The number of shared signals compared.
+  pub compared:     usize,
+  /// Every shared signal beyond the tolerance, sorted by label.
+  pub divergences:  Vec<Divergence>,
+  /// Labels only the primary matrix has.
+  pub only_primary: Vec<String>,
+  /// Labels only the shadow matrix has.
+  pub only_shadow:  Vec<String>,
+}
+
+impl ShadowReport {
+  /// Check whether every shared signal agreed within the tolerance.
+  pub fn agrees(&self) -> bool { self.divergences.is_empty() }
+}
+
+impl<'a, T: SignalDef> ShadowRun<'a, T>
+where
+  T::Value: Clone,
+{
+  /// Create a shadow run of two matrices, measuring how far apart two values
+  /// are with `distance`. Values are compared with a tolerance of 0.
+  pub fn new(
+    primary: &'a SignalMatrix<T>,
+    shadow: &'a SignalMatrix<T>,
+    distance: impl Fn(&T::Value, &T::Value) -> f64 + Sync + 'a,
+  ) -> Self {
+    ShadowRun {
+      primary,
+      shadow,
+      distance: Box::new(distance),
+      tolerance: 0.0,
+      inputs: Vec::new(),
+    }
+  }
+
+  /// Report values whose distance is above this tolerance.
+  pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+    self.tolerance = tolerance;
+    self
+  }
+
+  /// Pin the signal with the given label to a value in both matrices.
+  pub fn with_input(
+    mut self,
+    label: impl Into<String>,
+    value: T::Value,
+  ) -> Self {
+    self.inputs.push((label.into(), value));
+    self
+  }
+
+  fn evaluate(
+    &self,
+    matrix: &SignalMatrix<T>,
+    shared: &[(&str, Signal, Signal)],
+    side: fn(&(&str, Signal, Signal)) -> Signal,
+  ) -> EvaluationValueMap<T> {
+    let overrides: Vec<_> = self
+      .inputs
+      .iter()
+      .map(|(label, value)| {
+        let signal = matrix.defset().lookup(label).unwrap_or_else(|| {
+          panic!("input {label:?} isn't defined in both matrices")
+        });
+        (signal, value.clone())
+      })
+      .collect();
+    let plan =
+      matrix.plan_evaluation(&CustomPlanner::new(), shared.iter().map(side));
+    let values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    plan.run_with_overrides(values, overrides)
+  }
+
+  /// Evaluate every shared signal in both matrices at once and compare them.
+  ///
+  /// # Panics
+  ///
+  /// Panics if an input label isn't defined in both matrices.
+  pub fn run(&self) -> ShadowReport {
+    let (primary, shadow) = (self.primary.defset(), self.shadow.defset());
+    let mut report = ShadowReport::default();
+    let mut shared = Vec::new();
+    for (label, signal) in primary.labels() {
+      match shadow.lookup(label) {
+        Some(other) => shared.push((label, signal, other)),
+        None => report.only_primary.push(label.to_string()),
+      }
+    }
+    report.only_shadow = shadow
+      .labels()
+      .filter(|(label, _)| primary.lookup(label).is_none())
+      .map(|(label, _)| label.to_string())
+      .collect();
+
+    let (primary_values, shadow_values) = thread::scope(|scope| {
+      let shadow_run =
+        scope.spawn(|| self.evaluate(self.shadow, &shared, |s| s.2));
+      let primary_values = self.evaluate(self.primary, &shared, |s| s.1);
+      (primary_values, shadow_run.join().unwrap())
+    });
+
+    for (label, signal, other) in shared {
+      let (Some(a), Some(b)) =
+        (primary_values.get(signal), shadow_values.get(other))
+      else {
+        continue;
+      };
+      report.compared += 1;
+      let distance = (self.distance)(a, b);
+      if distance.is_nan() || distance > self.tolerance {
+        report.divergences.push(Divergence {
+          label: label.to_string(),
+          distance,
+          primary: format!("{a:?}"),
+          shadow: format!("{b:?}"),
+        });
+      }
+    }
+    report
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap, UnaryOp};
+
+  #[test]
+  fn test_shadow_run() {
+    let build = |scale: f64, extra: &str| {
+      let mut defset = SignalDefMap::new();
+      let x = defset.insert_labeled("x", FloatMapSignalDef::Constant(0.0));
+      let k = defset.insert(FloatMapSignalDef::Constant(scale));
+      let scaled = defset.insert_labeled(
+        "scaled",
+        FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(x, k)),
+      );
+      defset
+        .insert_labeled("negated", FloatMapSignalDef::UnaryOp(UnaryOp::Neg(x)));
+      defset.insert_labeled(
+        extra,
+        FloatMapSignalDef::UnaryOp(UnaryOp::Neg(scaled)),
+      );
+      SignalMatrix::new(defset)
+    };
+    let old = build(2.0, "old_only");
+    let new = build(2.001, "new_only");
+    let distance = |a: &f64, b: &f64| (a - b).abs();
+
+    let report = ShadowRun::new(&old, &new, distance)
+      .with_input("x", 10.0)
+      .with_tolerance(0.001)
+      .run();
+    assert_eq!(report.compared, 3);
+    assert_eq!(report.only_primary, ["old_only"]);
+    assert_eq!(report.only_shadow, ["new_only"]);
+    assert_eq!(report.divergences.len(), 1);
+    assert_eq!(report.divergences[0].label, "scaled");
+    assert_eq!(report.divergences[0].primary, "20.0");
+
+    let report = ShadowRun::new(&old, &new, distance)
+      .with_input("x", 10.0)
+      .with_tolerance(0.1)
+      .run();
+    assert!(report.agrees());
+  }
+}
@@ -0,0 +1,113 @@
+use crate::{
+  EvaluationPlanner, EvaluationValueMap, PlannedEvaluation, Signal, SignalDef,
+  SignalMatrix,
+};
+
+/// Steps a graph through time by re-running one plan, where each delay signal
+/// takes the value its source signal had on the previous step.
+///
+/// Delay signals break feedback loops: they have no dependencies of their own
+/// as far as the planner is concerned, and are never evaluated. Their
+/// definitions are only placeholders.
+pub struct Simulation<'m, T: SignalDef> {
+  plan:    PlannedEvaluation<'m, T>,
+  delays:  Vec<(Signal, Signal)>,
+  initial: Option<Vec<T::Value>>,
+  values:  EvaluationValueMap<T>,
+  steps:   u64,
+}
+
+impl<'m, T: SignalDef> Simulation<'m, T> {
+  /// Create a new simulation of the given root targets. `delays` lists each
+  /// delay signal with its source signal and its value on the first step.
+  pub fn new<P: EvaluationPlanner<T> + ?Sized>(
+    matrix: &'m SignalMatrix<T>,
+    planner: &P,
+    root_targets: impl IntoIterator<Item = Signal>,
+    delays: impl IntoIterator<Item = (Signal, Signal, T::Value)>,
+  ) -> Self {
+    let (links, initial): (Vec<_>, Vec<_>) = delays
+      .into_iter()
+      .map(|(delay, source, value)| ((delay, source), value))
+      .unzip();
+
+    // sources must be evaluated every step to feed their delays
+    let roots = root_targets
+      .into_iter()
+      .chain(links.iter().map(|(_, source)| *source));
+    let plan = matrix.plan_evaluation(planner, roots);
+
+    // every delay always has a value before a step runs, so drop them from the
+    // plan once up front
+    let plan = plan.skip_targets(links.iter().map(|(delay, _)| *delay));
+
+    Simulation {
+      plan,
+      delays: links,
+      initial: Some(initial),
+      values: EvaluationValueMap::new_empty(Default::default()),
+      steps: 0,
+    }
+  }
+
+  /// Run one step and return its values.
+  pub fn step(&mut self) -> &EvaluationValueMap<T> {
+    let mut next = EvaluationValueMap::new_empty(Default::default());
+    match self.initial.take() {
+      Some(initial) => {
+        for ((delay, _), value) in self.delays.iter().zip(initial) {
+          next.insert(*delay, value);
+        }
+      }
+      None => {
+        for (delay, source) in self.delays.iter() {
+          let value = self.values.take(*source).unwrap_or_else(|| {
+            panic!("delay source {source:?} has no value from the last step")
+          });
+          next.insert(*delay, value);
+        }
+      }
+    }
+
+    self.values = self.plan.run(next);
+    self.steps += 1;
+    &self.values
+  }
+
+  /// Get the values from the most recent step.
+  pub fn values(&self) -> &EvaluationValueMap<T> { &self.values }
+
+  /// Get the number of steps run so far.
+  pub fn steps(&self) -> u64 { self.steps }
+
+  /// Get the plan run on every step.
+  pub fn plan(&self) -> &PlannedEvaluation<'m, T> { &self.plan }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap};
+
+  #[test]
+  fn test_simulation_feedback() {
+    let mut defset = SignalDefMap::new();
+
+    // count = previous count + 1
+    let previous = defset.insert(FloatMapSignalDef::Constant(f64::NAN));
+    let one = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let count = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(
+      previous, one,
+    )));
+
+    let matrix = SignalMatrix::new(defset);
+    let mut sim = Simulation::new(&matrix, &CustomPlanner::new(), [count], [(
+      previous, count, 0.0,
+    )]);
+
+    assert_eq!(sim.step().get(count).unwrap(), &1.0);
+    assert_eq!(sim.step().get(count).unwrap(), &2.0);
+    assert_eq!(sim.step().get(count).unwrap(), &3.0);
+    assert_eq!(sim.steps(), 3);
+  }
+}
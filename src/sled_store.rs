@@ -0,0 +1,111 @@
+use std::{io, path::Path};
+
+use crate::{distributed::WireValue, Signal, ValueStore};
+
+/// A [`ValueStore`] in a [sled](https://docs.rs/sled) database, with values
+/// encoded as [`WireValue`]s.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+  tree: sled::Tree,
+}
+
+impl SledStore {
+  /// Open or create the database at the given path.
+  pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+    Ok(SledStore::new(
+      sled::open(path)
+        .map_err(io::Error::other)?
+        .open_tree("values")
+        .map_err(io::Error::other)?,
+    ))
+  }
+
+  /// Store values in the given tree of an open database.
+  pub fn new(tree: sled::Tree) -> Self { SledStore { tree } }
+}
+
+impl<V: WireValue> ValueStore<V> for SledStore {
+  fn load(&self, signal: Signal) -> io::Result<Option<V>> {
+    let bytes = self
+      .tree
+      .get(signal.0.to_be_bytes())
+      .map_err(io::Error::other)?;
+    bytes.map(|bytes| V::decode(&bytes)).transpose()
+  }
+
+  fn save(&self, signal: Signal, value: &V) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes);
+    self
+      .tree
+      .insert(signal.0.to_be_bytes(), bytes)
+      .map_err(io::Error::other)?;
+    Ok(())
+  }
+
+  fn remove(&self, signal: Signal) -> io::Result<()> {
+    self
+      .tree
+      .remove(signal.0.to_be_bytes())
+      .map_err(io::Error::other)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    SignalDefMap, SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_values_survive_restart() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.5));
+    let b = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(a, b)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [c]);
+
+    let path = std::env::temp_dir()
+      .join(format!("matrix-sled-store-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let open = || Arc::new(SledStore::open(&path).unwrap());
+
+    let store = open();
+    let values = EvaluationValueMap::new_empty(plan.all_queued_targets())
+      .with_store(store.clone())
+      .unwrap();
+    let values = plan.run(values);
+    assert_eq!(values.get(c), Some(&-2.25));
+    drop((values, store));
+
+    // a fresh map reads the stored values through, so nothing is re-run
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets())
+      .with_store(open())
+      .unwrap();
+    assert_eq!(values.get(c), Some(&-2.25));
+    let rerun = matrix
+      .plan_evaluation(&CustomPlanner::new(), [c])
+      .skip_evaluated(&values);
+    assert!(rerun.all_queued_targets().is_empty());
+
+    values.invalidate([c]);
+    drop(values);
+    let values = EvaluationValueMap::<FloatMapSignalDef>::new_empty(
+      plan.all_queued_targets(),
+    )
+    .with_store(open())
+    .unwrap();
+    assert_eq!(values.get(c), None);
+    assert_eq!(values.get(b), Some(&-1.5));
+
+    drop(values);
+    std::fs::remove_dir_all(&path).unwrap();
+  }
+}
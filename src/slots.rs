@@ -0,0 +1,109 @@
+use crate::Signal;
+
+/// A vector of value slots indexed by signal ID, for the dense, sequential
+/// IDs a [`SignalDefMap`](crate::SignalDefMap) hands out, so reading a
+/// dependency's value is an index instead of a hash lookup.
+///
+/// A slot is either absent, present but unevaluated (`Some(None)`), or
+/// evaluated, mirroring a map from signals to optional values.
+#[derive(Debug, Clone)]
+pub(crate) struct ValueSlots<V> {
+  slots: Vec<Option<Option<V>>>,
+}
+
+impl<V> ValueSlots<V> {
+  /// Create unevaluated slots for the given signals, sized to the largest
+  /// ID among them.
+  pub(crate) fn pending<'s>(
+    signals: impl IntoIterator<Item = &'s Signal> + Clone,
+  ) -> Self {
+    let size = signals.clone().into_iter().map(|s| s.index() + 1).max();
+    let mut slots = Vec::new();
+    slots.resize_with(size.unwrap_or(0), || None);
+    for signal in signals {
+      slots[signal.index()] = Some(None);
+    }
+    ValueSlots { slots }
+  }
+
+  pub(crate) fn get(&self, signal: &Signal) -> Option<&Option<V>> {
+    self.slots.get(signal.index())?.as_ref()
+  }
+
+  pub(crate) fn get_mut(&mut self, signal: &Signal) -> Option<&mut Option<V>> {
+    self.slots.get_mut(signal.index())?.as_mut()
+  }
+
+  /// Fill a slot, returning its previous contents.
+  pub(crate) fn insert(
+    &mut self,
+    signal: Signal,
+    value: Option<V>,
+  ) -> Option<Option<V>> {
+    let index = signal.index();
+    if index >= self.slots.len() {
+      self.slots.resize_with(index + 1, || None);
+    }
+    self.slots[index].replace(value)
+  }
+
+  /// Empty a slot, returning its previous contents.
+  pub(crate) fn remove(&mut self, signal: &Signal) -> Option<Option<V>> {
+    self.slots.get_mut(signal.index())?.take()
+  }
+
+  /// Iterate over the present slots, by ID.
+  pub(crate) fn iter(&self) -> impl Iterator<Item = (Signal, &Option<V>)> {
+    self
+      .slots
+      .iter()
+      .enumerate()
+      .filter_map(|(i, slot)| Some((Signal::from_index(i), slot.as_ref()?)))
+  }
+
+  /// Iterate mutably over the present slots, by ID.
+  pub(crate) fn iter_mut(
+    &mut self,
+  ) -> impl Iterator<Item = (Signal, &mut Option<V>)> {
+    self
+      .slots
+      .iter_mut()
+      .enumerate()
+      .filter_map(|(i, slot)| Some((Signal::from_index(i), slot.as_mut()?)))
+  }
+}
+
+impl<V> IntoIterator for ValueSlots<V> {
+  type Item = (Signal, Option<V>);
+  type IntoIter = std::iter::FilterMap<
+    std::iter::Enumerate<std::vec::IntoIter<Option<Option<V>>>>,
+    fn((usize, Option<Option<V>>)) -> Option<(Signal, Option<V>)>,
+  >;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self
+      .slots
+      .into_iter()
+      .enumerate()
+      .filter_map(|(i, slot)| Some((Signal::from_index(i), slot?)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_value_slots() {
+    let mut slots = ValueSlots::pending(&[Signal(1), Signal(3)]);
+    assert_eq!(slots.get(&Signal(1)), Some(&None));
+    assert_eq!(slots.get(&Signal(2)), None);
+    assert_eq!(slots.insert(Signal(3), Some(7)), Some(None));
+    // slots past the pre-sized end grow the vector
+    assert_eq!(slots.insert(Signal(9), Some(1)), None);
+    assert_eq!(slots.remove(&Signal(1)), Some(None));
+    let present: Vec<_> = slots.iter().collect();
+    assert_eq!(present, [(Signal(3), &Some(7)), (Signal(9), &Some(1))]);
+    assert_eq!(slots.into_iter().count(), 2);
+  }
+}
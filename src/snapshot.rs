@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use crate::{PlannedEvaluation, Signal, SignalDef, SignalDefMap};
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Sort signals into snapshot order: labeled signals first by label, then
+  /// the rest by ID.
+  fn sort_for_snapshot(&self, signals: &mut [Signal]) {
+    signals.sort_by(|a, b| match (self.label(*a), self.label(*b)) {
+      (Some(a), Some(b)) => a.cmp(b),
+      (Some(_), None) => std::cmp::Ordering::Less,
+      (None, Some(_)) => std::cmp::Ordering::Greater,
+      (None, None) => a.0.cmp(&b.0),
+    });
+  }
+
+  /// Write the list of signals in snapshot order.
+  fn write_snapshot_list(&self, out: &mut String, signals: &mut [Signal]) {
+    self.sort_for_snapshot(signals);
+    self.write_snapshot_names(out, signals);
+  }
+
+  /// Write a comma-separated list of signal names, in the given order.
+  fn write_snapshot_names(&self, out: &mut String, signals: &[Signal]) {
+    for (i, signal) in signals.iter().enumerate() {
+      if i > 0 {
+        out.push_str(", ");
+      }
+      write!(out, "{}", self.display(*signal)).unwrap();
+    }
+  }
+
+  /// Render the structure of the graph as canonical text for snapshot tests:
+  /// a line per signal, listing its dependencies.
+  ///
+  /// Signals are named by label, falling back to `#id`, and both lines and
+  /// dependency lists are sorted with labeled signals first by label, then
+  /// the rest by ID. Definitions themselves aren't included.
+  pub fn snapshot(&self) -> String {
+    let mut signals: Vec<_> = self.iter().map(|(s, _)| s).collect();
+    self.sort_for_snapshot(&mut signals);
+
+    let mut out = String::new();
+    let mut deps = Vec::new();
+    for signal in signals {
+      write!(out, "{}", self.display(signal)).unwrap();
+      deps.clear();
+      self.get(signal).unwrap().dependencies_into(&mut deps);
+      deps.sort_unstable_by_key(|s| s.0);
+      deps.dedup();
+      if !deps.is_empty() {
+        out.push_str(" <- ");
+        self.write_snapshot_list(&mut out, &mut deps);
+      }
+      out.push('\n');
+    }
+    out
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Render the plan as canonical text for snapshot tests: the root targets,
+  /// then a line per pass listing its targets. Sequential segments are
+  /// marked, and list their targets in evaluation order.
+  ///
+  /// Targets are named and ordered like in [`SignalDefMap::snapshot`], so the
+  /// text only changes when the planner's output does.
+  pub fn snapshot(&self) -> String {
+    let defset = self.matrix().defset();
+    let mut out = String::from("roots: ");
+    let mut roots: Vec<_> = self.root_targets().iter().copied().collect();
+    defset.write_snapshot_list(&mut out, &mut roots);
+    out.push('\n');
+    for (i, pass) in self.passes().iter().enumerate() {
+      if pass.is_sequential() {
+        write!(out, "pass {i} (sequential): ").unwrap();
+        defset.write_snapshot_names(&mut out, &pass.ordered_targets());
+      } else {
+        write!(out, "pass {i}: ").unwrap();
+        let mut targets: Vec<_> = pass.targets().iter().copied().collect();
+        defset.write_snapshot_list(&mut out, &mut targets);
+      }
+      out.push('\n');
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalDefMap,
+    SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_snapshot() {
+    let mut defset = SignalDefMap::new();
+    let b = defset.insert_labeled("b", FloatMapSignalDef::Constant(2.0));
+    let a = defset.insert_labeled("a", FloatMapSignalDef::Constant(1.0));
+    let c = defset.insert(FloatMapSignalDef::Constant(3.0));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(c, b)));
+    let total = defset.insert_labeled(
+      "total",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(sum, a)),
+    );
+    let neg = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(sum)));
+
+    assert_eq!(
+      defset.snapshot(),
+      "a\nb\ntotal <- a, #3\n#2\n#3 <- b, #2\n#5 <- #3\n"
+    );
+
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [neg, total]);
+    assert_eq!(
+      plan.snapshot(),
+      "roots: total, #5\npass 0: b, #2\npass 1: a, #3\npass 2: total, #5\n"
+    );
+
+    let plan = matrix
+      .plan_evaluation(&CustomPlanner::new(), [neg])
+      .collapse_chains();
+    assert_eq!(
+      plan.snapshot(),
+      "roots: #5\npass 0: b, #2\npass 1 (sequential): #3, #5\n"
+    );
+  }
+}
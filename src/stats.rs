@@ -0,0 +1,207 @@
+use std::{cmp::Reverse, fmt, sync::Mutex, time::Duration};
+
+use crate::{
+  eval::RunHooks, EvaluationValueMap, PlannedEvaluation, RunOptions, Signal,
+  SignalDef, SignalMap,
+};
+
+/// Accumulated statistics of one signal in a [`StatsRegistry`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SignalStats {
+  evaluations: u64,
+  errors:      u64,
+  total:       Duration,
+  max:         Duration,
+}
+
+impl SignalStats {
+  /// Get the number of times the signal was evaluated.
+  pub fn evaluations(&self) -> u64 { self.evaluations }
+
+  /// Get the number of evaluations that produced a failure.
+  pub fn errors(&self) -> u64 { self.errors }
+
+  /// Get the total time spent evaluating the signal.
+  pub fn total_duration(&self) -> Duration { self.total }
+
+  /// Get the mean time of one evaluation.
+  pub fn mean_duration(&self) -> Duration {
+    match self.evaluations {
+      0 => Duration::ZERO,
+      n => self.total.div_f64(n as f64),
+    }
+  }
+
+  /// Get the time of the slowest evaluation.
+  pub fn max_duration(&self) -> Duration { self.max }
+}
+
+type Failure<V> = Box<dyn Fn(&V) -> bool + Send + Sync>;
+
+#[derive(Default)]
+struct Tally {
+  runs:    u64,
+  signals: SignalMap<SignalStats>,
+}
+
+/// Per-signal statistics accumulated over many runs with
+/// [`PlannedEvaluation::run_recorded`], for finding hot or flaky signals in
+/// long-lived services.
+///
+/// The registry is shared by reference, so concurrent runs can record into
+/// the same one.
+pub struct StatsRegistry<V> {
+  tally:   Mutex<Tally>,
+  failure: Option<Failure<V>>,
+}
+
+impl<V> Default for StatsRegistry<V> {
+  fn default() -> Self {
+    StatsRegistry {
+      tally:   Mutex::default(),
+      failure: None,
+    }
+  }
+}
+
+impl<V> fmt::Debug for StatsRegistry<V> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let tally = self.tally.lock().unwrap();
+    f.debug_struct("StatsRegistry")
+      .field("runs", &tally.runs)
+      .field("signals", &tally.signals.len())
+      .finish()
+  }
+}
+
+impl<V> StatsRegistry<V> {
+  /// Create an empty registry that counts no errors.
+  pub fn new() -> Self { Self::default() }
+
+  /// Count evaluations whose value fails the given check as errors.
+  pub fn with_failure_check(
+    mut self,
+    failed: impl Fn(&V) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.failure = Some(Box::new(failed));
+    self
+  }
+
+  /// Get the number of runs recorded.
+  pub fn runs(&self) -> u64 { self.tally.lock().unwrap().runs }
+
+  /// Get the statistics of a signal, if it was ever evaluated.
+  pub fn get(&self, signal: Signal) -> Option<SignalStats> {
+    self.tally.lock().unwrap().signals.get(&signal).copied()
+  }
+
+  /// Get the statistics of every recorded signal, sorted by ID.
+  pub fn snapshot(&self) -> Vec<(Signal, SignalStats)> {
+    let tally = self.tally.lock().unwrap();
+    let mut stats: Vec<_> = tally
+      .signals
+      .iter()
+      .map(|(s, stats)| (*s, *stats))
+      .collect();
+    stats.sort_by_key(|(s, _)| s.0);
+    stats
+  }
+
+  /// Get the `n` signals with the most total evaluation time, slowest first.
+  pub fn hottest(&self, n: usize) -> Vec<(Signal, SignalStats)> {
+    let mut stats = self.snapshot();
+    stats.sort_by_key(|(_, stats)| Reverse(stats.total));
+    stats.truncate(n);
+    stats
+  }
+
+  /// Get the `n` signals with the most errors, most first, leaving out
+  /// signals that never failed.
+  pub fn most_failing(&self, n: usize) -> Vec<(Signal, SignalStats)> {
+    let mut stats = self.snapshot();
+    stats.retain(|(_, stats)| stats.errors > 0);
+    stats.sort_by_key(|(_, stats)| Reverse(stats.errors));
+    stats.truncate(n);
+    stats
+  }
+
+  /// Clear every statistic and the run count.
+  pub fn reset(&self) { *self.tally.lock().unwrap() = Tally::default(); }
+
+  pub(crate) fn start_run(&self) { self.tally.lock().unwrap().runs += 1; }
+
+  pub(crate) fn is_failure(&self, value: &V) -> bool {
+    self.failure.as_ref().is_some_and(|failed| failed(value))
+  }
+
+  /// Record the evaluations of a pass as signals with their evaluation time
+  /// and whether they failed.
+  pub(crate) fn record(&self, pass: &[(Signal, Duration, bool)]) {
+    let mut tally = self.tally.lock().unwrap();
+    for (signal, duration, failed) in pass {
+      let stats = tally.signals.entry(*signal).or_default();
+      stats.evaluations += 1;
+      stats.errors += u64::from(*failed);
+      stats.total += *duration;
+      stats.max = stats.max.max(*duration);
+    }
+  }
+}
+
+impl<T: SignalDef> PlannedEvaluation<'_, T> {
+  /// Run the planned evaluation like [`run_with`](Self::run_with), adding the
+  /// time and outcome of every evaluation to the registry.
+  pub fn run_recorded(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    registry: &StatsRegistry<T::Value>,
+  ) -> EvaluationValueMap<T> {
+    self.execute(values, options, RunHooks {
+      stats: Some(registry),
+      ..Default::default()
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, IntMapSignalDef, IntOp, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_stats_accumulate() {
+    let mut defset = SignalDefMap::new();
+    let zero = defset.insert(IntMapSignalDef::new(IntOp::Constant(0)));
+    let ten = defset.insert(IntMapSignalDef::new(IntOp::Constant(10)));
+    let bad = defset.insert(IntMapSignalDef::new(IntOp::Div(ten, zero)));
+    let fine = defset.insert(IntMapSignalDef::new(IntOp::Add(ten, ten)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [bad, fine]);
+
+    let registry = StatsRegistry::new().with_failure_check(Result::is_err);
+    for _ in 0..3 {
+      plan.run_recorded(
+        EvaluationValueMap::new_empty(plan.all_queued_targets()),
+        &RunOptions::new(),
+        &registry,
+      );
+    }
+    assert_eq!(registry.runs(), 3);
+    let stats = registry.get(fine).unwrap();
+    assert_eq!((stats.evaluations(), stats.errors()), (3, 0));
+    assert!(stats.max_duration() <= stats.total_duration());
+    assert_eq!(registry.get(bad).unwrap().errors(), 3);
+    assert_eq!(registry.most_failing(10), [(
+      bad,
+      registry.get(bad).unwrap()
+    )]);
+    assert_eq!(registry.hottest(2).len(), 2);
+
+    registry.reset();
+    assert_eq!(registry.runs(), 0);
+    assert!(registry.snapshot().is_empty());
+  }
+}
@@ -0,0 +1,230 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use crate::{
+  par::PassExecutor, ContextScratch, EvaluationValueMap, PlannedEvaluation,
+  RunOptions, Signal, SignalDef, SignalSet,
+};
+
+/// Runs a [`PlannedEvaluation`] one pass at a time, for hosts that need to
+/// interleave a long run with their own work. Created with
+/// [`PlannedEvaluation::stepper`].
+pub struct PassStepper<'p, 'm, T: SignalDef> {
+  plan:     &'p PlannedEvaluation<'m, T>,
+  options:  &'p RunOptions,
+  values:   EvaluationValueMap<T>,
+  releases: Option<Vec<SignalSet>>,
+  executor: PassExecutor,
+  next:     usize,
+}
+
+impl<T: SignalDef> PassStepper<'_, '_, T> {
+  /// Run the next pass, returning its index, or `None` if every pass has
+  /// run.
+  pub fn step_pass(&mut self) -> Option<usize> {
+    self.step_pass_observed(|_, _| {})
+  }
+
+  /// Run the next pass like [`step_pass`](Self::step_pass), calling
+  /// `observe` with a borrow of each value the pass evaluated, by ID, before
+  /// any intermediates are freed. Lets exporters stream every value without
+  /// keeping or cloning them.
+  pub fn step_pass_observed(
+    &mut self,
+    mut observe: impl FnMut(Signal, &T::Value),
+  ) -> Option<usize> {
+    let i = self.next;
+    let pass = self.plan.passes().get(i)?;
+    let pass_span = tracing::info_span!("evaluation_pass", i);
+    let _enter = pass_span.enter();
+
+    if pass.is_sequential() {
+      // a segment's targets each see the values of those before them
+      for target in pass.sequence().iter().copied() {
+        let value = {
+          let mut scratch = ContextScratch::new(self.options.summation());
+          self
+            .plan
+            .evaluate_target(i, target, &self.values, &mut scratch)
+        };
+        self.values.insert_evaluated(target, value);
+      }
+    } else {
+      let values = &self.values;
+      let evaluations = self.executor.map_collect(
+        self.options.parallelism(i, pass),
+        pass.targets(),
+        || ContextScratch::new(self.options.summation()),
+        |scratch, target| {
+          (
+            target,
+            self.plan.evaluate_target(i, target, values, scratch),
+          )
+        },
+      );
+      for (target, value) in evaluations {
+        self.values.insert_evaluated(target, value);
+      }
+    }
+    self
+      .values
+      .for_each_of(pass.ordered_targets(), &mut observe);
+    if let Some(releases) = &self.releases {
+      for signal in releases[i].iter() {
+        self.values.values.remove(signal);
+      }
+    }
+
+    self.next += 1;
+    Some(i)
+  }
+
+  /// Get the number of passes left to run.
+  pub fn remaining(&self) -> usize { self.plan.passes().len() - self.next }
+
+  /// Get the values so far.
+  pub fn values(&self) -> &EvaluationValueMap<T> { &self.values }
+
+  /// Stop stepping and take the values so far.
+  pub fn into_values(self) -> EvaluationValueMap<T> { self.values }
+}
+
+/// A future that is pending once before completing, handing control back to
+/// the executor polling it.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    if self.0 {
+      return Poll::Ready(());
+    }
+    self.0 = true;
+    cx.waker().wake_by_ref();
+    Poll::Pending
+  }
+}
+
+impl<'m, T: SignalDef> PlannedEvaluation<'m, T> {
+  /// Start a run that is driven one pass at a time with
+  /// [`PassStepper::step_pass`].
+  ///
+  /// Steps evaluate like [`run_with`](Self::run_with), freeing intermediates
+  /// if the plan does, but without profiling or other instrumentation.
+  pub fn stepper<'p>(
+    &'p self,
+    mut values: EvaluationValueMap<T>,
+    options: &'p RunOptions,
+  ) -> PassStepper<'p, 'm, T> {
+    values.begin_epoch();
+    PassStepper {
+      plan: self,
+      options,
+      values,
+      releases: self.free_intermediates().then(|| self.release_schedule()),
+      executor: PassExecutor::new(options),
+      next: 0,
+    }
+  }
+
+  /// Run the planned evaluation like [`stepper`](Self::stepper), yielding to
+  /// the async runtime between passes so a long run doesn't starve other
+  /// tasks. Works on any runtime.
+  ///
+  /// Each pass still blocks the polling thread while it runs.
+  pub async fn run_async(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+  ) -> EvaluationValueMap<T> {
+    let mut stepper = self.stepper(values, options);
+    while stepper.step_pass().is_some() {
+      YieldNow(false).await;
+    }
+    stepper.into_values()
+  }
+
+  /// Run the planned evaluation like [`stepper`](Self::stepper), calling
+  /// `observe` with the pass index and a borrow of each value as each pass
+  /// finishes, before intermediates are freed.
+  pub fn run_observed(
+    &self,
+    values: EvaluationValueMap<T>,
+    options: &RunOptions,
+    mut observe: impl FnMut(usize, Signal, &T::Value),
+  ) -> EvaluationValueMap<T> {
+    let mut stepper = self.stepper(values, options);
+    let mut pass = 0;
+    while stepper
+      .step_pass_observed(|signal, value| observe(pass, signal, value))
+      .is_some()
+    {
+      pass += 1;
+    }
+    stepper.into_values()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    generate::{GraphGenerator, GraphShape},
+    CustomPlanner, SignalDefMap, SignalMatrix,
+  };
+
+  #[tokio::test]
+  async fn test_stepping() {
+    let mut defset = SignalDefMap::new();
+    let roots = GraphGenerator::new(GraphShape::Tree {
+      leaves: 8,
+      arity:  2,
+    })
+    .build_float(&mut defset);
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots.clone());
+    let expected =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+
+    let options = RunOptions::new();
+    let mut stepper = plan.stepper(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &options,
+    );
+    assert_eq!(stepper.step_pass(), Some(0));
+    assert_eq!(stepper.remaining(), plan.passes().len() - 1);
+    assert_eq!(stepper.values().get(roots[0]), None);
+    while stepper.step_pass().is_some() {}
+    assert_eq!(stepper.remaining(), 0);
+    let stepped = stepper.into_values();
+
+    let awaited = plan
+      .run_async(
+        EvaluationValueMap::new_empty(plan.all_queued_targets()),
+        &options,
+      )
+      .await;
+    for root in roots.iter() {
+      assert_eq!(stepped.get(*root), expected.get(*root));
+      assert_eq!(awaited.get(*root), expected.get(*root));
+    }
+
+    // observers see every value, even intermediates that are then freed
+    let plan = plan.with_free_intermediates(true);
+    let mut seen = Vec::new();
+    let values = plan.run_observed(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &options,
+      |pass, signal, value| seen.push((pass, signal, *value)),
+    );
+    assert_eq!(seen.len(), plan.all_queued_targets().len());
+    assert_eq!(seen.last().unwrap().0, plan.passes().len() - 1);
+    let mut kept = Vec::new();
+    values.for_each(|signal, value| kept.push((signal, *value)));
+    assert_eq!(kept, [(roots[0], *expected.get(roots[0]).unwrap())]);
+  }
+}
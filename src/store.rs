@@ -0,0 +1,59 @@
+use std::{fmt, io, sync::Arc};
+
+use crate::{EvaluationValueMap, Signal, SignalDef};
+
+/// Persistent storage for evaluated values, so they survive restarts and
+/// memoization can span processes.
+///
+/// Values are keyed by signal ID, so a store is only meaningful for graphs
+/// built the same way each time.
+pub trait ValueStore<V>: fmt::Debug + Send + Sync {
+  /// Load the stored value of the given signal, if there is one.
+  fn load(&self, signal: Signal) -> io::Result<Option<V>>;
+  /// Store the value of the given signal, replacing any stored value.
+  fn save(&self, signal: Signal, value: &V) -> io::Result<()>;
+  /// Remove the stored value of the given signal.
+  fn remove(&self, signal: Signal) -> io::Result<()>;
+}
+
+impl<T: SignalDef> EvaluationValueMap<T> {
+  /// Back this map with a [`ValueStore`].
+  ///
+  /// Every unevaluated signal in the map reads through to the store, so
+  /// [`get`](Self::get) returns values stored by earlier runs, and
+  /// [`PlannedEvaluation::skip_evaluated`](crate::PlannedEvaluation::skip_evaluated)
+  /// skips them. From then on, inserted and evaluated values are written to
+  /// the store, and invalidated ones are removed from it.
+  pub fn with_store(
+    mut self,
+    store: Arc<dyn ValueStore<T::Value>>,
+  ) -> io::Result<Self> {
+    for (signal, value) in self.values.iter_mut() {
+      if value.is_none() {
+        *value = store.load(signal)?;
+      }
+    }
+    self.store = Some(store);
+    Ok(self)
+  }
+
+  /// Get the store backing this map, if it has one.
+  pub fn store(&self) -> Option<&Arc<dyn ValueStore<T::Value>>> {
+    self.store.as_ref()
+  }
+
+  pub(crate) fn write_through(&self, signal: Signal) {
+    let (Some(store), Some(value)) = (&self.store, self.get(signal)) else {
+      return;
+    };
+    if let Err(error) = store.save(signal, value) {
+      tracing::warn!(?signal, %error, "failed to store value");
+    }
+  }
+
+  pub(crate) fn remove_stored(&self, signal: Signal) {
+    if let Some(Err(error)) = self.store.as_ref().map(|s| s.remove(signal)) {
+      tracing::warn!(?signal, %error, "failed to remove stored value");
+    }
+  }
+}
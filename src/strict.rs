@@ -0,0 +1,95 @@
+use std::fmt;
+
+use crate::{Signal, SignalDef, SignalDefMap};
+
+/// A definition depends on a signal that isn't in its [`SignalDefMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownDependency {
+  /// The missing dependency.
+  pub dependency: Signal,
+}
+
+impl fmt::Display for UnknownDependency {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "dependency {} is neither defined nor declared",
+      self.dependency
+    )
+  }
+}
+
+impl std::error::Error for UnknownDependency {}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Make [`insert`](Self::insert), [`replace`](Self::replace) and
+  /// [`define`](Self::define) panic on definitions depending on signals that
+  /// are neither defined nor [declared](Self::declare) yet, so a dangling
+  /// reference fails where it's built instead of when it's evaluated.
+  pub fn with_strict_dependencies(mut self) -> Self {
+    self.strict = true;
+    self
+  }
+
+  /// Check whether the map is in strict mode.
+  pub fn is_strict(&self) -> bool { self.strict }
+
+  /// Insert a signal definition if every dependency is defined or declared,
+  /// whether or not the map is in strict mode.
+  pub fn try_insert(&mut self, def: T) -> Result<Signal, UnknownDependency> {
+    self.check_dependencies(&def)?;
+    Ok(self.insert(def))
+  }
+
+  /// Find the first dependency of a definition that isn't in the map.
+  pub(crate) fn check_dependencies(
+    &self,
+    def: &T,
+  ) -> Result<(), UnknownDependency> {
+    let mut deps = Vec::new();
+    def.dependencies_into(&mut deps);
+    match deps
+      .into_iter()
+      .find(|s| self.get(*s).is_none() && !self.declared.contains(s))
+    {
+      Some(dependency) => Err(UnknownDependency { dependency }),
+      None => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatMapSignalDef, UnaryOp};
+
+  #[test]
+  fn test_strict_dependencies() {
+    let mut defset = SignalDefMap::new().with_strict_dependencies();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let later = defset.declare();
+    let neg = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(later)));
+
+    let dangling = FloatMapSignalDef::UnaryOp(UnaryOp::Neg(Signal(99)));
+    assert_eq!(
+      defset.try_insert(dangling),
+      Err(UnknownDependency {
+        dependency: Signal(99),
+      })
+    );
+    let result = std::panic::catch_unwind(move || {
+      defset.replace(neg, FloatMapSignalDef::UnaryOp(UnaryOp::Neg(Signal(99))))
+    });
+    let message = *result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(message, "dependency #99 is neither defined nor declared");
+
+    // lenient maps only check on request
+    let mut defset = SignalDefMap::<FloatMapSignalDef>::new();
+    assert!(!defset.is_strict());
+    defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(Signal(99))));
+    assert!(defset
+      .try_insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(Signal(98))))
+      .is_err());
+  }
+}
@@ -0,0 +1,157 @@
+use num_traits::Float;
+
+use crate::{EvalContext, SignalDef};
+
+/// A fixed floating-point summation order, so sums come out bit-for-bit the
+/// same whatever the thread count, scratch reuse or hash map layout.
+///
+/// Terms are always taken in the order given; [`EvalContext::sum_dependencies`]
+/// gives them in signal ID order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Summation {
+  /// Pairwise summation: the terms are split in half until at most eight
+  /// remain, those are added left to right, and the halves are added. The
+  /// error grows with the logarithm of the number of terms.
+  #[default]
+  Pairwise,
+  /// Compensated summation in the Kahan–Babuška–Neumaier form: the terms are
+  /// added left to right, carrying the lost low-order bits in a separate
+  /// compensation term that is added at the end. Slower, but the error doesn't
+  /// grow with the number of terms.
+  Kahan,
+}
+
+/// The most terms [`Summation::Pairwise`] adds left to right.
+const PAIRWISE_BLOCK: usize = 8;
+
+fn pairwise<F: Float>(terms: &[F]) -> F {
+  if terms.len() <= PAIRWISE_BLOCK {
+    return terms.iter().fold(F::zero(), |sum, x| sum + *x);
+  }
+  let (left, right) = terms.split_at(terms.len() / 2);
+  pairwise(left) + pairwise(right)
+}
+
+fn kahan<F: Float>(terms: &[F]) -> F {
+  let mut sum = F::zero();
+  let mut compensation = F::zero();
+  for x in terms.iter().copied() {
+    let t = sum + x;
+    compensation = compensation
+      + match sum.abs() >= x.abs() {
+        true => (sum - t) + x,
+        false => (x - t) + sum,
+      };
+    sum = t;
+  }
+  sum + compensation
+}
+
+impl Summation {
+  /// Sum the terms in this order.
+  pub fn sum<F: Float>(self, terms: &[F]) -> F {
+    match self {
+      Summation::Pairwise => pairwise(terms),
+      Summation::Kahan => kahan(terms),
+    }
+  }
+}
+
+impl<T, F> EvalContext<'_, T>
+where
+  T: SignalDef<Value = F>,
+  F: Float,
+{
+  /// Sum the values of the distinct dependencies, for n-ary reductions.
+  ///
+  /// In a run with
+  /// [`RunOptions::with_reproducible_summation`](crate::RunOptions::with_reproducible_summation),
+  /// the values are summed in signal ID order with the run's [`Summation`].
+  /// Otherwise they're added in whatever order the context holds them, which
+  /// is fastest but can round differently between runs.
+  pub fn sum_dependencies(&self) -> F {
+    match self.summation {
+      Some(summation) => {
+        let mut terms: Vec<_> = self.values.iter().collect();
+        terms.sort_unstable_by_key(|(s, _)| s.0);
+        let terms: Vec<F> = terms.into_iter().map(|(_, v)| **v).collect();
+        summation.sum(&terms)
+      }
+      None => self.values.values().fold(F::zero(), |sum, x| sum + **x),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, EvaluationValueMap, PassParallelism, RunOptions, Signal,
+    SignalDefMap, SignalMatrix,
+  };
+
+  #[derive(Debug)]
+  enum Def {
+    Leaf(f64),
+    Total(Vec<Signal>),
+  }
+
+  impl SignalDef for Def {
+    type Value = f64;
+
+    fn dependencies_into(&self, out: &mut Vec<Signal>) {
+      if let Def::Total(terms) = self {
+        out.extend(terms);
+      }
+    }
+
+    fn evaluate(&self, ctx: &EvalContext<Self>) -> f64 {
+      match self {
+        Def::Leaf(value) => *value,
+        Def::Total(_) => ctx.sum_dependencies(),
+      }
+    }
+  }
+
+  #[test]
+  fn test_reproducible_summation() {
+    let terms = [1e16, 1.0, -1e16, 1.0];
+    assert_eq!(Summation::Kahan.sum(&terms), 2.0);
+    assert_eq!(Summation::Pairwise.sum(&terms), 1.0);
+    let tenths = [0.1; 1000];
+    assert_eq!(Summation::Kahan.sum(&tenths), 100.0);
+    assert_ne!(Summation::Pairwise.sum(&tenths), tenths.iter().sum::<f64>());
+
+    let mut defset = SignalDefMap::new();
+    let leaves: Vec<_> = (0..500)
+      .map(|i| defset.insert(Def::Leaf(1.0 / (i as f64 + 1.0).powi(2))))
+      .collect();
+    let total = defset.insert(Def::Total(leaves.clone()));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    let run = |threads, summation| {
+      let mut options =
+        RunOptions::new().with_parallelism_policy(move |_, _: &_| {
+          PassParallelism::Limited(threads)
+        });
+      if let Some(summation) = summation {
+        options = options.with_reproducible_summation(summation);
+      }
+      let values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+      *plan.run_with(values, &options).get(total).unwrap()
+    };
+
+    for summation in [Summation::Pairwise, Summation::Kahan] {
+      let expected = {
+        let terms: Vec<_> =
+          (0..500).map(|i| 1.0 / (i as f64 + 1.0).powi(2)).collect();
+        summation.sum(&terms)
+      };
+      for threads in [1, 2, 8] {
+        assert_eq!(run(threads, Some(summation)).to_bits(), expected.to_bits());
+      }
+    }
+    let loose = run(1, None);
+    assert!((loose - run(1, Some(Summation::Kahan))).abs() < 1e-12);
+  }
+}
@@ -0,0 +1,80 @@
+use crate::{Signal, SignalDef, SignalDefMap, SignalMatrix, SignalSet};
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Insert a signal definition with the given tags.
+  pub fn insert_tagged<S: Into<String>>(
+    &mut self,
+    def: T,
+    tags: impl IntoIterator<Item = S>,
+  ) -> Signal {
+    let signal = self.insert(def);
+    for tag in tags {
+      self.tag(signal, tag);
+    }
+    signal
+  }
+
+  /// Attach a tag to a signal.
+  pub fn tag(&mut self, signal: Signal, tag: impl Into<String>) {
+    self.tags.entry(tag.into()).or_default().insert(signal);
+  }
+
+  /// Remove a tag from a signal. Returns whether the signal had the tag.
+  pub fn untag(&mut self, signal: Signal, tag: &str) -> bool {
+    let Some(signals) = self.tags.get_mut(tag) else {
+      return false;
+    };
+    let removed = signals.remove(&signal);
+    if signals.is_empty() {
+      self.tags.remove(tag);
+    }
+    removed
+  }
+
+  /// Iterate over the tags of a signal, in tag order.
+  pub fn tags_of(&self, signal: Signal) -> impl Iterator<Item = &str> {
+    self
+      .tags
+      .iter()
+      .filter(move |(_, signals)| signals.contains(&signal))
+      .map(|(tag, _)| tag.as_str())
+  }
+
+  /// Get the signals with the given tag.
+  pub fn tagged(&self, tag: &str) -> Option<&SignalSet> { self.tags.get(tag) }
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Select every signal with the given tag, for use as root targets.
+  pub fn targets_with_tag(&self, tag: &str) -> SignalSet {
+    self.defset.tagged(tag).cloned().unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, FloatMapSignalDef, UnaryOp};
+
+  #[test]
+  fn test_tags() {
+    let mut defset = SignalDefMap::new();
+
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset
+      .insert_tagged(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)), ["report"]);
+    let c = defset
+      .insert_tagged(FloatMapSignalDef::Constant(2.0), ["report", "debug"]);
+    assert!(defset.untag(c, "debug"));
+    assert!(!defset.untag(c, "debug"));
+    assert_eq!(defset.tags_of(c).collect::<Vec<_>>(), vec!["report"]);
+
+    let matrix = SignalMatrix::new(defset);
+    let roots = matrix.targets_with_tag("report");
+    assert_eq!(roots, [b, c].into_iter().collect());
+    assert!(matrix.targets_with_tag("missing").is_empty());
+
+    let planned_eval = matrix.plan_evaluation(&CustomPlanner::new(), roots);
+    assert!(planned_eval.all_queued_targets().contains(&a));
+  }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+
+use num_traits::Float;
+
+use crate::{
+  CallSignalDef, FloatBinaryOp, FloatMapSignalDef, Signal, SignalDef,
+  SignalDefMap, SignalMap, UnaryOp,
+};
+
+/// A signal definition that can be copied with its dependencies replaced, for
+/// stamping out [`GraphTemplate`]s.
+pub trait Relink: SignalDef {
+  /// Copy this definition, replacing every dependency `s` with `map(s)`.
+  fn relink(&self, map: &mut dyn FnMut(Signal) -> Signal) -> Self;
+}
+
+impl<F: Float + fmt::Debug + Send + Sync> Relink for FloatMapSignalDef<F> {
+  fn relink(&self, map: &mut dyn FnMut(Signal) -> Signal) -> Self {
+    match self {
+      FloatMapSignalDef::Constant(value) => FloatMapSignalDef::Constant(*value),
+      FloatMapSignalDef::UnaryOp(UnaryOp::Neg(s)) => {
+        FloatMapSignalDef::UnaryOp(UnaryOp::Neg(map(*s)))
+      }
+      FloatMapSignalDef::BinaryOp(op) => {
+        FloatMapSignalDef::BinaryOp(match op {
+          FloatBinaryOp::Add(a, b) => FloatBinaryOp::Add(map(*a), map(*b)),
+          FloatBinaryOp::Sub(a, b) => FloatBinaryOp::Sub(map(*a), map(*b)),
+          FloatBinaryOp::Mul(a, b) => FloatBinaryOp::Mul(map(*a), map(*b)),
+          FloatBinaryOp::Div(a, b) => FloatBinaryOp::Div(map(*a), map(*b)),
+          FloatBinaryOp::Pow(a, b) => FloatBinaryOp::Pow(map(*a), map(*b)),
+        })
+      }
+    }
+  }
+}
+
+impl<T: Relink + Send> Relink for CallSignalDef<T> {
+  fn relink(&self, map: &mut dyn FnMut(Signal) -> Signal) -> Self {
+    match self {
+      CallSignalDef::Def(def) => CallSignalDef::Def(def.relink(map)),
+      CallSignalDef::Apply(function, args) => CallSignalDef::Apply(
+        function.clone(),
+        args.iter().map(|arg| map(*arg)).collect(),
+      ),
+    }
+  }
+}
+
+/// A reusable subgraph with named input and output ports, stamped out into a
+/// [`SignalDefMap`] with [`SignalDefMap::instantiate`].
+///
+/// Input ports are placeholder signals in the body: their definitions are
+/// never copied, and everything depending on them is wired to the signals
+/// bound at instantiation instead.
+#[derive(Debug)]
+pub struct GraphTemplate<T: SignalDef> {
+  body:    SignalDefMap<T>,
+  inputs:  Vec<(String, Signal)>,
+  outputs: Vec<(String, Signal)>,
+}
+
+impl<T: SignalDef> GraphTemplate<T> {
+  /// Create a template from a body with no ports.
+  pub fn new(body: SignalDefMap<T>) -> Self {
+    GraphTemplate {
+      body,
+      inputs: Vec::new(),
+      outputs: Vec::new(),
+    }
+  }
+
+  /// Declare an input port, bound to the given placeholder signal in the body.
+  pub fn with_input(mut self, name: impl Into<String>, signal: Signal) -> Self {
+    self.inputs.push((name.into(), signal));
+    self
+  }
+
+  /// Declare an output port, exposing the given signal in the body.
+  pub fn with_output(
+    mut self,
+    name: impl Into<String>,
+    signal: Signal,
+  ) -> Self {
+    self.outputs.push((name.into(), signal));
+    self
+  }
+
+  /// Get the names of the input ports, in declaration order.
+  pub fn inputs(&self) -> impl Iterator<Item = &str> {
+    self.inputs.iter().map(|(name, _)| name.as_str())
+  }
+
+  /// Get the names of the output ports, in declaration order.
+  pub fn outputs(&self) -> impl Iterator<Item = &str> {
+    self.outputs.iter().map(|(name, _)| name.as_str())
+  }
+}
+
+/// An error instantiating a [`GraphTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+  /// An input port wasn't bound.
+  UnboundInput(String),
+  /// A binding named a port the template doesn't have.
+  UnknownInput(String),
+  /// A body signal that is neither an input port nor defined in the body.
+  Dangling(Signal),
+}
+
+impl fmt::Display for TemplateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TemplateError::UnboundInput(name) => {
+        write!(f, "template input `{name}` is not bound")
+      }
+      TemplateError::UnknownInput(name) => {
+        write!(f, "template has no input named `{name}`")
+      }
+      TemplateError::Dangling(signal) => {
+        write!(f, "template body has no definition for #{}", signal.0)
+      }
+    }
+  }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl<T: Relink> SignalDefMap<T> {
+  /// Copy a template's body into this map, wiring its input ports to the
+  /// given signals, and return the signals of its output ports in declaration
+  /// order.
+  ///
+  /// Only the signals the outputs depend on are copied.
+  pub fn instantiate<'n>(
+    &mut self,
+    template: &GraphTemplate<T>,
+    input_bindings: impl IntoIterator<Item = (&'n str, Signal)>,
+  ) -> Result<Vec<Signal>, TemplateError> {
+    let mut mapped = SignalMap::default();
+    for (name, signal) in input_bindings {
+      let (_, port) = template
+        .inputs
+        .iter()
+        .find(|(port, _)| port == name)
+        .ok_or_else(|| TemplateError::UnknownInput(name.to_string()))?;
+      mapped.insert(*port, signal);
+    }
+    if let Some((name, _)) = template
+      .inputs
+      .iter()
+      .find(|(_, s)| !mapped.contains_key(s))
+    {
+      return Err(TemplateError::UnboundInput(name.clone()));
+    }
+
+    // copy in post-order, so every dependency is mapped before its dependents
+    let mut stack: Vec<_> =
+      template.outputs.iter().map(|(_, s)| (*s, false)).collect();
+    let mut deps = Vec::new();
+    while let Some((signal, expanded)) = stack.pop() {
+      if mapped.contains_key(&signal) {
+        continue;
+      }
+      let def = template
+        .body
+        .get(signal)
+        .ok_or(TemplateError::Dangling(signal))?;
+      if expanded {
+        let copy = def.relink(&mut |dep| mapped[&dep]);
+        mapped.insert(signal, self.insert(copy));
+        continue;
+      }
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      stack.push((signal, true));
+      stack.extend(deps.iter().map(|dep| (*dep, false)));
+    }
+
+    Ok(template.outputs.iter().map(|(_, s)| mapped[s]).collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalMatrix};
+
+  #[test]
+  fn test_instantiate() {
+    // lerp(a, b, t) = a + t * (b - a)
+    let mut body = SignalDefMap::new();
+    let a = body.insert(FloatMapSignalDef::Constant(0.0));
+    let b = body.insert(FloatMapSignalDef::Constant(0.0));
+    let t = body.insert(FloatMapSignalDef::Constant(0.0));
+    // not part of any output, so never copied
+    body.insert(FloatMapSignalDef::Constant(7.0));
+    let span =
+      body.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(b, a)));
+    let step =
+      body.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(t, span)));
+    let lerp =
+      body.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, step)));
+    let template = GraphTemplate::new(body)
+      .with_input("a", a)
+      .with_input("b", b)
+      .with_input("t", t)
+      .with_output("lerp", lerp)
+      .with_output("span", span);
+
+    let mut defset = SignalDefMap::new();
+    let lo = defset.insert(FloatMapSignalDef::Constant(10.0));
+    let hi = defset.insert(FloatMapSignalDef::Constant(20.0));
+    let quarter = defset.insert(FloatMapSignalDef::Constant(0.25));
+    let outputs = defset
+      .instantiate(&template, [("a", lo), ("b", hi), ("t", quarter)])
+      .unwrap();
+    let again = defset
+      .instantiate(&template, [("a", hi), ("b", lo), ("t", quarter)])
+      .unwrap();
+    // three defs per copy; the ports and unused signal aren't copied
+    assert_eq!(defset.len(), 3 + 2 * 3);
+
+    assert_eq!(
+      defset.instantiate(&template, [("a", lo), ("b", hi)]),
+      Err(TemplateError::UnboundInput("t".into()))
+    );
+    assert_eq!(
+      defset.instantiate(&template, [("x", lo)]),
+      Err(TemplateError::UnknownInput("x".into()))
+    );
+
+    let matrix = SignalMatrix::new(defset);
+    let roots = [outputs[0], outputs[1], again[0]];
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), roots);
+    let values = plan.run(EvaluationValueMap::new_empty(Default::default()));
+    assert_eq!(values.get(outputs[0]), Some(&12.5));
+    assert_eq!(values.get(outputs[1]), Some(&10.0));
+    assert_eq!(values.get(again[0]), Some(&17.5));
+  }
+}
@@ -0,0 +1,250 @@
+use std::{fmt, ops::Range};
+
+use ndarray::{ArrayD, Axis, Ix1, Ix2, IxDyn, Slice};
+
+use crate::{EvalContext, Signal, SignalDef};
+
+/// A signal definition for an `f64` tensor value or operation.
+///
+/// Evaluates to a `Result`: a signal whose operand shapes are incompatible
+/// holds a [`TensorError`] naming it, and every signal depending on it
+/// evaluates to that same error.
+#[derive(Debug)]
+pub enum TensorSignalDef {
+  Constant(ArrayD<f64>),
+  /// Elementwise addition, with broadcasting.
+  Add(Signal, Signal),
+  /// Elementwise subtraction, with broadcasting.
+  Sub(Signal, Signal),
+  /// Elementwise multiplication, with broadcasting.
+  Mul(Signal, Signal),
+  /// Matrix product of a 2-D tensor with a 2-D or 1-D tensor.
+  MatMul(Signal, Signal),
+  /// Sum along the given axis, or over every element if `None`.
+  Sum(Signal, Option<usize>),
+  /// Mean along the given axis, or over every element if `None`.
+  Mean(Signal, Option<usize>),
+  /// Reshape to the given shape, in row-major order.
+  Reshape(Signal, Vec<usize>),
+  /// Slice the leading axes to the given ranges; remaining axes are kept.
+  Slice(Signal, Vec<Range<usize>>),
+}
+
+/// An error evaluating a [`TensorSignalDef`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorError {
+  signal:  Signal,
+  message: String,
+}
+
+impl TensorError {
+  /// Get the signal where the error originated.
+  pub fn signal(&self) -> Signal { self.signal }
+
+  /// Get a description of the error.
+  pub fn message(&self) -> &str { &self.message }
+}
+
+impl fmt::Display for TensorError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "signal #{}: {}", self.signal.0, self.message)
+  }
+}
+
+impl std::error::Error for TensorError {}
+
+impl SignalDef for TensorSignalDef {
+  type Value = Result<ArrayD<f64>, TensorError>;
+
+  fn dependencies_into(&self, out: &mut Vec<Signal>) {
+    match self {
+      TensorSignalDef::Constant(_) => {}
+      TensorSignalDef::Add(a, b)
+      | TensorSignalDef::Sub(a, b)
+      | TensorSignalDef::Mul(a, b)
+      | TensorSignalDef::MatMul(a, b) => out.extend([*a, *b]),
+      TensorSignalDef::Sum(a, _)
+      | TensorSignalDef::Mean(a, _)
+      | TensorSignalDef::Reshape(a, _)
+      | TensorSignalDef::Slice(a, _) => out.push(*a),
+    }
+  }
+
+  fn evaluate(&self, ctx: &EvalContext<Self>) -> Self::Value {
+    let value = |s: &Signal| ctx.values[s].as_ref().map_err(Clone::clone);
+    let error = |message: String| TensorError {
+      signal: ctx.signal(),
+      message,
+    };
+    let broadcast = |a: &ArrayD<f64>, b: &ArrayD<f64>| {
+      broadcast_shape(a.shape(), b.shape()).ok_or_else(|| {
+        error(format!(
+          "can't broadcast {:?} with {:?}",
+          a.shape(),
+          b.shape()
+        ))
+      })
+    };
+
+    match self {
+      TensorSignalDef::Constant(a) => Ok(a.clone()),
+      TensorSignalDef::Add(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        broadcast(a, b)?;
+        Ok(a + b)
+      }
+      TensorSignalDef::Sub(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        broadcast(a, b)?;
+        Ok(a - b)
+      }
+      TensorSignalDef::Mul(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        broadcast(a, b)?;
+        Ok(a * b)
+      }
+      TensorSignalDef::MatMul(a, b) => {
+        let (a, b) = (value(a)?, value(b)?);
+        let mismatch = || {
+          error(format!("can't multiply {:?} by {:?}", a.shape(), b.shape()))
+        };
+        let lhs = a
+          .view()
+          .into_dimensionality::<Ix2>()
+          .map_err(|_| mismatch())?;
+        if lhs.ncols() != b.shape()[0] {
+          return Err(mismatch());
+        }
+        match b.ndim() {
+          1 => Ok(
+            lhs
+              .dot(&b.view().into_dimensionality::<Ix1>().unwrap())
+              .into_dyn(),
+          ),
+          2 => Ok(
+            lhs
+              .dot(&b.view().into_dimensionality::<Ix2>().unwrap())
+              .into_dyn(),
+          ),
+          _ => Err(mismatch()),
+        }
+      }
+      TensorSignalDef::Sum(a, axis) | TensorSignalDef::Mean(a, axis) => {
+        let a = value(a)?;
+        let mean = matches!(self, TensorSignalDef::Mean(..));
+        let empty = || error("can't take the mean of nothing".to_string());
+        match axis {
+          None if mean => {
+            Ok(ArrayD::from_elem(IxDyn(&[]), a.mean().ok_or_else(empty)?))
+          }
+          None => Ok(ArrayD::from_elem(IxDyn(&[]), a.sum())),
+          Some(axis) if *axis >= a.ndim() => Err(error(format!(
+            "axis {axis} is out of bounds for shape {:?}",
+            a.shape()
+          ))),
+          Some(axis) if mean => a.mean_axis(Axis(*axis)).ok_or_else(empty),
+          Some(axis) => Ok(a.sum_axis(Axis(*axis))),
+        }
+      }
+      TensorSignalDef::Reshape(a, shape) => {
+        let a = value(a)?;
+        let elements: Vec<_> = a.iter().copied().collect();
+        ArrayD::from_shape_vec(IxDyn(shape), elements).map_err(|_| {
+          error(format!("can't reshape {:?} to {shape:?}", a.shape()))
+        })
+      }
+      TensorSignalDef::Slice(a, ranges) => {
+        let a = value(a)?;
+        let in_bounds = ranges.len() <= a.ndim()
+          && ranges
+            .iter()
+            .zip(a.shape())
+            .all(|(range, len)| range.start <= range.end && range.end <= *len);
+        if !in_bounds {
+          return Err(error(format!(
+            "slice {ranges:?} is out of bounds for shape {:?}",
+            a.shape()
+          )));
+        }
+        let sliced =
+          a.slice_each_axis(|axis| match ranges.get(axis.axis.index()) {
+            Some(range) => Slice::from(range.clone()),
+            None => Slice::from(..),
+          });
+        Ok(sliced.to_owned())
+      }
+    }
+  }
+}
+
+/// Get the shape two shapes broadcast to, following NumPy's rules, if they're
+/// compatible.
+fn broadcast_shape(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+  let ndim = a.len().max(b.len());
+  let padded = |shape: &[usize], i: usize| {
+    (i + shape.len()).checked_sub(ndim).map_or(1, |i| shape[i])
+  };
+  (0..ndim)
+    .map(|i| match (padded(a, i), padded(b, i)) {
+      (x, y) if x == y => Some(x),
+      (1, y) => Some(y),
+      (x, 1) => Some(x),
+      _ => None,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use ndarray::{array, ArrayD};
+
+  use super::*;
+  use crate::{CustomPlanner, EvaluationValueMap, SignalDefMap, SignalMatrix};
+
+  #[test]
+  fn test_tensor_graph() {
+    let mut defset = SignalDefMap::new();
+
+    let x = defset.insert(TensorSignalDef::Constant(
+      array![[1.0, 2.0], [3.0, 4.0]].into_dyn(),
+    ));
+    let w =
+      defset.insert(TensorSignalDef::Constant(array![0.5, -1.0].into_dyn()));
+    let bias = defset.insert(TensorSignalDef::Constant(array![1.0].into_dyn()));
+    let y = defset.insert(TensorSignalDef::MatMul(x, w));
+    let y = defset.insert(TensorSignalDef::Add(y, bias));
+    let total = defset.insert(TensorSignalDef::Sum(y, None));
+    let row = defset.insert(TensorSignalDef::Slice(x, vec![1..2, 0..2]));
+    let tail = defset.insert(TensorSignalDef::Reshape(row, vec![2]));
+    let col_means = defset.insert(TensorSignalDef::Mean(x, Some(0)));
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix
+      .plan_evaluation(&CustomPlanner::new(), [y, total, tail, col_means]);
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let values = planned_eval.run(values);
+
+    let get = |s| values.get(s).unwrap().as_ref();
+    assert_eq!(get(y).unwrap(), &array![-0.5, -1.5].into_dyn());
+    assert_eq!(get(total).unwrap(), &ArrayD::from_elem(IxDyn(&[]), -2.0));
+    assert_eq!(get(tail).unwrap(), &array![3.0, 4.0].into_dyn());
+    assert_eq!(get(col_means).unwrap(), &array![2.0, 3.0].into_dyn());
+
+    let mut defset = SignalDefMap::new();
+    let a = defset
+      .insert(TensorSignalDef::Constant(array![1.0, 2.0, 3.0].into_dyn()));
+    let b =
+      defset.insert(TensorSignalDef::Constant(array![1.0, 2.0].into_dyn()));
+    let sum = defset.insert(TensorSignalDef::Add(a, b));
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix.plan_evaluation(&CustomPlanner::new(), [sum]);
+    let values = planned_eval.run(EvaluationValueMap::new_empty(
+      planned_eval.all_queued_targets(),
+    ));
+    assert_eq!(
+      values.get(sum).unwrap().as_ref().unwrap_err().to_string(),
+      format!("signal #{}: can't broadcast [3] with [2]", sum.0)
+    );
+  }
+}
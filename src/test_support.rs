@@ -0,0 +1,172 @@
+use std::fmt::Debug;
+
+use crate::{EvalContext, Signal, SignalDef, SignalMap};
+
+/// Evaluates single signal definitions, or small subgraphs of them, against
+/// stubbed dependency values, for testing a signal library without planning
+/// a graph.
+///
+/// Dependencies are either stubbed with [`stub`](Self::stub) or defined with
+/// [`define`](Self::define), and the harness hands out their signals, so
+/// definitions are built in dependency order.
+#[derive(Debug)]
+pub struct DefHarness<T: SignalDef> {
+  stubs: SignalMap<T::Value>,
+  defs:  Vec<(Signal, T)>,
+  next:  u64,
+}
+
+impl<T: SignalDef> Default for DefHarness<T> {
+  fn default() -> Self {
+    DefHarness {
+      stubs: SignalMap::default(),
+      defs:  Vec::new(),
+      next:  0,
+    }
+  }
+}
+
+impl<T: SignalDef> DefHarness<T> {
+  /// Create a harness with no dependencies.
+  pub fn new() -> Self { Self::default() }
+
+  fn allocate(&mut self) -> Signal {
+    let signal = Signal(self.next);
+    self.next += 1;
+    signal
+  }
+
+  /// Add a dependency with a fixed value, returning its signal.
+  pub fn stub(&mut self, value: T::Value) -> Signal {
+    let signal = self.allocate();
+    self.stubs.insert(signal, value);
+    signal
+  }
+
+  /// Add a dependency that is itself evaluated from earlier ones, returning
+  /// its signal.
+  pub fn define(&mut self, def: T) -> Signal {
+    let signal = self.allocate();
+    self.defs.push((signal, def));
+    signal
+  }
+
+  /// Get the signal the definition under test is evaluated as, e.g. to
+  /// build an expected error value naming it.
+  pub fn target(&self) -> Signal { Signal(self.next) }
+
+  /// Evaluate every defined dependency, returning their values.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a definition depends on a signal that wasn't stubbed or
+  /// defined before it.
+  pub fn evaluate_all(&self) -> SignalMap<T::Value> {
+    let mut locals = SignalMap::default();
+    for (signal, def) in &self.defs {
+      let value = self.evaluate_with(*signal, def, &locals);
+      locals.insert(*signal, value);
+    }
+    locals
+  }
+
+  /// Evaluate the given definition as [`target`](Self::target), after every
+  /// defined dependency.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a definition depends on a signal that wasn't stubbed or
+  /// defined before it.
+  pub fn evaluate(&self, def: &T) -> T::Value {
+    self.evaluate_with(self.target(), def, &self.evaluate_all())
+  }
+
+  /// Evaluate the given definition `n` times, for definitions that keep
+  /// state between evaluations. Defined dependencies are evaluated anew each
+  /// time.
+  pub fn evaluate_repeatedly(&self, def: &T, n: usize) -> Vec<T::Value> {
+    (0..n).map(|_| self.evaluate(def)).collect()
+  }
+
+  /// Evaluate the given definition and assert that it produces `expected`.
+  #[track_caller]
+  pub fn assert_evaluates(&self, def: &T, expected: T::Value)
+  where
+    T::Value: PartialEq,
+  {
+    assert_eq!(self.evaluate(def), expected, "evaluating {def:?}");
+  }
+
+  fn evaluate_with(
+    &self,
+    signal: Signal,
+    def: &T,
+    locals: &SignalMap<T::Value>,
+  ) -> T::Value {
+    let mut deps = Vec::new();
+    def.dependencies_into(&mut deps);
+    let values = deps.into_iter().map(|dep| {
+      let value = self.stubs.get(&dep).or_else(|| locals.get(&dep));
+      let value = value.unwrap_or_else(|| {
+        panic!("dependency {dep} of {signal} is neither stubbed nor defined")
+      });
+      (dep, value)
+    });
+    def.evaluate(&EvalContext::for_def(signal, def, values))
+  }
+}
+
+impl<T, V, E> DefHarness<T>
+where
+  T: SignalDef<Value = Result<V, E>>,
+  V: Debug,
+  E: Debug,
+{
+  /// Evaluate a fallible definition, panicking with the error if it fails.
+  #[track_caller]
+  pub fn evaluate_ok(&self, def: &T) -> V {
+    match self.evaluate(def) {
+      Ok(value) => value,
+      Err(error) => panic!("evaluating {def:?} failed: {error:?}"),
+    }
+  }
+
+  /// Evaluate a fallible definition, panicking with the value if it
+  /// succeeds.
+  #[track_caller]
+  pub fn evaluate_err(&self, def: &T) -> E {
+    match self.evaluate(def) {
+      Ok(value) => panic!("evaluating {def:?} succeeded with {value:?}"),
+      Err(error) => error,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ArithmeticError, IntMapSignalDef, IntOp};
+
+  #[test]
+  fn test_harness() {
+    let mut harness = DefHarness::new();
+    let six = harness.stub(Ok(6));
+    let zero = harness.stub(Ok(0));
+    let twelve = harness.define(IntMapSignalDef::new(IntOp::Add(six, six)));
+
+    harness
+      .assert_evaluates(&IntMapSignalDef::new(IntOp::Neg(twelve)), Ok(-12));
+    assert_eq!(
+      harness.evaluate_ok(&IntMapSignalDef::new(IntOp::Div(twelve, six))),
+      2
+    );
+    let error =
+      harness.evaluate_err(&IntMapSignalDef::new(IntOp::Div(six, zero)));
+    assert_eq!(error, ArithmeticError::DivisionByZero {
+      signal: harness.target(),
+    });
+    assert_eq!(harness.evaluate_all()[&twelve], Ok(12));
+    let constant = IntMapSignalDef::new(IntOp::Constant(1));
+    assert_eq!(harness.evaluate_repeatedly(&constant, 2), [Ok(1), Ok(1)]);
+  }
+}
@@ -0,0 +1,155 @@
+use std::{
+  collections::BTreeSet,
+  fmt::Write as _,
+  io::{self, Write},
+  time::Duration,
+};
+
+use crate::ProfileReport;
+
+/// Bar colors, one per definition variant in name order, wrapping around.
+const PALETTE: [&str; 10] = [
+  "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1",
+  "#ff9da7", "#9c755f", "#bab0ac",
+];
+
+/// The height of one worker thread's lane in a pass row, in pixels.
+const LANE_HEIGHT: usize = 18;
+
+const STYLE: &str = "body{font:13px sans-serif;margin:16px;color:#222}.legend \
+                     span{display:inline-block;margin-right:12px}.legend \
+                     i{display:inline-block;width:10px;height:10px;\
+                     margin-right:4px}.row{display:flex;border-top:1px solid \
+                     #ddd}.name{width:80px;flex:none;padding:2px \
+                     4px;color:#555}.track{position:relative;flex:1}.\
+                     bar{position:absolute;height:16px;min-width:1px;\
+                     box-sizing:border-box;border-right:1px solid #fff}";
+
+fn millis(d: Duration) -> f64 { d.as_secs_f64() * 1_000.0 }
+
+fn escape_html(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+impl ProfileReport {
+  /// Write the run as a self-contained HTML timeline, for sharing without a
+  /// trace viewer. Each pass is a row with one lane per worker thread, and
+  /// each signal a bar colored by its definition's
+  /// [variant](crate::SignalProfile::variant), with its label and duration
+  /// on hover.
+  ///
+  /// The output only depends on the report, so the same report always
+  /// renders the same page.
+  pub fn write_html_timeline(&self, mut w: impl Write) -> io::Result<()> {
+    let variants: BTreeSet<&str> =
+      self.signals().map(|s| s.variant()).collect();
+    let color = |variant: &str| {
+      PALETTE
+        [variants.iter().position(|v| *v == variant).unwrap() % PALETTE.len()]
+    };
+    let total = millis(self.total_duration()).max(f64::MIN_POSITIVE);
+    let percent = |d: Duration| millis(d) / total * 100.0;
+
+    let mut html = String::new();
+    let _ = write!(
+      html,
+      "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Evaluation \
+       timeline</title><style>{STYLE}</style></head><body>\n<h1>Evaluation \
+       timeline</h1>\n<p>{} passes, {} signals, {:.3} ms</p>\n<div \
+       class=\"legend\">",
+      self.passes().len(),
+      self.signals().count(),
+      millis(self.total_duration())
+    );
+    for variant in variants.iter() {
+      let _ = write!(
+        html,
+        "<span><i style=\"background:{}\"></i>{}</span>",
+        color(variant),
+        escape_html(variant)
+      );
+    }
+    html.push_str("</div>\n<div class=\"timeline\">\n");
+
+    for pass in self.passes() {
+      let threads: BTreeSet<usize> =
+        pass.signals().iter().map(|s| s.thread()).collect();
+      let mut signals: Vec<_> = pass.signals().iter().collect();
+      signals.sort_by_key(|s| (s.start(), s.signal().0));
+      let _ = write!(
+        html,
+        "<div class=\"row\" style=\"height:{}px\"><div class=\"name\" \
+         title=\"{:.3} ms\">pass {}</div><div class=\"track\">",
+        threads.len().max(1) * LANE_HEIGHT,
+        millis(pass.duration()),
+        pass.index()
+      );
+      for signal in signals {
+        let lane = threads.iter().position(|t| *t == signal.thread()).unwrap();
+        let _ = write!(
+          html,
+          "<div class=\"bar\" \
+           style=\"left:{:.4}%;width:{:.4}%;top:{}px;background:{}\" \
+           title=\"{} ({}) {:.3} ms\"></div>",
+          percent(signal.start()),
+          percent(signal.duration()),
+          lane * LANE_HEIGHT + 1,
+          color(signal.variant()),
+          escape_html(signal.label()),
+          escape_html(signal.variant()),
+          millis(signal.duration())
+        );
+      }
+      html.push_str("</div></div>\n");
+    }
+    html.push_str("</div>\n</body></html>\n");
+    w.write_all(html.as_bytes())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    CustomPlanner, EvaluationValueMap, FloatBinaryOp, FloatMapSignalDef,
+    RunOptions, SignalDefMap, SignalMatrix,
+  };
+
+  #[test]
+  fn test_html_timeline() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert_labeled("<a>", FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let c =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)));
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [c]);
+    let (_, report) = plan.run_profiled(
+      EvaluationValueMap::new_empty(plan.all_queued_targets()),
+      &RunOptions::new(),
+    );
+    assert_eq!(report.signals().next().unwrap().variant(), "Constant");
+
+    let mut html = Vec::new();
+    report.write_html_timeline(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("2 passes, 3 signals"));
+    assert_eq!(html.matches("class=\"row\"").count(), 2);
+    assert_eq!(html.matches("class=\"bar\"").count(), 3);
+    // variants get distinct colors, in name order
+    assert!(html.contains("<i style=\"background:#4e79a7\"></i>BinaryOp"));
+    assert!(html.contains("<i style=\"background:#f28e2b\"></i>Constant"));
+    assert!(html.contains("title=\"&lt;a&gt; (Constant)"));
+  }
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use fixedbitset::FixedBitSet;
+
+use crate::{csr::DependencyGraph, Signal, SignalDef, SignalMatrix};
+
+/// Which edges a traversal follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  /// From each signal to the signals it depends on.
+  Dependencies,
+  /// From each signal to the signals that depend on it.
+  Dependents,
+}
+
+/// What a traversal does after visiting a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+  /// Go on to the signal's neighbours.
+  Continue,
+  /// Don't go on to the signal's neighbours, but keep traversing elsewhere.
+  Skip,
+  /// End the traversal.
+  Stop,
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  fn graph_in(&self, direction: Direction) -> &DependencyGraph {
+    match direction {
+      Direction::Dependencies => self.dependency_graph(),
+      Direction::Dependents => self.dependents_graph(),
+    }
+  }
+
+  /// Visit the signals reachable from the given ones breadth-first, along
+  /// with their distance from the nearest start. Starts are visited first, at
+  /// distance 0, and every signal is visited at most once.
+  ///
+  /// Returns the signal the visitor stopped at, if it stopped.
+  pub fn bfs_from(
+    &self,
+    starts: impl IntoIterator<Item = Signal>,
+    direction: Direction,
+    mut visit: impl FnMut(Signal, usize) -> Flow,
+  ) -> Option<Signal> {
+    let graph = self.graph_in(direction);
+    let mut seen = FixedBitSet::with_capacity(graph.len());
+    let mut queue: VecDeque<_> = starts
+      .into_iter()
+      .filter(|s| s.index() < graph.len() && !seen.put(s.index()))
+      .map(|s| (s, 0))
+      .collect();
+    while let Some((signal, depth)) = queue.pop_front() {
+      match visit(signal, depth) {
+        Flow::Continue => {}
+        Flow::Skip => continue,
+        Flow::Stop => return Some(signal),
+      }
+      for next in graph.dependencies(signal) {
+        if !seen.put(next.index()) {
+          queue.push_back((*next, depth + 1));
+        }
+      }
+    }
+    None
+  }
+
+  /// Visit the signals reachable from the given ones depth-first in
+  /// pre-order, along with their depth in the search tree. Neighbours are
+  /// visited in ID order and every signal is visited at most once.
+  ///
+  /// Returns the signal the visitor stopped at, if it stopped.
+  pub fn dfs_from(
+    &self,
+    starts: impl IntoIterator<Item = Signal>,
+    direction: Direction,
+    mut visit: impl FnMut(Signal, usize) -> Flow,
+  ) -> Option<Signal> {
+    let graph = self.graph_in(direction);
+    let mut seen = FixedBitSet::with_capacity(graph.len());
+    let mut stack: Vec<_> = starts.into_iter().map(|s| (s, 0)).collect();
+    // pop the first start first
+    stack.reverse();
+    while let Some((signal, depth)) = stack.pop() {
+      if signal.index() >= graph.len() || seen.put(signal.index()) {
+        continue;
+      }
+      match visit(signal, depth) {
+        Flow::Continue => {}
+        Flow::Skip => continue,
+        Flow::Stop => return Some(signal),
+      }
+      let next = graph.dependencies(signal).iter().rev();
+      stack.extend(next.map(|next| (*next, depth + 1)));
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, SignalDefMap, UnaryOp};
+
+  #[test]
+  fn test_traversals() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let neg = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(neg, b)));
+    let top = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(sum)));
+    let matrix = SignalMatrix::new(defset);
+
+    let mut visited = Vec::new();
+    let stopped =
+      matrix.bfs_from([top], Direction::Dependencies, |s, depth| {
+        visited.push((s, depth));
+        Flow::Continue
+      });
+    assert_eq!(stopped, None);
+    assert_eq!(visited, [(top, 0), (sum, 1), (b, 2), (neg, 2), (a, 3)]);
+
+    // depth-first, without looking below `neg`
+    let mut visited = Vec::new();
+    matrix.dfs_from([top], Direction::Dependencies, |s, _| {
+      visited.push(s);
+      if s == neg {
+        Flow::Skip
+      } else {
+        Flow::Continue
+      }
+    });
+    assert_eq!(visited, [top, sum, b, neg]);
+
+    // find the first dependent of `a` that has two dependencies
+    let found =
+      matrix.bfs_from([a], Direction::Dependents, |s, _| {
+        match matrix.direct_dependencies(s).len() {
+          2 => Flow::Stop,
+          _ => Flow::Continue,
+        }
+      });
+    assert_eq!(found, Some(sum));
+  }
+}
@@ -0,0 +1,329 @@
+use std::{fmt, ops};
+
+use crate::{
+  FloatBinaryOp, FloatMapSignalDef, Signal, SignalDef, SignalDefMap, SignalMap,
+  UnaryOp,
+};
+
+const BASE_SYMBOLS: [&str; 4] = ["m", "kg", "s", "$"];
+
+/// A unit of measure, as integer exponents of the base units meters,
+/// kilograms, seconds and dollars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Unit {
+  exponents: [i8; 4],
+}
+
+impl Unit {
+  pub const DIMENSIONLESS: Unit = Unit { exponents: [0; 4] };
+  pub const METER: Unit = Unit::base(0);
+  pub const KILOGRAM: Unit = Unit::base(1);
+  pub const SECOND: Unit = Unit::base(2);
+  pub const DOLLAR: Unit = Unit::base(3);
+
+  const fn base(index: usize) -> Unit {
+    let mut exponents = [0; 4];
+    exponents[index] = 1;
+    Unit { exponents }
+  }
+
+  /// Whether this unit is dimensionless.
+  pub fn is_dimensionless(self) -> bool { self == Unit::DIMENSIONLESS }
+
+  /// Multiply units by adding their exponents.
+  fn combine(self, rhs: Unit) -> Unit {
+    Unit {
+      exponents: std::array::from_fn(|i| self.exponents[i] + rhs.exponents[i]),
+    }
+  }
+
+  /// Raise this unit to an integer power.
+  pub fn powi(self, n: i8) -> Unit {
+    Unit {
+      exponents: self.exponents.map(|e| e * n),
+    }
+  }
+}
+
+impl ops::Mul for Unit {
+  type Output = Unit;
+
+  fn mul(self, rhs: Unit) -> Unit { self.combine(rhs) }
+}
+
+impl ops::Div for Unit {
+  type Output = Unit;
+
+  fn div(self, rhs: Unit) -> Unit { self * rhs.powi(-1) }
+}
+
+impl fmt::Display for Unit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.is_dimensionless() {
+      return write!(f, "1");
+    }
+    let mut first = true;
+    for (symbol, e) in BASE_SYMBOLS.iter().zip(self.exponents) {
+      if e == 0 {
+        continue;
+      }
+      if !first {
+        write!(f, "*")?;
+      }
+      first = false;
+      match e {
+        1 => write!(f, "{symbol}")?,
+        _ => write!(f, "{symbol}^{e}")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Unit annotations for [`FloatMapSignalDef`] signals, with a static checker.
+///
+/// Unannotated constants are dimensionless; every other signal's unit is
+/// inferred from its operands. Annotating a non-constant signal asserts its
+/// inferred unit.
+#[derive(Debug, Clone, Default)]
+pub struct Units {
+  units: SignalMap<Unit>,
+}
+
+impl Units {
+  /// Create an empty set of annotations.
+  pub fn new() -> Self { Self::default() }
+
+  /// Annotate the given signal with a unit.
+  pub fn with_unit(mut self, signal: Signal, unit: Unit) -> Self {
+    self.units.insert(signal, unit);
+    self
+  }
+
+  /// Annotate the given signal with a unit.
+  pub fn set_unit(&mut self, signal: Signal, unit: Unit) {
+    self.units.insert(signal, unit);
+  }
+
+  /// Get the annotated unit of the given signal.
+  pub fn unit(&self, signal: Signal) -> Option<Unit> {
+    self.units.get(&signal).copied()
+  }
+
+  /// Infer the unit of every signal the given roots depend on, rejecting the
+  /// graph on the first incompatible operation or annotation.
+  pub fn check(
+    &self,
+    defset: &SignalDefMap<FloatMapSignalDef>,
+    roots: impl IntoIterator<Item = Signal>,
+  ) -> Result<SignalMap<Unit>, UnitError> {
+    let mut inferred = SignalMap::<Unit>::default();
+    // iterative post-order, so deep chains don't overflow the stack
+    let mut stack: Vec<_> = roots.into_iter().map(|s| (s, false)).collect();
+    let mut deps = Vec::new();
+    while let Some((signal, expanded)) = stack.pop() {
+      if inferred.contains_key(&signal) {
+        continue;
+      }
+      let def = defset.get(signal).expect("signal is not defined");
+      if !expanded {
+        stack.push((signal, true));
+        deps.clear();
+        def.dependencies_into(&mut deps);
+        stack.extend(deps.iter().map(|dep| (*dep, false)));
+        continue;
+      }
+      let unit = self.infer(defset, signal, def, &inferred)?;
+      inferred.insert(signal, unit);
+    }
+    Ok(inferred)
+  }
+
+  fn infer(
+    &self,
+    defset: &SignalDefMap<FloatMapSignalDef>,
+    signal: Signal,
+    def: &FloatMapSignalDef,
+    inferred: &SignalMap<Unit>,
+  ) -> Result<Unit, UnitError> {
+    let unit_of = |s: &Signal| inferred[s];
+    let label = || defset.display_label(signal);
+    let unit = match def {
+      FloatMapSignalDef::Constant(_) => {
+        return Ok(self.unit(signal).unwrap_or_default());
+      }
+      FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)) => unit_of(a),
+      FloatMapSignalDef::BinaryOp(op) => match op {
+        FloatBinaryOp::Add(a, b) | FloatBinaryOp::Sub(a, b) => {
+          let (left, right) = (unit_of(a), unit_of(b));
+          if left != right {
+            return Err(UnitError::Mismatch {
+              signal,
+              label: label(),
+              left,
+              right,
+            });
+          }
+          left
+        }
+        FloatBinaryOp::Mul(a, b) => unit_of(a) * unit_of(b),
+        FloatBinaryOp::Div(a, b) => unit_of(a) / unit_of(b),
+        FloatBinaryOp::Pow(a, b) => {
+          let (base, exponent) = (unit_of(a), unit_of(b));
+          if !exponent.is_dimensionless() {
+            return Err(UnitError::DimensionedExponent {
+              signal,
+              label: label(),
+              unit: exponent,
+            });
+          }
+          if base.is_dimensionless() {
+            base
+          } else {
+            // a dimensioned base needs an exponent known to be an integer
+            match defset.get(*b) {
+              Some(FloatMapSignalDef::Constant(n))
+                if n.fract() == 0.0 && n.abs() <= i8::MAX as f64 =>
+              {
+                base.powi(*n as i8)
+              }
+              _ => {
+                return Err(UnitError::NonIntegerExponent {
+                  signal,
+                  label: label(),
+                  base,
+                })
+              }
+            }
+          }
+        }
+      },
+    };
+
+    match self.unit(signal) {
+      Some(declared) if declared != unit => Err(UnitError::Annotation {
+        signal,
+        label: label(),
+        declared,
+        inferred: unit,
+      }),
+      _ => Ok(unit),
+    }
+  }
+}
+
+/// An incompatible use of units found by [`Units::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnitError {
+  /// An addition or subtraction of operands with different units.
+  Mismatch {
+    signal: Signal,
+    label:  String,
+    left:   Unit,
+    right:  Unit,
+  },
+  /// A power whose exponent has a unit.
+  DimensionedExponent {
+    signal: Signal,
+    label:  String,
+    unit:   Unit,
+  },
+  /// A power of a dimensioned base whose exponent isn't a constant integer.
+  NonIntegerExponent {
+    signal: Signal,
+    label:  String,
+    base:   Unit,
+  },
+  /// A signal whose inferred unit contradicts its annotation.
+  Annotation {
+    signal:   Signal,
+    label:    String,
+    declared: Unit,
+    inferred: Unit,
+  },
+}
+
+impl fmt::Display for UnitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UnitError::Mismatch {
+        label, left, right, ..
+      } => write!(
+        f,
+        "`{label}` combines incompatible units {left} and {right}"
+      ),
+      UnitError::DimensionedExponent { label, unit, .. } => {
+        write!(f, "`{label}` raises to an exponent with unit {unit}")
+      }
+      UnitError::NonIntegerExponent { label, base, .. } => write!(
+        f,
+        "`{label}` raises unit {base} to an exponent that isn't a constant \
+         integer"
+      ),
+      UnitError::Annotation {
+        label,
+        declared,
+        inferred,
+        ..
+      } => write!(
+        f,
+        "`{label}` is annotated as {declared} but has unit {inferred}"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for UnitError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unit_check() {
+    let mut defset = SignalDefMap::new();
+
+    let distance =
+      defset.insert_labeled("distance", FloatMapSignalDef::Constant(100.0));
+    let time = defset.insert_labeled("time", FloatMapSignalDef::Constant(9.58));
+    let two = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let speed = defset.insert_labeled(
+      "speed",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(distance, time)),
+    );
+    let accel = defset.insert_labeled(
+      "accel",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Div(speed, time)),
+    );
+    let area = defset.insert_labeled(
+      "area",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Pow(distance, two)),
+    );
+    let bogus = defset.insert_labeled(
+      "bogus",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(speed, distance)),
+    );
+
+    let units = Units::new()
+      .with_unit(distance, Unit::METER)
+      .with_unit(time, Unit::SECOND)
+      .with_unit(speed, Unit::METER / Unit::SECOND);
+
+    let inferred = units.check(&defset, [accel, area]).unwrap();
+    assert_eq!(inferred[&accel], Unit::METER / Unit::SECOND.powi(2));
+    assert_eq!(inferred[&accel].to_string(), "m*s^-2");
+    assert_eq!(inferred[&area], Unit::METER.powi(2));
+
+    let err = units.check(&defset, [bogus]).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "`bogus` combines incompatible units m*s^-1 and m"
+    );
+
+    let wrong = units.with_unit(accel, Unit::METER);
+    assert!(matches!(
+      wrong.check(&defset, [accel]),
+      Err(UnitError::Annotation { .. })
+    ));
+  }
+}
@@ -0,0 +1,287 @@
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+  EvaluationPassDescriptor, EvaluationValueMap, Signal, SignalDef,
+  SignalDefMap, SignalMap, SignalSet,
+};
+
+type Predicate<T> =
+  Box<dyn Fn(&[&<T as SignalDef>::Value]) -> bool + Send + Sync>;
+
+struct Check<T: SignalDef> {
+  name:      String,
+  signals:   Vec<Signal>,
+  predicate: Predicate<T>,
+}
+
+/// Predicates over evaluated values, checked by
+/// [`PlannedEvaluation::run_validated`](crate::PlannedEvaluation::run_validated)
+/// as soon as every value they read is available.
+///
+/// Checks run before intermediates are freed, so they can cover signals that
+/// don't survive the run.
+pub struct Validators<T: SignalDef> {
+  checks: Vec<Check<T>>,
+}
+
+impl<T: SignalDef> Default for Validators<T> {
+  fn default() -> Self { Validators { checks: Vec::new() } }
+}
+
+impl<T: SignalDef> fmt::Debug for Validators<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_list()
+      .entries(
+        self
+          .checks
+          .iter()
+          .map(|check| (&check.name, &check.signals)),
+      )
+      .finish()
+  }
+}
+
+impl<T: SignalDef> Validators<T> {
+  /// Create an empty set of validators.
+  pub fn new() -> Self { Self::default() }
+
+  /// Check that the value of the given signal satisfies the predicate.
+  pub fn with_check(
+    mut self,
+    signal: Signal,
+    name: impl Into<String>,
+    predicate: impl Fn(&T::Value) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.checks.push(Check {
+      name:      name.into(),
+      signals:   vec![signal],
+      predicate: Box::new(move |values| predicate(values[0])),
+    });
+    self
+  }
+
+  /// Check that the values of the given signal and another signal satisfy the
+  /// predicate, which receives them in that order.
+  pub fn with_relation(
+    mut self,
+    signal: Signal,
+    other: Signal,
+    name: impl Into<String>,
+    predicate: impl Fn(&T::Value, &T::Value) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.checks.push(Check {
+      name:      name.into(),
+      signals:   vec![signal, other],
+      predicate: Box::new(move |values| predicate(values[0], values[1])),
+    });
+    self
+  }
+
+  /// Get the number of checks.
+  pub fn len(&self) -> usize { self.checks.len() }
+
+  /// Whether there are no checks.
+  pub fn is_empty(&self) -> bool { self.checks.is_empty() }
+
+  /// Bucket the checks by when they become due: bucket 0 before the first
+  /// pass, bucket `i + 1` after pass `i`.
+  pub(crate) fn schedule(
+    &self,
+    passes: &[EvaluationPassDescriptor],
+  ) -> Vec<Vec<usize>> {
+    let mut evaluated_in = SignalMap::<usize>::default();
+    for (i, pass) in passes.iter().enumerate() {
+      for target in pass.targets() {
+        evaluated_in.insert(*target, i + 1);
+      }
+    }
+
+    let mut due = vec![Vec::new(); passes.len() + 1];
+    for (i, check) in self.checks.iter().enumerate() {
+      let bucket = check
+        .signals
+        .iter()
+        .filter_map(|s| evaluated_in.get(s).copied())
+        .max()
+        .unwrap_or(0);
+      due[bucket].push(i);
+    }
+    due
+  }
+
+  /// Postpone releases so every value a check reads is still alive when the
+  /// check becomes due.
+  pub(crate) fn defer_releases(
+    &self,
+    due: &[Vec<usize>],
+    releases: &mut [SignalSet],
+  ) {
+    let mut released_after = SignalMap::<usize>::default();
+    for (i, released) in releases.iter().enumerate() {
+      for signal in released {
+        released_after.insert(*signal, i);
+      }
+    }
+
+    for (bucket, checks) in due.iter().enumerate().skip(1) {
+      let pass = bucket - 1;
+      for check in checks {
+        for signal in self.checks[*check].signals.iter() {
+          if let Some(released) = released_after.get_mut(signal) {
+            if *released < pass {
+              releases[*released].remove(signal);
+              releases[pass].insert(*signal);
+              *released = pass;
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Run the given checks, recording any that fail. A check whose values are
+  /// missing counts as failed.
+  pub(crate) fn run_checks(
+    &self,
+    checks: &[usize],
+    pass: Option<usize>,
+    values: &EvaluationValueMap<T>,
+    defset: &SignalDefMap<T>,
+    violations: &mut Vec<Violation>,
+  ) {
+    for check in checks.iter().map(|i| &self.checks[*i]) {
+      let inputs: Option<Vec<_>> =
+        check.signals.iter().map(|s| values.get(*s)).collect();
+      if inputs
+        .as_deref()
+        .is_some_and(|inputs| (check.predicate)(inputs))
+      {
+        continue;
+      }
+      let signal = check.signals[0];
+      violations.push(Violation {
+        check: check.name.clone(),
+        signal,
+        label: defset.display_label(signal),
+        value: values.get(signal).map(|value| format!("{value:?}")),
+        pass,
+      });
+    }
+  }
+}
+
+impl<T: SignalDef<Value = f64>> Validators<T> {
+  /// Check that the given signal is not negative.
+  pub fn with_non_negative(self, signal: Signal) -> Self {
+    self.with_check(signal, "non_negative", |v| *v >= 0.0)
+  }
+
+  /// Check that the given signal lies within the given range.
+  pub fn with_range(self, signal: Signal, range: RangeInclusive<f64>) -> Self {
+    let name = format!("within {}..={}", range.start(), range.end());
+    self.with_check(signal, name, move |v| range.contains(v))
+  }
+
+  /// Check that the given signal is never less than another signal.
+  pub fn with_at_least(self, signal: Signal, other: Signal) -> Self {
+    self.with_relation(signal, other, "at_least", |a, b| a >= b)
+  }
+}
+
+/// A failed check from a validated run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+  check:  String,
+  signal: Signal,
+  label:  String,
+  value:  Option<String>,
+  pass:   Option<usize>,
+}
+
+impl Violation {
+  /// Get the name of the failed check.
+  pub fn check(&self) -> &str { &self.check }
+
+  /// Get the signal the check was registered on.
+  pub fn signal(&self) -> Signal { self.signal }
+
+  /// Get the label of the signal, or `#id` if it has none.
+  pub fn label(&self) -> &str { &self.label }
+
+  /// Get the debug-formatted value of the signal, or `None` if it had no
+  /// value when the check ran.
+  pub fn value(&self) -> Option<&str> { self.value.as_deref() }
+
+  /// Get the index of the pass after which the check ran, or `None` if it ran
+  /// before the first pass.
+  pub fn pass(&self) -> Option<usize> { self.pass }
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.value {
+      Some(value) => write!(
+        f,
+        "check `{}` failed for {} = {}",
+        self.check, self.label, value
+      ),
+      None => write!(
+        f,
+        "check `{}` failed for {}: missing value",
+        self.check, self.label
+      ),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_validators() {
+    let mut defset = SignalDefMap::new();
+
+    let a = defset.insert_labeled("a", FloatMapSignalDef::Constant(2.0));
+    let b = defset.insert_labeled("b", FloatMapSignalDef::Constant(3.0));
+    let sum = defset.insert_labeled(
+      "sum",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(a, b)),
+    );
+    let neg = defset
+      .insert_labeled("neg", FloatMapSignalDef::UnaryOp(UnaryOp::Neg(sum)));
+    let out = defset.insert_labeled(
+      "out",
+      FloatMapSignalDef::BinaryOp(FloatBinaryOp::Mul(neg, neg)),
+    );
+
+    let matrix = SignalMatrix::new(defset);
+    let planned_eval = matrix
+      .plan_evaluation(&CustomPlanner::new(), [out])
+      .with_free_intermediates(true);
+
+    let validators = Validators::new()
+      .with_non_negative(neg)
+      .with_range(out, 0.0..=100.0)
+      .with_at_least(sum, a)
+      .with_at_least(a, out);
+
+    let values =
+      EvaluationValueMap::new_empty(planned_eval.all_queued_targets());
+    let (values, violations) =
+      planned_eval.run_validated(values, &Default::default(), &validators);
+
+    assert_eq!(values.get(out), Some(&25.0));
+    // `neg` and `a` are intermediates, but outlive their checks
+    let failed: Vec<_> = violations.iter().map(|v| v.to_string()).collect();
+    assert_eq!(failed, [
+      "check `non_negative` failed for neg = -5.0",
+      "check `at_least` failed for a = 2.0",
+    ]);
+    assert_eq!(violations[0].pass(), Some(2));
+    assert_eq!(violations[1].pass(), Some(3));
+  }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::{EvaluationValueMap, Signal, SignalDef, SignalDefMap};
+
+/// Who may request a signal as a target or read its value, for serving one
+/// graph to clients with different permissions.
+///
+/// Levels are ordered from least to most protected, and a client's clearance
+/// is the most protected level it may see: a client cleared for `Internal`
+/// sees public and internal signals, but not restricted ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(rename_all = "lowercase")
+)]
+pub enum Visibility {
+  /// An output anyone may see.
+  #[default]
+  Public,
+  /// An intermediate for trusted clients.
+  Internal,
+  /// A sensitive signal for privileged clients only.
+  Restricted,
+}
+
+impl fmt::Display for Visibility {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Visibility::Public => "public",
+      Visibility::Internal => "internal",
+      Visibility::Restricted => "restricted",
+    })
+  }
+}
+
+/// A signal a client isn't cleared to see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessDenied {
+  pub signal:     Signal,
+  pub visibility: Visibility,
+  pub clearance:  Visibility,
+}
+
+impl fmt::Display for AccessDenied {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "signal {} is {}, but the client is only cleared for {}",
+      self.signal, self.visibility, self.clearance
+    )
+  }
+}
+
+impl std::error::Error for AccessDenied {}
+
+impl<T: SignalDef> SignalDefMap<T> {
+  /// Set the visibility of a signal. Signals are public by default.
+  pub fn set_visibility(&mut self, signal: Signal, visibility: Visibility) {
+    match visibility {
+      Visibility::Public => self.visibility.remove(&signal),
+      _ => self.visibility.insert(signal, visibility),
+    };
+  }
+
+  /// Get the visibility of a signal.
+  pub fn visibility(&self, signal: Signal) -> Visibility {
+    self.visibility.get(&signal).copied().unwrap_or_default()
+  }
+
+  /// Check whether a client with the given clearance may see a signal.
+  pub fn is_visible(&self, signal: Signal, clearance: Visibility) -> bool {
+    self.visibility(signal) <= clearance
+  }
+
+  /// Check that a client with the given clearance may see every given
+  /// signal, failing on the first one it may not.
+  pub fn check_access(
+    &self,
+    signals: impl IntoIterator<Item = Signal>,
+    clearance: Visibility,
+  ) -> Result<(), AccessDenied> {
+    for signal in signals {
+      let visibility = self.visibility(signal);
+      if visibility > clearance {
+        return Err(AccessDenied {
+          signal,
+          visibility,
+          clearance,
+        });
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<T: SignalDef> EvaluationValueMap<T> {
+  /// Iterate over the values a client with the given clearance may read.
+  pub fn visible_values<'a>(
+    &'a self,
+    defset: &'a SignalDefMap<T>,
+    clearance: Visibility,
+  ) -> impl Iterator<Item = (Signal, &'a T::Value)> + 'a {
+    self
+      .values
+      .iter()
+      .filter(move |(s, _)| defset.is_visible(*s, clearance))
+      .filter_map(|(s, v)| Some((s, v.as_ref()?)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    CustomPlanner, FloatBinaryOp, FloatMapSignalDef, SignalMatrix, UnaryOp,
+  };
+
+  #[test]
+  fn test_visibility() {
+    let mut defset = SignalDefMap::new();
+    let salary = defset.insert(FloatMapSignalDef::Constant(100.0));
+    let bonus = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(salary)));
+    let total = defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Sub(
+      salary, bonus,
+    )));
+    defset.set_visibility(salary, Visibility::Restricted);
+    defset.set_visibility(bonus, Visibility::Internal);
+    assert_eq!(defset.visibility(total), Visibility::Public);
+
+    assert!(defset.check_access([total], Visibility::Public).is_ok());
+    let denied = defset
+      .check_access([total, bonus], Visibility::Public)
+      .unwrap_err();
+    assert_eq!(denied, AccessDenied {
+      signal:     bonus,
+      visibility: Visibility::Internal,
+      clearance:  Visibility::Public,
+    });
+    assert_eq!(
+      denied.to_string(),
+      "signal #1 is internal, but the client is only cleared for public"
+    );
+    assert!(defset.check_access([bonus], Visibility::Restricted).is_ok());
+
+    // restricted signals still feed the public ones that depend on them
+    let matrix = SignalMatrix::new(defset);
+    let plan = matrix.plan_evaluation(&CustomPlanner::new(), [total]);
+    let values =
+      plan.run(EvaluationValueMap::new_empty(plan.all_queued_targets()));
+    let visible = |clearance| {
+      let mut signals: Vec<_> = values
+        .visible_values(matrix.defset(), clearance)
+        .map(|(s, _)| s)
+        .collect();
+      signals.sort_by_key(|s| s.0);
+      signals
+    };
+    assert_eq!(visible(Visibility::Public), [total]);
+    assert_eq!(visible(Visibility::Internal), [bonus, total]);
+    assert_eq!(visible(Visibility::Restricted), [salary, bonus, total]);
+  }
+}
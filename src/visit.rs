@@ -0,0 +1,160 @@
+use fixedbitset::FixedBitSet;
+
+use crate::{Signal, SignalDef, SignalDefMap, SignalMatrix};
+
+/// A visitor called with each signal and its definition by a [`Walker`].
+///
+/// Implemented for closures taking the signal and its definition.
+pub trait SignalVisitor<T: SignalDef> {
+  /// Visit a signal.
+  fn visit(&mut self, signal: Signal, def: &T);
+}
+
+impl<T: SignalDef, F> SignalVisitor<T> for F
+where
+  F: FnMut(Signal, &T),
+{
+  fn visit(&mut self, signal: Signal, def: &T) { self(signal, def) }
+}
+
+/// The order a [`Walker`] visits signals in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitOrder {
+  /// Every signal after its dependencies.
+  #[default]
+  Topological,
+  /// Every signal before its dependencies.
+  Reverse,
+}
+
+/// Walks the signals of a [`SignalDefMap`] in dependency order, optionally
+/// only those reachable from a set of roots.
+///
+/// The order is deterministic: a depth-first post-order from the roots, or
+/// from every signal by ID if there are none. Dependencies with no
+/// definition are skipped, and signals on a cycle are each visited once in
+/// an unspecified order.
+#[derive(Debug, Clone, Default)]
+pub struct Walker {
+  order: VisitOrder,
+  roots: Option<Vec<Signal>>,
+}
+
+impl Walker {
+  /// Create a walker visiting every signal in topological order.
+  pub fn new() -> Self { Self::default() }
+
+  /// Set the order signals are visited in.
+  pub fn with_order(mut self, order: VisitOrder) -> Self {
+    self.order = order;
+    self
+  }
+
+  /// Only visit the given roots and the signals they depend on.
+  pub fn with_roots(mut self, roots: impl IntoIterator<Item = Signal>) -> Self {
+    self.roots = Some(roots.into_iter().collect());
+    self
+  }
+
+  /// Get the signals the walk visits, in order.
+  pub fn order<T: SignalDef>(&self, defset: &SignalDefMap<T>) -> Vec<Signal> {
+    let starts: Vec<_> = match &self.roots {
+      Some(roots) => roots.clone(),
+      None => (0..defset.id_space()).map(Signal::from_index).collect(),
+    };
+
+    let mut seen = FixedBitSet::with_capacity(defset.id_space());
+    let mut order = Vec::with_capacity(defset.len());
+    let mut deps = Vec::new();
+    // iterative post-order, so deep chains don't overflow the stack
+    let mut stack: Vec<_> = starts.iter().rev().map(|s| (*s, false)).collect();
+    while let Some((signal, expanded)) = stack.pop() {
+      if expanded {
+        order.push(signal);
+        continue;
+      }
+      let Some(def) = defset.get(signal) else {
+        continue;
+      };
+      if seen.put(signal.index()) {
+        continue;
+      }
+      stack.push((signal, true));
+      deps.clear();
+      def.dependencies_into(&mut deps);
+      stack.extend(deps.iter().rev().map(|dep| (*dep, false)));
+    }
+
+    if self.order == VisitOrder::Reverse {
+      order.reverse();
+    }
+    order
+  }
+
+  /// Visit the signals of the map in order.
+  pub fn walk<T: SignalDef>(
+    &self,
+    defset: &SignalDefMap<T>,
+    visitor: &mut impl SignalVisitor<T>,
+  ) {
+    for signal in self.order(defset) {
+      visitor.visit(signal, defset.get(signal).unwrap());
+    }
+  }
+}
+
+impl<T: SignalDef> SignalMatrix<T> {
+  /// Iterate over every signal and its definition, each after its
+  /// dependencies, in the order of [`Walker`].
+  pub fn topo_iter(&self) -> impl Iterator<Item = (Signal, &T)> {
+    self.walk_iter(Walker::new())
+  }
+
+  /// Iterate over every signal and its definition, each before its
+  /// dependencies.
+  pub fn topo_iter_rev(&self) -> impl Iterator<Item = (Signal, &T)> {
+    self.walk_iter(Walker::new().with_order(VisitOrder::Reverse))
+  }
+
+  fn walk_iter(&self, walker: Walker) -> impl Iterator<Item = (Signal, &T)> {
+    let defset = self.defset();
+    walker
+      .order(defset)
+      .into_iter()
+      .map(|signal| (signal, defset.get(signal).unwrap()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{FloatBinaryOp, FloatMapSignalDef, UnaryOp};
+
+  #[test]
+  fn test_walk_orders() {
+    let mut defset = SignalDefMap::new();
+    let a = defset.insert(FloatMapSignalDef::Constant(1.0));
+    let b = defset.insert(FloatMapSignalDef::Constant(2.0));
+    let neg = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(a)));
+    let sum =
+      defset.insert(FloatMapSignalDef::BinaryOp(FloatBinaryOp::Add(neg, b)));
+    let other = defset.insert(FloatMapSignalDef::UnaryOp(UnaryOp::Neg(b)));
+
+    let mut visited = Vec::new();
+    Walker::new().walk(&defset, &mut |signal, _: &FloatMapSignalDef| {
+      visited.push(signal)
+    });
+    assert_eq!(visited, [a, b, neg, sum, other]);
+
+    let walker = Walker::new().with_roots([sum]);
+    assert_eq!(walker.order(&defset), [a, neg, b, sum]);
+    let reverse = walker.with_order(VisitOrder::Reverse);
+    assert_eq!(reverse.order(&defset), [sum, b, neg, a]);
+
+    let matrix = SignalMatrix::new(defset);
+    let order: Vec<_> = matrix.topo_iter().map(|(s, _)| s).collect();
+    assert_eq!(order, visited);
+    let order: Vec<_> = matrix.topo_iter_rev().map(|(s, _)| s).collect();
+    assert_eq!(order, [other, sum, neg, b, a]);
+  }
+}
@@ -0,0 +1,199 @@
+//! JavaScript bindings with `wasm-bindgen`, for in-browser calculation
+//! engines built on the same [`GraphConfig`] graphs as the
+//! HTTP server.
+//!
+//! A graph is built once from the JSON form of a [`GraphConfig`], planned for
+//! some target nodes and input nodes, and the plan run as often as the inputs
+//! change:
+//!
+//! ```js
+//! const graph = new Graph(JSON.stringify(config));
+//! const plan = graph.plan(["total"], ["rate"]);
+//! const [total] = plan.run(new Float64Array([0.07]));
+//! ```
+//!
+//! Plans run sequentially on the calling thread. Build for the web without
+//! the default features, e.g. `--no-default-features --features wasm`.
+
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+  config::GraphConfig, CustomPlanner, EvaluationPassDescriptor,
+  EvaluationValueMap, FloatMapSignalDef, PassParallelism, PlannedEvaluation,
+  RunOptions, Signal, SignalMatrix,
+};
+
+/// A graph built from a [`GraphConfig`].
+#[wasm_bindgen(js_name = Graph)]
+#[derive(Debug)]
+pub struct WasmGraph {
+  matrix: Rc<SignalMatrix<FloatMapSignalDef>>,
+  roots:  Vec<Signal>,
+}
+
+/// A plan for evaluating some nodes of a [`WasmGraph`] from the values of
+/// others.
+#[wasm_bindgen(js_name = Plan)]
+#[derive(Debug)]
+pub struct WasmPlan {
+  matrix:  Rc<SignalMatrix<FloatMapSignalDef>>,
+  targets: Vec<Signal>,
+  inputs:  Vec<Signal>,
+  passes:  Vec<EvaluationPassDescriptor>,
+}
+
+#[wasm_bindgen(js_class = Graph)]
+impl WasmGraph {
+  /// Build a graph from the JSON form of a [`GraphConfig`].
+  #[wasm_bindgen(constructor)]
+  pub fn new(config: &str) -> Result<WasmGraph, String> {
+    let config: GraphConfig =
+      serde_json::from_str(config).map_err(|e| e.to_string())?;
+    let graph = config.build().map_err(|e| e.to_string())?;
+    Ok(WasmGraph {
+      matrix: Rc::new(SignalMatrix::new(graph.defset)),
+      roots:  graph.roots,
+    })
+  }
+
+  /// Get the names of the graph's nodes.
+  pub fn nodes(&self) -> Vec<String> {
+    let mut nodes: Vec<_> = self
+      .matrix
+      .defset()
+      .labels()
+      .map(|(name, _)| name.to_string())
+      .collect();
+    nodes.sort();
+    nodes
+  }
+
+  /// Get the names of the graph's roots.
+  pub fn roots(&self) -> Vec<String> {
+    self.roots.iter().map(|s| self.name(*s)).collect()
+  }
+
+  /// Plan evaluating the named target nodes, or the roots if there are none,
+  /// with the values of the named input nodes given to each run instead of
+  /// being evaluated.
+  pub fn plan(
+    &self,
+    targets: Vec<String>,
+    inputs: Vec<String>,
+  ) -> Result<WasmPlan, String> {
+    let targets = match targets.is_empty() {
+      true => self.roots.clone(),
+      false => self.lookup_all(&targets)?,
+    };
+    let inputs = self.lookup_all(&inputs)?;
+    let plan = self
+      .matrix
+      .plan_evaluation(&CustomPlanner::new(), targets.iter().copied())
+      .skip_targets(inputs.iter().copied());
+    Ok(WasmPlan {
+      matrix: self.matrix.clone(),
+      passes: plan.passes().to_vec(),
+      targets,
+      inputs,
+    })
+  }
+}
+
+impl WasmGraph {
+  fn name(&self, signal: Signal) -> String {
+    self.matrix.defset().label(signal).unwrap().to_string()
+  }
+
+  fn lookup_all(&self, names: &[String]) -> Result<Vec<Signal>, String> {
+    names
+      .iter()
+      .map(|name| {
+        self
+          .matrix
+          .defset()
+          .lookup(name)
+          .ok_or_else(|| format!("no node named `{name}`"))
+      })
+      .collect()
+  }
+}
+
+#[wasm_bindgen(js_class = Plan)]
+impl WasmPlan {
+  /// Get the number of passes.
+  pub fn passes(&self) -> usize { self.passes.len() }
+
+  /// Run the plan with the values of its input nodes, in the order they were
+  /// named, returning the values of its targets in order.
+  pub fn run(&self, inputs: &[f64]) -> Result<Vec<f64>, String> {
+    if inputs.len() != self.inputs.len() {
+      return Err(format!(
+        "expected {} input values, got {}",
+        self.inputs.len(),
+        inputs.len()
+      ));
+    }
+    let plan = PlannedEvaluation::from_passes(
+      &self.matrix,
+      self.targets.iter().copied().collect(),
+      self.passes.clone(),
+    );
+    let mut values = EvaluationValueMap::new_empty(plan.all_queued_targets());
+    for (signal, value) in self.inputs.iter().zip(inputs) {
+      values.insert(*signal, *value);
+    }
+    let options = RunOptions::new()
+      .with_parallelism_policy(|_, _: &_| PassParallelism::Limited(1));
+    let values = plan.run_with(values, &options);
+    Ok(
+      self
+        .targets
+        .iter()
+        .map(|s| *values.get(*s).unwrap())
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_wasm_graph() {
+    let graph = WasmGraph::new(
+      r#"{
+        "roots": ["total"],
+        "nodes": {
+          "principal": 1000.0,
+          "rate": 0.5,
+          "one": 1.0,
+          "growth": { "op": "add", "args": ["one", "rate"] },
+          "total": { "op": "mul", "args": ["principal", "growth"] }
+        }
+      }"#,
+    )
+    .unwrap();
+    assert_eq!(graph.roots(), ["total"]);
+    assert_eq!(graph.nodes().len(), 5);
+
+    let plan = graph.plan(vec![], vec![]).unwrap();
+    assert_eq!(plan.run(&[]), Ok(vec![1500.0]));
+
+    let plan = graph
+      .plan(vec!["growth".into(), "total".into()], vec!["rate".into()])
+      .unwrap();
+    assert_eq!(plan.run(&[0.25]), Ok(vec![1.25, 1250.0]));
+    assert_eq!(plan.run(&[1.0]), Ok(vec![2.0, 2000.0]));
+    assert_eq!(
+      plan.run(&[]),
+      Err("expected 1 input values, got 0".to_string())
+    );
+    assert_eq!(
+      graph.plan(vec!["nope".into()], vec![]).unwrap_err(),
+      "no node named `nope`"
+    );
+  }
+}